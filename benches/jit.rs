@@ -0,0 +1,83 @@
+//! How long the tree walking interpreter, the bytecode VM and the JIT
+//! take to run numeric functions. Run with
+//! `cargo bench --bench jit --features jit`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use why_rs::Compiler;
+use why_rs::Interpreter;
+use why_rs::Parser;
+use why_rs::Vm;
+
+/// Programs that spend most of their time in typed numeric functions.
+const PROGRAMS: [(&str, &str); 2] = [
+    (
+        "recursive fib",
+        "$fib(int n) @int { if n < 2 { @n; } @fib(n - 1) + fib(n - 2); } fib(20)",
+    ),
+    (
+        "loop in a function",
+        "$sum(int n) @float {
+             float total = 0;
+             int i = 0;
+             while i < n {
+                 total += i * 0.5 - 1;
+                 i++;
+             }
+             @total;
+         }
+         sum(200000)",
+    ),
+];
+
+/// Runs `f` until a second has passed, returning the mean time a run
+/// took.
+fn measure(mut f: impl FnMut()) -> Duration {
+    f();
+
+    let start = Instant::now();
+    let mut runs = 0;
+
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+
+    start.elapsed() / runs
+}
+
+fn main() {
+    for (name, src) in PROGRAMS {
+        let ast = Parser::from_source(src).and_then(|mut parser| parser.parse());
+        let ast = ast.expect("benchmark programs parse");
+        let program = Compiler::new()
+            .compile(&ast)
+            .expect("benchmark programs compile");
+        let jit = || Interpreter::with_jit().expect("the jit supports this machine");
+
+        let expected = Interpreter::new().run(&ast);
+        assert_eq!(expected, Vm::new().run(&program));
+        assert_eq!(expected, jit().run(&ast));
+
+        let interpreted = measure(|| {
+            black_box(Interpreter::new().run(&ast).ok());
+        });
+        let compiled = measure(|| {
+            black_box(Vm::new().run(&program).ok());
+        });
+        // Includes compiling, which happens on every run
+        let native = measure(|| {
+            black_box(jit().run(&ast).ok());
+        });
+
+        println!(
+            "{:<20} {:>10.2} ms interpreted {:>10.2} ms on the vm {:>10.2} ms jitted ({:.1}x)",
+            name,
+            interpreted.as_secs_f64() * 1e3,
+            compiled.as_secs_f64() * 1e3,
+            native.as_secs_f64() * 1e3,
+            interpreted.as_secs_f64() / native.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Lexing and parsing throughput, and the memory lexing needs, on large
+//! generated sources. Run with `cargo bench`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use why_rs::Lexer;
+use why_rs::Parser;
+
+/// Counts the bytes allocated, to measure the peak memory of a run.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Generates a program of `funcs` functions, with a bit of everything
+/// the lexer handles.
+fn generate(funcs: usize) -> String {
+    let mut src = String::new();
+
+    for i in 0..funcs {
+        src.push_str(&format!(
+            "/// Sums to `n`, {i} of {funcs}.\n\
+             $sum_{i}(int n) @int {{\n\
+             \x20   let total = 0; // running total\n\
+             \x20   while n > 0 {{\n\
+             \x20       total += n * 2 ** 3 / 0x1F;\n\
+             \x20       n -= 1_000;\n\
+             \x20   }}\n\
+             \x20   %print(\"héllo, wörld\", 3.25e2, [1, 2], &{{\"k\" -> 1}});\n\
+             \x20   @total;\n\
+             }}\n\n"
+        ));
+    }
+
+    src
+}
+
+/// Runs `f` until a second has passed, returning the mean time a run
+/// took, and the peak memory allocated during a run.
+fn measure(mut f: impl FnMut()) -> (Duration, usize) {
+    let base = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    f();
+    let peak = PEAK.load(Ordering::Relaxed) - base;
+
+    let start = Instant::now();
+    let mut runs = 0;
+
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+
+    (start.elapsed() / runs, peak)
+}
+
+fn report(name: &str, src: &str, (time, peak): (Duration, usize)) {
+    let mb = src.len() as f64 / 1e6;
+
+    println!(
+        "{:<28} {:>8.2} MB/s {:>10.2} ms {:>10.2} MB peak ({:.1}x source)",
+        name,
+        mb / time.as_secs_f64(),
+        time.as_secs_f64() * 1e3,
+        peak as f64 / 1e6,
+        peak as f64 / src.len() as f64,
+    );
+}
+
+fn main() {
+    for funcs in [1_000, 10_000] {
+        let src = generate(funcs);
+        println!("{} functions, {:.2} MB", funcs, src.len() as f64 / 1e6);
+
+        report(
+            "lex",
+            &src,
+            measure(|| {
+                black_box(Lexer::new(&src).and_then(|mut lexer| lexer.lex()).ok());
+            }),
+        );
+
+        report(
+            "lex, streaming",
+            &src,
+            measure(|| {
+                let lexer = Lexer::new(&src).expect("source is not empty");
+                black_box(lexer.filter_map(Result::ok).count());
+            }),
+        );
+
+        report(
+            "lex and parse",
+            &src,
+            measure(|| {
+                let parser = Parser::from_source(&src).map(|mut parser| parser.parse());
+                black_box(parser.ok());
+            }),
+        );
+
+        println!();
+    }
+}
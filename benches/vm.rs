@@ -0,0 +1,85 @@
+//! How long the tree walking interpreter and the bytecode VM take to run
+//! loop heavy programs. Run with `cargo bench --bench vm`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use why_rs::Compiler;
+use why_rs::Interpreter;
+use why_rs::Parser;
+use why_rs::Vm;
+
+/// Programs that spend most of their time in loops and calls.
+const PROGRAMS: [(&str, &str); 3] = [
+    (
+        "counting loop",
+        "int total = 0;
+         int i = 0;
+         while i < 200000 {
+             total += i * 3 - 1;
+             i++;
+         }
+         total",
+    ),
+    (
+        "nested loops over arrays",
+        "let xs = [];
+         int i = 0;
+         while i < 100 { xs = xs + [i]; i++; }
+         int total = 0;
+         int j = 0;
+         while j < 200 {
+             int k = 0;
+             while k < 100 { total += xs[k]; k++; }
+             j++;
+         }
+         total",
+    ),
+    (
+        "recursive calls",
+        "$fib(int n) @int { if n < 2 { @n; } @fib(n - 1) + fib(n - 2); } fib(20)",
+    ),
+];
+
+/// Runs `f` until a second has passed, returning the mean time a run
+/// took.
+fn measure(mut f: impl FnMut()) -> Duration {
+    f();
+
+    let start = Instant::now();
+    let mut runs = 0;
+
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+
+    start.elapsed() / runs
+}
+
+fn main() {
+    for (name, src) in PROGRAMS {
+        let ast = Parser::from_source(src).and_then(|mut parser| parser.parse());
+        let ast = ast.expect("benchmark programs parse");
+        let program = Compiler::new()
+            .compile(&ast)
+            .expect("benchmark programs compile");
+
+        assert_eq!(Interpreter::new().run(&ast), Vm::new().run(&program));
+
+        let interpreted = measure(|| {
+            black_box(Interpreter::new().run(&ast).ok());
+        });
+        let compiled = measure(|| {
+            black_box(Vm::new().run(&program).ok());
+        });
+
+        println!(
+            "{:<28} {:>10.2} ms interpreted {:>10.2} ms on the vm ({:.1}x)",
+            name,
+            interpreted.as_secs_f64() * 1e3,
+            compiled.as_secs_f64() * 1e3,
+            interpreted.as_secs_f64() / compiled.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,170 @@
+#[cfg(test)]
+mod test_cgen {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use why_rs::CGen;
+    use why_rs::Interpreter;
+    use why_rs::Parser;
+
+    fn generate(src: &str) -> Result<String, String> {
+        let program = Parser::from_source(src)?.parse()?;
+
+        CGen::new().generate(&program)
+    }
+
+    /// Compiles the C for a program with gcc and runs it, returning its
+    /// stdout, stderr and exit code, or `None` if gcc isn't installed.
+    fn run(name: &str, src: &str) -> Option<(String, String, i32)> {
+        let dir = env::temp_dir().join(format!("why-test-cgen-{}", std::process::id()));
+        fs::create_dir_all(&dir).ok()?;
+
+        let c = dir.join(format!("{name}.c"));
+        let exe: PathBuf = dir.join(name);
+        fs::write(&c, generate(src).expect("program compiles")).ok()?;
+
+        let gcc = Command::new("gcc")
+            .args([
+                "-std=c11",
+                "-Wall",
+                "-Wextra",
+                "-pedantic",
+                "-Werror",
+                "-O1",
+            ])
+            .arg(&c)
+            .arg("-o")
+            .arg(&exe)
+            .arg("-lm")
+            .output()
+            .ok()?;
+
+        assert!(
+            gcc.status.success(),
+            "{}",
+            String::from_utf8_lossy(&gcc.stderr)
+        );
+
+        let output = Command::new(&exe).output().ok()?;
+
+        Some((
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+            output.status.code().unwrap_or(-1),
+        ))
+    }
+
+    #[test]
+    fn test_generate_errors() {
+        assert!(generate("break;").is_err());
+        assert!(generate("$f() { break; }").is_err());
+        assert!(generate("@1;").is_err());
+        assert!(generate("&Point { int x; }").is_err());
+        assert!(generate("%nope();").is_err());
+        assert!(generate("while true { break; } $f() { @; }").is_ok());
+    }
+
+    #[test]
+    fn test_includes_runtime() -> Result<(), String> {
+        let c = generate("let greeting = \"hé?\";")?;
+
+        assert!(c.contains(why_rs::cgen::RUNTIME));
+        assert!(c.contains("why_string(\"h\\303\\251\\?\", 4)"));
+        assert!(c.contains("int main(void) {"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_matches_interpreter() {
+        let programs = [
+            (
+                "arith",
+                "%print((2 + 1) * 3, 2 ** -1, \"why\" + \" not\", !(1 < 2.5), 7 / 2, -7 / 2);",
+                "9 0.5 why not false 3 -3\n",
+            ),
+            (
+                "floats",
+                "float f = 1; %print(f, 0.1 + 0.2, 1e16, 1e15, 0.0001, 0.00001, 1.0 / 0, -0.0);",
+                "1.0 0.30000000000000004 1e16 1000000000000000.0 0.0001 1e-5 inf -0.0\n",
+            ),
+            (
+                "values",
+                "let grid = [[1, 2], [3, 4]]; let copy = grid; grid[1][0] *= 10;\n\
+                 let m = &{\"a\" -> 1, \"a\" -> 2}; m[\"b\"] = 2; m[\"b\"] += m[\"a\"];\n\
+                 %print(grid, copy, m, [\"s\"], \"héllo\"[1], len(\"héllo\"));",
+                "[[1, 2], [30, 4]] [[1, 2], [3, 4]] &{\"a\"->2, \"b\"->4} [\"s\"] é 5\n",
+            ),
+            (
+                "calls",
+                "$fib(int n) @int { if n < 2 { @n; } @fib(n - 1) + fib(n - 2); }\n\
+                 $half(float x) @float { @x / 2; }\n\
+                 let p = print; p(fib(20), half(3), half, p, str([1.5]) + \"!\");",
+                "6765 1.5 <function half> <builtin print> [1.5]!\n",
+            ),
+            (
+                "scopes",
+                "int total = 0; int i = 0;\n\
+                 while true { i++; if i > 10 { break; } int x = i; total += x; }\n\
+                 $f() @int { @outer; } int outer = 7; $g() { int outer = 2; }\n\
+                 %print(total, f(), [1] == [1], [1] == [1.0], NULL == NULL);",
+                "55 7 true false true\n",
+            ),
+            (
+                "widening",
+                "array@float xs = [3]; xs[0] = 5; array@float ys = [1.5]; ys = [1];\n\
+                 mapping@string->float m = &{\"a\" -> 1}; m[\"b\"] = 3;\n\
+                 $f(array@float zs) @array@float { @zs + [7]; }\n\
+                 %print(xs[0] / 2, ys[0] / 2, m[\"a\"] / 2, m[\"b\"] / 2, f([3]));",
+                "2.5 0.5 0.5 1.5 [3.0, 7.0]\n",
+            ),
+        ];
+
+        for (name, src, expected) in programs {
+            let Some((stdout, stderr, code)) = run(name, src) else {
+                return;
+            };
+
+            assert_eq!(
+                (stdout.as_str(), stderr.as_str(), code),
+                (expected, "", 0),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_errors_match_interpreter() {
+        let programs = [
+            "9223372036854775807 + 1;",
+            "1 + \"one\";",
+            "const int x = 1; x = 2;",
+            "array@int xs = [1]; xs = [\"a\"];",
+            "y;",
+            "[1][1];",
+            "&{1 -> 2}[3];",
+            "while 1 {}",
+            "$f(int a) {} f(\"a\");",
+            "$f() @int {} f();",
+            "1();",
+            "%len(1, 2);",
+        ];
+
+        for (i, src) in programs.iter().enumerate() {
+            let program = Parser::from_source(src)
+                .and_then(|mut p| p.parse())
+                .unwrap();
+            let expected = Interpreter::new().run(&program).unwrap_err();
+
+            let Some((stdout, stderr, code)) = run(&format!("error{i}"), src) else {
+                return;
+            };
+
+            assert_eq!(
+                (stdout.as_str(), stderr.trim_end(), code),
+                ("", expected.as_str(), 5)
+            );
+        }
+    }
+}
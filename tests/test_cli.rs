@@ -0,0 +1,240 @@
+#[cfg(test)]
+mod test_cli {
+    use why_rs::cli::{Cli, Command, Emit, Failure};
+    use why_rs::doc::DocFormat;
+    use why_rs::dump::Format;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        Cli::parse(args)
+    }
+
+    #[test]
+    fn test_subcommands() -> Result<(), String> {
+        for (name, command) in [
+            ("run", Command::Run),
+            ("check", Command::Check),
+            ("tokens", Command::Tokens),
+            ("ast", Command::Ast),
+            ("fmt", Command::Fmt),
+            ("disasm", Command::Disasm),
+            ("build", Command::Build),
+        ] {
+            let cli = parse(&[name, "main.why"])?;
+            assert_eq!(cli.command, command);
+            assert_eq!(cli.file, Some("main.why".to_string()));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_defaults() -> Result<(), String> {
+        assert_eq!(parse(&[])?.command, Command::Repl);
+        assert_eq!(parse(&["repl"])?.command, Command::Repl);
+        assert_eq!(parse(&["main.why"])?.command, Command::Run);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdin() -> Result<(), String> {
+        let cli = parse(&["check", "-"])?;
+        assert_eq!(cli.command, Command::Check);
+        assert_eq!(cli.file, Some("-".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_help_and_version() -> Result<(), String> {
+        assert_eq!(parse(&["--help"])?.command, Command::Help);
+        assert_eq!(parse(&["run", "-h"])?.command, Command::Help);
+        assert_eq!(parse(&["help"])?.command, Command::Help);
+        assert_eq!(parse(&["-V"])?.command, Command::Version);
+        assert_eq!(parse(&["--version"])?.command, Command::Version);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(parse(&["check"]).is_err());
+        assert!(parse(&["run", "a.why", "b.why"]).is_err());
+        assert!(parse(&["--frobnicate"]).is_err());
+    }
+
+    #[test]
+    fn test_exit_codes_differ() {
+        let codes = [
+            Failure::Lex.code(),
+            Failure::Parse.code(),
+            Failure::Type.code(),
+            Failure::Runtime.code(),
+        ];
+
+        for (i, code) in codes.iter().enumerate() {
+            assert_ne!(*code, 0);
+            assert!(!codes[i + 1..].contains(code));
+        }
+
+        assert_eq!(Failure::Usage.code(), 1);
+    }
+
+    #[test]
+    fn test_trace_flags() -> Result<(), String> {
+        let cli = parse(&["check", "main.why"])?;
+        assert!(!cli.trace_lexer && !cli.trace_parser);
+
+        let cli = parse(&["--trace-parser", "check", "main.why"])?;
+        assert!(!cli.trace_lexer && cli.trace_parser);
+
+        let cli = parse(&["run", "main.why", "--trace-lexer"])?;
+        assert!(cli.trace_lexer && !cli.trace_parser);
+
+        let cli = parse(&["-v", "main.why"])?;
+        assert_eq!(cli.command, Command::Run);
+        assert!(cli.trace_lexer && cli.trace_parser);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_flag() -> Result<(), String> {
+        assert_eq!(parse(&["ast", "main.why"])?.format, Format::Text);
+        assert_eq!(
+            parse(&["ast", "--format", "json", "main.why"])?.format,
+            Format::Json
+        );
+        assert_eq!(
+            parse(&["tokens", "main.why", "--format=sexpr"])?.format,
+            Format::Sexpr
+        );
+
+        assert!(parse(&["ast", "main.why", "--format"]).is_err());
+        assert!(parse(&["ast", "--format", "yaml", "main.why"]).is_err());
+        assert!(parse(&["run", "--format", "json", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_flag() -> Result<(), String> {
+        assert!(parse(&["fmt", "--check", "main.why"])?.check);
+        assert!(!parse(&["fmt", "main.why"])?.check);
+        assert!(parse(&["run", "--check", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trivia_flag() -> Result<(), String> {
+        assert!(parse(&["tokens", "--trivia", "main.why"])?.trivia);
+        assert!(!parse(&["tokens", "main.why"])?.trivia);
+        assert!(parse(&["ast", "--trivia", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_html_flag() -> Result<(), String> {
+        assert_eq!(parse(&["doc", "main.why"])?.command, Command::Doc);
+        assert_eq!(parse(&["doc", "main.why"])?.doc_format, DocFormat::Markdown);
+        assert_eq!(
+            parse(&["doc", "--html", "main.why"])?.doc_format,
+            DocFormat::Html
+        );
+        assert!(parse(&["fmt", "--html", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_vm_flag() -> Result<(), String> {
+        assert!(parse(&["run", "--vm", "main.why"])?.vm);
+        assert!(parse(&["--vm", "main.why"])?.vm);
+        assert!(!parse(&["run", "main.why"])?.vm);
+        assert!(parse(&["disasm", "--vm", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_heap_stats_flag() -> Result<(), String> {
+        assert!(parse(&["run", "--heap-stats", "main.why"])?.heap_stats);
+        assert!(parse(&["--vm", "--heap-stats", "main.whyc"])?.heap_stats);
+        assert!(parse(&["run", "--gc-stats", "main.why"])?.heap_stats);
+        assert!(!parse(&["run", "main.why"])?.heap_stats);
+        assert!(parse(&["check", "--heap-stats", "main.why"]).is_err());
+        assert!(parse(&["check", "--gc-stats", "main.why"])
+            .is_err_and(|e| e.contains("--gc-stats only applies")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_depth_flag() -> Result<(), String> {
+        assert_eq!(parse(&["run", "main.why"])?.max_depth, 512);
+        assert_eq!(
+            parse(&["run", "--max-depth", "64", "main.why"])?.max_depth,
+            64
+        );
+        assert_eq!(parse(&["--max-depth=8", "main.whyc"])?.max_depth, 8);
+        assert!(parse(&["run", "--max-depth", "0", "main.why"]).is_err());
+        assert_eq!(parse(&["--max-depth=32768", "main.why"])?.max_depth, 32768);
+        assert!(parse(&["--max-depth=32769", "main.why"])
+            .is_err_and(|e| e.contains("expected 1 to 32768")));
+        assert!(parse(&["run", "--max-depth", &usize::MAX.to_string(), "main.why"]).is_err());
+        assert!(parse(&["run", "--max-depth", "deep", "main.why"]).is_err());
+        assert!(parse(&["run", "--max-depth", "main.why"]).is_err());
+        assert!(parse(&["check", "--max-depth", "8", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_optimize_flags() -> Result<(), String> {
+        assert_eq!(parse(&["run", "main.why"])?.opt_level, 2);
+        assert_eq!(
+            parse(&["run", "--opt-level", "0", "main.why"])?.opt_level,
+            0
+        );
+        assert_eq!(parse(&["build", "--opt-level=1", "main.why"])?.opt_level, 1);
+        assert!(parse(&["ast", "--optimized", "main.why"])?.optimized);
+        assert!(parse(&["ast", "--optimized", "--opt-level", "1", "main.why"]).is_ok());
+        assert!(parse(&["ast", "--opt-level", "1", "main.why"]).is_err());
+        assert!(parse(&["run", "--opt-level", "3", "main.why"]).is_err());
+        assert!(parse(&["run", "--opt-level", "fast", "main.why"]).is_err());
+        assert!(parse(&["run", "--optimized", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_jit_flag() -> Result<(), String> {
+        assert!(parse(&["run", "--jit", "main.why"])?.jit);
+        assert!(!parse(&["run", "main.why"])?.jit);
+        assert!(parse(&["build", "--jit", "main.why"]).is_err());
+        assert!(parse(&["run", "--jit", "--vm", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_flag() -> Result<(), String> {
+        let cli = parse(&["build", "-o", "out.whyc", "main.why"])?;
+        assert_eq!(cli.output.as_deref(), Some("out.whyc"));
+        assert_eq!(parse(&["build", "main.why"])?.output, None);
+        assert!(parse(&["build", "--output", "out.whyc", "-"]).is_ok());
+        assert!(parse(&["build", "-"]).is_err());
+        assert!(parse(&["build", "main.why", "-o"]).is_err());
+        assert!(parse(&["run", "-o", "out.whyc", "main.why"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_emit_flag() -> Result<(), String> {
+        assert_eq!(parse(&["build", "main.why"])?.emit, Emit::Whyc);
+        assert_eq!(parse(&["build", "--emit", "c", "main.why"])?.emit, Emit::C);
+        assert_eq!(
+            parse(&["build", "--emit=whyc", "main.why"])?.emit,
+            Emit::Whyc
+        );
+        assert_eq!(
+            parse(&["build", "--emit=wasm", "main.why"])?.emit,
+            Emit::Wasm
+        );
+        assert_eq!(Emit::C.extension(), "c");
+        assert_eq!(Emit::Wat.extension(), "wat");
+        assert!(parse(&["build", "--emit", "asm", "main.why"]).is_err());
+        assert!(parse(&["run", "--emit", "c", "main.why"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+#[cfg(test)]
+mod test_doc {
+    use why_rs::doc::{self, DocFormat, ItemKind};
+    use why_rs::Expr;
+    use why_rs::Parser;
+
+    const SRC: &str = "\
+/// The answer.
+const int ANSWER = 42;
+
+/// Adds, see [Point].
+$add(int a, int b) @int { @a + b; }
+
+/// A <point>.
+&Point {
+    /// Across.
+    int x = 0;
+    /// Length.
+    $len(&self) @int { @x; }
+}
+
+/// Makes a point.
+$origin() @Point {}
+
+int hidden = 1;
+";
+
+    fn parse(src: &str) -> Result<Expr, String> {
+        Parser::from_source(src)?.parse()
+    }
+
+    #[test]
+    fn test_collect() -> Result<(), String> {
+        let items = doc::collect(&parse(SRC)?);
+        let names: Vec<_> = items
+            .iter()
+            .map(|item| (item.kind, item.name.as_str()))
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                (ItemKind::Constant, "ANSWER"),
+                (ItemKind::Function, "add"),
+                (ItemKind::Class, "Point"),
+                (ItemKind::Function, "origin"),
+            ]
+        );
+        assert_eq!(items[1].signature, "$add(int a, int b) @int");
+        assert_eq!(items[3].types, vec!["Point"]);
+
+        let members: Vec<_> = items[2].members.iter().map(|item| item.anchor()).collect();
+        assert_eq!(members, vec!["field.Point.x", "method.Point.len"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_markdown() -> Result<(), String> {
+        let markdown = doc::generate("lib", &parse(SRC)?, DocFormat::Markdown);
+
+        assert!(markdown.starts_with("# lib\n"));
+        assert!(markdown.contains("- [ANSWER](#const.ANSWER) (constant): The answer."));
+        assert!(markdown.contains("<a id=\"method.Point.len\"></a>"));
+        assert!(markdown.contains("Adds, see [Point](#class.Point)."));
+        assert!(markdown.contains("### `$origin() @Point`\n\nUses [Point](#class.Point).\n"));
+        assert!(!markdown.contains("hidden"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_html() -> Result<(), String> {
+        let html = doc::generate("lib", &parse(SRC)?, DocFormat::Html);
+
+        assert!(html.contains("<p>A &lt;point&gt;.</p>"));
+        assert!(html.contains("<a href=\"#class.Point\">Point</a>"));
+        assert!(html.contains("<code>$origin() @<a href=\"#class.Point\">Point</a></code>"));
+        assert!(html.contains("<code>$add(int a, int b) @int</code>"));
+        assert!(html.contains("\"anchor\": \"field.Point.x\""));
+        assert!(html.contains("<input id=\"search\""));
+        Ok(())
+    }
+}
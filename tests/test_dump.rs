@@ -0,0 +1,134 @@
+#[cfg(test)]
+mod test_dump {
+    use why_rs::dump::{self, Dump, Format};
+    use why_rs::json;
+    use why_rs::Expr;
+    use why_rs::Lexer;
+    use why_rs::Parser;
+    use why_rs::Token;
+
+    fn lex(src: &str) -> Result<Vec<Token<'_>>, String> {
+        Lexer::new(src)?.lex()
+    }
+
+    fn parse(src: &str) -> Result<Expr, String> {
+        Parser::from_tokens(lex(src)?).parse()
+    }
+
+    #[test]
+    fn test_tokens_json() -> Result<(), String> {
+        let expected = r#"[
+  {
+    "kind": "token",
+    "type": "Ident",
+    "value": "x",
+    "float": false,
+    "line": 1,
+    "col": 1
+  },
+  {
+    "kind": "token",
+    "type": "NumLiteral",
+    "value": "2.5",
+    "float": true,
+    "line": 1,
+    "col": 3
+  },
+  {
+    "kind": "token",
+    "type": "Eof",
+    "value": "",
+    "float": false,
+    "line": 1,
+    "col": 6
+  }
+]
+"#;
+
+        assert_eq!(dump::tokens(&lex("x 2.5")?, Format::Json), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokens_sexpr() -> Result<(), String> {
+        assert_eq!(
+            dump::tokens(&lex("'a'")?, Format::Sexpr),
+            "((token \"StrLiteral\" \"a\" false 1 1) (token \"Eof\" \"\" false 1 4))\n",
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expr_sexpr() -> Result<(), String> {
+        let ast = parse("let x = -(1 + 2.0) * y[0]; if x < 3 { break; }")?;
+        let expected = "\
+(program
+  ((var
+     \"let\"
+     nil
+     \"x\"
+     (binary
+       \"*\"
+       (unary \"-\" (paren (binary \"+\" (int 1) (float 2.0))))
+       (index (ident \"y\") (int 0)))
+     (loc 1 5)
+     nil)
+   (if (compare \"<\" (ident \"x\") (int 3)) ((break)) nil (loc 1 28))))
+";
+
+        assert_eq!(dump::expr(&ast, Format::Sexpr), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expr_json() -> Result<(), String> {
+        let ast = parse("$f() { @; }")?;
+        let expected = r#"{
+  "kind": "program",
+  "body": [
+    {
+      "kind": "func",
+      "name": "f",
+      "params": [],
+      "ret": "void",
+      "body": [
+        {
+          "kind": "return",
+          "value": null,
+          "loc": {
+            "kind": "loc",
+            "line": 1,
+            "col": 8
+          }
+        }
+      ],
+      "loc": {
+        "kind": "loc",
+        "line": 1,
+        "col": 2
+      },
+      "doc": null
+    }
+  ]
+}
+"#;
+
+        assert_eq!(dump::expr(&ast, Format::Json), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_escapes() {
+        assert_eq!(json::quote("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+        assert_eq!(Dump::Float(f64::INFINITY).write(Format::Json), "null\n");
+    }
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!(Format::try_from("json"), Ok(Format::Json));
+        assert_eq!(Format::try_from("sexpr"), Ok(Format::Sexpr));
+        assert_eq!(Format::try_from("text"), Ok(Format::Text));
+        assert!(Format::try_from("yaml").is_err());
+    }
+}
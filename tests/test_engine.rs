@@ -0,0 +1,225 @@
+#[cfg(test)]
+mod test_engine {
+    use std::thread;
+
+    use why_rs::Engine;
+    use why_rs::FromValue;
+    use why_rs::Value;
+
+    #[test]
+    fn test_eval_keeps_declarations() -> Result<(), String> {
+        let mut engine = Engine::new();
+
+        engine.eval("int x = 40; $inc(int n) @int { @n + 1; }")?;
+        assert_eq!(engine.eval("inc(x) + 1;")?, Value::Int(42));
+        assert!(engine.eval("int y = \"one\";").is_err());
+        assert!(engine.eval("y;").is_err());
+
+        // Nor do runtime errors, after which the variable was never set
+        assert!(engine.eval("int z = 1 / 0;").is_err());
+        assert!(engine.eval("$g() @int { @z; }").is_err());
+        assert_eq!(engine.eval("int z = 2; z;")?, Value::Int(2));
+
+        // But the declarations that ran before one failed are kept
+        assert!(engine
+            .eval("int a = 1; $f() @int { @a; } 1 / 0; int b = 2;")
+            .is_err());
+        assert_eq!(engine.global::<i64>("a")?, 1);
+        assert_eq!(engine.eval("a + f();")?, Value::Int(2));
+        assert!(engine.eval("b;").is_err());
+
+        // Including redeclarations, as their new type
+        assert!(engine.eval("string a = \"one\"; 1 / 0;").is_err());
+        assert_eq!(
+            engine.eval("a + \"!\";")?,
+            Value::String("one!".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_natives() -> Result<(), String> {
+        let mut engine = Engine::new();
+
+        engine.register("add", |a: i64, b: i64| a + b)?;
+        engine.register("mean", |xs: Vec<f64>| {
+            xs.iter().sum::<f64>() / xs.len() as f64
+        })?;
+        engine.register("shout", |s: String| s.to_uppercase())?;
+        engine.register("answer", || 42)?;
+        engine.register("halve", |n: i64| match n % 2 {
+            0 => Ok(n / 2),
+            _ => Err(format!("{n} is odd")),
+        })?;
+
+        assert_eq!(engine.eval("add(1, 2);")?, Value::Int(3));
+        assert_eq!(engine.eval("mean([1, 2.5, 4.5, 2]);")?, Value::Float(2.5));
+        assert_eq!(engine.eval("shout(\"why\");")?, Value::String("WHY".into()));
+        assert_eq!(engine.eval("let f = answer; f();")?, Value::Int(42));
+        assert_eq!(engine.eval("halve(4);")?, Value::Int(2));
+        assert_eq!(
+            engine.eval("halve(3);"),
+            Err("Traceback (innermost last):\n  line 1, column 1, in <main>\n     1 | halve(3);\n       | ^\n3 is odd".to_string())
+        );
+
+        // Natives are type checked like Why functions
+        assert!(engine.eval("add(1, \"2\");").is_err());
+        assert!(engine.eval("add(1);").is_err());
+        assert!(engine.eval("string s = add(1, 2);").is_err());
+        assert!(engine.register("print", |_: i64| 0).is_err());
+        assert!(engine.register("while", || 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_globals() -> Result<(), String> {
+        let mut engine = Engine::new();
+
+        engine.set_global("name", "why")?;
+        engine.set_global("scores", vec![1_i64, 2, 3])?;
+        engine.set_global("empty", Vec::<String>::new())?;
+        engine.eval("scores[0] = len(name); empty = empty + [\"a\"];")?;
+
+        assert_eq!(engine.global::<Vec<i64>>("scores")?, [3, 2, 3]);
+        assert_eq!(engine.global::<Vec<String>>("empty")?, ["a"]);
+        assert_eq!(engine.global::<Value>("name")?, Value::String("why".into()));
+        assert!(engine.global::<i64>("name").is_err());
+        assert!(engine.global::<i64>("missing").is_err());
+
+        // Globals keep their type
+        assert!(engine.eval("scores = [\"x\"];").is_err());
+        engine.set_global("name", 2.5)?;
+        assert_eq!(engine.eval("name * 2;")?, Value::Float(5.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_call() -> Result<(), String> {
+        let mut engine = Engine::new();
+
+        engine.eval(
+            "$fib(int n) @int { if n < 2 { @n; } @fib(n - 1) + fib(n - 2); }\n\
+             $greet(string name, float times) @string { @name + \"!\" + str(times); }\n\
+             int count = 0;",
+        )?;
+
+        assert_eq!(engine.call::<i64>("fib", (20_i64,))?, 6765);
+        assert_eq!(engine.call::<String>("greet", ("hi", 2_i64))?, "hi!2.0");
+        assert_eq!(
+            engine.call::<Value>("fib", vec![Value::Int(10)])?,
+            Value::Int(55)
+        );
+        assert!(engine.call::<i64>("fib", ("ten",)).is_err());
+        assert!(engine.call::<String>("fib", (1_i64,)).is_err());
+        assert!(engine.call::<i64>("count", ()).is_err());
+        assert!(engine.call::<i64>("missing", ()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_instruction_budget() -> Result<(), String> {
+        let mut engine = Engine::new().with_instruction_budget(1_000);
+
+        engine.eval("$spin() { while true { } }")?;
+        assert!(engine
+            .eval("spin();")
+            .is_err_and(|e| e.ends_with("Instruction budget exceeded: more than 1000 steps")));
+        assert!(engine.call::<()>("spin", ()).is_err());
+
+        // Each run gets the whole budget
+        for _ in 0..3 {
+            assert_eq!(
+                engine.eval("int i = 0; while i < 50 { i++; } i;")?,
+                Value::Int(50)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_limit() -> Result<(), String> {
+        let mut engine = Engine::new().with_memory_limit(64 * 1024);
+
+        engine.eval("let s = \"0123456789\";")?;
+        assert!(engine
+            .eval("while true { s = s + s; }")
+            .is_err_and(|e| e.contains("Memory limit exceeded")));
+
+        // Memory freed by deleting variables can be used again
+        engine.eval("delete s; let xs = []; int i = 0; while i < 100 { xs = xs + [i]; i++; }")?;
+        assert!(engine.set_global("big", "x".repeat(128 * 1024)).is_err());
+        assert_eq!(engine.eval("len(xs);")?, Value::Int(100));
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_limit_collects_cycles() -> Result<(), String> {
+        let mut engine = Engine::new().with_memory_limit(64 * 1024);
+
+        engine.eval("&Pair { string s = \"0123456789\"; Pair other; }")?;
+        engine
+            .eval("$pair() @Pair { Pair a = Pair(); a.other = Pair(); a.other.other = a; @a; }")?;
+
+        // Far more pairs than fit are made, but each is garbage by the next
+        engine.eval("int i = 0; while i < 2000 { pair(); i++; }")?;
+
+        // Unless they're kept
+        assert!(engine
+            .eval("let ps = []; while true { ps = ps + [pair()]; }")
+            .is_err_and(|e| e.contains("Memory limit exceeded")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_have_tracebacks() -> Result<(), String> {
+        let mut engine = Engine::new().with_max_depth(16);
+
+        let error = engine
+            .eval("$f(int n) @int {\n    @f(n + 1);\n}\nf(0);")
+            .unwrap_err();
+
+        assert!(
+            error.starts_with("Traceback (innermost last):\n"),
+            "{}",
+            error
+        );
+        assert!(
+            error.ends_with("Stack overflow: more than 16 nested calls"),
+            "{}",
+            error
+        );
+
+        // Calls from the host are rendered the same way
+        engine.eval("$g(int n) @int {\n    @n / 0;\n}")?;
+        let error = engine.call::<i64>("g", (1_i64,)).unwrap_err();
+        assert_eq!(
+            error,
+            "Traceback (innermost last):\n  called from the host\n  line 2, column 5, in g\nError! --> Division by zero"
+        );
+        assert_eq!(i64::from_value(Value::Int(1))?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_runaway_recursion_on_a_small_thread() -> Result<(), String> {
+        // The default stack limit leaves room for the host in 2MB
+        let run = thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(|| {
+                let mut engine = Engine::new();
+                let eval = engine.eval("$f(int n) @int { @f(n + 1); } f(0);");
+                let call = engine.call::<i64>("f", (0,));
+                (eval.map(|_| ()), call.map(|_| ()))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let (eval, call) = run
+            .join()
+            .map_err(|_| "The runaway recursion crashed".to_string())?;
+
+        assert!(eval.is_err_and(|e| e.contains("Stack overflow")));
+        assert!(call.is_err_and(|e| e.contains("Stack overflow")));
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+#[cfg(test)]
+mod test_formatter {
+    use why_rs::Expr;
+    use why_rs::Formatter;
+    use why_rs::Lexer;
+    use why_rs::Operator;
+    use why_rs::Parser;
+    use why_rs::Stmt;
+
+    fn parse(src: &str) -> Result<Expr, String> {
+        Parser::from_source(src)?.parse()
+    }
+
+    fn format(src: &str) -> Result<String, String> {
+        Ok(Formatter::format(&parse(src)?))
+    }
+
+    #[test]
+    fn test_format_declarations() -> Result<(), String> {
+        assert_eq!(format("int   x=1")?, "int x = 1;\n");
+        assert_eq!(
+            format("const float y=2.5;let z=x;")?,
+            "const float y = 2.5;\nlet z = x;\n"
+        );
+        assert_eq!(
+            format("mapping@string->array@int m=&{'a'->[1,2],};")?,
+            "mapping@string->array@int m = &{\"a\" -> [1, 2]};\n",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_blocks() -> Result<(), String> {
+        let src = "$f(int a,int b)@int{if a<b{@a;}else if a==b{@0;}else{@b;}} f(1,2);x++;";
+        let expected = "\
+$f(int a, int b) @int {
+    if a < b {
+        @a;
+    } else if a == b {
+        @0;
+    } else {
+        @b;
+    }
+}
+
+f(1, 2);
+x++;
+";
+
+        assert_eq!(format(src)?, expected);
+        assert_eq!(
+            format("$log(string s){print(s);}")?,
+            "$log(string s) {\n    print(s);\n}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_is_idempotent() -> Result<(), String> {
+        let src = "while i<10{i+=1;if !done{break;}} %print('say \"hi\"', -(1+2)**2, xs[0], p . next.get());";
+        let once = format(src)?;
+
+        assert_eq!(format(&once)?, once);
+        assert_eq!(
+            Formatter::format(&parse(&once)?),
+            Formatter::format(&parse(src)?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_adds_needed_parentheses() {
+        let sum = Expr::BinaryOp(
+            Operator::Add,
+            Box::new(Expr::Int(1)),
+            Box::new(Expr::Int(2)),
+        );
+        let product = Expr::BinaryOp(
+            Operator::Mult,
+            Box::new(sum.clone()),
+            Box::new(Expr::Int(3)),
+        );
+        let difference = Expr::BinaryOp(Operator::Subtract, Box::new(Expr::Int(3)), Box::new(sum));
+
+        assert_eq!(Formatter::expr(&product), "(1 + 2) * 3");
+        assert_eq!(Formatter::expr(&difference), "3 - (1 + 2)");
+    }
+
+    #[test]
+    fn test_format_keeps_comments() -> Result<(), String> {
+        let src = "// header\nint   x=1; // one\n/* about\n   f */\n$f(){ // opens\n  x++; /* bump */\n  // last\n} // end\nprint(x);";
+        let expected = "\
+// header
+int x = 1; // one
+
+/* about
+   f */
+$f() { // opens
+    x++; /* bump */
+    // last
+} // end
+
+print(x);
+";
+
+        assert_eq!(Formatter::format_source(src)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_source_is_idempotent() -> Result<(), String> {
+        let src = "if a{b();} // after\nelse{ c(); }\nlet m=&{1->[2, // mid\n3]};\n// end";
+        let once = Formatter::format_source(src)?;
+
+        assert_eq!(Formatter::format_source(&once)?, once);
+        assert!(once.contains("// after"));
+        assert!(once.contains("// mid"));
+        assert!(once.ends_with("// end\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_keeps_doc_comments() -> Result<(), String> {
+        let src =
+            "///  Adds.\n$add(int a,int b)@int{@a+b;}\n&P{\n/// X.\nint x=0;}\n/// stray\nf();";
+        let expected = "\
+///  Adds.
+$add(int a, int b) @int {
+    @a + b;
+}
+
+&P {
+    /// X.
+    int x = 0;
+}
+
+/// stray
+f();
+";
+
+        assert_eq!(Formatter::format_source(src)?, expected);
+        assert_eq!(Formatter::format_source(expected)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_keeps_mixed_comments_in_order() -> Result<(), String> {
+        let src = "/// doc\n// note\nlet x = 1;\n// note\n/// doc\nlet y = 2;\n/// a\n/* b */\n/// c\n$f() {}\n";
+        let expected = "\
+/// doc
+// note
+let x = 1;
+// note
+/// doc
+let y = 2;
+
+/// a
+/* b */
+/// c
+$f() {
+}
+";
+
+        assert_eq!(Formatter::format_source(src)?, expected);
+        assert_eq!(Formatter::format_source(expected)?, expected);
+
+        // Only the doc comment right above a declaration documents it
+        let tokens = Lexer::new(src)?.with_trivia().lex()?;
+        let Expr::Main(stmts) = Parser::from_tokens(tokens).parse()? else {
+            return Err("Expected a program".to_string());
+        };
+        let docs: Vec<_> = stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::VarDecl(decl) => Some(decl.doc.clone()),
+                Stmt::Func(func) => Some(func.doc.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(docs, [None, Some("doc".to_string()), Some("c".to_string())]);
+        Ok(())
+    }
+}
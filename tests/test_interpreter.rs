@@ -0,0 +1,448 @@
+#[cfg(test)]
+mod test_interpreter {
+    use why_rs::traceback;
+    use why_rs::CallSite;
+    use why_rs::Checker;
+    use why_rs::HeapStats;
+    use why_rs::Interpreter;
+    use why_rs::Loc;
+    use why_rs::Parser;
+    use why_rs::Symbol;
+    use why_rs::Value;
+    use why_rs::VarType;
+
+    fn run(src: &str) -> Result<Value, String> {
+        let program = Parser::from_source(src)?.parse()?;
+
+        Interpreter::new().run(&program)
+    }
+
+    fn check(src: &str) -> Result<Checker, String> {
+        let program = Parser::from_source(src)?.parse()?;
+
+        let mut checker = Checker::new();
+        checker.check(&program)?;
+        Ok(checker)
+    }
+
+    #[test]
+    fn test_arithmetic() -> Result<(), String> {
+        assert_eq!(run("(2 + 1) * 3")?, Value::Int(9));
+        assert_eq!(run("2 ** 10 - 1")?, Value::Int(1023));
+        assert_eq!(run("7 / 2")?, Value::Int(3));
+        assert_eq!(run("7 / 2.0")?, Value::Float(3.5));
+        assert_eq!(run("2 ** -1")?, Value::Float(0.5));
+        assert_eq!(run("-(3 - 5)")?, Value::Int(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_arithmetic_errors() {
+        assert!(run("1 / 0").is_err());
+        assert!(run("9223372036854775807 + 1").is_err());
+        assert!(run("1 + \"one\"").is_err());
+    }
+
+    #[test]
+    fn test_strings_and_comparisons() -> Result<(), String> {
+        assert_eq!(
+            run("\"why\" + \" not\"")?,
+            Value::String("why not".to_string())
+        );
+        assert_eq!(run("1 < 2.5")?, Value::Bool(true));
+        assert_eq!(run("\"a\" != \"b\"")?, Value::Bool(true));
+        assert_eq!(run("!(1 == 1)")?, Value::Bool(false));
+        Ok(())
+    }
+
+    #[test]
+    fn test_variables() -> Result<(), String> {
+        assert_eq!(run("int x = 1; x += 4; x++; x * 2")?, Value::Int(12));
+        assert_eq!(run("float f = 1; f")?, Value::Float(1.0));
+        assert!(run("const int x = 1; x = 2;").is_err());
+        assert!(run("int x = 1; x = \"one\";").is_err());
+        assert!(run("y").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_collections_widen_ints() -> Result<(), String> {
+        let half = Value::Float(1.5);
+
+        // Declared, assigned, assigned by index, bound and returned
+        assert_eq!(run("array@float xs = [3]; xs[0] / 2")?, half);
+        assert_eq!(run("array@float xs = [1.5]; xs = [3]; xs[0] / 2")?, half);
+        assert_eq!(run("array@float xs = [1.5]; xs[0] = 3; xs[0] / 2")?, half);
+        assert_eq!(
+            run("$f(array@float xs) @float { @xs[0] / 2; } f([3])")?,
+            half
+        );
+        assert_eq!(run("$f() @array@float { @[3]; } f()[0] / 2")?, half);
+
+        // Mappings widen their keys and values, and nested collections too
+        assert_eq!(
+            run("mapping@string->float m = &{\"a\" -> 3}; m[\"a\"] / 2")?,
+            half
+        );
+        assert_eq!(
+            run("mapping@string->float m = &{}; m[\"a\"] = 3; m[\"a\"] / 2")?,
+            half
+        );
+        assert_eq!(
+            run("array@array@float grid = [[1], [3]]; grid[1][0] / 2")?,
+            half
+        );
+        assert_eq!(run("array@int xs = [3]; xs[0] / 2")?, Value::Int(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_collections() -> Result<(), String> {
+        assert_eq!(
+            run("array@int xs = [1, 2, 3]; xs[1] = 5; xs[1] + len(xs)")?,
+            Value::Int(8)
+        );
+        assert_eq!(
+            run("let m = &{\"a\" -> 1}; m[\"b\"] = 2; m[\"a\"] + m[\"b\"]")?,
+            Value::Int(3),
+        );
+        assert_eq!(run("\"why\"[1]")?, Value::String("h".to_string()));
+        assert!(run("[1][1]").is_err());
+        assert!(run("&{1 -> 2}[3]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_functions() -> Result<(), String> {
+        let src = "
+            $fib(int n) @int {
+                if n < 2 {
+                    @n;
+                }
+
+                @fib(n - 1) + fib(n - 2);
+            }
+
+            fib(15)
+        ";
+
+        assert_eq!(run(src)?, Value::Int(610));
+        assert_eq!(run("later(); $later() @int { @1; }")?, Value::Null);
+        assert!(run("$f(int a) {} f();").is_err());
+        assert!(run("$f(int a) {} f(\"a\");").is_err());
+        assert!(run("$f() @int {} f();").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_loops() -> Result<(), String> {
+        let src = "
+            int total = 0;
+            int i = 0;
+
+            while true {
+                i++;
+
+                if i > 10 {
+                    break;
+                }
+
+                total += i;
+            }
+
+            total
+        ";
+
+        assert_eq!(run(src)?, Value::Int(55));
+        assert!(run("while 1 {}").is_err());
+        assert!(run("break;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_scopes() -> Result<(), String> {
+        assert!(run("if true { int inner = 1; } inner").is_err());
+        assert_eq!(run("int x = 1; if true { x = 2; } x")?, Value::Int(2));
+        assert!(run("$f() @int { @outer; } int outer = 1; $g() { int outer = 2; } f()").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete() -> Result<(), String> {
+        assert!(run("int x = 1; delete x; x").is_err());
+        assert!(run("delete x;").is_err());
+        assert!(run("$f() {} delete f; f();").is_err());
+        assert_eq!(
+            run("int x = 1; if true { int x = 2; delete x; x++; } x")?,
+            Value::Int(2)
+        );
+        assert_eq!(
+            run("int x = 1; $f() { delete x; } f(); int x = 3; x")?,
+            Value::Int(3)
+        );
+
+        assert!(check("int x = 1; delete x; x;").is_err());
+        assert!(check("delete nothing;").is_err());
+        assert!(check("int x = 1; delete x; string x = \"x\"; x;").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_traceback() -> Result<(), String> {
+        let src = "$inner(int x) @int {\n    @x / 0;\n}\n\
+                   $outer(int x) @int {\n    @inner(x + 1);\n}\n\
+                   outer(1);";
+        let program = Parser::from_source(src)?.parse()?;
+        let mut interpreter = Interpreter::new();
+
+        assert!(interpreter.run(&program).is_err());
+        assert_eq!(
+            interpreter.traceback,
+            [
+                CallSite {
+                    name: Symbol::intern("outer"),
+                    loc: Loc::at(7, 1),
+                },
+                CallSite {
+                    name: Symbol::intern("inner"),
+                    loc: Loc::at(5, 6),
+                },
+                CallSite {
+                    name: Symbol::intern("inner"),
+                    loc: Loc::at(2, 5),
+                },
+            ]
+        );
+        assert!(interpreter.calls.is_empty());
+
+        // A later run starts a new traceback, failing at the top level
+        let program = Parser::from_source("int x = 1;\nx / 0;")?.parse()?;
+        assert!(interpreter.run(&program).is_err());
+        assert_eq!(
+            interpreter.traceback,
+            [CallSite {
+                name: Symbol::intern("<main>"),
+                loc: Loc::at(2, 1),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_overflow() -> Result<(), String> {
+        let depth = |n: usize, max_depth: usize| -> Result<Value, String> {
+            let src = format!(
+                "$depth(int n) @int {{ if n == 0 {{ @0; }} @depth(n - 1) + 1; }} depth({n});"
+            );
+            let program = Parser::from_source(&src)?.parse()?;
+
+            Interpreter::new().with_max_depth(max_depth).run(&program)
+        };
+
+        assert_eq!(depth(9, 10)?, Value::Int(9));
+        assert_eq!(
+            depth(10, 10),
+            Err("Error! --> Stack overflow: more than 10 nested calls".to_string())
+        );
+        let runaway = |stack_size: usize, stack_limit: Option<usize>| {
+            std::thread::Builder::new()
+                .stack_size(stack_size)
+                .spawn(move || {
+                    let program = Parser::from_source("$f() @int { @f(); } f();")?.parse()?;
+                    let interpreter = Interpreter::new();
+                    let mut interpreter = match stack_limit {
+                        Some(limit) => interpreter.with_stack_limit(limit),
+                        None => interpreter,
+                    };
+
+                    interpreter.run(&program).map(|_| ())
+                })
+                .map_err(|e| e.to_string())?
+                .join()
+                .map_err(|_| "The runaway recursion crashed".to_string())
+        };
+
+        // By default, the stack runs out before a spawned thread's does
+        assert!(runaway(2 << 20, None)?.is_err_and(|e| e.contains("Stack overflow")));
+
+        // The default depth is reached on a thread sized for it
+        let limit = traceback::stack_limit(traceback::MAX_DEPTH);
+        let deep = runaway(traceback::stack_size(traceback::MAX_DEPTH), Some(limit))?;
+        assert!(deep.is_err_and(|e| e.contains("Stack overflow: more than 512")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_heap_stats() -> Result<(), String> {
+        let stats = |src: &str| -> Result<HeapStats, String> {
+            let program = Parser::from_source(src)?.parse()?;
+            let mut interpreter = Interpreter::new().with_heap_stats();
+
+            interpreter.run(&program)?;
+            Ok(interpreter.heap.unwrap_or_default())
+        };
+
+        let counted = |allocated, freed, peak| HeapStats {
+            allocated,
+            freed,
+            peak,
+            ..HeapStats::default()
+        };
+
+        assert_eq!(stats("int x = 1; x = 2;")?, counted(0, 0, 0));
+        assert_eq!(stats("let s = \"a\"; s = s + \"b\";")?, counted(2, 1, 2));
+        assert_eq!(
+            stats("let xs = [\"a\", \"b\"]; delete xs;")?,
+            counted(3, 3, 3)
+        );
+        assert_eq!(stats("let xs = [[1], [2]]; xs[0] = [];")?, counted(4, 1, 4));
+        assert_eq!(
+            stats("$f(string s) @int { let t = s; @1; } f(\"a\"); f(\"b\");")?,
+            counted(5, 4, 3)
+        );
+        assert_eq!(stats("let x = [1]; let x = [2];")?.live(), 1);
+        assert_eq!(
+            stats("let xs = [\"a\"]; delete xs;")?.to_string(),
+            "heap: 2 allocated, 2 freed, 0 live, 2 at peak; 0 objects made, 0 collected in 0 collections"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_classes() -> Result<(), String> {
+        let node = "&Node {\n    int value = 1;\n    Node next;\n    $get(&self) @int { @self.value; }\n    $twice(&self, int n) @int { @self.get() * n; }\n}\n";
+        let run_node = |src: &str| run(&format!("{node}{src}"));
+
+        assert_eq!(run_node("Node().twice(21)")?, Value::Int(21));
+        assert_eq!(
+            run_node("Node a = Node(); Node b = a; b.value = 5; a.value")?,
+            Value::Int(5)
+        );
+        assert_eq!(
+            run_node("Node a = Node(); a.next = a; a.next.next.get()")?,
+            Value::Int(1)
+        );
+        assert_eq!(run_node("Node().next == NULL")?, Value::Bool(true));
+        assert_eq!(run_node("let f = Node().get; f()")?, Value::Int(1));
+        assert_eq!(
+            run("&A { int x = 1; $get(&self) @int { @self.x; } }\n&B is A { int y = 2; $get(&self) @int { @self.x + self.y; } }\nB().get()")?,
+            Value::Int(3)
+        );
+        assert!(run_node("Node().missing").is_err());
+        assert!(run_node("Node().next.value").is_err());
+
+        assert!(check(&format!("{node}Node n = Node(); n.value = n.get() + 1;")).is_ok());
+        assert!(check(&format!("{node}Node().value = \"one\";")).is_err());
+        assert!(check(&format!("{node}Node().get = 1;")).is_err());
+        assert!(check(&format!("{node}Node().missing;")).is_err());
+        assert!(check(&format!("{node}int x = Node().twice();")).is_err());
+        assert!(check("&B is Missing {}").is_err());
+        assert!(check("int x = 1; x.y;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_collects_cycles() -> Result<(), String> {
+        let pairs = "&Node { Node next; }\n$pair() { Node a = Node(); a.next = Node(); a.next.next = a; }\nint i = 0; while i < 10 { pair(); i++; }";
+        let program = Parser::from_source(pairs)?.parse()?;
+
+        // The cycles outlive the calls that made them until collected
+        let mut interpreter = Interpreter::new().with_heap_stats();
+        interpreter.run(&program)?;
+        assert_eq!(interpreter.gc.objects.len(), 20);
+        assert_eq!(interpreter.collect(), 20);
+        assert_eq!(interpreter.collect(), 0);
+
+        let heap = interpreter.heap.unwrap_or_default();
+        assert_eq!(
+            (heap.objects, heap.collected, heap.collections),
+            (20, 20, 2)
+        );
+
+        // Or once enough objects were made since the last collection
+        let mut interpreter = Interpreter::new().with_heap_stats().with_gc_threshold(4);
+        interpreter.run(&program)?;
+        let heap = interpreter.heap.unwrap_or_default();
+        assert!(heap.collections >= 4, "{}", heap);
+        assert_eq!(heap.collected + interpreter.collect(), 20);
+
+        // Objects the program can still reach are kept
+        let kept = "&Node { Node next; }\nNode a = Node(); a.next = Node(); a.next.next = a;";
+        let mut interpreter = Interpreter::new();
+        interpreter.run(&Parser::from_source(kept)?.parse()?)?;
+        assert_eq!(interpreter.collect(), 0);
+
+        // Deleting a variable that held one collects straight away
+        let deleted = format!("{kept}\ndelete a;");
+        let mut interpreter = Interpreter::new().with_heap_stats();
+        interpreter.run(&Parser::from_source(&deleted)?.parse()?)?;
+        let heap = interpreter.heap.unwrap_or_default();
+        assert_eq!((heap.collected, heap.collections), (2, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_repr() {
+        let value = Value::Array(vec![Value::String("a".to_string()), Value::Float(1.0)]);
+        assert_eq!(value.repr(), "[\"a\", 1.0]");
+        assert_eq!(Value::String("a".to_string()).to_string(), "a");
+        assert_eq!(value.typ(), VarType::Array(Box::new(VarType::String)));
+    }
+
+    #[test]
+    fn test_checker_infers_types() -> Result<(), String> {
+        let mut checker = check("$half(int n) @float { @n / 2.0; } let xs = [1, 2];")?;
+
+        assert_eq!(
+            checker.lookup("half".into()).map(|b| b.0.to_string()),
+            Some("$(int)@float".to_string()),
+        );
+        assert_eq!(
+            checker.lookup("xs".into()).map(|b| b.0.clone()),
+            Some(VarType::Array(Box::new(VarType::Int)))
+        );
+
+        let expr = why_rs::Expr::Call(
+            Box::new(why_rs::Expr::Ident("half".into())),
+            vec![why_rs::Expr::Int(3)],
+            why_rs::Loc::new(),
+        );
+        assert_eq!(checker.infer(&expr)?, VarType::Float);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checker_rejects_type_errors() {
+        // Errors are reported where their statement or call is
+        let errors = [
+            ("int x = \"one\";", "line 1, column 5:"),
+            ("$f(int a) @int { @\"a\"; }", "line 1, column 18:"),
+            ("$f(int a) @int { @a; } f(1, 2);", "line 1, column 24:"),
+            ("if 1 {}", "line 1, column 1:"),
+            ("let xs = [1, \"two\"];", "line 1, column 5:"),
+            ("@1;", "line 1, column 1:"),
+            ("const int x = 1; x += 1;", "line 1, column 18:"),
+            ("$f() @int {\n    @b;\n}", "line 2, column 5:"),
+            ("$f() @int { @1; }\nf() + \"a\";", "line 2, column 1:"),
+            ("int x = 1;\nprint(x, -\"a\");", "line 2, column 1:"),
+            ("int x = 1;\nx();", "line 2, column 1:"),
+            ("while 1 {}\n", "line 1, column 1:"),
+        ];
+
+        for (src, prefix) in errors {
+            let error = check(src).unwrap_err();
+            assert!(error.starts_with(prefix), "{src}: {error}");
+        }
+    }
+
+    #[test]
+    fn test_checker_hides_enclosing_locals() {
+        // Functions only see their own variables and the globals
+        let src = "$outer() @int { int y = 10; $inner() @int { @y + 1; } @inner(); }";
+        assert!(check(src).unwrap_err().contains("Undefined variable 'y'"));
+        assert!(run(&format!("{src} outer();")).is_err());
+
+        assert!(check("int y = 10; $outer() @int { $inner() @int { @y; } @inner(); }").is_ok());
+        assert!(check("$f() @int { int y = 1; if true { @y; } @0; }").is_ok());
+    }
+}
@@ -0,0 +1,167 @@
+#[cfg(all(test, feature = "jit"))]
+mod test_jit {
+    use std::thread;
+
+    use why_rs::traceback;
+    use why_rs::Interpreter;
+    use why_rs::Parser;
+    use why_rs::Symbol;
+    use why_rs::Value;
+
+    /// Runs a program with and without the JIT, which must agree,
+    /// returning the JIT's interpreter and the result.
+    fn run(src: &str) -> (Interpreter, Result<Value, String>) {
+        let program = Parser::from_source(src)
+            .and_then(|mut p| p.parse())
+            .unwrap();
+
+        let expected = Interpreter::new().run(&program);
+        let mut interpreter = Interpreter::with_jit().unwrap();
+        let result = interpreter.run(&program);
+
+        assert_eq!(result, expected, "{src}");
+        (interpreter, result)
+    }
+
+    fn is_compiled(interpreter: &Interpreter, name: &str) -> bool {
+        let Some(Value::Func(func)) = interpreter
+            .lookup(Symbol::intern(name))
+            .map(|b| b.value.clone())
+        else {
+            panic!("'{name}' is not a function");
+        };
+
+        let jit = interpreter.jit.as_ref().unwrap();
+        let compiled = jit.borrow().is_compiled(&func);
+        compiled
+    }
+
+    #[test]
+    fn test_compiles_numeric_functions() {
+        let (interpreter, result) = run(
+            "$fib(int n) @int { if n < 2 { @n; } @fib(n - 1) + fib(n - 2); }\n\
+             $mean(int n) @float {\n\
+                 float total = 0; int i = 0;\n\
+                 while true { i++; if i > n { break; } total += i; }\n\
+                 @total / n;\n\
+             }\n\
+             $check() @bool { @!(fib(10) == 55.0) != false; }\n\
+             $power(float x) @float { @x ** 2 + 2 ** 10; }\n\
+             fib(20) + mean(4) + power(1.5) - fib(3) ** 2;",
+        );
+
+        assert_eq!(result, Ok(Value::Float(6765.0 + 2.5 + 2.25 + 1024.0 - 4.0)));
+
+        for name in ["fib", "mean", "power"] {
+            assert!(is_compiled(&interpreter, name), "{}", name);
+        }
+
+        // Never called, so never compiled
+        assert!(!is_compiled(&interpreter, "check"));
+    }
+
+    #[test]
+    fn test_falls_back_to_the_interpreter() {
+        let (interpreter, result) = run("int calls = 0;\n\
+             $count() @int { calls++; @calls; }\n\
+             $name(int n) @string { @str(n); }\n\
+             $loud(int n) @int { print(n); @n; }\n\
+             $add(int a) @int { @a + count(); }\n\
+             count(); add(1); name(2); loud(3) + calls;");
+
+        assert_eq!(result, Ok(Value::Int(5)));
+
+        for name in ["count", "name", "loud", "add"] {
+            assert!(!is_compiled(&interpreter, name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_errors_match_interpreter() {
+        let programs = [
+            "$f(int n) @int { @n * n; } f(4294967296);",
+            "$f(int n) @int { @-n; } f(-9223372036854775807 - 1);",
+            "$f(int a, int b) @int { @a / b; } f(1, 0);",
+            "$f(int a) @int { @a / -1; } f(-9223372036854775807 - 1);",
+            "$f(int n) @int { if n > 0 { @n; } } f(1); f(0);",
+            "$f(float x) @bool { @x < 1; } f(0.0 / 0);",
+            "$f(int n) @int { @n ** 40; } f(3);",
+            "$f(int n) @int { @2 ** n; } f(-1);",
+            "$f(int n) @int { @n; } f(1.5);",
+            "$f(int n) @int { @n; } f(1, 2);",
+            "$f(int n) @int { @g(n) + 1; } $g(int n) @int { @n * 2 ** 62 * 2; } f(1);",
+        ];
+
+        for src in programs {
+            assert!(run(src).1.is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_negative_powers_fall_back() {
+        // Compiled code only makes ints from ints, so the interpreter
+        // makes the float
+        let (interpreter, result) = run("$f(int n) @float { @2 ** n; } f(3) + f(-1);");
+
+        assert_eq!(result, Ok(Value::Float(8.5)));
+        assert!(is_compiled(&interpreter, "f"));
+    }
+
+    #[test]
+    fn test_redeclared_functions() -> Result<(), String> {
+        let mut interpreter = Interpreter::with_jit()?;
+        let mut run = |src: &str| {
+            let program = Parser::from_source(src)?.parse()?;
+            interpreter.run(&program)
+        };
+
+        run("$g() @int { @1; } $f() @int { @g(); }")?;
+        assert_eq!(run("f();")?, Value::Int(1));
+        assert_eq!(run("$g() @int { @2; } f();")?, Value::Int(2));
+        assert_eq!(run("$g() @int { @3; } f();")?, Value::Int(3));
+        assert_eq!(run("$f() @int { @g() * 10; } f();")?, Value::Int(30));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_overflow() -> Result<(), String> {
+        let depth = "$depth(int n) @int { if n == 0 { @0; } @depth(n - 1) + 1; }";
+        let program = Parser::from_source(&format!("{depth} depth(9);"))?.parse()?;
+        let mut interpreter = Interpreter::with_jit()?.with_max_depth(10);
+
+        assert_eq!(interpreter.run(&program)?, Value::Int(9));
+        assert!(is_compiled(&interpreter, "depth"));
+
+        // Compiled code fails too, so the interpreter reports where
+        let program = Parser::from_source(&format!("{depth} depth(10);"))?.parse()?;
+        assert_eq!(
+            interpreter.run(&program),
+            Err("Error! --> Stack overflow: more than 10 nested calls".to_string())
+        );
+        assert_eq!(interpreter.traceback.len(), 11);
+
+        // Runaway recursion, on a thread with room for the default depth
+        let runaway = thread::Builder::new()
+            .stack_size(traceback::stack_size(traceback::MAX_DEPTH))
+            .spawn(|| {
+                let limit = traceback::stack_limit(traceback::MAX_DEPTH);
+                let program =
+                    Parser::from_source("$f(int n) @int { @f(n + 1); } f(0);")?.parse()?;
+
+                let expected = Interpreter::new().with_stack_limit(limit).run(&program);
+                let result = Interpreter::with_jit()?
+                    .with_stack_limit(limit)
+                    .run(&program);
+                Ok::<_, String>((expected.map(|_| ()), result.map(|_| ())))
+            })
+            .map_err(|e| e.to_string())?
+            .join()
+            .map_err(|_| "The runaway recursion crashed".to_string())??;
+
+        assert_eq!(runaway.0, runaway.1);
+        assert!(runaway
+            .1
+            .is_err_and(|e| e.contains("Stack overflow: more than 512")));
+        Ok(())
+    }
+}
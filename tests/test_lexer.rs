@@ -1,5 +1,5 @@
 #[allow(dead_code)] // TODO: Add more tests from this code here.
-const SOURCE: &'static str = {
+const SOURCE: &str = {
     r#"
 
     // Iterate each item in the list
@@ -61,7 +61,7 @@ mod test_lexer {
         assert_eq!(lexer.line, 1);
         assert_eq!(lexer.col, 1);
         assert_eq!(lexer.src.len(), src.len());
-        assert_eq!(lexer.src.iter().collect::<String>(), src.to_string());
+        assert_eq!(lexer.src, src);
         assert!(lexer.tokens.is_empty());
         println!("{}", lexer.tokens.capacity());
         assert!(lexer.tokens.capacity() == 4);
@@ -132,8 +132,8 @@ mod test_lexer {
 
         Lexer::skip_comment(&mut lexer, true)?;
         assert_eq!(lexer.line, 2);
-        assert_eq!(lexer.col, 9);
-        assert_eq!(lexer.idx, 29);
+        assert_eq!(lexer.col, 8);
+        assert_eq!(lexer.idx, 28);
 
         Ok(())
     }
@@ -168,7 +168,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_eq_token(),
-            Token { typ: TokenType::Eq, value: "=".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::Eq, value: "=".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -182,7 +182,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_eq_token(),
-            Token { typ: TokenType::EqEq, value: "==".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::EqEq, value: "==".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -196,7 +196,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_eq_token(),
-            Token { typ: TokenType::LargeRArrow, value: "=>".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::LargeRArrow, value: "=>".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -211,7 +211,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.tokens[0],
-            Token { typ: TokenType::Eq, value: "=".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::Eq, value: "=".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -226,7 +226,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.tokens[0],
-            Token { typ: TokenType::LargeRArrow, value: "=>".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::LargeRArrow, value: "=>".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -240,7 +240,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_minus_token(),
-            Token { typ: TokenType::Minus, value: "-".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::Minus, value: "-".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -254,7 +254,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_minus_token(),
-            Token { typ: TokenType::MinusMinus, value: "--".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::MinusMinus, value: "--".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -268,7 +268,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_minus_token(),
-            Token { typ: TokenType::MinusEq, value: "-=".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::MinusEq, value: "-=".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -282,7 +282,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_minus_token(),
-            Token { typ: TokenType::SmallRArrow, value: "->".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::SmallRArrow, value: "->".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -297,7 +297,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.tokens[0],
-            Token { typ: TokenType::Minus, value: "-".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::Minus, value: "-".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -312,7 +312,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.tokens[0],
-            Token { typ: TokenType::SmallRArrow, value: "->".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::SmallRArrow, value: "->".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -326,7 +326,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_plus_token(),
-            Token { typ: TokenType::Plus, value: "+".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::Plus, value: "+".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -340,7 +340,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_plus_token(),
-            Token { typ: TokenType::PlusPlus, value: "++".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::PlusPlus, value: "++".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -354,7 +354,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_plus_token(),
-            Token { typ: TokenType::PlusEq, value: "+=".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::PlusEq, value: "+=".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -369,7 +369,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.tokens[0],
-            Token { typ: TokenType::Plus, value: "+".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::Plus, value: "+".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -384,7 +384,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.tokens[0],
-            Token { typ: TokenType::PlusPlus, value: "++".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::PlusPlus, value: "++".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -398,7 +398,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_star_token(),
-            Token { typ: TokenType::Star, value: "*".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::Star, value: "*".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -412,7 +412,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_star_token(),
-            Token { typ: TokenType::StarStar, value: "**".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::StarStar, value: "**".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -426,7 +426,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.get_star_token(),
-            Token { typ: TokenType::StarEq, value: "*=".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::StarEq, value: "*=".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -441,7 +441,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.tokens[0],
-            Token { typ: TokenType::Star, value: "*".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::Star, value: "*".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -456,7 +456,7 @@ mod test_lexer {
 
         assert_eq!(
             lexer.tokens[0],
-            Token { typ: TokenType::StarStar, value: "**".to_string(), loc: Loc::default(), addtl: None },
+            Token { typ: TokenType::StarStar, value: "**".into(), loc: Loc::default(), raw: None },
         );
 
         Ok(())
@@ -470,10 +470,10 @@ mod test_lexer {
         let received_tokens = lexer.lex()?;
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Ident, value: "hello".to_string(), loc: Loc::default(), addtl: None },
-            Token { typ: TokenType::Ident, value: "world".to_string(), loc: Loc { line: 1, col: 7 }, addtl: None },
-            Token { typ: TokenType::Semi, value: ";".to_string(), loc: Loc { line: 1, col: 12 }, addtl: None },
-            Token { typ: TokenType::Eof, value: "".to_string(), loc: Loc { line: 1, col: 13 }, addtl: None },
+            Token { typ: TokenType::Ident, value: "hello".into(), loc: Loc::default(), raw: None },
+            Token { typ: TokenType::Ident, value: "world".into(), loc: Loc { line: 1, col: 7 }, raw: None },
+            Token { typ: TokenType::Semi, value: ";".into(), loc: Loc { line: 1, col: 12 }, raw: None },
+            Token { typ: TokenType::Eof, value: "".into(), loc: Loc { line: 1, col: 13 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, received_tokens);
@@ -488,9 +488,9 @@ mod test_lexer {
         let received_tokens = lexer.lex()?;
 
         let expected_tokens = vec![
-            Token { typ: TokenType::NumLiteral(false), value: "123".to_string(), loc: Loc::default(), addtl: None },
-            Token { typ: TokenType::Semi, value: ";".to_string(), loc: Loc { line: 1, col: 4 }, addtl: None },
-            Token { typ: TokenType::Eof, value: "".to_string(), loc: Loc { line: 1, col: 5 }, addtl: None },
+            Token { typ: TokenType::NumLiteral(false), value: "123".into(), loc: Loc::default(), raw: None },
+            Token { typ: TokenType::Semi, value: ";".into(), loc: Loc { line: 1, col: 4 }, raw: None },
+            Token { typ: TokenType::Eof, value: "".into(), loc: Loc { line: 1, col: 5 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, received_tokens);
@@ -505,7 +505,7 @@ mod test_lexer {
         Lexer::lex_semi(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Semi, value: ";".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::Semi, value: ";".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -520,7 +520,7 @@ mod test_lexer {
         Lexer::lex_dot(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Dot, value: ".".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::Dot, value: ".".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -535,7 +535,7 @@ mod test_lexer {
         Lexer::lex_comma(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Comma, value: ",".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::Comma, value: ",".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -550,7 +550,7 @@ mod test_lexer {
         Lexer::lex_colon(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Colon, value: ":".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::Colon, value: ":".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -565,7 +565,7 @@ mod test_lexer {
         Lexer::lex_at(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::At, value: "@".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::At, value: "@".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -580,7 +580,7 @@ mod test_lexer {
         Lexer::lex_and(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::And, value: "&".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::And, value: "&".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -595,7 +595,7 @@ mod test_lexer {
         Lexer::lex_dollar(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Dollar, value: "$".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::Dollar, value: "$".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -610,7 +610,7 @@ mod test_lexer {
         Lexer::lex_exclamation(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Exclamation, value: "!".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::Exclamation, value: "!".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -625,7 +625,7 @@ mod test_lexer {
         Lexer::lex_caret(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Caret, value: "^".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::Caret, value: "^".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -640,7 +640,7 @@ mod test_lexer {
         Lexer::lex_question_mark(&mut lexer);
 
         let expected_tokens = vec![
-            Token { typ: TokenType::QuestionMark, value: "?".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::QuestionMark, value: "?".into(), loc: Loc { line: 1, col: 1 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, lexer.tokens);
@@ -656,42 +656,42 @@ mod test_lexer {
         Lexer::lex_enclosures(&mut lexer)?;
         Lexer::next(&mut lexer);
         assert_eq!(
-            Token { typ: TokenType::LBracket, value: "[".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::LBracket, value: "[".into(), loc: Loc { line: 1, col: 1 }, raw: None },
             lexer.tokens.pop().unwrap(),
         );
 
         Lexer::lex_enclosures(&mut lexer)?;
         Lexer::next(&mut lexer);
         assert_eq!(
-            Token { typ: TokenType::RBracket, value: "]".to_string(), loc: Loc { line: 1, col: 2 }, addtl: None },
+            Token { typ: TokenType::RBracket, value: "]".into(), loc: Loc { line: 1, col: 2 }, raw: None },
             lexer.tokens.pop().unwrap(),
         );
 
         Lexer::lex_enclosures(&mut lexer)?;
         Lexer::next(&mut lexer);
         assert_eq!(
-            Token { typ: TokenType::LParen, value: "(".to_string(), loc: Loc { line: 1, col: 3 }, addtl: None },
+            Token { typ: TokenType::LParen, value: "(".into(), loc: Loc { line: 1, col: 3 }, raw: None },
             lexer.tokens.pop().unwrap(),
         );
 
         Lexer::lex_enclosures(&mut lexer)?;
         Lexer::next(&mut lexer);
         assert_eq!(
-            Token { typ: TokenType::RParen, value: ")".to_string(), loc: Loc { line: 1, col: 4 }, addtl: None },
+            Token { typ: TokenType::RParen, value: ")".into(), loc: Loc { line: 1, col: 4 }, raw: None },
             lexer.tokens.pop().unwrap(),
         );
 
         Lexer::lex_enclosures(&mut lexer)?;
         Lexer::next(&mut lexer);
         assert_eq!(
-            Token { typ: TokenType::LBrace, value: "{".to_string(), loc: Loc { line: 1, col: 5 }, addtl: None },
+            Token { typ: TokenType::LBrace, value: "{".into(), loc: Loc { line: 1, col: 5 }, raw: None },
             lexer.tokens.pop().unwrap(),
         );
 
         Lexer::lex_enclosures(&mut lexer)?;
         Lexer::next(&mut lexer);
         assert_eq!(
-            Token { typ: TokenType::RBrace, value: "}".to_string(), loc: Loc { line: 1, col: 6 }, addtl: None },
+            Token { typ: TokenType::RBrace, value: "}".into(), loc: Loc { line: 1, col: 6 }, raw: None },
             lexer.tokens.pop().unwrap(),
         );
 
@@ -714,7 +714,7 @@ mod test_lexer {
         Lexer::lex_string(&mut lexer)?;
 
         assert_eq!(
-            Token { typ: TokenType::StrLiteral, value: "goodbye, world!".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::StrLiteral, value: "goodbye, world!".into(), loc: Loc { line: 1, col: 1 }, raw: None },
             lexer.tokens.pop().unwrap(),
         );
 
@@ -737,7 +737,7 @@ mod test_lexer {
         Lexer::lex_string(&mut lexer)?;
 
         assert_eq!(
-            Token { typ: TokenType::StrLiteral, value: "lolcat".to_string(), loc: Loc { line: 1, col: 1 }, addtl: None },
+            Token { typ: TokenType::StrLiteral, value: "lolcat".into(), loc: Loc { line: 1, col: 1 }, raw: None },
             lexer.tokens.pop().unwrap(),
         );
 
@@ -804,12 +804,12 @@ mod test_lexer {
         let received_tokens = lexer.lex()?;
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Ident, value: "int".to_string(), loc: Loc::default(), addtl: None },
-            Token { typ: TokenType::Ident, value: "my_num".to_string(), loc: Loc { line: 1, col: 5 }, addtl: None },
-            Token { typ: TokenType::Eq, value: "=".to_string(), loc: Loc { line: 1, col: 12 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "69".to_string(), loc: Loc { line: 1, col: 14 }, addtl: None },
-            Token { typ: TokenType::Semi, value: ";".to_string(), loc: Loc { line: 1, col: 16 }, addtl: None },
-            Token { typ: TokenType::Eof, value: "".to_string(), loc: Loc { line: 1, col: 17 }, addtl: None },
+            Token { typ: TokenType::Ident, value: "int".into(), loc: Loc::default(), raw: None },
+            Token { typ: TokenType::Ident, value: "my_num".into(), loc: Loc { line: 1, col: 5 }, raw: None },
+            Token { typ: TokenType::Eq, value: "=".into(), loc: Loc { line: 1, col: 12 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "69".into(), loc: Loc { line: 1, col: 14 }, raw: None },
+            Token { typ: TokenType::Semi, value: ";".into(), loc: Loc { line: 1, col: 16 }, raw: None },
+            Token { typ: TokenType::Eof, value: "".into(), loc: Loc { line: 1, col: 17 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, received_tokens);
@@ -824,12 +824,12 @@ mod test_lexer {
         let received_tokens = lexer.lex()?;
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Ident, value: "float".to_string(), loc: Loc::default(), addtl: None },
-            Token { typ: TokenType::Ident, value: "my_float".to_string(), loc: Loc { line: 1, col: 7 }, addtl: None },
-            Token { typ: TokenType::Eq, value: "=".to_string(), loc: Loc { line: 1, col: 16 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(true), value: "69.420".to_string(), loc: Loc { line: 1, col: 18 }, addtl: None },
-            Token { typ: TokenType::Semi, value: ";".to_string(), loc: Loc { line: 1, col: 24 }, addtl: None },
-            Token { typ: TokenType::Eof, value: "".to_string(), loc: Loc { line: 1, col: 25 }, addtl: None },
+            Token { typ: TokenType::Ident, value: "float".into(), loc: Loc::default(), raw: None },
+            Token { typ: TokenType::Ident, value: "my_float".into(), loc: Loc { line: 1, col: 7 }, raw: None },
+            Token { typ: TokenType::Eq, value: "=".into(), loc: Loc { line: 1, col: 16 }, raw: None },
+            Token { typ: TokenType::NumLiteral(true), value: "69.420".into(), loc: Loc { line: 1, col: 18 }, raw: None },
+            Token { typ: TokenType::Semi, value: ";".into(), loc: Loc { line: 1, col: 24 }, raw: None },
+            Token { typ: TokenType::Eof, value: "".into(), loc: Loc { line: 1, col: 25 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, received_tokens);
@@ -852,28 +852,28 @@ mod test_lexer {
         let received_tokens = lexer.lex()?;
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Ident, value: "array".to_string(), loc: Loc::default(), addtl: None },
-            Token { typ: TokenType::At, value: "@".to_string(), loc: Loc { line: 1, col: 6 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "int".to_string(), loc: Loc { line: 1, col: 7 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "my_list".to_string(), loc: Loc { line: 1, col: 11 }, addtl: None },
-            Token { typ: TokenType::Eq, value: "=".to_string(), loc: Loc { line: 1, col: 19 }, addtl: None },
-            Token { typ: TokenType::LBracket, value: "[".to_string(), loc: Loc { line: 1, col: 21 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "1".to_string(), loc: Loc { line: 1, col: 22 }, addtl: None },
-            Token { typ: TokenType::Comma, value: ",".to_string(), loc: Loc { line: 1, col: 23 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "2".to_string(), loc: Loc { line: 1, col: 25 }, addtl: None },
-            Token { typ: TokenType::Comma, value: ",".to_string(), loc: Loc { line: 1, col: 26 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "3".to_string(), loc: Loc { line: 1, col: 28 }, addtl: None },
-            Token { typ: TokenType::Comma, value: ",".to_string(), loc: Loc { line: 1, col: 29 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "4".to_string(), loc: Loc { line: 1, col: 31 }, addtl: None },
-            Token { typ: TokenType::Comma, value: ",".to_string(), loc: Loc { line: 1, col: 32 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "5".to_string(), loc: Loc { line: 1, col: 34 }, addtl: None },
-            Token { typ: TokenType::Comma, value: ",".to_string(), loc: Loc { line: 1, col: 35 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "6".to_string(), loc: Loc { line: 1, col: 37 }, addtl: None },
-            Token { typ: TokenType::Comma, value: ",".to_string(), loc: Loc { line: 1, col: 38 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "7".to_string(), loc: Loc { line: 1, col: 40 }, addtl: None },
-            Token { typ: TokenType::RBracket, value: "]".to_string(), loc: Loc { line: 1, col: 41 }, addtl: None },
-            Token { typ: TokenType::Semi, value: ";".to_string(), loc: Loc { line: 1, col: 42 }, addtl: None },
-            Token { typ: TokenType::Eof, value: "".to_string(), loc: Loc { line: 1, col: 43 }, addtl: None },
+            Token { typ: TokenType::Ident, value: "array".into(), loc: Loc::default(), raw: None },
+            Token { typ: TokenType::At, value: "@".into(), loc: Loc { line: 1, col: 6 }, raw: None },
+            Token { typ: TokenType::Ident, value: "int".into(), loc: Loc { line: 1, col: 7 }, raw: None },
+            Token { typ: TokenType::Ident, value: "my_list".into(), loc: Loc { line: 1, col: 11 }, raw: None },
+            Token { typ: TokenType::Eq, value: "=".into(), loc: Loc { line: 1, col: 19 }, raw: None },
+            Token { typ: TokenType::LBracket, value: "[".into(), loc: Loc { line: 1, col: 21 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "1".into(), loc: Loc { line: 1, col: 22 }, raw: None },
+            Token { typ: TokenType::Comma, value: ",".into(), loc: Loc { line: 1, col: 23 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "2".into(), loc: Loc { line: 1, col: 25 }, raw: None },
+            Token { typ: TokenType::Comma, value: ",".into(), loc: Loc { line: 1, col: 26 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "3".into(), loc: Loc { line: 1, col: 28 }, raw: None },
+            Token { typ: TokenType::Comma, value: ",".into(), loc: Loc { line: 1, col: 29 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "4".into(), loc: Loc { line: 1, col: 31 }, raw: None },
+            Token { typ: TokenType::Comma, value: ",".into(), loc: Loc { line: 1, col: 32 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "5".into(), loc: Loc { line: 1, col: 34 }, raw: None },
+            Token { typ: TokenType::Comma, value: ",".into(), loc: Loc { line: 1, col: 35 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "6".into(), loc: Loc { line: 1, col: 37 }, raw: None },
+            Token { typ: TokenType::Comma, value: ",".into(), loc: Loc { line: 1, col: 38 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "7".into(), loc: Loc { line: 1, col: 40 }, raw: None },
+            Token { typ: TokenType::RBracket, value: "]".into(), loc: Loc { line: 1, col: 41 }, raw: None },
+            Token { typ: TokenType::Semi, value: ";".into(), loc: Loc { line: 1, col: 42 }, raw: None },
+            Token { typ: TokenType::Eof, value: "".into(), loc: Loc { line: 1, col: 43 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, received_tokens);
@@ -888,19 +888,19 @@ mod test_lexer {
         let received_tokens = lexer.lex()?;
 
         let expected_tokens = vec![
-            Token { typ: TokenType::Dollar, value: "$".to_string(), loc: Loc::default(), addtl: None },
-            Token { typ: TokenType::LParen, value: "(".to_string(), loc: Loc { line: 1, col: 2 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "int".to_string(), loc: Loc { line: 1, col: 3 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "num".to_string(), loc: Loc { line: 1, col: 7 }, addtl: None },
-            Token { typ: TokenType::RParen, value: ")".to_string(), loc: Loc { line: 1, col: 10 }, addtl: None },
-            Token { typ: TokenType::At, value: "@".to_string(), loc: Loc { line: 1, col: 12 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "int".to_string(), loc: Loc { line: 1, col: 13 }, addtl: None },
-            Token { typ: TokenType::LBrace, value: "{".to_string(), loc: Loc { line: 1, col: 17 }, addtl: None },
-            Token { typ: TokenType::At, value: "@".to_string(), loc: Loc { line: 2, col: 5 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "num".to_string(), loc: Loc { line: 2, col: 6 }, addtl: None },
-            Token { typ: TokenType::Semi, value: ";".to_string(), loc: Loc { line: 2, col: 9 }, addtl: None },
-            Token { typ: TokenType::RBrace, value: "}".to_string(), loc: Loc { line: 3, col: 1 }, addtl: None },
-            Token { typ: TokenType::Eof, value: "".to_string(), loc: Loc { line: 3, col: 2 }, addtl: None },
+            Token { typ: TokenType::Dollar, value: "$".into(), loc: Loc::default(), raw: None },
+            Token { typ: TokenType::LParen, value: "(".into(), loc: Loc { line: 1, col: 2 }, raw: None },
+            Token { typ: TokenType::Ident, value: "int".into(), loc: Loc { line: 1, col: 3 }, raw: None },
+            Token { typ: TokenType::Ident, value: "num".into(), loc: Loc { line: 1, col: 7 }, raw: None },
+            Token { typ: TokenType::RParen, value: ")".into(), loc: Loc { line: 1, col: 10 }, raw: None },
+            Token { typ: TokenType::At, value: "@".into(), loc: Loc { line: 1, col: 12 }, raw: None },
+            Token { typ: TokenType::Ident, value: "int".into(), loc: Loc { line: 1, col: 13 }, raw: None },
+            Token { typ: TokenType::LBrace, value: "{".into(), loc: Loc { line: 1, col: 17 }, raw: None },
+            Token { typ: TokenType::At, value: "@".into(), loc: Loc { line: 2, col: 5 }, raw: None },
+            Token { typ: TokenType::Ident, value: "num".into(), loc: Loc { line: 2, col: 6 }, raw: None },
+            Token { typ: TokenType::Semi, value: ";".into(), loc: Loc { line: 2, col: 9 }, raw: None },
+            Token { typ: TokenType::RBrace, value: "}".into(), loc: Loc { line: 3, col: 1 }, raw: None },
+            Token { typ: TokenType::Eof, value: "".into(), loc: Loc { line: 3, col: 2 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, received_tokens);
@@ -915,28 +915,343 @@ mod test_lexer {
         let received_tokens = lexer.lex()?;
 
         let expected_tokens: Vec<Token> = vec![
-            Token { typ: TokenType::Ident, value: "mapping".to_string(), loc: Loc::default(), addtl: None },
-            Token { typ: TokenType::At, value: "@".to_string(), loc: Loc { line: 1, col: 8 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "int".to_string(), loc: Loc { line: 1, col: 9 }, addtl: None },
-            Token { typ: TokenType::SmallRArrow, value: "->".to_string(), loc: Loc { line: 1, col: 12 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "int".to_string(), loc: Loc { line: 1, col: 14 }, addtl: None },
-            Token { typ: TokenType::Ident, value: "my_dict".to_string(), loc: Loc { line: 1, col: 18 }, addtl: None },
-            Token { typ: TokenType::Eq, value: "=".to_string(), loc: Loc { line: 1, col: 26 }, addtl: None },
-            Token { typ: TokenType::And, value: "&".to_string(), loc: Loc { line: 1, col: 28 }, addtl: None },
-            Token { typ: TokenType::LBrace, value: "{".to_string(), loc: Loc { line: 1, col: 29 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "1".to_string(), loc: Loc { line: 1, col: 31 }, addtl: None },
-            Token { typ: TokenType::SmallRArrow, value: "->".to_string(), loc: Loc { line: 1, col: 32 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "2".to_string(), loc: Loc { line: 1, col: 34 }, addtl: None },
-            Token { typ: TokenType::Comma, value: ",".to_string(), loc: Loc { line: 1, col: 35 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "3".to_string(), loc: Loc { line: 1, col: 37 }, addtl: None },
-            Token { typ: TokenType::SmallRArrow, value: "->".to_string(), loc: Loc { line: 1, col: 38 }, addtl: None },
-            Token { typ: TokenType::NumLiteral(false), value: "4".to_string(), loc: Loc { line: 1, col: 40 }, addtl: None },
-            Token { typ: TokenType::RBrace, value: "}".to_string(), loc: Loc { line: 1, col: 42 }, addtl: None },
-            Token { typ: TokenType::Semi, value: ";".to_string(), loc: Loc { line: 1, col: 43 }, addtl: None },
-            Token { typ: TokenType::Eof, value: "".to_string(), loc: Loc { line: 1, col: 44 }, addtl: None },
+            Token { typ: TokenType::Ident, value: "mapping".into(), loc: Loc::default(), raw: None },
+            Token { typ: TokenType::At, value: "@".into(), loc: Loc { line: 1, col: 8 }, raw: None },
+            Token { typ: TokenType::Ident, value: "int".into(), loc: Loc { line: 1, col: 9 }, raw: None },
+            Token { typ: TokenType::SmallRArrow, value: "->".into(), loc: Loc { line: 1, col: 12 }, raw: None },
+            Token { typ: TokenType::Ident, value: "int".into(), loc: Loc { line: 1, col: 14 }, raw: None },
+            Token { typ: TokenType::Ident, value: "my_dict".into(), loc: Loc { line: 1, col: 18 }, raw: None },
+            Token { typ: TokenType::Eq, value: "=".into(), loc: Loc { line: 1, col: 26 }, raw: None },
+            Token { typ: TokenType::And, value: "&".into(), loc: Loc { line: 1, col: 28 }, raw: None },
+            Token { typ: TokenType::LBrace, value: "{".into(), loc: Loc { line: 1, col: 29 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "1".into(), loc: Loc { line: 1, col: 31 }, raw: None },
+            Token { typ: TokenType::SmallRArrow, value: "->".into(), loc: Loc { line: 1, col: 32 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "2".into(), loc: Loc { line: 1, col: 34 }, raw: None },
+            Token { typ: TokenType::Comma, value: ",".into(), loc: Loc { line: 1, col: 35 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "3".into(), loc: Loc { line: 1, col: 37 }, raw: None },
+            Token { typ: TokenType::SmallRArrow, value: "->".into(), loc: Loc { line: 1, col: 38 }, raw: None },
+            Token { typ: TokenType::NumLiteral(false), value: "4".into(), loc: Loc { line: 1, col: 40 }, raw: None },
+            Token { typ: TokenType::RBrace, value: "}".into(), loc: Loc { line: 1, col: 42 }, raw: None },
+            Token { typ: TokenType::Semi, value: ";".into(), loc: Loc { line: 1, col: 43 }, raw: None },
+            Token { typ: TokenType::Eof, value: "".into(), loc: Loc { line: 1, col: 44 }, raw: None },
         ];
 
         assert_eq!(expected_tokens, received_tokens);
         Ok(())
     }
+
+    fn lex_values(src: &str) -> Result<Vec<(TokenType, String)>, String> {
+        let mut lexer = Lexer::new(src)?;
+        let tokens = lexer.lex()?;
+
+        Ok(tokens
+            .into_iter()
+            .map(|t| (t.typ, t.value.into_owned()))
+            .collect())
+    }
+
+    #[test]
+    fn test_lexing_number_forms() -> Result<(), String> {
+        let int = TokenType::NumLiteral(false);
+        let float = TokenType::NumLiteral(true);
+
+        for (src, typ) in [
+            ("1_000_000", int),
+            ("0xFF", int),
+            ("0b1010", int),
+            ("0o17", int),
+            ("1e-9", float),
+            ("2.5E+3", float),
+            (".5", float),
+            ("10u", int),
+            ("7i", int),
+            ("2f", float),
+            ("0x_dead_beefu", int),
+        ] {
+            let tokens = lex_values(src)?;
+            assert_eq!(tokens[0], (typ, src.to_string()));
+            assert_eq!(tokens[1].0, TokenType::Eof);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_number_followed_by_dot() -> Result<(), String> {
+        let int = TokenType::NumLiteral(false);
+
+        assert_eq!(
+            lex_values("1.;")?,
+            vec![
+                (int, "1".to_string()),
+                (TokenType::Dot, ".".to_string()),
+                (TokenType::Semi, ";".to_string()),
+                (TokenType::Eof, "".to_string()),
+            ],
+        );
+
+        assert_eq!(
+            lex_values("1..5")?,
+            vec![
+                (int, "1".to_string()),
+                (TokenType::Dot, ".".to_string()),
+                (TokenType::Dot, ".".to_string()),
+                (int, "5".to_string()),
+                (TokenType::Eof, "".to_string()),
+            ],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_number_loc() -> Result<(), String> {
+        let mut lexer = Lexer::new("x = 0x1F;")?;
+        let tokens = lexer.lex()?;
+
+        assert_eq!(tokens[2].loc, Loc { line: 1, col: 5 });
+        assert_eq!(tokens[3].loc, Loc { line: 1, col: 9 });
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_invalid_numbers_fails() -> Result<(), String> {
+        for src in [
+            "0b102", "0x", "0o8", "1_", "1e", "1e+", "1.5i", "0b1f", "12abc", "1.2.3",
+        ] {
+            let mut lexer = Lexer::new(src)?;
+            assert!(lexer.lex().is_err(), "{src} should not lex");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_eq_keeps_next_char() -> Result<(), String> {
+        let types: Vec<TokenType> = lex_values("m=&{}")?.into_iter().map(|t| t.0).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Ident,
+                TokenType::Eq,
+                TokenType::And,
+                TokenType::LBrace,
+                TokenType::RBrace,
+                TokenType::Eof,
+            ],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_comments() -> Result<(), String> {
+        let src = "// lead\nx; /* trail */ y /= 2;\n/* multi\nline */z";
+        let skipped: Vec<TokenType> = Lexer::new(src)?.lex()?.into_iter().map(|t| t.typ).collect();
+        let kept = Lexer::new(src)?.with_trivia().lex()?;
+        let is_comment =
+            |t: &TokenType| matches!(t, TokenType::LineComment | TokenType::BlockComment);
+
+        assert!(!skipped.iter().any(is_comment));
+        assert!(skipped.contains(&TokenType::Ident));
+        assert_eq!(
+            kept.iter()
+                .filter(|t| is_comment(&t.typ))
+                .map(|t| (t.typ, t.value.as_ref(), t.loc.line, t.loc.col))
+                .collect::<Vec<_>>(),
+            vec![
+                (TokenType::LineComment, "// lead", 1, 1),
+                (TokenType::BlockComment, "/* trail */", 2, 4),
+                (TokenType::BlockComment, "/* multi\nline */", 3, 1),
+            ],
+        );
+
+        // Lexing resumes right after a multi line comment
+        assert_eq!(kept[kept.len() - 2].value, "z");
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_trivia_is_lossless() -> Result<(), String> {
+        let sources = [
+            "int x = 1;\t// c\r\n$f() { @\"s\\\"q\" + ''; }",
+            "/* a\n * b */\nlet s = 'héllo wörld';   \n\n",
+            "y /= 2 // end",
+            "x",
+            " # stray ~ ",
+            "/// Doc.\n///\n//// Not doc.\nconst int A = 1;",
+        ];
+
+        // Unexpected characters are errors, but still lexed
+        for src in sources {
+            let tokens = Lexer::new(src)?.with_trivia().lex_recovering();
+            let text: String = tokens.iter().map(|t| t.text()).collect();
+            assert_eq!(text, src);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_unexpected_characters() -> Result<(), String> {
+        let mut lexer = Lexer::new("a ~b\t`\\")?;
+        let tokens: Vec<(TokenType, String)> = lexer
+            .lex_recovering()
+            .into_iter()
+            .map(|t| (t.typ, t.value.into_owned()))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenType::Ident, "a".to_string()),
+                (TokenType::Error, "~".to_string()),
+                (TokenType::Ident, "b".to_string()),
+                (TokenType::Error, "`".to_string()),
+                (TokenType::Error, "\\".to_string()),
+                (TokenType::Eof, String::new()),
+            ]
+        );
+        assert_eq!(
+            lexer.errors,
+            vec![
+                "line 1, column 3: --> Unexpected character '~'",
+                "line 1, column 6: --> Unexpected character '`'",
+                "line 1, column 7: --> Unexpected character '\\'",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_trivia_tokens() -> Result<(), String> {
+        let tokens = Lexer::new("a  // c\r\n/* d */'s'")?.with_trivia().lex()?;
+        let trivia: Vec<(TokenType, &str, usize, usize)> = tokens
+            .iter()
+            .map(|t| (t.typ, t.text(), t.loc.line, t.loc.col))
+            .collect();
+
+        assert_eq!(
+            trivia,
+            vec![
+                (TokenType::Ident, "a", 1, 1),
+                (TokenType::Whitespace, "  ", 1, 2),
+                (TokenType::LineComment, "// c", 1, 4),
+                (TokenType::Newline, "\r\n", 1, 8),
+                (TokenType::BlockComment, "/* d */", 3, 1),
+                (TokenType::StrLiteral, "'s'", 3, 8),
+                (TokenType::Eof, "", 3, 11),
+            ],
+        );
+
+        // The value of a string is still its contents
+        assert_eq!(tokens[5].value, "s");
+        assert!(!Lexer::new("a  b")?.lex()?.iter().any(|t| t.typ.is_trivia()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_recovers_from_errors() -> Result<(), String> {
+        let mut lexer = Lexer::new("a = 0b12 + 1.2.3;\nb = 'open")?;
+        let tokens = lexer.lex_recovering();
+        let typs: Vec<TokenType> = tokens.iter().map(|t| t.typ).collect();
+
+        assert_eq!(
+            typs,
+            vec![
+                TokenType::Ident,
+                TokenType::Eq,
+                TokenType::Error,
+                TokenType::Plus,
+                TokenType::Error,
+                TokenType::Semi,
+                TokenType::Ident,
+                TokenType::Eq,
+                TokenType::Error,
+                TokenType::Eof,
+            ],
+        );
+        assert_eq!(tokens[2].value, "0b12");
+        assert_eq!(tokens[4].value, "1.2.3");
+        assert_eq!(lexer.errors.len(), 3);
+        assert!(lexer.errors[2].starts_with("line 2, column"));
+
+        // `lex` reports every error, one per line
+        let error = Lexer::new("0b2 0o9")?.lex().unwrap_err();
+        assert_eq!(error.lines().count(), 2);
+        assert!(error.contains("Invalid digit '2' in binary literal"));
+        assert!(error.contains("Invalid digit '9' in octal literal"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_is_lazy() -> Result<(), String> {
+        let src = "let a = 1;\n".repeat(10_000);
+        let mut lexer = Lexer::new(&src)?;
+
+        let values: Vec<String> = lexer
+            .by_ref()
+            .take(3)
+            .map(|token| token.map(|t| t.value.into_owned()))
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(values, vec!["let", "a", "="]);
+        assert!(lexer.idx < 10, "lexed up to {}", lexer.idx);
+        assert!(lexer.tokens.len() <= 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_yields_errors() -> Result<(), String> {
+        let lexer = Lexer::new("x = 0b2 + 'open")?;
+        let items: Vec<Result<TokenType, String>> = lexer.map(|item| item.map(|t| t.typ)).collect();
+
+        assert_eq!(items.len(), 8);
+        assert_eq!(items[..2], [Ok(TokenType::Ident), Ok(TokenType::Eq)]);
+        assert!(items[2]
+            .as_ref()
+            .is_err_and(|e| e.starts_with("line 1, column 5")));
+        assert_eq!(items[3], Ok(TokenType::Error));
+        assert!(items[5].is_err());
+        assert_eq!(items[7], Ok(TokenType::Eof));
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_streams_trivia() -> Result<(), String> {
+        let src = "int  x = 1; // one\r\n\n/* two */ f(x);";
+        let text: String = Lexer::new(src)?
+            .with_trivia()
+            .map(|token| token.map(|t| t.text().to_string()))
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(text, src);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexing_unicode() -> Result<(), String> {
+        let tokens = Lexer::new("let né = \"😀\";\nné")?.lex()?;
+        let found: Vec<(&str, usize, usize)> = tokens
+            .iter()
+            .map(|t| (t.value.as_ref(), t.loc.line, t.loc.col))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("let", 1, 1),
+                ("né", 1, 5),
+                ("=", 1, 8),
+                ("😀", 1, 10),
+                (";", 1, 13),
+                ("né", 2, 1),
+                ("", 2, 3),
+            ]
+        );
+        Ok(())
+    }
 }
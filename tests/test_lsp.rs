@@ -0,0 +1,389 @@
+#[cfg(test)]
+mod test_lsp {
+    use std::io::Cursor;
+
+    use why_rs::json::Json;
+    use why_rs::lsp::{self, Analysis, Server, SymbolKind};
+
+    const URI: &str = "file:///main.why";
+
+    const SRC: &str = "\
+/// The answer.
+const int ANSWER = 42;
+
+$add(int a, int b) @int {
+    let sum = a + b;
+    @sum;
+}
+
+let x = add(ANSWER, 1);
+";
+
+    fn position(line: usize, character: usize) -> Json {
+        Json::object(vec![
+            ("line", Json::int(line)),
+            ("character", Json::int(character)),
+        ])
+    }
+
+    fn request(id: usize, method: &str, params: Json) -> Json {
+        Json::object(vec![
+            ("jsonrpc", Json::str("2.0")),
+            ("id", Json::int(id)),
+            ("method", Json::str(method)),
+            ("params", params),
+        ])
+    }
+
+    fn notification(method: &str, params: Json) -> Json {
+        Json::object(vec![
+            ("jsonrpc", Json::str("2.0")),
+            ("method", Json::str(method)),
+            ("params", params),
+        ])
+    }
+
+    fn document(extra: Vec<(&str, Json)>) -> Json {
+        let mut fields = vec![("textDocument", Json::object(vec![("uri", Json::str(URI))]))];
+        fields.extend(extra);
+        Json::object(fields)
+    }
+
+    fn did_open(text: &str) -> Json {
+        notification(
+            "textDocument/didOpen",
+            Json::object(vec![(
+                "textDocument",
+                Json::object(vec![
+                    ("uri", Json::str(URI)),
+                    ("languageId", Json::str("why")),
+                    ("version", Json::int(1)),
+                    ("text", Json::str(text)),
+                ]),
+            )]),
+        )
+    }
+
+    /// Runs a session over framed stdio, like an editor would, and
+    /// returns the messages the server sent back.
+    fn session(messages: &[Json]) -> Result<(Server, Vec<Json>), String> {
+        let mut input = Vec::new();
+        for message in messages {
+            lsp::write_message(&mut input, message)?;
+        }
+
+        let mut server = Server::new();
+        let mut output = Vec::new();
+        server.run(Cursor::new(input), &mut output)?;
+
+        let mut replies = Vec::new();
+        let mut output = Cursor::new(output);
+        while let Some(reply) = lsp::read_message(&mut output)? {
+            replies.push(reply);
+        }
+
+        Ok((server, replies))
+    }
+
+    fn open(src: &str, requests: Vec<Json>) -> Result<Vec<Json>, String> {
+        let mut messages = vec![
+            request(0, "initialize", Json::object(vec![])),
+            notification("initialized", Json::object(vec![])),
+            did_open(src),
+        ];
+        messages.extend(requests);
+
+        Ok(session(&messages)?.1)
+    }
+
+    #[test]
+    fn test_json_round_trip() -> Result<(), String> {
+        let text = r#"{"a": [1, -2.5e3, true, null], "b": "x\"\né😀"}"#;
+        let json = Json::parse(text)?;
+
+        assert_eq!(json.get("b").as_str(), Some("x\"\né😀"));
+        assert_eq!(json.path(&["a"]).as_array().map(<[Json]>::len), Some(4));
+        assert_eq!(Json::parse(&json.write())?, json);
+        assert!(Json::parse("{\"a\": }").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lifecycle() -> Result<(), String> {
+        let (server, replies) = session(&[
+            request(1, "textDocument/hover", Json::object(vec![])),
+            request(2, "initialize", Json::object(vec![])),
+            request(3, "shutdown", Json::Null),
+            notification("exit", Json::Null),
+            request(4, "initialize", Json::object(vec![])),
+        ])?;
+
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0].path(&["error", "code"]), &Json::Int(-32002));
+        assert_eq!(
+            replies[1].path(&["result", "capabilities", "textDocumentSync"]),
+            &Json::int(1)
+        );
+        assert_eq!(replies[2].get("result"), &Json::Null);
+        assert!(server.shutdown && server.exited);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_json_is_a_parse_error() -> Result<(), String> {
+        let mut input = b"Content-Length: 9\r\n\r\n{\"id\": 1,".to_vec();
+        lsp::write_message(&mut input, &request(2, "initialize", Json::object(vec![])))?;
+
+        let mut output = Vec::new();
+        Server::new().run(Cursor::new(input), &mut output)?;
+
+        let mut output = Cursor::new(output);
+        let error = lsp::read_message(&mut output)?.unwrap_or(Json::Null);
+        let initialized = lsp::read_message(&mut output)?.unwrap_or(Json::Null);
+
+        assert_eq!(error.path(&["error", "code"]), &Json::Int(-32700));
+        assert_eq!(error.get("id"), &Json::Null);
+        assert_eq!(initialized.get("id"), &Json::int(2));
+
+        // Only broken framing ends the session
+        let framing = Server::new().run(Cursor::new(b"\r\n{}".to_vec()), &mut Vec::new());
+        assert!(framing.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_huge_messages_are_refused() -> Result<(), String> {
+        // Claims far more than could be allocated, then ends
+        let huge = format!("Content-Length: {}\r\n\r\n{{}}", usize::MAX).into_bytes();
+        let mut output = Vec::new();
+        Server::new().run(Cursor::new(huge.clone()), &mut output)?;
+
+        let error = lsp::read_message(&mut Cursor::new(output))?.unwrap_or(Json::Null);
+        assert_eq!(error.path(&["error", "code"]), &Json::Int(-32600));
+        assert!(lsp::read_body(&mut Cursor::new(huge)).is_err());
+
+        // The body is skipped, and the session carries on after it
+        let mut input = format!("Content-Length: {}\r\n\r\n", lsp::MAX_MESSAGE + 1).into_bytes();
+        input.resize(input.len() + lsp::MAX_MESSAGE + 1, b' ');
+        lsp::write_message(&mut input, &request(2, "initialize", Json::object(vec![])))?;
+
+        let mut output = Vec::new();
+        Server::new().run(Cursor::new(input), &mut output)?;
+
+        let mut output = Cursor::new(output);
+        let error = lsp::read_message(&mut output)?.unwrap_or(Json::Null);
+        let initialized = lsp::read_message(&mut output)?.unwrap_or(Json::Null);
+
+        assert_eq!(error.path(&["error", "code"]), &Json::Int(-32600));
+        assert_eq!(initialized.get("id"), &Json::int(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_method() -> Result<(), String> {
+        let replies = open("", vec![request(1, "workspace/frobnicate", Json::Null)])?;
+
+        assert_eq!(replies[2].path(&["error", "code"]), &Json::Int(-32601));
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics_on_change() -> Result<(), String> {
+        let change = notification(
+            "textDocument/didChange",
+            document(vec![(
+                "contentChanges",
+                Json::Array(vec![Json::object(vec![("text", Json::str("int x = 1;"))])]),
+            )]),
+        );
+        let replies = open("int x = 1;\nint y = ;", vec![change])?;
+
+        let diagnostics = replies[1].path(&["params", "diagnostics"]);
+        assert_eq!(
+            replies[1].get("method").as_str(),
+            Some("textDocument/publishDiagnostics")
+        );
+        assert_eq!(diagnostics.as_array().map(<[Json]>::len), Some(1));
+        assert_eq!(
+            diagnostics.as_array().unwrap()[0].path(&["range", "start"]),
+            &position(1, 8)
+        );
+
+        let cleared = replies[2].path(&["params", "diagnostics"]);
+        assert_eq!(cleared, &Json::Array(vec![]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_errors_are_diagnostics() {
+        let analysis = Analysis::new("int x = \"no\";");
+
+        assert_eq!(analysis.diagnostics.len(), 1);
+        assert!(analysis.diagnostics[0].message.contains("Can't assign"));
+        assert_eq!(analysis.diagnostics[0].loc, why_rs::Loc::at(1, 5));
+
+        // Not at the start of the document, where errors without one go
+        let analysis = Analysis::new("int x = 1;\n$f() @int {\n    @y;\n}");
+
+        assert_eq!(analysis.diagnostics.len(), 1);
+        assert!(analysis.diagnostics[0]
+            .message
+            .contains("Undefined variable 'y'"));
+        assert_eq!(analysis.diagnostics[0].loc, why_rs::Loc::at(3, 5));
+    }
+
+    #[test]
+    fn test_definition() -> Result<(), String> {
+        let definition = |line, character| {
+            request(
+                1,
+                "textDocument/definition",
+                document(vec![("position", position(line, character))]),
+            )
+        };
+
+        // `add` and `ANSWER` in the last line, then `a` in the body
+        let replies = open(
+            SRC,
+            vec![definition(8, 9), definition(8, 13), definition(4, 14)],
+        )?;
+
+        let range = |reply: &Json| reply.path(&["result", "range", "start"]).clone();
+        assert_eq!(range(&replies[2]), position(3, 1));
+        assert_eq!(range(&replies[3]), position(1, 10));
+        assert_eq!(range(&replies[4]), position(3, 9));
+        assert_eq!(replies[2].path(&["result", "uri"]).as_str(), Some(URI));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scopes() {
+        let analysis = Analysis::new("int x = 1;\n$f(int x) { @x; }\n@x;");
+        let resolved = |line, col| {
+            let idx = analysis.ident_at(&why_rs::Loc::at(line, col)).unwrap();
+            analysis.resolve(idx).map(|symbol| symbol.kind)
+        };
+
+        assert_eq!(resolved(2, 14), Some(SymbolKind::Parameter));
+        assert_eq!(resolved(3, 2), Some(SymbolKind::Variable));
+    }
+
+    #[test]
+    fn test_hover() -> Result<(), String> {
+        let hover = |line, character| {
+            request(
+                1,
+                "textDocument/hover",
+                document(vec![("position", position(line, character))]),
+            )
+        };
+
+        let replies = open(SRC, vec![hover(8, 14), hover(4, 9), hover(8, 4)])?;
+        let contents = |reply: &Json| {
+            reply
+                .path(&["result", "contents", "value"])
+                .as_str()
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        assert_eq!(
+            contents(&replies[2]),
+            "```why\nconst int ANSWER = 42\n```\n\nThe answer."
+        );
+        // Inferred from `a + b`, and from what `add` returns
+        assert_eq!(contents(&replies[3]), "```why\nint sum\n```");
+        assert_eq!(contents(&replies[4]), "```why\nint x\n```");
+        Ok(())
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let analysis = Analysis::new("// hi\nconst int N = 1;\n%print(N);");
+
+        let tokens: Vec<_> = analysis
+            .semantic_tokens()
+            .chunks(5)
+            .map(<[usize]>::to_vec)
+            .collect();
+        let names = |typ: usize| lsp::TOKEN_TYPES[typ];
+
+        assert_eq!(tokens[0], vec![0, 0, 5, 11, 0]);
+        assert_eq!(
+            tokens[1..]
+                .iter()
+                .map(|t| (names(t[3]), t[4]))
+                .collect::<Vec<_>>(),
+            vec![
+                ("keyword", 0),
+                ("type", 0),
+                ("variable", 0b11),
+                ("operator", 0),
+                ("number", 0),
+                ("function", 0b100),
+                ("variable", 0b10),
+            ]
+        );
+        // `%print` is on the third line, `N` after it on the same line
+        assert_eq!(tokens[6][..3], [1, 1, 5]);
+        assert_eq!(tokens[7][..3], [0, 6, 1]);
+
+        // Doc comments are comments too, slashes and all
+        let doc = Analysis::new("/// Doc.\nint x = 1;").semantic_tokens();
+        assert_eq!(doc[..5], [0, 0, 8, 11, 0]);
+    }
+
+    #[test]
+    fn test_document_symbols() -> Result<(), String> {
+        let src = "$f() {\n    $g() {}\n}\n&Point {\n    int x;\n    $len(&self) @int { @x; }\n}";
+        let replies = open(
+            src,
+            vec![request(1, "textDocument/documentSymbol", document(vec![]))],
+        )?;
+
+        let symbols = replies[2]
+            .get("result")
+            .as_array()
+            .unwrap_or_default()
+            .to_vec();
+        let names = |symbols: &Json| -> Vec<String> {
+            symbols
+                .as_array()
+                .unwrap_or_default()
+                .iter()
+                .map(|s| s.get("name").as_str().unwrap_or_default().to_string())
+                .collect()
+        };
+
+        assert_eq!(names(&Json::Array(symbols.clone())), vec!["f", "Point"]);
+        assert_eq!(names(symbols[0].get("children")), vec!["g"]);
+        assert_eq!(names(symbols[1].get("children")), vec!["x", "len"]);
+        assert_eq!(symbols[1].get("kind"), &Json::int(5));
+        assert_eq!(symbols[1].path(&["range", "start"]), &position(3, 0));
+        assert_eq!(symbols[1].path(&["range", "end"]), &position(6, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        let analysis = Analysis::new("let s = \"😀\"; let t = s;");
+
+        assert_eq!(analysis.position(&why_rs::Loc::at(1, 14)), position(0, 14));
+        assert_eq!(analysis.loc(&position(0, 14)), Some(why_rs::Loc::at(1, 14)));
+    }
+
+    #[test]
+    fn test_every_error_is_a_diagnostic() {
+        let analysis = Analysis::new("$f() @int { @1; }\nint x = ;\nlet y = 0b2;\nlet z = 1 2;");
+
+        let lines: Vec<usize> = analysis.diagnostics.iter().map(|d| d.loc.line).collect();
+        assert_eq!(lines, vec![3, 2, 4]);
+
+        // What did parse is still known
+        let idx = analysis.ident_at(&why_rs::Loc::at(1, 2)).unwrap();
+        assert_eq!(
+            analysis.resolve(idx).map(|s| s.kind),
+            Some(SymbolKind::Function)
+        );
+    }
+}
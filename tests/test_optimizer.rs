@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod test_optimizer {
+    use why_rs::Expr;
+    use why_rs::Formatter;
+    use why_rs::Interpreter;
+    use why_rs::Optimizer;
+    use why_rs::Parser;
+
+    fn parse(src: &str) -> Result<Expr, String> {
+        Parser::from_source(src)?.parse()
+    }
+
+    fn optimize(src: &str, level: u8) -> Result<Expr, String> {
+        Ok(Optimizer::new().with_level(level)?.optimize(&parse(src)?))
+    }
+
+    /// Asserts a program optimizes to another, compared as formatted so
+    /// where the code was doesn't matter.
+    fn assert_optimizes(src: &str, expected: &str) -> Result<(), String> {
+        assert_optimizes_at(src, Optimizer::MAX_LEVEL, expected)
+    }
+
+    fn assert_optimizes_at(src: &str, level: u8, expected: &str) -> Result<(), String> {
+        let optimized = Formatter::format(&optimize(src, level)?);
+
+        assert_eq!(optimized, Formatter::format(&parse(expected)?), "{src}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_levels() -> Result<(), String> {
+        let src = "int x = 1 + 2; if false { x; } 3;";
+
+        assert_eq!(optimize(src, 0)?, parse(src)?);
+        assert_optimizes_at(src, 1, "int x = 3; 3;")?;
+        assert_optimizes_at(src, 2, "3;")?;
+        assert!(Optimizer::new().with_level(3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_folds_constants() -> Result<(), String> {
+        assert_optimizes("2 ** 10 * 3;", "3072;")?;
+        // `**` is right associative, which folding must not change
+        assert_optimizes("2 ** 3 ** 2;", "512;")?;
+        assert_optimizes("(2 ** 3) ** 2;", "64;")?;
+        assert_optimizes("2 ** -1 + 7 / 2;", "3.5;")?;
+        assert_optimizes("-(2 + 3) * 2;", "-10;")?;
+        assert_optimizes("-2.5 * 2 - 1;", "-6.0;")?;
+        assert_optimizes("!(1 < 2.5) == false;", "true;")?;
+        assert_optimizes("\"wh\" + \"y\" == \"why\";", "true;")?;
+        assert_optimizes(
+            "$f(int n) @int { @n * (60 * 60); }",
+            "$f(int n) @int { @n * 3600; }",
+        )?;
+        assert_optimizes("[1 + 1, len(\"a\" + \"b\")];", "[2, len(\"ab\")];")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_leaves_failures() -> Result<(), String> {
+        let programs = [
+            "1 / 0;",
+            "9223372036854775807 + 1;",
+            "-9223372036854775807 - 1;",
+            "1.0 / 0;",
+            "1 + \"one\";",
+            "0.0 / 0 < 1;",
+        ];
+
+        for src in programs {
+            assert_eq!(optimize(src, Optimizer::MAX_LEVEL)?, parse(src)?, "{src}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_removes_dead_branches() -> Result<(), String> {
+        assert_optimizes("if 1 > 2 { print(1); } 0;", "0;")?;
+        assert_optimizes(
+            "if 1 > 2 { print(1); } else { print(2); } 0;",
+            "print(2); 0;",
+        )?;
+        assert_optimizes("while 1 > 2 { print(1); } 0;", "0;")?;
+        assert_optimizes("while true { if true { break; } }", "while true { break; }")?;
+        // Declarations stay in their own scope
+        assert_optimizes(
+            "if true { int x = 1; print(x); } else { print(2); } 0;",
+            "if true { int x = 1; print(x); } 0;",
+        )?;
+        // Conditions that aren't bools are left to fail
+        assert_optimizes("if 1 { print(1); } 0;", "if 1 { print(1); } 0;")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_removes_unused_variables() -> Result<(), String> {
+        assert_optimizes(
+            "int a = 1; const int b = 2; let c = [1]; int d = f();\n\
+             $f() @int { float e = 2 * 3; @b; } 0;",
+            "const int b = 2; let c = [1]; int d = f();\n\
+             $f() @int { @b; } 0;",
+        )?;
+        // Used anywhere, even before its declaration, counts
+        assert_optimizes(
+            "$f() @int { @x; } int x = 1; 0;",
+            "$f() @int { @x; } int x = 1; 0;",
+        )?;
+        // Declarations that would fail are kept
+        assert_optimizes("int x = \"one\"; 0;", "int x = \"one\"; 0;")?;
+        // As is the last statement, which is the program's value
+        assert_optimizes("int x = 1;", "int x = 1;")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_results_match_unoptimized() -> Result<(), String> {
+        let programs = [
+            "$fib(int n) @int { if n < 1 + 1 { @n; } @fib(n - 1) + fib(n - 2); } fib(10 + 5);",
+            "int total = 0; int i = 0; while true { i++; if i > 2 * 5 { break; } total += i; } total;",
+            "int x = 1; if true { int x = 2; x++; } x;",
+            "int x = 1; if 2 > 1 { x = 5; } x * (2 ** 2);",
+            "float f = 1 + 1; f / 4;",
+            "$f() @int { if false { @1; } } f();",
+            "int unused = 2 ** 62; 2 ** 62 + 2 ** 62;",
+            "if true { break; }",
+        ];
+
+        for src in programs {
+            let expected = Interpreter::new().run(&parse(src)?);
+
+            for level in 1..=Optimizer::MAX_LEVEL {
+                let result = Interpreter::new().run(&optimize(src, level)?);
+                assert_eq!(result, expected, "{src}");
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,611 @@
+#[cfg(test)]
+mod test_parser {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use why_rs::trace::{RecordingTracer, Stage, Tracer};
+    use why_rs::ClassDecl;
+    use why_rs::Condition;
+    use why_rs::Expr;
+    use why_rs::Field;
+    use why_rs::FuncDecl;
+    use why_rs::Keyword;
+    use why_rs::Lexer;
+    use why_rs::Loc;
+    use why_rs::Operator;
+    use why_rs::Param;
+    use why_rs::Parser;
+    use why_rs::Stmt;
+    use why_rs::Symbol;
+    use why_rs::Token;
+    use why_rs::TokenType;
+    use why_rs::VarDecl;
+    use why_rs::VarType;
+
+    fn parse(src: &str) -> Result<Expr, String> {
+        Parser::from_source(src)?.parse()
+    }
+
+    fn number(value: &str, is_float: bool) -> Token<'_> {
+        Token::with_value(TokenType::NumLiteral(is_float), value.to_string())
+    }
+
+    #[test]
+    fn test_parse_number_int() -> Result<(), String> {
+        assert_eq!(Parser::parse_number(&number("42", false))?, Expr::Int(42));
+        assert_eq!(
+            Parser::parse_number(&number("1_000_000", false))?,
+            Expr::Int(1_000_000)
+        );
+        assert_eq!(
+            Parser::parse_number(&number("0xFF", false))?,
+            Expr::Int(255)
+        );
+        assert_eq!(
+            Parser::parse_number(&number("0b1010", false))?,
+            Expr::Int(10)
+        );
+        assert_eq!(Parser::parse_number(&number("0o17", false))?, Expr::Int(15));
+        assert_eq!(Parser::parse_number(&number("10u", false))?, Expr::Int(10));
+        assert_eq!(
+            Parser::parse_number(&number("0xABi", false))?,
+            Expr::Int(171)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_number_float() -> Result<(), String> {
+        assert_eq!(
+            Parser::parse_number(&number("69.420", true))?,
+            Expr::Float(69.420)
+        );
+        assert_eq!(Parser::parse_number(&number(".5", true))?, Expr::Float(0.5));
+        assert_eq!(
+            Parser::parse_number(&number("1e-9", true))?,
+            Expr::Float(1e-9)
+        );
+        assert_eq!(
+            Parser::parse_number(&number("1_0.2_5", true))?,
+            Expr::Float(10.25)
+        );
+        assert_eq!(Parser::parse_number(&number("2f", true))?, Expr::Float(2.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_number_overflow_is_an_error() {
+        let mut token = number("99999999999999999999", false);
+        token.loc = Loc::at(3, 7);
+
+        let error = Parser::parse_number(&token).unwrap_err();
+        assert!(error.starts_with("line 3, column 7:"), "{}", error);
+        assert!(Parser::parse_number(&number("1e999", true)).is_err());
+    }
+
+    #[test]
+    fn test_parse_arithmetic_precedence() -> Result<(), String> {
+        let expected = Expr::Main(vec![Stmt::Expr(
+            Expr::BinaryOp(
+                Operator::Add,
+                Box::new(Expr::Int(1)),
+                Box::new(Expr::BinaryOp(
+                    Operator::Mult,
+                    Box::new(Expr::Float(2.5)),
+                    Box::new(Expr::BinaryOp(
+                        Operator::Pow,
+                        Box::new(Expr::Int(2)),
+                        Box::new(Expr::BinaryOp(
+                            Operator::Pow,
+                            Box::new(Expr::Int(3)),
+                            Box::new(Expr::Int(2)),
+                        )),
+                    )),
+                )),
+            ),
+            Loc::at(1, 1),
+        )]);
+
+        assert_eq!(parse("1 + 2.5 * 2 ** 3 ** 2")?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_parenthesized() -> Result<(), String> {
+        let expected = Expr::Main(vec![Stmt::Expr(
+            Expr::BinaryOp(
+                Operator::Mult,
+                Box::new(Expr::Parenthesized(Box::new(Expr::BinaryOp(
+                    Operator::Add,
+                    Box::new(Expr::Int(2)),
+                    Box::new(Expr::Int(1)),
+                )))),
+                Box::new(Expr::Int(3)),
+            ),
+            Loc::at(1, 1),
+        )]);
+
+        assert_eq!(parse("(2 + 1) * 3")?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_literal_is_an_error() {
+        assert!(parse("1 + 0x7FFFFFFFFFFFFFFFF").is_err());
+    }
+
+    fn parse_stmts(src: &str) -> Result<Vec<Stmt>, String> {
+        match parse(src)? {
+            Expr::Main(stmts) => Ok(stmts),
+            expr => Err(format!("Expected a program, got {expr:?}")),
+        }
+    }
+
+    #[test]
+    fn test_parse_var_decls() -> Result<(), String> {
+        let stmts = parse_stmts("int x = 1; const float y = 2.5; let z = x;")?;
+
+        assert_eq!(
+            stmts,
+            vec![
+                Stmt::VarDecl(VarDecl {
+                    keyword: None,
+                    typ: Some(VarType::Int),
+                    name: "x".into(),
+                    value: Expr::Int(1),
+                    loc: Loc::at(1, 5),
+                    doc: None,
+                }),
+                Stmt::VarDecl(VarDecl {
+                    keyword: Some(Keyword::Const),
+                    typ: Some(VarType::Float),
+                    name: "y".into(),
+                    value: Expr::Float(2.5),
+                    loc: Loc::at(1, 24),
+                    doc: None,
+                }),
+                Stmt::VarDecl(VarDecl {
+                    keyword: Some(Keyword::Let),
+                    typ: None,
+                    name: "z".into(),
+                    value: Expr::Ident("x".into()),
+                    loc: Loc::at(1, 37),
+                    doc: None,
+                }),
+            ],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_collection_types() -> Result<(), String> {
+        let stmts = parse_stmts("mapping@string->array@int m = &{ \"a\" -> [1, 2], };")?;
+
+        let expected_typ = VarType::Mapping(
+            Box::new(VarType::String),
+            Box::new(VarType::Array(Box::new(VarType::Int))),
+        );
+
+        let expected_value = Expr::Mapping(vec![(
+            Expr::String("a".to_string()),
+            Expr::Array(vec![Expr::Int(1), Expr::Int(2)]),
+        )]);
+
+        match &stmts[0] {
+            Stmt::VarDecl(decl) => {
+                assert_eq!(decl.typ, Some(expected_typ));
+                assert_eq!(decl.value, expected_value);
+            }
+            stmt => panic!("Expected a declaration, got {stmt:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_function() -> Result<(), String> {
+        let stmts = parse_stmts("$add(int a, int b) @int {\n    @a + b;\n}")?;
+
+        assert_eq!(
+            stmts,
+            vec![Stmt::Func(FuncDecl {
+                name: "add".into(),
+                params: vec![
+                    Param {
+                        name: "a".into(),
+                        typ: VarType::Int
+                    },
+                    Param {
+                        name: "b".into(),
+                        typ: VarType::Int
+                    },
+                ],
+                ret: VarType::Int,
+                body: vec![Stmt::Return(
+                    Some(Expr::BinaryOp(
+                        Operator::Add,
+                        Box::new(Expr::Ident("a".into())),
+                        Box::new(Expr::Ident("b".into())),
+                    )),
+                    Loc::at(2, 5),
+                )],
+                loc: Loc::at(1, 2),
+                doc: None,
+            })],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_calls_and_indexes() -> Result<(), String> {
+        let stmts = parse_stmts("%print(xs[0], f());")?;
+
+        assert_eq!(
+            stmts,
+            vec![Stmt::Expr(
+                Expr::Call(
+                    Box::new(Expr::Builtin("print".into())),
+                    vec![
+                        Expr::Index(Box::new(Expr::Ident("xs".into())), Box::new(Expr::Int(0)),),
+                        Expr::Call(Box::new(Expr::Ident("f".into())), vec![], Loc::at(1, 15)),
+                    ],
+                    Loc::at(1, 1),
+                ),
+                Loc::at(1, 1)
+            )],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fields() -> Result<(), String> {
+        let field = |object, name: &str| {
+            Expr::BinaryOp(
+                Operator::Dot,
+                Box::new(object),
+                Box::new(Expr::Ident(name.into())),
+            )
+        };
+        let next = field(Expr::Ident("p".into()), "next");
+
+        assert_eq!(
+            parse_stmts("p.next.x = p.next;")?,
+            vec![Stmt::Assign(
+                Operator::Assign,
+                field(next.clone(), "x"),
+                next,
+                Loc::at(1, 1)
+            )],
+        );
+        assert!(parse("p.1;").is_err());
+        assert!(parse("f().x = 1;").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_control_flow() -> Result<(), String> {
+        let stmts = parse_stmts(
+            "while i < 10 { if i == 5 { break; } else if !done { i++; } else { i += 2; } }",
+        )?;
+
+        let i = || Expr::Ident("i".into());
+        let compare = |cond, value| Expr::Compare(cond, Box::new(i()), Box::new(Expr::Int(value)));
+
+        assert_eq!(
+            stmts,
+            vec![Stmt::While(
+                compare(Condition::Lt, 10),
+                vec![Stmt::If(
+                    compare(Condition::EqEq, 5),
+                    vec![Stmt::Break],
+                    Some(vec![Stmt::If(
+                        Expr::UnaryOp(Operator::Not, Box::new(Expr::Ident("done".into()))),
+                        vec![Stmt::Assign(
+                            Operator::Increment,
+                            i(),
+                            Expr::Int(1),
+                            Loc::at(1, 53)
+                        )],
+                        Some(vec![Stmt::Assign(
+                            Operator::IncrementBy,
+                            i(),
+                            Expr::Int(2),
+                            Loc::at(1, 67),
+                        )]),
+                        Loc::at(1, 42),
+                    )]),
+                    Loc::at(1, 16),
+                )],
+                Loc::at(1, 1),
+            )],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_delete() -> Result<(), String> {
+        assert_eq!(
+            parse_stmts("delete a;")?,
+            vec![Stmt::Delete("a".into(), Loc::at(1, 8))]
+        );
+        assert!(parse("delete a b;").is_err());
+        assert!(parse("delete 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_missing_semi_fails() {
+        assert!(parse("int x = 1 int y = 2;").is_err());
+        assert!(parse("$f() { @1 }").is_err());
+    }
+
+    #[test]
+    fn test_parse_unclosed_block_fails() {
+        assert!(parse("while true { x++;").is_err());
+    }
+
+    #[test]
+    fn test_tracing() -> Result<(), String> {
+        let recorder = Rc::new(RecordingTracer::default());
+        let tracer: Rc<dyn Tracer> = recorder.clone();
+
+        let tokens = Lexer::new("x;")?.with_tracer(Rc::clone(&tracer)).lex()?;
+        let mut peekable_tokens = tokens.iter().peekable();
+        Parser::new(&mut peekable_tokens)
+            .with_tracer(tracer)
+            .parse()?;
+
+        let messages = recorder.messages.borrow();
+        assert_eq!(messages[0], (Stage::Lexer, "1:1 'x'".to_string()));
+        assert!(messages.contains(&(Stage::Lexer, "lexed 3 tokens".to_string())));
+        assert!(messages.contains(&(
+            Stage::Parser,
+            "primary at Ident('x') @ Line: 1, Col: 1".to_string()
+        )));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_silent_by_default() -> Result<(), String> {
+        let lexer = Lexer::new("x")?;
+        assert!(!lexer.tracer.enabled(Stage::Lexer));
+        assert!(!lexer.tracer.enabled(Stage::Parser));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_skips_trivia() -> Result<(), String> {
+        let src = "int x = 1; // one\n/* two */ $f(int a) @int { @a; }\nf(x);";
+        let tokens = Lexer::new(src)?.with_trivia().lex()?;
+        let mut peekable_tokens = tokens.iter().peekable();
+
+        // Comments are kept as statements, trailing the code on their line
+        let Expr::Main(stmts) = Parser::new(&mut peekable_tokens).parse()? else {
+            panic!("Expected a program");
+        };
+
+        assert_eq!(stmts[1], Stmt::Comment("// one".to_string(), true));
+        assert_eq!(stmts[2], Stmt::Comment("/* two */".to_string(), false));
+
+        let code: Vec<Stmt> = stmts
+            .into_iter()
+            .filter(|s| !matches!(s, Stmt::Comment(..)))
+            .collect();
+        assert_eq!(Expr::Main(code), parse(src)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_doc_comments() -> Result<(), String> {
+        let stmts = parse_stmts(
+            "/// The answer.\n/// Really.\nconst int A = 42;\n/// Adds.\n$f() {}\nint b = 1;",
+        )?;
+
+        match (&stmts[0], &stmts[1], &stmts[2]) {
+            (Stmt::VarDecl(a), Stmt::Func(f), Stmt::VarDecl(b)) => {
+                assert_eq!(a.doc.as_deref(), Some("The answer.\nReally."));
+                assert_eq!(f.doc.as_deref(), Some("Adds."));
+                assert_eq!(b.doc, None);
+            }
+            stmts => panic!("Expected declarations, got {stmts:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_orphan_doc_comment() -> Result<(), String> {
+        let stmts = parse_stmts("/// Nothing.\nf();\n//// Not a doc.\nint x = 1;")?;
+
+        assert_eq!(stmts[0], Stmt::Comment("/// Nothing.".to_string(), false));
+        match &stmts[2] {
+            Stmt::VarDecl(decl) => assert_eq!(decl.doc, None),
+            stmt => panic!("Expected a declaration, got {stmt:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_class() -> Result<(), String> {
+        let src = "/// A point.\n&Point is Shape {\n    /// Across.\n    int x = 0;\n    int y;\n    $len(&self) @int { @x; }\n}";
+        let stmts = parse_stmts(src)?;
+
+        let class = match &stmts[0] {
+            Stmt::Class(class) => class,
+            stmt => panic!("Expected a class, got {stmt:?}"),
+        };
+
+        assert_eq!(
+            (
+                class.name.as_str(),
+                class.base.map(Symbol::as_str),
+                class.doc.as_deref()
+            ),
+            ("Point", Some("Shape"), Some("A point."))
+        );
+        assert_eq!(
+            class.fields,
+            vec![
+                Field {
+                    name: "x".into(),
+                    typ: VarType::Int,
+                    value: Some(Expr::Int(0)),
+                    loc: Loc::at(4, 9),
+                    doc: Some("Across.".to_string()),
+                },
+                Field {
+                    name: "y".into(),
+                    typ: VarType::Int,
+                    value: None,
+                    loc: Loc::at(5, 9),
+                    doc: None,
+                },
+            ]
+        );
+        assert_eq!(class.methods[0].name, "len");
+        assert_eq!(class.methods[0].params[0].name, "self");
+        assert!(matches!(stmts[..], [Stmt::Class(ClassDecl { .. })]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_class_types() -> Result<(), String> {
+        let src = "&Point { $copy(&self) @Point { @self; } }
+$origin(array@Point ps) @Point {}
+Point p = origin([]);";
+        let stmts = parse_stmts(src)?;
+        let point = VarType::Class("Point".into());
+
+        match (&stmts[0], &stmts[1], &stmts[2]) {
+            (Stmt::Class(class), Stmt::Func(func), Stmt::VarDecl(decl)) => {
+                assert_eq!(class.methods[0].ret, point);
+                assert_eq!(func.params[0].typ, VarType::Array(Box::new(point.clone())));
+                assert_eq!(func.ret, point);
+                assert_eq!(decl.typ, Some(point));
+            }
+            stmts => panic!("Expected declarations, got {stmts:?}"),
+        }
+
+        // Only declared classes are types
+        let error = parse("$origin() @Point {}\n&Point {}").unwrap_err();
+        assert!(error.contains("Unknown type \"Point\""), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_reports_every_error() -> Result<(), String> {
+        let error = parse("int x = ;\nint y = 2;\nlet z = 1 2;\n$f() { @; }").unwrap_err();
+        let lines: Vec<&str> = error.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("line 1, column 9:"), "{}", lines[0]);
+        assert!(lines[1].starts_with("line 3, column 11:"), "{}", lines[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_recovering() -> Result<(), String> {
+        let tokens = Lexer::new(
+            "int x = ;\n$f() {\n    @a +;\n    int ok = 1;\n}\nwhile x < { x++; }\nf();",
+        )?
+        .lex()?;
+        let mut peekable_tokens = tokens.iter().peekable();
+        let mut parser = Parser::new(&mut peekable_tokens);
+
+        let stmts = match parser.parse_recovering() {
+            Expr::Main(stmts) => stmts,
+            expr => panic!("Expected a program, got {expr:?}"),
+        };
+
+        assert_eq!(parser.errors.len(), 3);
+        assert_eq!(stmts.len(), 4);
+        assert_eq!(stmts[0], Stmt::Expr(Expr::Error, Loc::at(1, 1)));
+        assert_eq!(stmts[2], Stmt::Expr(Expr::Error, Loc::at(6, 1)));
+
+        // The error inside the function only loses its own statement
+        match &stmts[1] {
+            Stmt::Func(func) => {
+                assert_eq!(func.body[0], Stmt::Expr(Expr::Error, Loc::at(3, 5)));
+                assert!(matches!(func.body[1], Stmt::VarDecl(_)));
+            }
+            stmt => panic!("Expected a function, got {stmt:?}"),
+        }
+
+        assert!(matches!(stmts[3], Stmt::Expr(Expr::Call(..), _)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_lex_errors_once() -> Result<(), String> {
+        let tokens = Lexer::new("let a = 0b2 + 1;")?.lex_recovering();
+        let mut peekable_tokens = tokens.iter().peekable();
+        let mut parser = Parser::new(&mut peekable_tokens);
+
+        parser.parse_recovering();
+        assert!(parser.errors.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_no_errors_after_bad_tokens() {
+        // Only the lexer's error, not one for each rule the token breaks
+        for src in ["1 ~ 2;", "let a = 1 ` 2;\nint b = 2;", "f(1 ~);", "~"] {
+            let error = parse(src).unwrap_err();
+            assert_eq!(error.lines().count(), 1, "{error}");
+            assert!(error.contains("Unexpected character"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_parser_constructors() -> Result<(), String> {
+        let src = "let x = [1, 2];\nx[0] += 1;";
+        let tokens = Lexer::new(src)?.lex()?;
+        let expected = parse(src)?;
+
+        let mut peekable_tokens = tokens.iter().peekable();
+        assert_eq!(Parser::new(&mut peekable_tokens).parse()?, expected);
+        assert_eq!(Parser::from_tokens(tokens.clone()).parse()?, expected);
+        assert_eq!(Parser::from_stream(tokens.into_iter()).parse()?, expected);
+        assert!(Parser::from_source("let s = \"open;")?.parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_peek_nth() -> Result<(), String> {
+        let tokens = Lexer::new("int /* a */ x = 1;")?.with_trivia().lex()?;
+        let mut parser = Parser::from_tokens(tokens);
+
+        let typ = |token: Option<&Token>| token.map(|t| t.typ);
+        assert_eq!(typ(parser.peek_nth(1)), Some(TokenType::Ident));
+        assert_eq!(typ(parser.peek_nth(2)), Some(TokenType::Eq));
+        assert_eq!(typ(parser.peek_nth(5)), Some(TokenType::Eof));
+        assert_eq!(typ(parser.peek_nth(6)), None);
+
+        assert_eq!(parser.consumed, 0);
+        assert_eq!(parser.next().map(|t| t.value), Some("int".into()));
+        assert_eq!(typ(parser.peek_nth(0)), Some(TokenType::Ident));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_is_read_lazily() -> Result<(), String> {
+        let tokens = Lexer::new("x; y; z;")?.lex()?;
+        let read = Cell::new(0);
+        let mut parser =
+            Parser::from_stream(tokens.into_iter().inspect(|_| read.set(read.get() + 1)));
+
+        assert_eq!(
+            parser.parse_stmt()?,
+            Stmt::Expr(Expr::Ident("x".into()), Loc::at(1, 1))
+        );
+        assert!(read.get() <= 3, "read {} tokens", read.get());
+        Ok(())
+    }
+}
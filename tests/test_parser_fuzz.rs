@@ -0,0 +1,197 @@
+#[cfg(test)]
+mod test_parser_fuzz {
+    use why_rs::Checker;
+    use why_rs::Lexer;
+    use why_rs::Loc;
+    use why_rs::Parser;
+    use why_rs::Token;
+    use why_rs::TokenType;
+
+    /// Tokens to draw from, with values the parser will accept where it
+    /// looks at them.
+    const POOL: &[(TokenType, &str)] = &[
+        (TokenType::Ident, "x"),
+        (TokenType::Ident, "int"),
+        (TokenType::Ident, "array"),
+        (TokenType::Ident, "mapping"),
+        (TokenType::Ident, "self"),
+        (TokenType::Keyword, "let"),
+        (TokenType::Keyword, "const"),
+        (TokenType::Keyword, "if"),
+        (TokenType::Keyword, "else"),
+        (TokenType::Keyword, "while"),
+        (TokenType::Keyword, "break"),
+        (TokenType::Keyword, "is"),
+        (TokenType::Keyword, "for"),
+        (TokenType::NumLiteral(false), "1"),
+        (TokenType::NumLiteral(false), "0x"),
+        (TokenType::NumLiteral(true), "1.5"),
+        (TokenType::StrLiteral, "s"),
+        (TokenType::Null, "null"),
+        (TokenType::Eq, "="),
+        (TokenType::EqEq, "=="),
+        (TokenType::Lt, "<"),
+        (TokenType::Plus, "+"),
+        (TokenType::PlusPlus, "++"),
+        (TokenType::PlusEq, "+="),
+        (TokenType::Minus, "-"),
+        (TokenType::Star, "*"),
+        (TokenType::StarStar, "**"),
+        (TokenType::Slash, "/"),
+        (TokenType::Exclamation, "!"),
+        (TokenType::Dot, "."),
+        (TokenType::Comma, ","),
+        (TokenType::Colon, ":"),
+        (TokenType::Semi, ";"),
+        (TokenType::LParen, "("),
+        (TokenType::RParen, ")"),
+        (TokenType::LBrace, "{"),
+        (TokenType::RBrace, "}"),
+        (TokenType::LBracket, "["),
+        (TokenType::RBracket, "]"),
+        (TokenType::SmallRArrow, "->"),
+        (TokenType::Dollar, "$"),
+        (TokenType::And, "&"),
+        (TokenType::At, "@"),
+        (TokenType::Percent, "%"),
+        (TokenType::Hash, "#"),
+        (TokenType::Error, "0b2"),
+        (TokenType::LineComment, "// c"),
+        (TokenType::DocComment, "d"),
+        (TokenType::Whitespace, " "),
+        (TokenType::Eof, ""),
+    ];
+
+    /// A small xorshift generator, so every run sees the same inputs.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as usize
+        }
+    }
+
+    fn random_tokens(rng: &mut Rng) -> Vec<Token<'static>> {
+        let len = rng.next() % 24;
+
+        (0..len)
+            .map(|i| {
+                let (typ, value) = POOL[rng.next() % POOL.len()];
+                Token {
+                    typ,
+                    value: value.into(),
+                    loc: Loc::at(1, i + 1),
+                    raw: None,
+                }
+            })
+            .collect()
+    }
+
+    fn parse(tokens: &[Token]) -> Result<why_rs::Expr, String> {
+        Parser::from_stream(tokens.iter().cloned()).parse()
+    }
+
+    #[test]
+    fn test_random_tokens_never_panic() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+        for _ in 0..20_000 {
+            let mut tokens = random_tokens(&mut rng);
+            let _ = parse(&tokens);
+
+            tokens.push(Token {
+                typ: TokenType::Eof,
+                ..Token::default()
+            });
+            let _ = parse(&tokens);
+        }
+    }
+
+    #[test]
+    fn test_missing_eof_is_an_error() {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+
+        for _ in 0..5_000 {
+            let tokens: Vec<Token> = random_tokens(&mut rng)
+                .into_iter()
+                .filter(|t| t.typ != TokenType::Eof)
+                .collect();
+
+            match parse(&tokens) {
+                Ok(_) => panic!("Parsed {tokens:?} without an Eof"),
+                Err(e) => assert!(e.contains("Unexpected end of input"), "{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_end_of_input() {
+        let x = Token {
+            typ: TokenType::Ident,
+            value: "x".into(),
+            loc: Loc::at(3, 7),
+            raw: None,
+        };
+
+        assert_eq!(
+            parse(&[]),
+            Err("Error! --> Unexpected end of input.".to_string())
+        );
+        assert!(parse(&[x]).is_err_and(
+            |e| e.contains("line 3, column 7") && e.contains("Unexpected end of input after")
+        ));
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error() {
+        let deep = 50_000;
+        let sources = [
+            format!("x = {}1{};", "(".repeat(deep), ")".repeat(deep)),
+            format!("x = {}1{};", "[".repeat(deep), "]".repeat(deep)),
+            format!("x = {}1;", "- ".repeat(deep)),
+            format!("x = 2{};", " ** 2".repeat(deep)),
+            format!("{}{}", "if x { ".repeat(deep), "}".repeat(deep)),
+            format!("{}int a = [];", "array@".repeat(deep)),
+        ];
+
+        for src in &sources {
+            let error = Parser::from_source(src).and_then(|mut p| p.parse());
+            assert!(
+                error.is_err_and(|e| e.contains("Nested more than 256 levels deep")),
+                "{}",
+                &src[..20]
+            );
+
+            // Recovering reports it once, without overflowing either
+            let mut parser = Parser::from_source(src).unwrap();
+            parser.parse_recovering();
+            assert_eq!(parser.errors.len(), 1, "{:?}", parser.errors);
+        }
+
+        // Nesting within the limit still parses, and checks
+        let src = format!("int x = {}1{};", "(".repeat(100), ")".repeat(100));
+        let ast = Parser::from_source(&src).and_then(|mut p| p.parse());
+        assert!(ast.is_ok_and(|ast| Checker::new().check(&ast).is_ok()));
+    }
+
+    #[test]
+    fn test_random_source_never_panics() {
+        let chars: Vec<char> = "x1 =+-*/<>!.;,:(){}[]$&@%\"'#\n\\_let if else while const int"
+            .chars()
+            .collect();
+        let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+
+        for _ in 0..5_000 {
+            let len = rng.next() % 40 + 1;
+            let src: String = (0..len).map(|_| chars[rng.next() % chars.len()]).collect();
+
+            let Ok(mut lexer) = Lexer::new(&src) else {
+                continue;
+            };
+            Parser::from_tokens(lexer.lex_recovering()).parse_recovering();
+        }
+    }
+}
@@ -0,0 +1,84 @@
+#[cfg(all(test, feature = "python"))]
+mod test_python {
+    use std::ffi::CString;
+    use std::sync::Once;
+
+    use pyo3::prelude::*;
+    use why_rs::python::why_rs;
+
+    /// Runs Python code that imports the module as `why_rs`, which
+    /// asserts what it expects.
+    fn python(code: &str) {
+        static INIT: Once = Once::new();
+
+        INIT.call_once(|| {
+            pyo3::append_to_inittab!(why_rs);
+            pyo3::prepare_freethreaded_python();
+        });
+
+        let code = CString::new(format!("import why_rs\n{code}")).unwrap();
+
+        Python::with_gil(|py| {
+            if let Err(e) = py.run(&code, None, None) {
+                e.print(py);
+                panic!("{}", e);
+            }
+        });
+    }
+
+    #[test]
+    fn test_lex() {
+        python(
+            "tokens = why_rs.lex('let x = 1.5;')\n\
+             assert [t.type for t in tokens] == \
+             ['Keyword', 'Ident', 'Eq', 'NumLiteral', 'Semi', 'Eof'], tokens\n\
+             assert tokens[1].value == 'x' and tokens[1].col == 5\n\
+             assert tokens[3].float is True\n\
+             assert tokens[0].kind == 'token'\n\
+             assert tokens[0].fields == ['type', 'value', 'float', 'line', 'col']\n\
+             assert why_rs.lex('let x = 1;') == why_rs.lex('let x = 1;')\n\
+             try:\n    why_rs.lex('\"open')\nexcept why_rs.WhyError as e:\n    assert 'line 1' in str(e)\n\
+             else:\n    raise AssertionError('expected a WhyError')",
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        python(
+            "program = why_rs.parse('print(1 + 2);')\n\
+             assert program.kind == 'program'\n\
+             call = program.body[0].expr\n\
+             assert call.kind == 'call' and call.callee.name == 'print'\n\
+             assert call.loc.line == 1\n\
+             assert call.args[0].op == '+' and call.args[0].right.value == 2\n\
+             assert call.to_dict()['args'][0]['left'] == {'kind': 'int', 'value': 1}\n\
+             assert repr(call.args[0].left) == \"Node('int', value=1)\"\n\
+             try:\n    call.nope\nexcept AttributeError:\n    pass\n\
+             else:\n    raise AssertionError('expected an AttributeError')\n\
+             try:\n    why_rs.parse('let = 1;')\nexcept why_rs.WhyError:\n    pass\n\
+             else:\n    raise AssertionError('expected a WhyError')",
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        python(
+            "assert why_rs.run('$f(int n) @int { @n * 2; } f(21);') == 42\n\
+             assert why_rs.run('[1.5, 2];') == [1.5, 2.0]\n\
+             assert why_rs.run('&{\"a\" -> [true]};') == {'a': [True]}\n\
+             assert why_rs.run('&{[1] -> \"one\"};') == {(1,): 'one'}\n\
+             assert why_rs.run('int x = 1;') is None\n\
+             assert why_rs.run('len;') == '<builtin len>'\n\
+             try:\n    why_rs.run('int x = \"one\";')\nexcept why_rs.WhyError as e:\n\
+             \x20   assert str(e).startswith('line 1, column 5'), e\n\
+             else:\n    raise AssertionError('expected a type error')\n\
+             for src in ['1 / 0;', '$f() @int { @f(); } f();']:\n\
+             \x20   try:\n\
+             \x20       why_rs.run(src)\n\
+             \x20   except why_rs.WhyError as e:\n\
+             \x20       assert 'Error! --> ' in str(e), e\n\
+             \x20   else:\n\
+             \x20       raise AssertionError(src)",
+        );
+    }
+}
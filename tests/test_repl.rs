@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod test_repl {
+    use why_rs::Repl;
+
+    #[test]
+    fn test_state_is_kept_between_lines() -> Result<(), String> {
+        let mut repl = Repl::new();
+
+        assert_eq!(repl.feed("int x = 20;")?, None);
+        assert_eq!(repl.feed("$double(int n) @int { @n * 2; }")?, None);
+        assert_eq!(repl.feed("double(x) + 2")?, Some("42".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_line_input() -> Result<(), String> {
+        let mut repl = Repl::new();
+
+        assert_eq!(repl.prompt(), ">>> ");
+        assert_eq!(repl.feed("$greet(string name) @string {")?, None);
+        assert_eq!(repl.prompt(), "... ");
+        assert_eq!(repl.feed("    @\"Hello, \" + name;")?, None);
+        assert_eq!(repl.feed("}")?, None);
+        assert_eq!(repl.prompt(), ">>> ");
+        assert_eq!(
+            repl.feed("greet(\"why\")")?,
+            Some("\"Hello, why\"".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_do_not_keep_declarations() -> Result<(), String> {
+        let mut repl = Repl::new();
+
+        assert!(repl.feed("int x = 1; string y = 2;").is_err());
+        assert!(repl.feed("x").is_err());
+        assert_eq!(repl.prompt(), ">>> ");
+
+        // Nor do runtime errors, after which the variable was never set
+        assert!(repl.feed("int y = 1 / 0;").is_err());
+        assert!(repl
+            .feed("y")
+            .unwrap_err()
+            .contains("Undefined variable 'y'"));
+        assert!(repl.feed("int y = 2;").is_ok());
+        assert_eq!(repl.feed("y")?, Some("2".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(Repl::depth("$f() {"), 1);
+        assert_eq!(Repl::depth("f([1, (2"), 3);
+        assert_eq!(Repl::depth("{ }"), 0);
+        assert_eq!(Repl::depth("\"{"), 0);
+    }
+
+    #[test]
+    fn test_commands() -> Result<(), String> {
+        let mut repl = Repl::new();
+        repl.feed("let xs = [1.5];")?;
+
+        assert_eq!(repl.feed(":type xs[0] * 2")?, Some("float".to_string()));
+        assert_eq!(repl.feed(":type \"a\" == \"b\"")?, Some("bool".to_string()));
+        assert_eq!(
+            repl.feed(":tokens 1;")?,
+            Some("NumLiteral(false)('1') @ Line: 1, Col: 1\nSemi(';') @ Line: 1, Col: 2\nEof('') @ Line: 1, Col: 3".to_string()),
+        );
+        assert_eq!(
+            repl.feed(":ast 1")?,
+            Some("Main([Expr(Int(1), Loc { line: 1, col: 1 })])".to_string())
+        );
+        assert!(repl.feed(":type nope").is_err());
+        assert!(repl.feed(":bogus").is_err());
+        assert!(Repl::is_quit(":quit"));
+        Ok(())
+    }
+}
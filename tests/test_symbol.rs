@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod test_symbol {
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+    use std::thread;
+
+    use why_rs::Expr;
+    use why_rs::Parser;
+    use why_rs::Stmt;
+    use why_rs::Symbol;
+    use why_rs::KEYWORDS;
+    use why_rs::MAX_NAME_LEN;
+
+    /// Tests that intern names run one at a time, so the names they
+    /// intern can be counted.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn serial() -> MutexGuard<'static, ()> {
+        SERIAL.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[test]
+    fn test_intern() {
+        let _serial = serial();
+        let a = Symbol::intern("apple");
+        let b = Symbol::intern(&String::from("apple"));
+
+        assert_eq!(a, b);
+        assert_ne!(a, Symbol::intern("apples"));
+        assert_eq!(a.as_str(), "apple");
+        assert_eq!(a, "apple");
+        assert_eq!(format!("{a} {a:?}"), "apple \"apple\"");
+    }
+
+    #[test]
+    fn test_keywords() {
+        let _serial = serial();
+        for keyword in KEYWORDS {
+            assert!(Symbol::intern(keyword).is_keyword(), "{}", keyword);
+        }
+
+        assert!(!Symbol::intern("iffy").is_keyword());
+        assert!(!Symbol::intern("print").is_keyword());
+        assert!(KEYWORDS.iter().all(|keyword| why_rs::is_keyword(keyword)));
+        assert!(!why_rs::is_keyword("iffy"));
+    }
+
+    #[test]
+    fn test_many_names() {
+        let _serial = serial();
+        let names: Vec<String> = (0..5000).map(|i| format!("name{i}")).collect();
+        let symbols: Vec<Symbol> = names.iter().map(|name| Symbol::intern(name)).collect();
+
+        let read = thread::spawn(move || symbols.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+        assert_eq!(read.join().unwrap(), names);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let _serial = serial();
+        let here = Symbol::intern("shared");
+        let there = thread::spawn(|| Symbol::intern("shared")).join().unwrap();
+
+        assert_eq!(here, there);
+    }
+
+    #[test]
+    fn test_parsed_names_are_interned() -> Result<(), String> {
+        let _serial = serial();
+        let Expr::Main(stmts) = Parser::from_source("let x = 1;\nx;")?.parse()? else {
+            panic!("Expected a program");
+        };
+
+        match (&stmts[0], &stmts[1]) {
+            (Stmt::VarDecl(decl), Stmt::Expr(Expr::Ident(name), _)) => {
+                assert_eq!(decl.name, *name);
+                assert_eq!(*name, Symbol::intern("x"));
+            }
+            stmts => panic!("Unexpected statements {stmts:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_interning_is_bounded() -> Result<(), String> {
+        let _serial = serial();
+
+        // Looking a name up doesn't intern it
+        assert!(!Parser::from_source("1;")?.is_class("never_interned"));
+        assert_eq!(Symbol::lookup("never_interned"), None);
+        let symbol = Symbol::intern("never_interned");
+        assert_eq!(Symbol::lookup("never_interned"), Some(symbol));
+
+        // Nor are names too long to be one
+        let long = "n".repeat(MAX_NAME_LEN + 1);
+        let error = Parser::from_source(&format!("let {long} = 1;"))?
+            .parse()
+            .unwrap_err();
+        assert!(
+            error.contains("Names can't be longer than 255 bytes"),
+            "{}",
+            error
+        );
+        assert_eq!(Symbol::lookup(&long), None);
+        Parser::from_source(&format!("let {} = 1;", &long[1..]))?.parse()?;
+
+        // Parsing the same names again interns nothing new
+        let src = "let counter = 1;\n$bump(int by) @int { @counter + by; }";
+        Parser::from_source(src)?.parse()?;
+        let count = Symbol::count();
+
+        for _ in 0..100 {
+            Parser::from_source(src)?.parse()?;
+        }
+
+        assert_eq!(Symbol::count(), count);
+        assert!(count >= KEYWORDS.len());
+        Ok(())
+    }
+}
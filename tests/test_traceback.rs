@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod test_traceback {
+    use why_rs::internal;
+    use why_rs::traceback;
+    use why_rs::CallSite;
+    use why_rs::Loc;
+    use why_rs::Symbol;
+
+    fn call(name: &str, line: usize, col: usize) -> CallSite {
+        CallSite {
+            name: Symbol::intern(name),
+            loc: Loc::at(line, col),
+        }
+    }
+
+    #[test]
+    fn test_snippet() {
+        let src = "int x = 1;\n\tprint(f(x));\n";
+
+        assert_eq!(
+            internal::snippet(src, &Loc::at(1, 5)),
+            "   1 | int x = 1;\n     |     ^\n"
+        );
+        assert_eq!(
+            internal::snippet(src, &Loc::at(2, 8)),
+            "   2 | \tprint(f(x));\n     | \t      ^\n"
+        );
+        assert_eq!(
+            internal::snippet(src, &Loc::at(2, 0)),
+            "   2 | \tprint(f(x));\n"
+        );
+        assert_eq!(internal::snippet(src, &Loc::at(3, 1)), "");
+        assert_eq!(internal::snippet(src, &Loc::at(0, 1)), "");
+    }
+
+    #[test]
+    fn test_render() {
+        let src = "$f(int n) @int {\n    @g(n);\n}\nf(1);\n";
+        let calls = [call("f", 4, 1), call("g", 2, 6)];
+
+        assert_eq!(
+            traceback::render("Error! --> Oops", &calls, src),
+            "Traceback (innermost last):\n\
+             \x20 line 4, column 1, in <main>\n\
+             \x20    4 | f(1);\n\
+             \x20      | ^\n\
+             \x20 line 2, column 6, in f\n\
+             \x20    2 |     @g(n);\n\
+             \x20      |      ^\n\
+             Error! --> Oops"
+        );
+        assert_eq!(
+            traceback::render("Error! --> Oops", &[], src),
+            "Error! --> Oops"
+        );
+    }
+
+    #[test]
+    fn test_render_collapses_recursion() {
+        let src = "$f(int n) @int {\n    @f(n + 1);\n}\nf(0);\n";
+        let mut calls = vec![call("f", 4, 0)];
+        calls.extend((0..5).map(|_| call("f", 2, 0)));
+
+        let rendered = traceback::render("Error! --> Oops", &calls, src);
+
+        assert_eq!(
+            rendered,
+            "Traceback (innermost last):\n\
+             \x20 line 4, in <main>\n\
+             \x20    4 | f(0);\n\
+             \x20 line 2, in f\n\
+             \x20    2 |     @f(n + 1);\n\
+             \x20 [the call above repeated 4 more times]\n\
+             Error! --> Oops"
+        );
+    }
+
+    #[test]
+    fn test_stack_sizes_saturate() {
+        let deepest = traceback::stack_size(traceback::DEPTH_LIMIT);
+
+        assert!(traceback::stack_size(traceback::MAX_DEPTH) < deepest);
+        assert!(deepest <= 2 << 30);
+        assert_eq!(traceback::stack_limit(usize::MAX), usize::MAX);
+        assert_eq!(traceback::stack_size(usize::MAX), usize::MAX);
+    }
+}
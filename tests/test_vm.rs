@@ -0,0 +1,214 @@
+#[cfg(test)]
+mod test_vm {
+    use why_rs::Compiler;
+    use why_rs::Interpreter;
+    use why_rs::Loc;
+    use why_rs::Op;
+    use why_rs::Parser;
+    use why_rs::Program;
+    use why_rs::Slot;
+    use why_rs::Value;
+    use why_rs::Vm;
+
+    fn compile(src: &str) -> Result<Program, String> {
+        let program = Parser::from_source(src)?.parse()?;
+
+        Compiler::new().compile(&program)
+    }
+
+    fn run(src: &str) -> Result<Value, String> {
+        Vm::new().run(&compile(src)?)
+    }
+
+    /// Runs the source on the tree walking interpreter too, so the
+    /// two can be compared.
+    fn interpret(src: &str) -> Result<Value, String> {
+        let program = Parser::from_source(src)?.parse()?;
+
+        Interpreter::new().run(&program)
+    }
+
+    #[test]
+    fn test_matches_interpreter() {
+        let sources = [
+            "(2 + 1) * 3",
+            "2 ** -1",
+            "\"why\" + \" not\"",
+            "!(1 < 2.5)",
+            "int x = 1; x += 4; x++; x * 2",
+            "float f = 1; f",
+            "array@float xs = [3]; xs[1 - 1] = 5; xs = xs + [7]; [xs[0] / 2, xs[1] / 2]",
+            "$f(array@float xs) @array@float { @xs + [1]; } f([3])[1] / 2",
+            "mapping@string->float m = &{\"a\" -> 3}; m[\"b\"] = 1; m[\"a\"] / m[\"b\"] / 2",
+            "array@int xs = [1, 2, 3]; xs[1] = 5; xs[1] + len(xs)",
+            "let m = &{\"a\" -> 1, \"a\" -> 2}; m[\"b\"] = 2; m[\"b\"] += m[\"a\"]; m",
+            "let grid = [[1, 2], [3, 4]]; grid[1][0] *= 10; grid",
+            "\"why\"[1]",
+            "$fib(int n) @int { if n < 2 { @n; } @fib(n - 1) + fib(n - 2); } fib(15)",
+            "later(); $later() @int { @1; }",
+            "$f() @int { @outer; } int outer = 1; $g() { int outer = 2; } f()",
+            "int x = 1; if true { int x = 2; x++; } x",
+            "let n = 0; if n > 0 { n = 1; } else if n == 0 { n = 2; } else { n = 3; } n",
+            "int total = 0; int i = 0; while true { i++; if i > 10 { break; } total += i; } total",
+            "$f() { $g() @int { @2; } @g(); } f()",
+            "let i = 0; while i < 3 { let j = i; i++; } i",
+            "str(1.5) + str([1])",
+            "let s = print; s",
+            "int x = 1; if true { int x = 2; delete x; x++; } x",
+            "int x = 1; $f() { delete x; } f(); int x = 3; x",
+            "$f() @int { int x = 1; delete x; @x; } int x = 5; f()",
+            "int x = 1; delete x; x",
+            "delete x;",
+            "$f() {} delete f; f()",
+            "$f() @int { int x = 1; int i = 0; while i < 2 { delete x; i += 1; } @0; } f()",
+            "$f() @int { int x = 1; delete x; x = 2; @0; } f()",
+            // Errors should be the same too
+            "1 / 0",
+            "9223372036854775807 + 1",
+            "1 + \"one\"",
+            "const int x = 1; x = 2;",
+            "int x = 1; x = \"one\";",
+            "y",
+            "y = 1;",
+            "[1][1]",
+            "&{1 -> 2}[3]",
+            "while 1 {}",
+            "$f(int a) {} f();",
+            "$f(int a) {} f(\"a\");",
+            "$f() @int {} f();",
+            "1()",
+            "%len(1, 2)",
+        ];
+
+        for src in sources {
+            assert_eq!(run(src), interpret(src), "{src}");
+        }
+    }
+
+    #[test]
+    fn test_heap_stats_match_interpreter() -> Result<(), String> {
+        let sources = [
+            "let s = \"a\"; s = s + \"b\"; s += \"c\";",
+            "let xs = [[1], [2]]; xs[0] = []; xs[1] += [\"x\"]; delete xs;",
+            "let m = &{\"a\" -> [1]}; m[\"b\"] = [2, 3];",
+            "$f(string s) @int { let t = [s]; @len(t); } f(\"a\"); f(\"b\");",
+            "let xs = []; int i = 0; while i < 5 { xs = xs + [str(i)]; i++; }",
+        ];
+
+        for src in sources {
+            let program = Parser::from_source(src)?.parse()?;
+            let mut interpreter = Interpreter::new().with_heap_stats();
+            let mut vm = Vm::new().with_heap_stats();
+
+            interpreter.run(&program)?;
+            vm.run(&compile(src)?)?;
+            assert_eq!(vm.heap, interpreter.heap, "{src}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_traceback_matches_interpreter() -> Result<(), String> {
+        let sources = [
+            "$inner(int x) @int {\n    @x / 0;\n}\n$outer(int x) @int {\n    @inner(x + 1);\n}\nouter(1);",
+            "$f(int n) @int {\n    if n == 0 {\n        @1 / n;\n    }\n    @f(n - 1);\n}\nf(3);",
+            "$f() @int { @f(); }\nf();",
+            "int x = 1;\nx + y;",
+            "$f(int n) @int {\n    while n > 0 {\n        n--;\n    }\n    n + y;\n}\nf(2);",
+            "$f(int n) @int {\n    if n > 0 {\n        @\"n\";\n    }\n}\nf(1);",
+            "$f(int n) @int {\n    if n > 0 {\n        @1;\n    }\n}\nf(0);",
+            "$f(int n) @int { @n; }\nint x = f(1) + f(\"1\");",
+            "$f() @int { @1; }\nif f() { 1; }",
+            "$f() @int { @1; }\nlet xs = [1];\nxs[f()] = 2;",
+        ];
+
+        for src in sources {
+            let program = Parser::from_source(src)?.parse()?;
+            let mut interpreter = Interpreter::new().with_max_depth(64);
+            let mut vm = Vm::new().with_max_depth(64);
+
+            let expected = interpreter.run(&program);
+            assert_eq!(vm.run(&compile(src)?), expected, "{src}");
+
+            assert_eq!(vm.traceback, interpreter.traceback, "{src}");
+            assert!(vm.traceback.iter().all(|c| c.loc.col > 0), "{}", src);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert!(compile("break;").is_err());
+        assert!(compile("if true { break; }").is_err());
+        assert!(compile("while true { $f() { break; } }").is_err());
+        assert!(compile("@1;").is_err());
+        assert!(compile("&Point { int x; }").is_err());
+        assert!(compile("%nope();").is_err());
+        assert!(compile("while true { break; } $f() { @; }").is_ok());
+    }
+
+    #[test]
+    fn test_slots() -> Result<(), String> {
+        let program = compile("int x = 1; $f(int a) { int b = a; if true { int c = b + x; } }")?;
+        let Some(Value::Compiled(f)) = program.main.chunk.constants.first() else {
+            panic!("Expected f to be compiled first");
+        };
+
+        let names = |names: &[why_rs::Symbol]| -> Vec<String> {
+            names.iter().map(ToString::to_string).collect()
+        };
+
+        // `x` is first seen in the body of `f`, which is compiled first
+        assert_eq!(names(&program.globals), vec!["x", "f"]);
+        assert_eq!(names(&f.locals), vec!["a", "b", "c"]);
+        assert!(program.main.locals.is_empty());
+        assert!(f.chunk.code.contains(&Op::GetGlobal(0)));
+        assert!(f.chunk.code.contains(&Op::GetLocal(1)));
+        assert!(matches!(f.chunk.code[1], Op::Define(Slot::Local(1), _)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_jumps() -> Result<(), String> {
+        let program = compile("let i = 0;\nwhile i < 3 {\n    i++;\n}")?;
+        let code = &program.main.chunk.code;
+
+        let exit = code
+            .iter()
+            .find_map(|op| match op {
+                Op::JumpIfFalse(target) => Some(*target as usize),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(code[exit - 1], Op::Jump(2));
+        assert_eq!(program.main.chunk.locs[2], Loc::at(2, 1));
+        assert_eq!(program.main.chunk.locs[exit - 2], Loc::at(3, 5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_disassemble() -> Result<(), String> {
+        let src = "$double(int n) @int {\n    @n * 2;\n}\n\nlet x = double(21);";
+        let listing = compile(src)?.disassemble(src);
+
+        assert!(listing.starts_with("== <main>() @any, 0 local(s) ==\n"));
+        assert!(listing.contains("   5 | let x = double(21);\n"));
+        assert!(listing.contains("Define       global 1 'x' (let)"));
+        assert!(listing.contains("\n== double(int n) @int, 1 local(s) ==\n"));
+        assert!(listing.contains("   2 | @n * 2;\n0000   GetLocal     local 0 'n'\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_vm_is_reusable() -> Result<(), String> {
+        let mut vm = Vm::new();
+
+        assert!(vm.run(&compile("$f() @int { @\"no\"; } f()")?).is_err());
+        assert_eq!(vm.run(&compile("let x = 2; x * 3")?)?, Value::Int(6));
+        assert!(vm.frames.is_empty());
+        Ok(())
+    }
+}
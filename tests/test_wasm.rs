@@ -0,0 +1,212 @@
+#[cfg(test)]
+mod test_wasm {
+    use wasmi::{Caller, Engine, Linker, Module, Store, Val};
+
+    use why_rs::Interpreter;
+    use why_rs::Parser;
+    use why_rs::WasmGen;
+
+    fn generate(src: &str) -> Result<why_rs::wasm::Module, String> {
+        let program = Parser::from_source(src)?.parse()?;
+
+        WasmGen::new().generate(&program)
+    }
+
+    /// Runs a module in wasmi, returning what it printed and the repr of
+    /// the value `main` returned, or the trap.
+    fn execute(bytes: &[u8]) -> Result<(String, String), String> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, bytes).map_err(|e| e.to_string())?;
+        let mut store = Store::new(&engine, String::new());
+        let mut linker = <Linker<String>>::new(&engine);
+
+        linker
+            .func_wrap("why", "print_int", |mut c: Caller<String>, n: i64| {
+                c.data_mut().push_str(&n.to_string());
+            })
+            .and_then(|l| {
+                l.func_wrap("why", "print_float", |mut c: Caller<String>, n: f64| {
+                    c.data_mut().push_str(&format!("{n:?}"));
+                })
+            })
+            .and_then(|l| {
+                l.func_wrap("why", "print_bool", |mut c: Caller<String>, b: i32| {
+                    c.data_mut().push_str(&(b != 0).to_string());
+                })
+            })
+            .and_then(|l| {
+                l.func_wrap("why", "print_char", |mut c: Caller<String>, ch: i32| {
+                    c.data_mut().push(char::from(ch as u8));
+                })
+            })
+            .and_then(|l| l.func_wrap("why", "pow", |_: Caller<String>, a: f64, b: f64| a.powf(b)))
+            .map_err(|e| e.to_string())?;
+
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| e.to_string())?;
+        let main = instance.get_func(&store, "main").ok_or("no main")?;
+
+        let mut results = vec![Val::I32(0); main.ty(&store).results().len()];
+        main.call(&mut store, &[], &mut results)
+            .map_err(|e| e.to_string())?;
+
+        let value = match results.first() {
+            Some(Val::I64(n)) => n.to_string(),
+            Some(Val::F64(n)) => format!("{:?}", f64::from(*n)),
+            Some(Val::I32(b)) => (*b != 0).to_string(),
+            _ => "NULL".to_string(),
+        };
+
+        Ok((store.into_data(), value))
+    }
+
+    /// Runs a program both from its binary and its text module, which
+    /// must behave the same.
+    fn run(src: &str) -> Result<(String, String), String> {
+        let module = generate(src)?;
+        let binary = execute(&module.wasm());
+        let text = wat::parse_str(module.wat()).map_err(|e| e.to_string())?;
+
+        assert_eq!(binary, execute(&text), "{}", module.wat());
+        binary
+    }
+
+    #[test]
+    fn test_generate_errors() {
+        assert!(generate("break;").is_err());
+        assert!(generate("@1;").is_err());
+        assert!(generate("&Point { int x; }").is_err());
+        assert!(generate("let s = \"why\";").is_err());
+        assert!(generate("let xs = [1];").is_err());
+        assert!(generate("$f(string s) {}").is_err());
+        assert!(generate("$f() {} let g = f;").is_err());
+        assert!(generate("int x = 1; x();").is_err());
+        assert!(generate("%len(1);").is_err());
+        assert!(generate("int x = 1; float x = 2;").is_err());
+        assert!(generate("const int x = 1; x = 2;").is_err());
+        assert!(generate("$f() @int { @1.5; }").is_err());
+        assert!(generate("$f() { @1; }").is_err());
+        assert!(generate("if 1 {}").is_err());
+        assert!(generate("y;").is_err());
+        assert!(generate("while true { break; } $f() { @; } int x = 1; int x = 2;").is_ok());
+    }
+
+    #[test]
+    fn test_output() -> Result<(), String> {
+        let programs = [
+            (
+                "%print((2 + 1) * 3, 2 ** 10, !(1 < 2.5), 7 / 2, -7 / 2, 2.0 ** 0.5);",
+                "9 1024 false 3 -3 1.4142135623730951\n",
+            ),
+            (
+                "float f = 1; %print(f, 0.1 + 0.2, 1e16, 1.0 / 0, -0.0, 1 == 1.0, true != false);",
+                "1.0 0.30000000000000004 1e16 inf -0.0 true true\n",
+            ),
+            (
+                "$fib(int n) @int { if n < 2 { @n; } @fib(n - 1) + fib(n - 2); }\n\
+                 $half(float x) @float { @x / 2; }\n\
+                 print(fib(20), half(3)); print();",
+                "6765 1.5\n\n",
+            ),
+            (
+                "int total = 0; int i = 0;\n\
+                 while true { i++; if i > 10 { break; } int x = i; total += x; }\n\
+                 $f() @int { @outer; } int outer = 7;\n\
+                 $g() { int outer = 2; $h() @int { @outer; } print(h()); }\n\
+                 %print(total, f()); g();",
+                "55 7\n7\n",
+            ),
+            (
+                "$even(int n) @bool { if n == 0 { @true; } @odd(n - 1); }\n\
+                 $odd(int n) @bool { if n == 0 { @false; } @even(n - 1); }\n\
+                 let i = 0;\n\
+                 while i < 3 { let j = 0; while true { j++; if j > i { break; } } print(i, j, even(i)); i++; }",
+                "0 1 true\n1 2 false\n2 3 true\n",
+            ),
+        ];
+
+        for (src, expected) in programs {
+            assert_eq!(run(src)?.0, expected, "{src}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_results_match_interpreter() -> Result<(), String> {
+        let programs = [
+            "1 + 2 * 3;",
+            "float x = 2; x *= 3.5; x;",
+            "int x = 7; x /= 2; x -= 10; x;",
+            "-(2 ** 62) - 2 ** 62;",
+            "(-3) ** 3;",
+            "1 ** 4294967295;",
+            "9007199254740993 == 9007199254740992;",
+            "0.0 / 0 == 0.0 / 0;",
+            "1 != true;",
+            "$f(float x) @float { @x; } f(1) / 3;",
+            "$f() @bool { let b = false; b = !b; @b; } f();",
+            "$f() {} f();",
+        ];
+
+        for src in programs {
+            let program = Parser::from_source(src)?.parse()?;
+            let expected = Interpreter::new().run(&program)?.repr();
+
+            assert_eq!(run(src)?, (String::new(), expected), "{src}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_trap() -> Result<(), String> {
+        let programs = [
+            "9223372036854775807 + 1;",
+            "-9223372036854775807 - 2;",
+            "-(-9223372036854775807 - 1);",
+            "4294967296 * 4294967296;",
+            "-1 * (-9223372036854775807 - 1);",
+            "1 / 0;",
+            "(-9223372036854775807 - 1) / -1;",
+            "3 ** 40;",
+            "1 ** 4294967296;",
+            "0.0 / 0 < 1;",
+            "$f() @int {} f();",
+        ];
+
+        for src in programs {
+            let program = Parser::from_source(src)?.parse()?;
+            assert!(Interpreter::new().run(&program).is_err(), "{}", src);
+            generate(src)?;
+            assert!(run(src).is_err(), "{}", src);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_powers_trap() -> Result<(), String> {
+        // The interpreter makes a float, which a module can't, as it
+        // needs to know the type up front
+        assert!(run("2 ** -1;").is_err());
+        assert_eq!(run("2.0 ** -1;")?.1, "0.5");
+        Ok(())
+    }
+
+    #[test]
+    fn test_encoding() -> Result<(), String> {
+        let module = generate("let x = 63; let y = 64; let z = 1.5;")?;
+        let wasm = module.wasm();
+
+        assert_eq!(&wasm[..8], b"\0asm\x01\0\0\0");
+        // Signed LEB128 needs an extra byte when the sign bit is set
+        assert!(wasm.windows(3).any(|w| w == [0x42, 0xc0, 0x00]));
+        assert!(wasm.windows(2).any(|w| w == [0x42, 0x3f]));
+        assert!(module.wat().contains("(global $z (mut f64) (f64.const 0))"));
+        assert!(module.wat().contains("(export \"main\" (func $main))"));
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+#[cfg(test)]
+mod test_whyc {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    use why_rs::whyc;
+    use why_rs::Compiler;
+    use why_rs::Parser;
+    use why_rs::Program;
+    use why_rs::Value;
+    use why_rs::Vm;
+
+    const SRC: &str = "\
+$outer(array@int xs) @mapping@string->float {
+    $inner(int n) @float { @n * 1.5; }
+    const int step = 1;
+    mapping@string->float m = &{\"sum\" -> 0.0};
+    let i = 0;
+    while i < len(xs) {
+        m[\"sum\"] += inner(xs[i]);
+        i += step;
+    }
+    @m;
+}
+
+let f = print;
+(outer([1, 2, 3])[\"sum\"] != 0) == true";
+
+    fn compile(src: &str) -> Result<Program, String> {
+        let program = Parser::from_source(src)?.parse()?;
+
+        Compiler::new().compile(&program)
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), String> {
+        let program = compile(SRC)?;
+        let bytes = whyc::encode(&program, "main.why", SRC)?;
+        let (header, decoded) = whyc::decode(&bytes)?;
+
+        assert!(bytes.starts_with(&whyc::MAGIC));
+        assert_eq!(header.version, whyc::VERSION);
+        assert_eq!(header.source_hash, whyc::hash(SRC));
+        assert_eq!(header.source, "main.why");
+        assert_eq!(decoded, program);
+        assert_eq!(decoded.disassemble(SRC), program.disassemble(SRC));
+        assert_eq!(Vm::new().run(&decoded)?, Value::Bool(true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash() {
+        // The hash is written to files, so it must never change
+        assert_eq!(whyc::hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(whyc::hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(whyc::hash("let x = 1;"), whyc::hash("let x = 2;"));
+    }
+
+    #[test]
+    fn test_rejects_bad_files() -> Result<(), String> {
+        let bytes = whyc::encode(&compile(SRC)?, "main.why", SRC)?;
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(whyc::decode(&magic)
+            .unwrap_err()
+            .contains("Not a .whyc file"));
+
+        let mut version = bytes.clone();
+        version[4] = version[4].wrapping_add(1);
+        assert!(whyc::decode(&version).unwrap_err().contains("rebuild it"));
+
+        // Every prefix is rejected rather than read out of bounds
+        for len in 0..bytes.len() {
+            assert!(whyc::decode(&bytes[..len]).is_err(), "{}", len);
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(whyc::decode(&trailing).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_deep_nesting() {
+        // A header and the start of a function with no parameters,
+        // followed by what would be its return type
+        let mut start = whyc::MAGIC.to_vec();
+        start.extend(whyc::VERSION.to_le_bytes());
+        start.extend(0_u64.to_le_bytes());
+        start.extend(0_u32.to_le_bytes());
+        start.extend(0_u32.to_le_bytes());
+        start.extend(1_u32.to_le_bytes());
+        start.extend(4_u32.to_le_bytes());
+        start.extend(b"main");
+        start.extend(0_u32.to_le_bytes());
+
+        // An array of arrays of arrays... of a type
+        let mut types = start.clone();
+        types.extend(std::iter::repeat_n(6, 5_000_000));
+        assert!(whyc::decode(&types).unwrap_err().contains("levels deep"));
+
+        // A void function with no locals, and a constant that's an array
+        // of one array of one array...
+        let mut values = start;
+        values.push(4);
+        values.extend(0_u32.to_le_bytes());
+        values.extend(1_u32.to_le_bytes());
+        for _ in 0..1_000_000 {
+            values.push(5);
+            values.extend(1_u32.to_le_bytes());
+        }
+        assert!(whyc::decode(&values).unwrap_err().contains("levels deep"));
+    }
+
+    #[test]
+    fn test_rejects_ops_out_of_range() -> Result<(), String> {
+        let mut program = compile("let x = 1; x")?;
+        let main = std::rc::Rc::make_mut(&mut program.main);
+        main.chunk.code[0] = why_rs::Op::Constant(7);
+
+        let bytes = whyc::encode(&program, "main.why", "")?;
+        assert!(whyc::decode(&bytes).unwrap_err().contains("out of range"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_rejects_stale_files() -> Result<(), String> {
+        let dir = env::temp_dir().join(format!("why-test-whyc-{}", std::process::id()));
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        let source = dir.join("main.why").to_string_lossy().into_owned();
+        let output = dir.join("main.whyc").to_string_lossy().into_owned();
+        let src = "let x = 1;";
+
+        let bytes = whyc::encode(&compile(src)?, &source, src)?;
+        fs::write(&output, bytes).map_err(|e| e.to_string())?;
+
+        // Without its source, a file is trusted
+        assert!(whyc::load(&output)?.1.is_none());
+
+        fs::write(&source, src).map_err(|e| e.to_string())?;
+        assert_eq!(whyc::load(&output)?.1.as_deref(), Some(src));
+
+        fs::write(&source, "let x = 2;").map_err(|e| e.to_string())?;
+        let stale = whyc::load(&output);
+
+        fs::remove_dir_all(&dir).map_err(|e| e.to_string())?;
+        assert!(stale.unwrap_err().contains("stale"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_is_found_next_to_the_file() -> Result<(), String> {
+        let dir = env::temp_dir().join(format!("why-test-whyc-relative-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).map_err(|e| e.to_string())?;
+
+        let source = dir.join("src/main.why").to_string_lossy().into_owned();
+        let output = dir.join("main.whyc").to_string_lossy().into_owned();
+        let src = "let x = 1;";
+
+        let recorded = whyc::source_path(&source, &output);
+        assert_eq!(
+            recorded,
+            Path::new("src").join("main.why").to_string_lossy()
+        );
+        assert_eq!(whyc::source_path("-", &output), "");
+
+        fs::write(&source, src).map_err(|e| e.to_string())?;
+        let bytes = whyc::encode(&compile(src)?, &recorded, src)?;
+        fs::write(&output, bytes).map_err(|e| e.to_string())?;
+
+        // Found relative to the file, not to the working directory
+        let found = whyc::load(&output);
+
+        // Moved along with its source, it's still checked
+        let moved = dir.join("moved");
+        fs::create_dir_all(&moved).map_err(|e| e.to_string())?;
+        fs::rename(dir.join("src"), moved.join("src")).map_err(|e| e.to_string())?;
+        fs::rename(&output, moved.join("main.whyc")).map_err(|e| e.to_string())?;
+        fs::write(moved.join("src/main.why"), "let x = 2;").map_err(|e| e.to_string())?;
+        let stale = whyc::load(&moved.join("main.whyc").to_string_lossy());
+
+        fs::remove_dir_all(&dir).map_err(|e| e.to_string())?;
+        assert_eq!(found?.1.as_deref(), Some(src));
+        assert!(stale.unwrap_err().contains("stale"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_compiled() {
+        assert!(whyc::is_compiled("main.whyc"));
+        assert!(whyc::is_compiled("dir/main.whyc"));
+        assert!(!whyc::is_compiled("main.why"));
+        assert!(!whyc::is_compiled("-"));
+    }
+}
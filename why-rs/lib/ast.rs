@@ -1,7 +1,9 @@
+use super::Loc;
+use super::Symbol;
 use super::Token;
 use super::TokenType;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Operator {
     Add,
     Increment,
@@ -15,9 +17,11 @@ pub enum Operator {
     Mult,
     Div,
     Dot,
+    Assign,
+    Not,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Condition {
     Lt,
     Gt,
@@ -39,29 +43,149 @@ pub enum Keyword {
     Else,
     Const,
     While,
+    Delete,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum VarType {
+    Int,
+    Float,
+    String,
+    Bool,
+    Void,
+    Any,
+    Array(Box<VarType>),
+    Mapping(Box<VarType>, Box<VarType>),
+    Function(Vec<VarType>, Box<VarType>),
+    /// A declared class, e.g. the `Point` in `$origin() @Point`.
+    Class(Symbol),
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
     Parenthesized(Box<Expr>),
-    VarAssign(Keyword, Box<Expr>, Box<Expr>),
-    VarAccess(Box<Expr>),
     BinaryOp(Operator, Box<Expr>, Box<Expr>),
     UnaryOp(Operator, Box<Expr>),
+    Compare(Condition, Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>, Loc),
+    Index(Box<Expr>, Box<Expr>),
+    Array(Vec<Expr>),
+    Mapping(Vec<(Expr, Expr)>),
     Int(i64),
     Float(f64),
     String(String),
-    Ident(String),
-    Main(Box<Expr>),
+    Bool(bool),
+    Ident(Symbol),
+    Builtin(Symbol),
+    Main(Vec<Stmt>),
     Null,
+    /// Stands in for code that failed to parse, so the rest of the
+    /// program can still be used.
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stmt {
+    /// An expression statement, and where it starts.
+    Expr(Expr, Loc),
+    VarDecl(VarDecl),
+    /// An assignment, and where its target starts.
+    Assign(Operator, Expr, Expr, Loc),
+    Func(FuncDecl),
+    Class(ClassDecl),
+    /// A `@` or `return`, and where it is.
+    Return(Option<Expr>, Loc),
+    /// An `if`, its `else` if any, and where the `if` is.
+    If(Expr, Vec<Stmt>, Option<Vec<Stmt>>, Loc),
+    /// A `while` loop, and where the `while` is.
+    While(Expr, Vec<Stmt>, Loc),
+    Break,
+    /// A `delete` of a variable or function, and where its name is.
+    Delete(Symbol, Loc),
+    /// A comment, parsed from tokens lexed with comments kept or from a
+    /// doc comment that documents nothing, and whether it trails code on
+    /// its line.
+    Comment(String, bool),
+}
+
+/// A variable declaration, e.g. `const int x = 1;` or `let y = 2;`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarDecl {
+    /// `Let` or `Const`, if the declaration used a keyword.
+    pub keyword: Option<Keyword>,
+    /// The declared type, `None` when it is inferred by `let`.
+    pub typ: Option<VarType>,
+    pub name: Symbol,
+    pub value: Expr,
+    pub loc: Loc,
+    /// The `///` doc comment above the declaration.
+    pub doc: Option<String>,
+}
+
+/// A function parameter, e.g. `int num`. A method's `&self` is a
+/// parameter named `self` of type `any`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Param {
+    pub name: Symbol,
+    pub typ: VarType,
+}
+
+/// A function declaration, e.g. `$add(int a, int b) @int { @a + b; }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuncDecl {
+    pub name: Symbol,
+    pub params: Vec<Param>,
+    pub ret: VarType,
+    pub body: Vec<Stmt>,
+    pub loc: Loc,
+    /// The `///` doc comment above the declaration.
+    pub doc: Option<String>,
+}
+
+/// A class declaration, e.g. `&Person is Animal { string name; }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassDecl {
+    pub name: Symbol,
+    /// The class inherited from, if any.
+    pub base: Option<Symbol>,
+    pub fields: Vec<Field>,
+    pub methods: Vec<FuncDecl>,
+    pub loc: Loc,
+    /// The `///` doc comment above the declaration.
+    pub doc: Option<String>,
+}
+
+/// A class field, e.g. `string name = "Unknown";`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: Symbol,
+    pub typ: VarType,
+    /// The default value, if any.
+    pub value: Option<Expr>,
+    pub loc: Loc,
+    /// The `///` doc comment above the field.
+    pub doc: Option<String>,
+}
+
+impl Stmt {
+    /// Attaches a doc comment to a documentable declaration, dropping
+    /// it for any other statement.
+    pub fn set_doc(&mut self, doc: Option<String>) {
+        match self {
+            Stmt::VarDecl(decl) => decl.doc = doc,
+            Stmt::Func(func) => func.doc = doc,
+            Stmt::Class(class) => class.doc = doc,
+            _ => (),
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////
 
-impl TryFrom<&Token> for Condition {
+impl TryFrom<&Token<'_>> for Condition {
     type Error = &'static str;
 
-    fn try_from(token: &Token) -> Result<Self, Self::Error> {
+    fn try_from(token: &Token<'_>) -> Result<Self, Self::Error> {
         match token.typ {
             TokenType::EqEq => Ok(Condition::EqEq),
             TokenType::Ne => Ok(Condition::Ne),
@@ -74,10 +198,10 @@ impl TryFrom<&Token> for Condition {
     }
 }
 
-impl TryFrom<&Token> for Operator {
+impl TryFrom<&Token<'_>> for Operator {
     type Error = &'static str;
 
-    fn try_from(token: &Token) -> Result<Self, Self::Error> {
+    fn try_from(token: &Token<'_>) -> Result<Self, Self::Error> {
         match token.typ {
             TokenType::Plus => Ok(Operator::Add),
             TokenType::PlusPlus => Ok(Operator::Increment),
@@ -91,18 +215,19 @@ impl TryFrom<&Token> for Operator {
             TokenType::Star => Ok(Operator::Mult),
             TokenType::Slash => Ok(Operator::Div),
             TokenType::Dot => Ok(Operator::Dot),
-            // TokenType::Eq => Ok(Operator::Assign),
+            TokenType::Eq => Ok(Operator::Assign),
+            TokenType::Exclamation => Ok(Operator::Not),
             _ => Err("Failed to convert operator token"),
         }
     }
 }
 
-impl TryFrom<&Token> for Keyword {
+impl TryFrom<&Token<'_>> for Keyword {
     type Error = &'static str;
 
-    fn try_from(token: &Token) -> Result<Self, Self::Error> {
+    fn try_from(token: &Token<'_>) -> Result<Self, Self::Error> {
         match token.typ {
-            TokenType::Keyword => match token.value.as_str() {
+            TokenType::Keyword => match token.value.as_ref() {
                 "in" => Ok(Keyword::In),
                 "if" => Ok(Keyword::If),
                 "is" => Ok(Keyword::Is),
@@ -113,6 +238,7 @@ impl TryFrom<&Token> for Keyword {
                 "const" => Ok(Keyword::Const),
                 "for" => Ok(Keyword::For),
                 "while" => Ok(Keyword::While),
+                "delete" => Ok(Keyword::Delete),
                 _ => Err("Unknown Keyword token"),
             },
             _ => Err("Failed to convert keyword token"),
@@ -122,61 +248,143 @@ impl TryFrom<&Token> for Keyword {
 
 ///////////////////////////////////////////////////////////////
 
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Operator::Add => "+",
+            Operator::Increment => "++",
+            Operator::IncrementBy => "+=",
+            Operator::Subtract => "-",
+            Operator::Decrement => "--",
+            Operator::DecrementBy => "-=",
+            Operator::MultBy => "*=",
+            Operator::DivBy => "/=",
+            Operator::Pow => "**",
+            Operator::Mult => "*",
+            Operator::Div => "/",
+            Operator::Dot => ".",
+            Operator::Assign => "=",
+            Operator::Not => "!",
+        };
+
+        write!(f, "{symbol}")
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Condition::Lt => "<",
+            Condition::Gt => ">",
+            Condition::Lte => "<=",
+            Condition::Gte => ">=",
+            Condition::Ne => "!=",
+            Condition::EqEq => "==",
+        };
+
+        write!(f, "{symbol}")
+    }
+}
+
 impl std::fmt::Display for Keyword {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Keyword({})", format!("'{:?}'", self).to_lowercase())
+        write!(f, "Keyword({})", format!("'{self:?}'").to_lowercase())
+    }
+}
+
+impl TryFrom<&Token<'_>> for VarType {
+    type Error = String;
+
+    fn try_from(token: &Token<'_>) -> Result<Self, Self::Error> {
+        match token.value.as_ref() {
+            "int" => Ok(VarType::Int),
+            "float" => Ok(VarType::Float),
+            "string" => Ok(VarType::String),
+            "bool" => Ok(VarType::Bool),
+            "void" => Ok(VarType::Void),
+            _ => super::parse_exc!(token, "Unknown type {:?}", token.value),
+        }
+    }
+}
+
+impl VarType {
+    /// The names that can begin a type annotation.
+    pub const NAMES: [&'static str; 7] =
+        ["int", "float", "string", "bool", "void", "array", "mapping"];
+
+    /// Returns true if a value of type `other` may be stored in a
+    /// variable of this type. Objects are references, which may be
+    /// `NULL`.
+    #[must_use]
+    pub fn accepts(&self, other: &VarType) -> bool {
+        match (self, other) {
+            (VarType::Any, _)
+            | (_, VarType::Any)
+            | (VarType::Float, VarType::Int)
+            | (VarType::Class(_), VarType::Void) => true,
+            (VarType::Array(a), VarType::Array(b)) => a.accepts(b),
+            (VarType::Mapping(k1, v1), VarType::Mapping(k2, v2)) => {
+                k1.accepts(k2) && v1.accepts(v2)
+            }
+            _ => self == other,
+        }
+    }
+
+    /// The type of the items of an array, or the values of a mapping.
+    #[must_use]
+    pub fn item(&self) -> Option<&VarType> {
+        match self {
+            VarType::Array(item) | VarType::Mapping(_, item) => Some(item),
+            _ => None,
+        }
+    }
+
+    /// Whether storing a value as this type can widen ints in it to
+    /// floats.
+    #[must_use]
+    pub fn widens(&self) -> bool {
+        match self {
+            VarType::Float => true,
+            VarType::Array(item) => item.widens(),
+            VarType::Mapping(key, value) => key.widens() || value.widens(),
+            _ => false,
+        }
+    }
+
+    /// The classes a type names, including inside arrays, mappings and
+    /// functions, in the order they are written.
+    #[must_use]
+    pub fn classes(&self) -> Vec<Symbol> {
+        match self {
+            VarType::Class(name) => vec![*name],
+            VarType::Array(item) => item.classes(),
+            VarType::Mapping(key, value) => [key.classes(), value.classes()].concat(),
+            VarType::Function(params, ret) => params
+                .iter()
+                .chain([&**ret])
+                .flat_map(VarType::classes)
+                .collect(),
+            _ => Vec::new(),
+        }
     }
 }
 
-// pub struct EvalResult {
-//     inner: T,
-// }
-
-// impl EvalResult {
-//     pub fn new<T>(inner: T) -> Self {
-//         Self { inner }
-//     }
-// }
-
-// impl TryFrom<&Token> for VarType {
-//     type Error = &'static str;
-
-//     fn try_from(token: &Token) -> Result<Self, Self::Error> {
-//         match token.value.as_str() {
-//             "int" => Ok(VarType::Int),
-//             "string" => Ok(VarType::String),
-//             "float" => Ok(VarType::Float),
-//             _ => Err("Failed to convert operator token"),
-//         }
-//     }
-// }
-
-// #[derive(Clone, Debug, PartialEq)]
-// pub enum VarType {
-//     Int,
-//     Float,
-//     String,
-//     Array(Box<VarType>),
-//     Mapping(Box<VarType>, Box<VarType>),
-// }
-
-// impl Expr {
-//     /// Evaluate the expression.
-//     ///
-//     /// # Panics
-//     /// - If an unknown type of expression was encountered.
-//     pub fn eval(&mut self) -> i64 {
-//         match self {
-//             Expr::Int(num) => *num,
-//             Expr::Unary(_, expr) => -expr.eval(),
-//             Expr::Binary(Operator::Mult, expr1, expr2) => expr1.eval() * expr2.eval(),
-//             Expr::Binary(Operator::Add, expr1, expr2) => expr1.eval() + expr2.eval(),
-//             Expr::Binary(Operator::Div, expr1, expr2) => expr1.eval() / expr2.eval(),
-//             Expr::Binary(Operator::Subtract, expr1, expr2) => expr1.eval() - expr2.eval(),
-//             Expr::Assign(VarType::Int, expr1, expr2) => {
-//                 expr1.eval() = expr2.eval(),
-//             }
-//             _ => panic!("Unknown expression evaluation {:?}", self),
-//         }
-//     }
-// }
+impl std::fmt::Display for VarType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VarType::Int => write!(f, "int"),
+            VarType::Float => write!(f, "float"),
+            VarType::String => write!(f, "string"),
+            VarType::Bool => write!(f, "bool"),
+            VarType::Void => write!(f, "void"),
+            VarType::Any => write!(f, "any"),
+            VarType::Array(inner) => write!(f, "array@{inner}"),
+            VarType::Mapping(key, value) => write!(f, "mapping@{key}->{value}"),
+            VarType::Function(params, ret) => {
+                let params: Vec<String> = params.iter().map(ToString::to_string).collect();
+                write!(f, "$({})@{}", params.join(", "), ret)
+            }
+            VarType::Class(name) => write!(f, "{name}"),
+        }
+    }
+}
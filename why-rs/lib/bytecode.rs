@@ -0,0 +1,240 @@
+use std::fmt::Write;
+use std::rc::Rc;
+
+use super::internal;
+use super::Condition;
+use super::Loc;
+use super::Operator;
+use super::Param;
+use super::Symbol;
+use super::Value;
+use super::VarType;
+
+/// Where a variable is stored.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Slot {
+    /// A slot in the locals of the current call, the parameters first.
+    Local(u32),
+    /// A global, by its index in [`Program::globals`].
+    Global(u32),
+}
+
+/// A single instruction for the [`super::Vm`], which works on a stack
+/// of values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    /// Pushes a constant from the chunk's pool.
+    Constant(u32),
+    /// Discards the value on top of the stack.
+    Pop,
+    GetLocal(u32),
+    GetGlobal(u32),
+    /// Pops a value and declares it in a slot, with the type and
+    /// constness of a declaration in the chunk.
+    Define(Slot, u32),
+    /// Pops the given number of indexes, then a value, and assigns the
+    /// value to the variable in a slot or the element the indexes reach.
+    Assign(Slot, Operator, u32),
+    /// Pops the given number of items into an array.
+    Array(u32),
+    /// Pops the given number of key and value pairs into a mapping.
+    Mapping(u32),
+    /// Pops an index and the value it indexes.
+    Index,
+    Unary(Operator),
+    Binary(Operator),
+    Compare(Condition),
+    /// Continues from the op at the given offset.
+    Jump(u32),
+    /// Pops a condition, which must be a bool, and jumps if it is false.
+    JumpIfFalse(u32),
+    /// Pops the given number of arguments and then the callee, and calls
+    /// it with them.
+    Call(u32),
+    /// Pops the value to return from the current call.
+    Return,
+    /// Deletes the variable in a slot, freeing its value.
+    Delete(Slot),
+}
+
+/// A sequence of ops with the constants and declarations they refer to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    /// The source location of each op.
+    pub locs: Vec<Loc>,
+    pub constants: Vec<Value>,
+    /// The declared type, if any, of each declaration and whether it is
+    /// constant.
+    pub decls: Vec<(Option<VarType>, bool)>,
+}
+
+/// A compiled function, or the top level of a program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: Symbol,
+    pub params: Vec<Param>,
+    pub ret: VarType,
+    pub chunk: Chunk,
+    /// The name of each local slot, the parameters first.
+    pub locals: Vec<Symbol>,
+}
+
+/// A compiled program, ready to be run by the [`super::Vm`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    /// The top level of the program.
+    pub main: Rc<Function>,
+    /// The name of each global.
+    pub globals: Vec<Symbol>,
+}
+
+impl Chunk {
+    /// Appends an op from the given source location, returning its
+    /// offset.
+    pub fn push(&mut self, op: Op, loc: Loc) -> usize {
+        self.code.push(op);
+        self.locs.push(loc);
+        self.code.len() - 1
+    }
+
+    /// Adds a constant to the pool, reusing an equal one if there is
+    /// one, and returns its index. Floats aren't reused, as `0.0` and
+    /// `-0.0` are equal.
+    pub fn constant(&mut self, value: Value) -> u32 {
+        let reusable = !matches!(value, Value::Float(_) | Value::Compiled(_));
+        let existing = self.constants.iter().position(|c| reusable && *c == value);
+
+        let idx = existing.unwrap_or_else(|| {
+            self.constants.push(value);
+            self.constants.len() - 1
+        });
+
+        internal::index(idx)
+    }
+}
+
+impl Function {
+    /// The name of a variable in a slot.
+    #[must_use]
+    pub fn slot_name(&self, slot: Slot, program: &Program) -> Symbol {
+        match slot {
+            Slot::Local(idx) => self.locals[idx as usize],
+            Slot::Global(idx) => program.globals[idx as usize],
+        }
+    }
+}
+
+impl Program {
+    /// Lists the ops of the program and every function in it, with the
+    /// line of source each run of ops was compiled from.
+    #[must_use]
+    pub fn disassemble(&self, src: &str) -> String {
+        let lines: Vec<&str> = src.lines().collect();
+        let mut out = String::new();
+        let mut pending = vec![Rc::clone(&self.main)];
+
+        while let Some(function) = pending.pop() {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+
+            self.disassemble_function(&function, &lines, &mut out);
+
+            // List nested functions in the order they were declared
+            let nested = function
+                .chunk
+                .constants
+                .iter()
+                .rev()
+                .filter_map(|c| match c {
+                    Value::Compiled(nested) => Some(Rc::clone(nested)),
+                    _ => None,
+                });
+            pending.extend(nested);
+        }
+
+        out
+    }
+
+    fn disassemble_function(&self, function: &Function, lines: &[&str], out: &mut String) {
+        let chunk = &function.chunk;
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|p| format!("{} {}", p.typ, p.name))
+            .collect();
+
+        let _ = writeln!(
+            out,
+            "== {}({}) @{}, {} local(s) ==",
+            function.name,
+            params.join(", "),
+            function.ret,
+            function.locals.len()
+        );
+
+        let mut last_line = 0;
+
+        for (offset, op) in chunk.code.iter().enumerate() {
+            let line = chunk.locs[offset].line;
+
+            if line != last_line {
+                let text = lines.get(line.wrapping_sub(1)).map_or("", |l| l.trim());
+                let _ = writeln!(out, "{line:>4} | {text}");
+                last_line = line;
+            }
+
+            let _ = writeln!(out, "{:04}   {}", offset, self.op(function, *op));
+        }
+    }
+
+    /// Describes an op, with the constants and names it refers to.
+    fn op(&self, function: &Function, op: Op) -> String {
+        let chunk = &function.chunk;
+        let slot = |slot: Slot| match slot {
+            Slot::Local(idx) => format!("local {} '{}'", idx, function.slot_name(slot, self)),
+            Slot::Global(idx) => format!("global {} '{}'", idx, function.slot_name(slot, self)),
+        };
+
+        match op {
+            Op::Constant(idx) => {
+                let value = &chunk.constants[idx as usize];
+                format!("{:<12} {} ({})", "Constant", idx, value.repr())
+            }
+            Op::Pop => "Pop".to_string(),
+            Op::GetLocal(idx) => format!("{:<12} {}", "GetLocal", slot(Slot::Local(idx))),
+            Op::GetGlobal(idx) => format!("{:<12} {}", "GetGlobal", slot(Slot::Global(idx))),
+            Op::Define(target, decl) => {
+                let decl = match &chunk.decls[decl as usize] {
+                    (Some(typ), true) => format!("const {typ}"),
+                    (Some(typ), false) => typ.to_string(),
+                    (None, true) => "const".to_string(),
+                    (None, false) => "let".to_string(),
+                };
+                format!("{:<12} {} ({})", "Define", slot(target), decl)
+            }
+            Op::Assign(target, op, 0) => format!("{:<12} {} {}", "Assign", slot(target), op),
+            Op::Assign(target, op, depth) => {
+                format!(
+                    "{:<12} {}{} {}",
+                    "Assign",
+                    slot(target),
+                    "[]".repeat(depth as usize),
+                    op
+                )
+            }
+            Op::Array(len) => format!("{:<12} {}", "Array", len),
+            Op::Mapping(len) => format!("{:<12} {}", "Mapping", len),
+            Op::Index => "Index".to_string(),
+            Op::Unary(op) => format!("{:<12} {}", "Unary", op),
+            Op::Binary(op) => format!("{:<12} {}", "Binary", op),
+            Op::Compare(cond) => format!("{:<12} {}", "Compare", cond),
+            Op::Jump(target) => format!("{:<12} -> {:04}", "Jump", target),
+            Op::JumpIfFalse(target) => format!("{:<12} -> {:04}", "JumpIfFalse", target),
+            Op::Call(args) => format!("{:<12} {}", "Call", args),
+            Op::Return => "Return".to_string(),
+            Op::Delete(target) => format!("{:<12} {}", "Delete", slot(target)),
+        }
+    }
+}
@@ -0,0 +1,761 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::internal;
+use super::Condition;
+use super::Expr;
+use super::FuncDecl;
+use super::Keyword;
+use super::Loc;
+use super::Operator;
+use super::Stmt;
+use super::Symbol;
+use super::VarType;
+use super::BUILTINS;
+
+type GenResult = Result<(), String>;
+
+/// The runtime every generated program starts with.
+pub const RUNTIME: &str = include_str!("runtime.h");
+
+/// A value computed by generated code, and who owns it.
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    /// A value that isn't reference counted, like an int.
+    Plain(String),
+    /// A value owned by a variable or constant, which must be retained
+    /// to be kept.
+    Borrowed(String),
+    /// A temporary, which must be released once it has been used.
+    Owned(String),
+}
+
+impl Operand {
+    fn code(&self) -> &str {
+        match self {
+            Operand::Plain(code) | Operand::Borrowed(code) | Operand::Owned(code) => code,
+        }
+    }
+
+    /// The code of a value that is handed over to be kept.
+    fn take(&self) -> String {
+        match self {
+            Operand::Borrowed(code) => format!("why_retain({code})"),
+            Operand::Plain(code) | Operand::Owned(code) => code.clone(),
+        }
+    }
+}
+
+/// Transpiles a checked syntax tree to a C11 program, which includes
+/// the [`RUNTIME`] and can be compiled with `gcc -std=c11 FILE.c -lm`.
+///
+/// Variables are stored like the [`super::Compiler`] stores them, so
+/// top level declarations are globals and every other variable gets a
+/// slot in the locals of the C function it is declared in. Operators,
+/// calls and type checks go through the runtime, so programs behave
+/// like they do in the interpreter, errors included.
+#[derive(Clone, Debug)]
+pub struct CGen {
+    /// The name of each global.
+    pub globals: Vec<Symbol>,
+    /// The index of each global by name.
+    pub global_ids: HashMap<Symbol, u32>,
+    /// The string constants, which are created when the program starts.
+    pub strings: Vec<String>,
+    /// The compound types the program declares, and what they're called.
+    pub types: Vec<(VarType, String)>,
+    /// The declarations of the types and function descriptors.
+    pub decls: String,
+    /// The definitions of the C functions for each function.
+    pub functions: String,
+    /// The body of the function being generated.
+    pub body: String,
+    pub indent: usize,
+    /// The number of local slots of the function being generated.
+    pub locals: u32,
+    /// The block scopes of the function being generated, innermost
+    /// last, with the slot of each local declared in them. Empty at the
+    /// top level of the program, where declarations are globals.
+    pub scopes: Vec<Vec<(Symbol, u32)>>,
+    /// How many loops enclose the code being generated.
+    pub loops: usize,
+    /// Whether a function is being generated, rather than the top level.
+    pub in_function: bool,
+    /// The number of temporaries in the function being generated.
+    pub temps: usize,
+    /// The number of functions generated so far.
+    pub funcs: usize,
+}
+
+impl Default for CGen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CGen {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            globals: Vec::new(),
+            global_ids: HashMap::new(),
+            strings: Vec::new(),
+            types: Vec::new(),
+            decls: String::new(),
+            functions: String::new(),
+            body: String::new(),
+            indent: 1,
+            locals: 0,
+            scopes: Vec::new(),
+            loops: 0,
+            in_function: false,
+            temps: 0,
+            funcs: 0,
+        }
+    }
+
+    /// Generates a C program that runs the given program.
+    ///
+    /// # Returns
+    /// - [`Result<String, String>`] - The C source on success.
+    ///
+    /// # Errors
+    /// - If the program contains code that can't be compiled, such as
+    ///   a class or a `break` outside of a loop.
+    pub fn generate(mut self, program: &Expr) -> Result<String, String> {
+        match program {
+            Expr::Main(stmts) => {
+                self.hoist(stmts)?;
+
+                for stmt in stmts {
+                    self.stmt(stmt)?;
+                }
+            }
+            expr => self.stmt(&Stmt::Expr(expr.clone(), Loc::new()))?,
+        }
+
+        let mut out = String::new();
+
+        out.push_str("/* Generated by `why build --emit c`. Compile it with\n");
+        out.push_str(" *     gcc -std=c11 -O2 FILE.c -o FILE -lm\n */\n\n");
+        out.push_str(RUNTIME);
+
+        let _ = writeln!(out, "\nstatic WhyBinding g[{}];", self.globals.len().max(1));
+        let _ = writeln!(out, "static WhyValue k[{}];", self.strings.len().max(1));
+        out.push_str(&self.decls);
+        out.push_str(&self.functions);
+
+        out.push_str("\nint main(void) {\n");
+        let _ = writeln!(out, "    WhyBinding l[{}] = {{0}};", self.locals.max(1));
+
+        for (i, s) in self.strings.iter().enumerate() {
+            let _ = writeln!(out, "    k[{}] = why_string({}, {});", i, c_str(s), s.len());
+        }
+
+        out.push_str(&self.body);
+        out.push_str("    why_bindings_release(l, sizeof l / sizeof *l);\n");
+        out.push_str("    why_bindings_release(g, sizeof g / sizeof *g);\n\n");
+        out.push_str("    for (size_t i = 0; i < sizeof k / sizeof *k; i++) {\n");
+        out.push_str("        why_release(k[i]);\n");
+        out.push_str("    }\n\n");
+        out.push_str("    return 0;\n}\n");
+
+        Ok(out)
+    }
+
+    fn line(&mut self, code: &str) {
+        for _ in 0..self.indent {
+            self.body.push_str("    ");
+        }
+
+        self.body.push_str(code);
+        self.body.push('\n');
+    }
+
+    /// Stores a value computed by a line of code in a new temporary.
+    fn temp(&mut self, code: &str) -> Operand {
+        let name = format!("t{}", self.temps);
+        self.temps += 1;
+
+        self.line(&format!("WhyValue {name} = {code};"));
+        Operand::Owned(name)
+    }
+
+    fn release(&mut self, operands: &[&Operand]) {
+        for operand in operands {
+            if let Operand::Owned(name) = operand {
+                self.line(&format!("why_release({name});"));
+            }
+        }
+    }
+
+    fn global(&mut self, name: Symbol) -> u32 {
+        if let Some(idx) = self.global_ids.get(&name) {
+            return *idx;
+        }
+
+        let idx = internal::index(self.globals.len());
+        self.globals.push(name);
+        self.global_ids.insert(name, idx);
+        idx
+    }
+
+    /// The binding a variable is stored in, which is a global if it
+    /// isn't a local of the function being generated.
+    fn resolve(&mut self, name: Symbol) -> String {
+        let local = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|(n, _)| *n == name));
+
+        match local {
+            Some((_, slot)) => format!("l[{slot}]"),
+            None => format!("g[{}]", self.global(name)),
+        }
+    }
+
+    /// Declares a variable in the innermost scope, returning its binding.
+    /// Declaring the same name twice in a scope reuses the binding.
+    fn declare(&mut self, name: Symbol) -> String {
+        let Some(scope) = self.scopes.last_mut() else {
+            return format!("g[{}]", self.global(name));
+        };
+
+        if let Some((_, slot)) = scope.iter().find(|(n, _)| *n == name) {
+            return format!("l[{slot}]");
+        }
+
+        let slot = self.locals;
+        self.locals += 1;
+        scope.push((name, slot));
+        format!("l[{slot}]")
+    }
+
+    /// The descriptor of a type, declaring it if it is compound.
+    fn typ(&mut self, typ: &VarType) -> String {
+        let simple = match typ {
+            VarType::Int => "int",
+            VarType::Float => "float",
+            VarType::String => "string",
+            VarType::Bool => "bool",
+            VarType::Void => "void",
+            VarType::Any => "any",
+            _ => "",
+        };
+
+        if !simple.is_empty() {
+            return format!("&why_t_{simple}");
+        }
+
+        if let Some((_, name)) = self.types.iter().find(|(t, _)| t == typ) {
+            return format!("&{name}");
+        }
+
+        // Declare the types this one refers to first
+        let decl = match typ {
+            VarType::Array(item) => {
+                let item = self.typ(item);
+                format!("{{WHY_T_ARRAY, false, {item}, NULL, 0, NULL}}")
+            }
+            VarType::Mapping(key, value) => {
+                let (key, value) = (self.typ(key), self.typ(value));
+                format!("{{WHY_T_MAPPING, false, {key}, {value}, 0, NULL}}")
+            }
+            VarType::Function(params, ret) => {
+                let ret = self.typ(ret);
+                let params = self.types_array(params);
+                format!("{{WHY_T_FUNCTION, false, NULL, {ret}, {params}}}")
+            }
+            _ => unreachable!("{} is simple", typ),
+        };
+
+        let name = format!("type{}", self.types.len());
+        let _ = writeln!(self.decls, "static const WhyType {name} = {decl};");
+        self.types.push((typ.clone(), name.clone()));
+        format!("&{name}")
+    }
+
+    /// Declares an array of type descriptors, returning its length and
+    /// the array as fields of a [`VarType::Function`] descriptor.
+    fn types_array(&mut self, types: &[VarType]) -> String {
+        if types.is_empty() {
+            return "0, NULL".to_string();
+        }
+
+        let types: Vec<String> = types.iter().map(|t| self.typ(t)).collect();
+        let name = format!("params{}", self.types.len());
+
+        let _ = writeln!(
+            self.decls,
+            "static const WhyType *const {}[] = {{{}}};",
+            name,
+            types.join(", ")
+        );
+
+        format!("{}, {}", types.len(), name)
+    }
+
+    /// Declares every function in a block up front, so they can call
+    /// each other regardless of order.
+    ///
+    /// # Errors
+    /// - If a function's body can't be compiled.
+    pub fn hoist(&mut self, stmts: &[Stmt]) -> GenResult {
+        for stmt in stmts {
+            if let Stmt::Func(func) = stmt {
+                let descriptor = self.func(func)?;
+                let binding = self.declare(func.name);
+
+                self.line(&format!(
+                    "why_define(&{}, {}, NULL, false, why_func(&{}));",
+                    binding,
+                    c_str(func.name.as_str()),
+                    descriptor
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generates a C function for a function declaration, returning the
+    /// name of its descriptor.
+    ///
+    /// # Errors
+    /// - If the body contains code that can't be compiled.
+    pub fn func(&mut self, func: &FuncDecl) -> Result<String, String> {
+        let name = format!("f{}", self.funcs);
+        self.funcs += 1;
+        let params = (0..).zip(&func.params).map(|(i, p)| (p.name, i)).collect();
+
+        let outer = (
+            std::mem::take(&mut self.body),
+            std::mem::replace(&mut self.indent, 1),
+            std::mem::replace(&mut self.locals, internal::index(func.params.len())),
+            std::mem::replace(&mut self.scopes, vec![params]),
+            std::mem::take(&mut self.loops),
+            std::mem::replace(&mut self.in_function, true),
+            std::mem::take(&mut self.temps),
+        );
+
+        let result = self.block(&func.body);
+
+        let body = std::mem::replace(&mut self.body, outer.0);
+        let locals = self.locals;
+        (
+            self.indent,
+            self.locals,
+            self.scopes,
+            self.loops,
+            self.in_function,
+            self.temps,
+        ) = (outer.1, outer.2, outer.3, outer.4, outer.5, outer.6);
+
+        result?;
+
+        let types: Vec<VarType> = func.params.iter().map(|p| p.typ.clone()).collect();
+        let typ = self.typ(&VarType::Function(types, Box::new(func.ret.clone())));
+
+        let param_names = if func.params.is_empty() {
+            "NULL".to_string()
+        } else {
+            let names: Vec<String> = func.params.iter().map(|p| c_str(p.name.as_str())).collect();
+            let _ = writeln!(
+                self.decls,
+                "static const char *const {}_params[] = {{{}}};",
+                name,
+                names.join(", ")
+            );
+            format!("{name}_params")
+        };
+
+        let _ = writeln!(self.decls, "static WhyValue {name}(WhyValue *args);");
+        let _ = writeln!(
+            self.decls,
+            "static const WhyFunc {}_func = {{{}, {}, {}, {}}};",
+            name,
+            c_str(func.name.as_str()),
+            param_names,
+            typ,
+            name
+        );
+
+        let mut out = format!(
+            "\nstatic WhyValue {}(WhyValue *args) {{\n    WhyBinding l[{}] = {{0}};\n",
+            name,
+            locals.max(1)
+        );
+
+        if func.params.is_empty() {
+            out.push_str("    (void)args;\n");
+        }
+
+        for (i, param) in func.params.iter().enumerate() {
+            let typ = self.typ(&param.typ);
+            let _ = writeln!(out, "    why_param(&l[{i}], args[{i}], {typ});");
+        }
+
+        out.push_str(&body);
+        out.push_str("    why_bindings_release(l, sizeof l / sizeof *l);\n");
+        out.push_str("    return why_null();\n}\n");
+        self.functions.push_str(&out);
+
+        Ok(format!("{name}_func"))
+    }
+
+    /// Generates a block of statements in a new scope.
+    ///
+    /// # Errors
+    /// - If a statement can't be compiled.
+    pub fn block(&mut self, stmts: &[Stmt]) -> GenResult {
+        self.scopes.push(Vec::new());
+
+        let result = self
+            .hoist(stmts)
+            .and_then(|()| stmts.iter().try_for_each(|stmt| self.stmt(stmt)));
+
+        self.scopes.pop();
+        result
+    }
+
+    /// Generates a single statement.
+    ///
+    /// # Errors
+    /// - If the statement can't be compiled.
+    #[allow(clippy::too_many_lines)] // One arm per kind of statement
+    pub fn stmt(&mut self, stmt: &Stmt) -> GenResult {
+        match stmt {
+            Stmt::Expr(expr, _) => {
+                let value = self.expr(expr)?;
+                self.release(&[&value]);
+            }
+            Stmt::VarDecl(decl) => {
+                let value = self.expr(&decl.value)?;
+                let typ = match &decl.typ {
+                    Some(typ) => self.typ(typ),
+                    None => "NULL".to_string(),
+                };
+                let constant = decl.keyword == Some(Keyword::Const);
+                let binding = self.declare(decl.name);
+
+                self.line(&format!(
+                    "why_define(&{}, {}, {}, {}, {});",
+                    binding,
+                    c_str(decl.name.as_str()),
+                    typ,
+                    constant,
+                    value.take()
+                ));
+            }
+            Stmt::Assign(op, target, value, loc) => {
+                let value = self.expr(value)?;
+
+                let mut indexes = Vec::new();
+                let mut root = target;
+
+                while let Expr::Index(base, index) = root {
+                    indexes.push(self.expr(index)?);
+                    root = base;
+                }
+
+                let Expr::Ident(name) = root else {
+                    return super::loc_exc!(loc, "Can't assign to {:?}", root);
+                };
+
+                let binding = self.resolve(*name);
+                let list = if indexes.is_empty() {
+                    "NULL".to_string()
+                } else {
+                    let codes: Vec<&str> = indexes.iter().map(Operand::code).collect();
+                    format!("(WhyValue[]){{{}}}", codes.join(", "))
+                };
+
+                self.line(&format!(
+                    "why_assign(&{}, {}, {}, {}, {}, {});",
+                    binding,
+                    c_str(name.as_str()),
+                    c_op(*op),
+                    indexes.len(),
+                    list,
+                    value.take()
+                ));
+                self.release(&indexes.iter().collect::<Vec<_>>());
+            }
+            Stmt::Func(_) | Stmt::Comment(..) => (),
+            Stmt::Return(value, loc) => {
+                if !self.in_function {
+                    return super::loc_exc!(loc, "'return' outside of a function");
+                }
+
+                let value = match value {
+                    Some(value) => self.expr(value)?,
+                    None => Operand::Plain("why_null()".to_string()),
+                };
+
+                self.line("{");
+                self.indent += 1;
+                self.line(&format!("WhyValue result = {};", value.take()));
+                self.line("why_bindings_release(l, sizeof l / sizeof *l);");
+                self.line("return result;");
+                self.indent -= 1;
+                self.line("}");
+            }
+            Stmt::If(cond, body, otherwise, _) => {
+                let cond = self.expr(cond)?;
+                self.line(&format!("if (why_cond({})) {{", cond.code()));
+
+                self.indent += 1;
+                self.block(body)?;
+                self.indent -= 1;
+
+                if let Some(otherwise) = otherwise {
+                    self.line("} else {");
+                    self.indent += 1;
+                    self.block(otherwise)?;
+                    self.indent -= 1;
+                }
+
+                self.line("}");
+            }
+            Stmt::While(cond, body, _) => {
+                self.line("for (;;) {");
+                self.indent += 1;
+
+                let cond = self.expr(cond)?;
+                self.line(&format!("if (!why_cond({})) {{", cond.code()));
+                self.line("    break;");
+                self.line("}");
+
+                self.loops += 1;
+                let result = self.block(body);
+                self.loops -= 1;
+                result?;
+
+                self.indent -= 1;
+                self.line("}");
+            }
+            Stmt::Break => {
+                if self.loops == 0 {
+                    return super::exc!("'break' outside of a loop");
+                }
+
+                self.line("break;");
+            }
+            Stmt::Class(class) => {
+                return super::loc_exc!(class.loc, "Classes are not supported yet")
+            }
+            Stmt::Delete(_, loc) => {
+                return super::loc_exc!(loc, "delete is not supported in C yet")
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generates the code computing an expression, returning its value.
+    ///
+    /// # Errors
+    /// - If the expression can't be compiled.
+    #[allow(clippy::too_many_lines)] // One arm per kind of expression
+    fn expr(&mut self, expr: &Expr) -> Result<Operand, String> {
+        let operand = match expr {
+            Expr::Int(num) if *num == i64::MIN => Operand::Plain("why_int(INT64_MIN)".to_string()),
+            Expr::Int(num) => Operand::Plain(format!("why_int({num})")),
+            Expr::Float(num) if num.is_nan() => Operand::Plain("why_float(NAN)".to_string()),
+            Expr::Float(num) if num.is_infinite() => {
+                let sign = if *num < 0.0 { "-" } else { "" };
+                Operand::Plain(format!("why_float({sign}HUGE_VAL)"))
+            }
+            Expr::Float(num) => Operand::Plain(format!("why_float({num:?})")),
+            Expr::String(s) => {
+                let existing = self.strings.iter().position(|c| c == s);
+                let idx = existing.unwrap_or_else(|| {
+                    self.strings.push(s.clone());
+                    self.strings.len() - 1
+                });
+
+                Operand::Borrowed(format!("k[{idx}]"))
+            }
+            Expr::Bool(b) => Operand::Plain(format!("why_bool({b})")),
+            Expr::Null => Operand::Plain("why_null()".to_string()),
+            Expr::Error => return super::exc!("Can't run code that failed to parse"),
+            Expr::Main(_) => return super::exc!("Can't compile a program within a program"),
+            Expr::Parenthesized(inner) => self.expr(inner)?,
+            Expr::Ident(name) => {
+                let binding = self.resolve(*name);
+
+                // Locals can't change while an expression is computed, as
+                // only statements assign and calls have their own locals
+                if binding.starts_with('l') {
+                    Operand::Borrowed(format!("{binding}.value"))
+                } else {
+                    let builtin = BUILTINS.iter().position(|b| *b == name.as_str());
+                    let builtin = builtin.and_then(|b| i32::try_from(b).ok()).unwrap_or(-1);
+
+                    self.temp(&format!(
+                        "why_global(&{}, {}, {})",
+                        binding,
+                        c_str(name.as_str()),
+                        builtin
+                    ))
+                }
+            }
+            Expr::Builtin(name) if BUILTINS.contains(&name.as_str()) => {
+                Operand::Plain(format!("why_builtin(WHY_{})", name.as_str().to_uppercase()))
+            }
+            Expr::Builtin(name) => return super::exc!("Unknown builtin '%{}'", name),
+            Expr::Array(items) if items.is_empty() => self.temp("why_array_new(0)"),
+            Expr::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.expr(item).map(|item| item.take()))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                self.temp(&format!(
+                    "why_array_of({}, (WhyValue[]){{{}}})",
+                    items.len(),
+                    items.join(", ")
+                ))
+            }
+            Expr::Mapping(pairs) if pairs.is_empty() => self.temp("why_mapping_new(0)"),
+            Expr::Mapping(pairs) => {
+                let mut items = Vec::with_capacity(pairs.len() * 2);
+
+                for (key, value) in pairs {
+                    items.push(self.expr(key)?.take());
+                    items.push(self.expr(value)?.take());
+                }
+
+                self.temp(&format!(
+                    "why_mapping_of({}, (WhyValue[]){{{}}})",
+                    pairs.len(),
+                    items.join(", ")
+                ))
+            }
+            Expr::Index(base, index) => {
+                let base = self.expr(base)?;
+                let index = self.expr(index)?;
+                let value = self.temp(&format!("why_index({}, {})", base.code(), index.code()));
+
+                self.release(&[&base, &index]);
+                value
+            }
+            Expr::UnaryOp(op, inner) => {
+                let inner = self.expr(inner)?;
+                let value = self.temp(&format!("why_unary({}, {})", c_op(*op), inner.code()));
+
+                self.release(&[&inner]);
+                value
+            }
+            Expr::BinaryOp(op, left, right) => {
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                let value = self.temp(&format!(
+                    "why_binary({}, {}, {})",
+                    c_op(*op),
+                    left.code(),
+                    right.code()
+                ));
+
+                self.release(&[&left, &right]);
+                value
+            }
+            Expr::Compare(cond, left, right) => {
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                let value = self.temp(&format!(
+                    "why_compare({}, {}, {})",
+                    c_cond(*cond),
+                    left.code(),
+                    right.code()
+                ));
+
+                self.release(&[&left, &right]);
+                value
+            }
+            Expr::Call(callee, args, _) => {
+                let callee = self.expr(callee)?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.expr(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let list = if args.is_empty() {
+                    "NULL".to_string()
+                } else {
+                    let codes: Vec<&str> = args.iter().map(Operand::code).collect();
+                    format!("(WhyValue[]){{{}}}", codes.join(", "))
+                };
+
+                let value = self.temp(&format!(
+                    "why_call({}, {}, {})",
+                    callee.code(),
+                    args.len(),
+                    list
+                ));
+
+                self.release(&[&callee]);
+                self.release(&args.iter().collect::<Vec<_>>());
+                value
+            }
+        };
+
+        Ok(operand)
+    }
+}
+
+/// The runtime's name for an operator.
+fn c_op(op: Operator) -> &'static str {
+    match op {
+        Operator::Add => "WHY_OP_ADD",
+        Operator::Increment => "WHY_OP_INCREMENT",
+        Operator::IncrementBy => "WHY_OP_INCREMENT_BY",
+        Operator::Subtract => "WHY_OP_SUBTRACT",
+        Operator::Decrement => "WHY_OP_DECREMENT",
+        Operator::DecrementBy => "WHY_OP_DECREMENT_BY",
+        Operator::MultBy => "WHY_OP_MULT_BY",
+        Operator::DivBy => "WHY_OP_DIV_BY",
+        Operator::Pow => "WHY_OP_POW",
+        Operator::Mult => "WHY_OP_MULT",
+        Operator::Div => "WHY_OP_DIV",
+        Operator::Dot => "WHY_OP_DOT",
+        Operator::Assign => "WHY_OP_ASSIGN",
+        Operator::Not => "WHY_OP_NOT",
+    }
+}
+
+/// The runtime's name for a condition.
+fn c_cond(cond: Condition) -> &'static str {
+    match cond {
+        Condition::Lt => "WHY_LT",
+        Condition::Gt => "WHY_GT",
+        Condition::Lte => "WHY_LTE",
+        Condition::Gte => "WHY_GTE",
+        Condition::Ne => "WHY_NE",
+        Condition::EqEq => "WHY_EQEQ",
+    }
+}
+
+/// Quotes a string as a C string literal, escaping every byte that
+/// isn't printable ASCII.
+#[must_use]
+pub fn c_str(s: &str) -> String {
+    let mut out = String::from("\"");
+
+    for byte in s.bytes() {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            // Avoid trigraphs
+            b'?' => out.push_str("\\?"),
+            0x20..=0x7e => out.push(byte as char),
+            _ => {
+                let _ = write!(out, "\\{byte:03o}");
+            }
+        }
+    }
+
+    out.push('"');
+    out
+}
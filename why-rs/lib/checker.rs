@@ -0,0 +1,583 @@
+use std::collections::HashMap;
+
+use super::Class;
+use super::ClassDecl;
+use super::Expr;
+use super::FuncDecl;
+use super::Keyword;
+use super::Loc;
+use super::Operator;
+use super::Stmt;
+use super::Symbol;
+use super::VarType;
+use super::BUILTINS;
+
+type TypeResult = Result<VarType, String>;
+
+/// The types of the fields and methods of a class, including those it
+/// inherited. Methods are typed without their `&self`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClassType {
+    pub fields: HashMap<Symbol, VarType>,
+    pub methods: HashMap<Symbol, VarType>,
+}
+
+/// A static type checker, which keeps the types of declared variables
+/// between calls to [`Checker::check`].
+#[derive(Clone, Debug)]
+pub struct Checker {
+    /// The scopes of declared variables and whether they are constant,
+    /// the first being the global scope.
+    pub scopes: Vec<HashMap<Symbol, (VarType, bool)>>,
+    /// The index of the first scope of the function currently being
+    /// checked. Like at runtime, a function body only sees its own
+    /// scopes and the globals, not the locals of enclosing functions.
+    pub frame: usize,
+    /// The return type of the function currently being checked.
+    pub ret: Option<VarType>,
+    /// The type of every variable declaration checked so far, by the
+    /// location of its name.
+    pub declared: Vec<(Loc, VarType)>,
+    /// Where the statement or call being checked is, which errors are
+    /// reported at.
+    pub loc: Loc,
+    /// The classes declared so far.
+    pub classes: HashMap<Symbol, ClassType>,
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            frame: 0,
+            ret: None,
+            declared: Vec::new(),
+            loc: Loc::new(),
+            classes: HashMap::new(),
+        }
+    }
+
+    /// Checks a program, declaring its variables in the global scope.
+    ///
+    /// # Errors
+    /// - If the program contains a type error.
+    pub fn check(&mut self, program: &Expr) -> Result<(), String> {
+        self.loc = Loc::new();
+
+        match program {
+            Expr::Main(stmts) => {
+                self.hoist(stmts);
+                stmts.iter().try_for_each(|stmt| self.check_stmt(stmt))
+            }
+            expr => self.infer(expr).map(|_| ()),
+        }
+    }
+
+    /// Looks up the type of a variable, searching the current
+    /// function's scopes before the globals.
+    #[must_use]
+    pub fn lookup(&self, name: Symbol) -> Option<&(VarType, bool)> {
+        self.visible().find_map(|scope| scope.get(&name))
+    }
+
+    /// The scopes the current function can see,
+    /// innermost first.
+    fn visible(&self) -> impl Iterator<Item = &HashMap<Symbol, (VarType, bool)>> {
+        let globals = (self.frame > 0).then(|| &self.scopes[0]);
+        self.scopes[self.frame..].iter().rev().chain(globals)
+    }
+
+    /// Declares a variable in the innermost scope.
+    ///
+    /// # Panics
+    /// - If there are no scopes, though the global scope is never popped.
+    pub fn declare(&mut self, name: Symbol, typ: VarType, constant: bool) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name, (typ, constant));
+    }
+
+    /// The type of a function declaration.
+    #[must_use]
+    pub fn func_type(func: &FuncDecl) -> VarType {
+        VarType::Function(
+            func.params.iter().map(|p| p.typ.clone()).collect(),
+            Box::new(func.ret.clone()),
+        )
+    }
+
+    /// Declares every function in a block up front, so they can call
+    /// each other regardless of order.
+    pub fn hoist(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if let Stmt::Func(func) = stmt {
+                self.declare(func.name, Checker::func_type(func), false);
+            }
+        }
+    }
+
+    /// Checks a block of statements in a new scope.
+    ///
+    /// # Errors
+    /// - If the block contains a type error.
+    pub fn check_block(&mut self, stmts: &[Stmt]) -> Result<(), String> {
+        self.scopes.push(HashMap::new());
+        self.hoist(stmts);
+
+        let result = stmts.iter().try_for_each(|stmt| self.check_stmt(stmt));
+
+        self.scopes.pop();
+        result
+    }
+
+    /// Checks a single statement.
+    ///
+    /// # Errors
+    /// - If the statement contains a type error.
+    pub fn check_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
+        match stmt {
+            Stmt::Expr(expr, loc) => {
+                self.loc = loc.clone();
+                self.infer(expr)?;
+            }
+            Stmt::VarDecl(decl) => {
+                self.loc = decl.loc.clone();
+                let value = self.infer(&decl.value)?;
+                let typ = decl.typ.clone().unwrap_or_else(|| value.clone());
+
+                if !typ.accepts(&value) {
+                    return super::loc_exc!(
+                        decl.loc,
+                        "Can't assign {} to '{}' of type {}",
+                        value,
+                        (decl.name),
+                        typ
+                    );
+                }
+
+                let constant = decl.keyword == Some(Keyword::Const);
+                self.declare(decl.name, typ.clone(), constant);
+                self.declared.push((decl.loc.clone(), typ));
+            }
+            Stmt::Assign(op, target, value, loc) => self.check_assign(op, target, value, loc)?,
+            Stmt::Func(func) => self.check_func(func)?,
+            Stmt::Class(class) => self.check_class(class)?,
+            Stmt::Return(value, loc) => {
+                self.loc = loc.clone();
+
+                let typ = match value {
+                    Some(expr) => self.infer(expr)?,
+                    None => VarType::Void,
+                };
+
+                match &self.ret {
+                    None => return super::loc_exc!(loc, "'return' outside of a function"),
+                    Some(ret) if !ret.accepts(&typ) => {
+                        return super::loc_exc!(loc, "Expected to return {}, but got {}", ret, typ)
+                    }
+                    Some(_) => (),
+                }
+            }
+            Stmt::If(cond, body, otherwise, loc) => {
+                self.loc = loc.clone();
+                self.check_cond(cond)?;
+                self.check_block(body)?;
+
+                if let Some(otherwise) = otherwise {
+                    self.check_block(otherwise)?;
+                }
+            }
+            Stmt::While(cond, body, loc) => {
+                self.loc = loc.clone();
+                self.check_cond(cond)?;
+                self.check_block(body)?;
+            }
+            Stmt::Delete(name, loc) => {
+                let idx = (self.frame..self.scopes.len())
+                    .rev()
+                    .chain([0])
+                    .find(|i| self.scopes[*i].contains_key(name));
+
+                if idx.and_then(|i| self.scopes[i].remove(name)).is_none() {
+                    return super::loc_exc!(loc, "Can't delete undefined variable '{}'", name);
+                }
+            }
+            Stmt::Break | Stmt::Comment(..) => (),
+        }
+
+        Ok(())
+    }
+
+    /// Checks the body of a function against its return type. Like at
+    /// runtime, it sees its params and the globals.
+    ///
+    /// # Errors
+    /// - If the body contains a type error.
+    pub fn check_func(&mut self, func: &FuncDecl) -> Result<(), String> {
+        self.loc = func.loc.clone();
+        let outer = self.ret.replace(func.ret.clone());
+        let mut scope = HashMap::new();
+
+        for param in &func.params {
+            scope.insert(param.name, (param.typ.clone(), false));
+        }
+
+        self.scopes.push(scope);
+        let frame = std::mem::replace(&mut self.frame, self.scopes.len() - 1);
+        let result = self.check_block(&func.body);
+        self.frame = frame;
+        self.scopes.pop();
+        self.ret = outer;
+
+        result.map_err(|e| format!("{} (in '{}')", e, func.name))
+    }
+
+    /// Checks a class, declaring it as a function that makes objects of
+    /// it. Field defaults see only the globals, and methods see `self`
+    /// as an object of the class.
+    ///
+    /// # Errors
+    /// - If the base class is undefined, a default doesn't fit its
+    ///   field, or a method contains a type error.
+    pub fn check_class(&mut self, class: &ClassDecl) -> Result<(), String> {
+        self.loc = class.loc.clone();
+
+        let mut typ = match class.base {
+            Some(base) => match self.classes.get(&base) {
+                Some(typ) => typ.clone(),
+                None => return super::loc_exc!(class.loc, "Undefined class '{}'", base),
+            },
+            None => ClassType::default(),
+        };
+
+        for field in &class.fields {
+            typ.fields.insert(field.name, field.typ.clone());
+        }
+
+        for method in &class.methods {
+            typ.methods.insert(method.name, Class::method_type(method));
+        }
+
+        // Declared first, so fields and methods can make objects of it
+        let make = VarType::Function(vec![], Box::new(VarType::Class(class.name)));
+        self.classes.insert(class.name, typ);
+        self.declare(class.name, make, true);
+
+        for field in &class.fields {
+            let Some(value) = &field.value else {
+                continue;
+            };
+
+            self.loc = field.loc.clone();
+            self.scopes.push(HashMap::new());
+            let frame = std::mem::replace(&mut self.frame, self.scopes.len() - 1);
+            let value_typ = self.infer(value);
+            self.frame = frame;
+            self.scopes.pop();
+
+            let value_typ = value_typ?;
+
+            if !field.typ.accepts(&value_typ) {
+                return super::loc_exc!(
+                    field.loc,
+                    "Can't assign {} to '{}' of type {}",
+                    value_typ,
+                    (field.name),
+                    (field.typ)
+                );
+            }
+        }
+
+        for method in &class.methods {
+            let mut method = method.clone();
+
+            if Class::binds(&method) {
+                method.params[0].typ = VarType::Class(class.name);
+            }
+
+            self.check_func(&method)?;
+        }
+
+        Ok(())
+    }
+
+    /// The type of a field or method of an object, and whether it's a
+    /// method.
+    ///
+    /// # Errors
+    /// - If the type isn't a class, or the class has no such field or
+    ///   method.
+    pub fn field(&self, object: &VarType, field: &Expr) -> Result<(VarType, bool), String> {
+        let Expr::Ident(name) = field else {
+            return super::loc_exc!(self.loc, "Expected a field name, but got {:?}", field);
+        };
+
+        let class = match object {
+            VarType::Any => return Ok((VarType::Any, false)),
+            VarType::Class(class) => class,
+            typ => return super::loc_exc!(self.loc, "Can't get field '{}' of {}", name, typ),
+        };
+
+        let Some(typ) = self.classes.get(class) else {
+            return super::loc_exc!(self.loc, "Undefined class '{}'", class);
+        };
+
+        match (typ.fields.get(name), typ.methods.get(name)) {
+            (Some(field), _) => Ok((field.clone(), false)),
+            (None, Some(method)) => Ok((method.clone(), true)),
+            (None, None) => super::loc_exc!(self.loc, "'{}' has no field '{}'", class, name),
+        }
+    }
+
+    /// Checks an assignment to a variable or indexed element.
+    ///
+    /// # Errors
+    /// - If the target is constant, or the value doesn't fit its type.
+    pub fn check_assign(
+        &mut self,
+        op: &Operator,
+        target: &Expr,
+        value: &Expr,
+        loc: &Loc,
+    ) -> Result<(), String> {
+        self.loc = loc.clone();
+
+        if let Expr::Ident(name) = target {
+            if let Some((_, true)) = self.lookup(*name) {
+                return super::loc_exc!(loc, "Can't assign to constant '{}'", name);
+            }
+        }
+
+        if let Expr::BinaryOp(Operator::Dot, object, field) = target {
+            let object = self.infer(object)?;
+
+            if let (_, true) = self.field(&object, field)? {
+                return super::loc_exc!(loc, "Can't assign to method {:?}", field);
+            }
+        }
+
+        let target_typ = self.infer(target)?;
+        let value = self.infer(value)?;
+
+        let result = match op {
+            Operator::Assign => value,
+            Operator::IncrementBy | Operator::Increment => {
+                self.binary(&Operator::Add, &target_typ, &value)?
+            }
+            Operator::DecrementBy | Operator::Decrement => {
+                self.binary(&Operator::Subtract, &target_typ, &value)?
+            }
+            Operator::MultBy => self.binary(&Operator::Mult, &target_typ, &value)?,
+            _ => self.binary(&Operator::Div, &target_typ, &value)?,
+        };
+
+        if !target_typ.accepts(&result) {
+            return super::loc_exc!(loc, "Can't assign {} to {}", result, target_typ);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a condition is a bool.
+    ///
+    /// # Errors
+    /// - If the condition is another type.
+    pub fn check_cond(&mut self, cond: &Expr) -> Result<(), String> {
+        match self.infer(cond)? {
+            VarType::Bool | VarType::Any => Ok(()),
+            typ => super::loc_exc!(self.loc, "Condition must be a bool, but got {}", typ),
+        }
+    }
+
+    /// Infers the type of an expression.
+    ///
+    /// # Returns
+    /// - [`TypeResult`] - The type on success.
+    ///
+    /// # Errors
+    /// - If the expression contains a type error.
+    #[allow(clippy::too_many_lines)] // One arm per kind of expression
+    pub fn infer(&mut self, expr: &Expr) -> TypeResult {
+        match expr {
+            Expr::Int(_) => Ok(VarType::Int),
+            Expr::Float(_) => Ok(VarType::Float),
+            Expr::String(_) => Ok(VarType::String),
+            Expr::Bool(_) => Ok(VarType::Bool),
+            Expr::Compare(_, left, right) => {
+                self.infer(left)?;
+                self.infer(right)?;
+                Ok(VarType::Bool)
+            }
+            Expr::Null | Expr::Main(_) => Ok(VarType::Void),
+            Expr::Error => Ok(VarType::Any),
+            Expr::Parenthesized(inner) => self.infer(inner),
+            Expr::Ident(name) => match self.lookup(*name) {
+                Some((typ, _)) => Ok(typ.clone()),
+                None if BUILTINS.contains(&name.as_str()) => Ok(Checker::builtin(name.as_str())),
+                None => super::loc_exc!(self.loc, "Undefined variable '{}'", name),
+            },
+            Expr::Builtin(name) if BUILTINS.contains(&name.as_str()) => {
+                Ok(Checker::builtin(name.as_str()))
+            }
+            Expr::Builtin(name) => super::loc_exc!(self.loc, "Unknown builtin '%{}'", name),
+            Expr::Array(items) => {
+                let mut typ = VarType::Any;
+
+                for item in items {
+                    let item = self.infer(item)?;
+
+                    if typ == VarType::Any || (typ == VarType::Int && item == VarType::Float) {
+                        typ = item;
+                    } else if !typ.accepts(&item) {
+                        return super::loc_exc!(
+                            self.loc,
+                            "Array items must share a type, got {} and {}",
+                            typ,
+                            item
+                        );
+                    }
+                }
+
+                Ok(VarType::Array(Box::new(typ)))
+            }
+            Expr::Mapping(pairs) => {
+                let mut key_typ = VarType::Any;
+                let mut value_typ = VarType::Any;
+
+                for (key, value) in pairs {
+                    let key = self.infer(key)?;
+                    let value = self.infer(value)?;
+
+                    if key_typ == VarType::Any {
+                        key_typ = key;
+                        value_typ = value;
+                    } else if !key_typ.accepts(&key) || !value_typ.accepts(&value) {
+                        return super::loc_exc!(
+                            self.loc,
+                            "Mapping entries must share a type, got {}->{} and {}->{}",
+                            key_typ,
+                            value_typ,
+                            key,
+                            value
+                        );
+                    }
+                }
+
+                Ok(VarType::Mapping(Box::new(key_typ), Box::new(value_typ)))
+            }
+            Expr::Index(base, index) => {
+                let base = self.infer(base)?;
+                let index = self.infer(index)?;
+
+                match (base, index) {
+                    (VarType::Array(item), VarType::Int | VarType::Any) => Ok(*item),
+                    (VarType::String, VarType::Int | VarType::Any) => Ok(VarType::String),
+                    (VarType::Mapping(key, value), index) if key.accepts(&index) => Ok(*value),
+                    (VarType::Any, _) => Ok(VarType::Any),
+                    (base, index) => {
+                        super::loc_exc!(self.loc, "Can't index {} with {}", base, index)
+                    }
+                }
+            }
+            Expr::UnaryOp(op, inner) => match (op, self.infer(inner)?) {
+                (Operator::Subtract, typ @ (VarType::Int | VarType::Float | VarType::Any)) => {
+                    Ok(typ)
+                }
+                (Operator::Not, VarType::Bool | VarType::Any) => Ok(VarType::Bool),
+                (op, typ) => {
+                    super::loc_exc!(self.loc, "Unsupported operand type for {}: {}", op, typ)
+                }
+            },
+            Expr::BinaryOp(Operator::Dot, object, field) => {
+                let object = self.infer(object)?;
+                self.field(&object, field).map(|(typ, _)| typ)
+            }
+            Expr::BinaryOp(op, left, right) => {
+                let left = self.infer(left)?;
+                let right = self.infer(right)?;
+                self.binary(op, &left, &right)
+            }
+            Expr::Call(callee, args, loc) => {
+                let builtin = match &**callee {
+                    Expr::Builtin(name) => Some(name),
+                    Expr::Ident(name) if self.lookup(*name).is_none() => Some(name),
+                    _ => None,
+                };
+
+                // Errors in the call are reported where it is, and those
+                // after it where the statement is
+                let outer = std::mem::replace(&mut self.loc, loc.clone());
+                let callee = self.infer(callee)?;
+                let args: Result<Vec<VarType>, String> =
+                    args.iter().map(|a| self.infer(a)).collect();
+                let args = args?;
+                self.loc = outer;
+
+                match callee {
+                    VarType::Function(_, ret) if builtin.is_some() => Ok(*ret),
+                    VarType::Function(params, _) if params.len() != args.len() => super::loc_exc!(
+                        loc,
+                        "Expected {} argument(s), but {} were given",
+                        (params.len()),
+                        (args.len())
+                    ),
+                    VarType::Function(params, ret) => {
+                        for (param, arg) in params.iter().zip(&args) {
+                            if !param.accepts(arg) {
+                                return super::loc_exc!(loc, "Expected {}, but got {}", param, arg);
+                            }
+                        }
+
+                        Ok(*ret)
+                    }
+                    VarType::Any => Ok(VarType::Any),
+                    typ => super::loc_exc!(loc, "{} is not callable", typ),
+                }
+            }
+        }
+    }
+
+    /// The type of a builtin function. Builtins accept any arguments, so
+    /// only the return type is meaningful.
+    #[must_use]
+    pub fn builtin(name: &str) -> VarType {
+        let ret = match name {
+            "len" => VarType::Int,
+            "str" => VarType::String,
+            _ => VarType::Void,
+        };
+
+        VarType::Function(vec![], Box::new(ret))
+    }
+
+    /// Infers the type of a binary operation.
+    ///
+    /// # Errors
+    /// - If the operator doesn't apply to the types.
+    pub fn binary(&self, op: &Operator, left: &VarType, right: &VarType) -> TypeResult {
+        match (op, left, right) {
+            (_, VarType::Any, _) | (_, _, VarType::Any) => Ok(VarType::Any),
+            (_, VarType::Int, VarType::Int) => Ok(VarType::Int),
+            (_, VarType::Int | VarType::Float, VarType::Int | VarType::Float) => Ok(VarType::Float),
+            (Operator::Add, VarType::String, VarType::String) => Ok(VarType::String),
+            (Operator::Add, VarType::Array(_), VarType::Array(_)) if left.accepts(right) => {
+                Ok(left.clone())
+            }
+            _ => super::loc_exc!(
+                self.loc,
+                "Unsupported operand types for {}: {} and {}",
+                op,
+                left,
+                right
+            ),
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use super::doc::DocFormat;
+use super::dump::Format;
+use super::traceback;
+use super::Optimizer;
+
+/// The subcommands of the `why` binary.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Command {
+    Run,
+    Check,
+    Tokens,
+    Ast,
+    Fmt,
+    Doc,
+    Disasm,
+    Build,
+    Repl,
+    Help,
+    Version,
+}
+
+/// What `build` compiles a program to.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Emit {
+    /// Bytecode in a `.whyc` file, which `run` can load.
+    #[default]
+    Whyc,
+    /// A C program, which includes its runtime.
+    C,
+    /// A WebAssembly module in the text format.
+    Wat,
+    /// A WebAssembly module in the binary format.
+    Wasm,
+}
+
+/// The stage of the pipeline that failed, which decides the exit code.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Failure {
+    Usage,
+    Io,
+    Lex,
+    Parse,
+    Type,
+    Runtime,
+    /// `fmt --check` found source that isn't formatted.
+    Unformatted,
+}
+
+/// The parsed command line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[allow(clippy::struct_excessive_bools)] // Each is an independent flag
+pub struct Cli {
+    pub command: Command,
+    /// The source file, `-` meaning stdin.
+    pub file: Option<String>,
+    /// Whether to trace the lexer to stderr.
+    pub trace_lexer: bool,
+    /// Whether to trace the parser to stderr.
+    pub trace_parser: bool,
+    /// The output format of `tokens` and `ast`.
+    pub format: Format,
+    /// Whether `fmt` should only check the source is formatted.
+    pub check: bool,
+    /// Whether `tokens` should include whitespace and comments.
+    pub trivia: bool,
+    /// The output format of `doc`.
+    pub doc_format: DocFormat,
+    /// Whether `run` should compile the program to bytecode and run it
+    /// on the virtual machine.
+    pub vm: bool,
+    /// Whether `run` should compile numeric functions to native code
+    /// when they're first called.
+    pub jit: bool,
+    /// Whether `run` should report the heap values it allocated and
+    /// freed, and the objects it collected, to stderr.
+    pub heap_stats: bool,
+    /// How many calls `run` may nest before failing with a stack
+    /// overflow.
+    pub max_depth: usize,
+    /// Where `build` should write the compiled program.
+    pub output: Option<String>,
+    /// What `build` should compile the program to.
+    pub emit: Emit,
+    /// Whether `ast` should print the program once it's optimized.
+    pub optimized: bool,
+    /// Which [`Optimizer`] passes run before the program is run or
+    /// compiled.
+    pub opt_level: u8,
+}
+
+pub const USAGE: &str = "\
+Usage: why [COMMAND] [OPTIONS] [FILE]
+
+Commands:
+  run <FILE>     Check and run a program or a .whyc file (the default
+                 with a FILE)
+  check <FILE>   Lex, parse and type check a program
+  tokens <FILE>  Print the tokens of a program
+  ast <FILE>     Print the syntax tree of a program
+  fmt <FILE>     Print a program in the canonical style
+  doc <FILE>     Print the documentation of a program as Markdown
+  disasm <FILE>  Print the bytecode a program compiles to
+  build <FILE>   Compile a program to a .whyc file, which run can load
+  repl           Start the interactive REPL (the default with no FILE)
+
+Options:
+  -h, --help      Print this message
+  -V, --version   Print the version
+  -v, --verbose   Trace the lexer and parser to stderr
+  --trace-lexer   Trace the lexer to stderr
+  --trace-parser  Trace the parser to stderr
+  --format <FMT>  Output `tokens` or `ast` as text, json or sexpr
+  --optimized     Make `ast` print the program once it's optimized
+  --check         Make `fmt` fail if the FILE isn't formatted
+  --trivia        Include whitespace and comments in `tokens`
+  --html          Make `doc` print a static HTML page instead
+  --vm            Make `run` compile to bytecode and run it on the VM
+  --jit           Make `run` compile numeric functions to native code
+  --gc-stats      Make `run` report the heap values it allocated and
+                  freed, and the objects it collected, to stderr
+  --max-depth <N> How many calls `run` may nest before failing with a
+                  stack overflow (512 by default, at most 32768)
+  --emit <KIND>   Make `build` emit whyc bytecode (the default), c source,
+                  or a wat or wasm WebAssembly module
+  -o <FILE>       Where `build` writes to (FILE with the KIND's extension)
+  --opt-level <N> How much to optimize before running or compiling: 0 for
+                  nothing, 1 to fold constants and remove dead branches,
+                  2 to also remove unused variables (the default)
+
+Use `-` as the FILE to read from stdin.
+
+Exit codes:
+  0 success, 1 usage or I/O error or unformatted FILE, 2 lex error,
+  3 parse error, 4 type error, 5 runtime error";
+
+impl Failure {
+    /// The process exit code for this failure.
+    #[must_use]
+    pub fn code(self) -> i32 {
+        match self {
+            Failure::Usage | Failure::Io | Failure::Unformatted => 1,
+            Failure::Lex => 2,
+            Failure::Parse => 3,
+            Failure::Type => 4,
+            Failure::Runtime => 5,
+        }
+    }
+}
+
+impl Emit {
+    /// The extension of the files this is written to.
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Emit::Whyc => "whyc",
+            Emit::C => "c",
+            Emit::Wat => "wat",
+            Emit::Wasm => "wasm",
+        }
+    }
+}
+
+impl TryFrom<&str> for Emit {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "whyc" => Ok(Emit::Whyc),
+            "c" => Ok(Emit::C),
+            "wat" => Ok(Emit::Wat),
+            "wasm" => Ok(Emit::Wasm),
+            _ => super::exc!("Unknown kind to emit: {:?}", name),
+        }
+    }
+}
+
+impl TryFrom<&str> for Command {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "run" => Ok(Command::Run),
+            "check" => Ok(Command::Check),
+            "tokens" => Ok(Command::Tokens),
+            "ast" => Ok(Command::Ast),
+            "fmt" => Ok(Command::Fmt),
+            "doc" => Ok(Command::Doc),
+            "disasm" => Ok(Command::Disasm),
+            "build" => Ok(Command::Build),
+            "repl" => Ok(Command::Repl),
+            "help" => Ok(Command::Help),
+            _ => super::exc!("Unknown command: {:?}", name),
+        }
+    }
+}
+
+impl Cli {
+    /// Parses the command line arguments, not including the program
+    /// name. A lone file argument is shorthand for `why run <FILE>`.
+    ///
+    /// # Returns
+    /// - [`Result<Cli, String>`] - The parsed command line on success.
+    ///
+    /// # Errors
+    /// - If an option or command is unknown, or a file is missing or
+    ///   given more than once.
+    #[allow(clippy::too_many_lines)] // One arm per option, then their checks
+    pub fn parse<I, S>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut command = None;
+        let mut file = None;
+        let mut trace_lexer = false;
+        let mut trace_parser = false;
+        let mut format = None;
+        let mut check = false;
+        let mut trivia = false;
+        let mut doc_format = DocFormat::default();
+        let mut vm = false;
+        let mut jit = false;
+        let mut heap_stats = false;
+        let mut max_depth = None;
+        let mut output = None;
+        let mut emit = None;
+        let mut optimized = false;
+        let mut opt_level = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+
+            match arg {
+                "--format" => match args.next() {
+                    Some(name) => format = Some(Format::try_from(name.as_ref())?),
+                    None => return super::exc!("Missing value for --format"),
+                },
+                "--emit" => match args.next() {
+                    Some(name) => emit = Some(Emit::try_from(name.as_ref())?),
+                    None => return super::exc!("Missing value for --emit"),
+                },
+                _ if arg.starts_with("--emit=") => {
+                    emit = Some(Emit::try_from(&arg["--emit=".len()..])?);
+                }
+                "--opt-level" => match args.next() {
+                    Some(level) => opt_level = Some(Cli::opt_level(level.as_ref())?),
+                    None => return super::exc!("Missing value for --opt-level"),
+                },
+                _ if arg.starts_with("--opt-level=") => {
+                    opt_level = Some(Cli::opt_level(&arg["--opt-level=".len()..])?);
+                }
+                "--max-depth" => match args.next() {
+                    Some(depth) => max_depth = Some(Cli::max_depth(depth.as_ref())?),
+                    None => return super::exc!("Missing value for --max-depth"),
+                },
+                _ if arg.starts_with("--max-depth=") => {
+                    max_depth = Some(Cli::max_depth(&arg["--max-depth=".len()..])?);
+                }
+                "-o" | "--output" => match args.next() {
+                    Some(path) => output = Some(path.as_ref().to_string()),
+                    None => return super::exc!("Missing value for {}", arg),
+                },
+                _ if arg.starts_with("--format=") => {
+                    format = Some(Format::try_from(&arg["--format=".len()..])?);
+                }
+                "-h" | "--help" => command = Some(Command::Help),
+                "-V" | "--version" => command = Some(Command::Version),
+                "-v" | "--verbose" => (trace_lexer, trace_parser) = (true, true),
+                "--trace-lexer" => trace_lexer = true,
+                "--trace-parser" => trace_parser = true,
+                "--check" => check = true,
+                "--trivia" => trivia = true,
+                "--html" => doc_format = DocFormat::Html,
+                "--vm" => vm = true,
+                "--jit" => jit = true,
+                // The name it had before there was a collector
+                "--gc-stats" | "--heap-stats" => heap_stats = true,
+                "--optimized" => optimized = true,
+                "-" => file = Cli::set_file(file, arg)?,
+                _ if arg.starts_with('-') => return super::exc!("Unknown option: {:?}", arg),
+                _ if command.is_none() && file.is_none() => match Command::try_from(arg) {
+                    Ok(parsed) => command = Some(parsed),
+                    Err(_) => file = Some(arg.to_string()),
+                },
+                _ => file = Cli::set_file(file, arg)?,
+            }
+        }
+
+        let command = match (command, &file) {
+            (Some(command), _) => command,
+            (None, Some(_)) => Command::Run,
+            (None, None) => Command::Repl,
+        };
+
+        let needs_file = !matches!(command, Command::Repl | Command::Help | Command::Version);
+
+        if needs_file && file.is_none() {
+            return super::exc!("Missing required argument: the file to compile.");
+        }
+
+        if format.is_some() && !matches!(command, Command::Tokens | Command::Ast) {
+            return super::exc!("--format only applies to the tokens and ast commands");
+        }
+
+        if check && command != Command::Fmt {
+            return super::exc!("--check only applies to the fmt command");
+        }
+
+        if trivia && command != Command::Tokens {
+            return super::exc!("--trivia only applies to the tokens command");
+        }
+
+        if doc_format != DocFormat::default() && command != Command::Doc {
+            return super::exc!("--html only applies to the doc command");
+        }
+
+        if vm && command != Command::Run {
+            return super::exc!("--vm only applies to the run command");
+        }
+
+        if jit && command != Command::Run {
+            return super::exc!("--jit only applies to the run command");
+        }
+
+        if heap_stats && command != Command::Run {
+            return super::exc!("--gc-stats only applies to the run command");
+        }
+
+        if max_depth.is_some() && command != Command::Run {
+            return super::exc!("--max-depth only applies to the run command");
+        }
+
+        if jit && vm {
+            return super::exc!("--jit and --vm can't be used together");
+        }
+
+        if optimized && command != Command::Ast {
+            return super::exc!("--optimized only applies to the ast command");
+        }
+
+        let takes_level = matches!(command, Command::Run | Command::Disasm | Command::Build);
+
+        if opt_level.is_some() && !takes_level && !optimized {
+            return super::exc!(
+                "--opt-level only applies to run, disasm, build and ast --optimized"
+            );
+        }
+
+        if output.is_some() && command != Command::Build {
+            return super::exc!("-o only applies to the build command");
+        }
+
+        if emit.is_some() && command != Command::Build {
+            return super::exc!("--emit only applies to the build command");
+        }
+
+        if command == Command::Build && output.is_none() && file.as_deref() == Some("-") {
+            return super::exc!("build needs -o when reading from stdin");
+        }
+
+        Ok(Self {
+            command,
+            file,
+            trace_lexer,
+            trace_parser,
+            format: format.unwrap_or_default(),
+            check,
+            trivia,
+            doc_format,
+            vm,
+            jit,
+            heap_stats,
+            max_depth: max_depth.unwrap_or(traceback::MAX_DEPTH),
+            output,
+            emit: emit.unwrap_or_default(),
+            optimized,
+            opt_level: opt_level.unwrap_or(Optimizer::MAX_LEVEL),
+        })
+    }
+
+    fn opt_level(level: &str) -> Result<u8, String> {
+        match level.parse() {
+            Ok(level) if level <= Optimizer::MAX_LEVEL => Ok(level),
+            _ => super::exc!(
+                "Unknown optimization level {:?}, expected 0 to {}",
+                level,
+                (Optimizer::MAX_LEVEL)
+            ),
+        }
+    }
+
+    fn max_depth(depth: &str) -> Result<usize, String> {
+        match depth.parse() {
+            Ok(depth) if (1..=traceback::DEPTH_LIMIT).contains(&depth) => Ok(depth),
+            _ => super::exc!(
+                "Invalid max depth {:?}, expected 1 to {}",
+                depth,
+                (traceback::DEPTH_LIMIT)
+            ),
+        }
+    }
+
+    fn set_file(file: Option<String>, arg: &str) -> Result<Option<String>, String> {
+        match file {
+            Some(file) => super::exc!("Unexpected argument {:?} after {:?}", arg, file),
+            None => Ok(Some(arg.to_string())),
+        }
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::internal;
+use super::Chunk;
+use super::Expr;
+use super::FuncDecl;
+use super::Function;
+use super::Keyword;
+use super::Loc;
+use super::Op;
+use super::Program;
+use super::Slot;
+use super::Stmt;
+use super::Symbol;
+use super::Value;
+use super::VarType;
+use super::BUILTINS;
+
+type CompileResult = Result<(), String>;
+
+/// Compiles a syntax tree into bytecode for the [`super::Vm`].
+///
+/// Variables declared at the top level of the program are globals,
+/// found by index, and every other variable gets a slot in the locals
+/// of the function it is declared in. Like the [`super::Interpreter`],
+/// functions only see their own variables and the globals.
+#[derive(Clone, Debug)]
+pub struct Compiler {
+    /// The name of each global.
+    pub globals: Vec<Symbol>,
+    /// The index of each global by name.
+    pub global_ids: HashMap<Symbol, u32>,
+    /// The function being compiled.
+    pub function: Function,
+    /// The block scopes of the function being compiled, innermost last,
+    /// with the slot of each local declared in them. Empty at the top
+    /// level of the program, where declarations are globals.
+    pub scopes: Vec<Vec<(Symbol, u32)>>,
+    /// The offsets of the `break` jumps in each enclosing loop.
+    pub loops: Vec<Vec<usize>>,
+    /// Whether a function is being compiled, rather than the top level.
+    pub in_function: bool,
+    /// Where the code being compiled is in the source. It moves to each
+    /// statement and call as they are compiled, as the interpreter's
+    /// does as they run, so both report the same place when they fail.
+    pub loc: Loc,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compiler {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            globals: Vec::new(),
+            global_ids: HashMap::new(),
+            function: Compiler::function(Symbol::intern("<main>"), VarType::Any),
+            scopes: Vec::new(),
+            loops: Vec::new(),
+            in_function: false,
+            loc: Loc::new(),
+        }
+    }
+
+    /// Compiles a program. The compiled program returns the value of
+    /// its last statement if that statement was an expression, like
+    /// [`super::Interpreter::run`].
+    ///
+    /// # Returns
+    /// - [`Result<Program, String>`] - The compiled program on success.
+    ///
+    /// # Errors
+    /// - If the program contains code that can't be compiled, such as
+    ///   a class or a `break` outside of a loop.
+    pub fn compile(mut self, program: &Expr) -> Result<Program, String> {
+        match program {
+            Expr::Main(stmts) => {
+                self.hoist(stmts)?;
+
+                for (i, stmt) in stmts.iter().enumerate() {
+                    match stmt {
+                        Stmt::Expr(expr, loc) if i == stmts.len() - 1 => {
+                            self.loc = loc.clone();
+                            self.expr(expr)?;
+                            self.emit(Op::Return);
+                        }
+                        _ => self.stmt(stmt)?,
+                    }
+                }
+
+                if !matches!(stmts.last(), Some(Stmt::Expr(..))) {
+                    self.constant(Value::Null);
+                    self.emit(Op::Return);
+                }
+            }
+            expr => {
+                self.expr(expr)?;
+                self.emit(Op::Return);
+            }
+        }
+
+        Ok(Program {
+            main: Rc::new(self.function),
+            globals: self.globals,
+        })
+    }
+
+    fn function(name: Symbol, ret: VarType) -> Function {
+        Function {
+            name,
+            params: Vec::new(),
+            ret,
+            chunk: Chunk::default(),
+            locals: Vec::new(),
+        }
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.function.chunk.push(op, self.loc.clone())
+    }
+
+    fn constant(&mut self, value: Value) {
+        let idx = self.function.chunk.constant(value);
+        self.emit(Op::Constant(idx));
+    }
+
+    /// Points the jump at an offset to the next op to be emitted.
+    fn patch(&mut self, offset: usize) {
+        let target = internal::index(self.function.chunk.code.len());
+
+        match &mut self.function.chunk.code[offset] {
+            Op::Jump(to) | Op::JumpIfFalse(to) => *to = target,
+            op => unreachable!("Can't patch {:?}", op),
+        }
+    }
+
+    /// Finds the slot of a variable, which is a global if it isn't a
+    /// local of the function being compiled.
+    #[must_use]
+    pub fn resolve(&mut self, name: Symbol) -> Slot {
+        let local = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|(n, _)| *n == name));
+
+        match local {
+            Some((_, slot)) => Slot::Local(*slot),
+            None => Slot::Global(self.global(name)),
+        }
+    }
+
+    fn global(&mut self, name: Symbol) -> u32 {
+        if let Some(idx) = self.global_ids.get(&name) {
+            return *idx;
+        }
+
+        let idx = internal::index(self.globals.len());
+        self.globals.push(name);
+        self.global_ids.insert(name, idx);
+        idx
+    }
+
+    /// Declares a variable in the innermost scope, returning its slot.
+    /// Declaring the same name twice in a scope reuses the slot.
+    pub fn declare(&mut self, name: Symbol) -> Slot {
+        let Some(scope) = self.scopes.last_mut() else {
+            return Slot::Global(self.global(name));
+        };
+
+        if let Some((_, slot)) = scope.iter().find(|(n, _)| *n == name) {
+            return Slot::Local(*slot);
+        }
+
+        let slot = internal::index(self.function.locals.len());
+        self.function.locals.push(name);
+        scope.push((name, slot));
+        Slot::Local(slot)
+    }
+
+    /// Emits a declaration of the value on top of the stack.
+    fn define(&mut self, name: Symbol, typ: Option<VarType>, constant: bool) {
+        let slot = self.declare(name);
+        let decls = &mut self.function.chunk.decls;
+
+        decls.push((typ, constant));
+        let decl = internal::index(decls.len() - 1);
+        self.emit(Op::Define(slot, decl));
+    }
+
+    /// Declares every function in a block up front, so they can call
+    /// each other regardless of order.
+    ///
+    /// # Errors
+    /// - If a function's body can't be compiled.
+    pub fn hoist(&mut self, stmts: &[Stmt]) -> CompileResult {
+        for stmt in stmts {
+            if let Stmt::Func(func) = stmt {
+                let function = self.compile_func(func)?;
+
+                self.loc = func.loc.clone();
+                self.constant(Value::Compiled(Rc::new(function)));
+                self.define(func.name, None, false);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles a function declaration into its own function, with its
+    /// own locals.
+    ///
+    /// # Errors
+    /// - If the body contains code that can't be compiled.
+    pub fn compile_func(&mut self, func: &FuncDecl) -> Result<Function, String> {
+        let mut function = Compiler::function(func.name, func.ret.clone());
+        function.params.clone_from(&func.params);
+        function.locals = func.params.iter().map(|p| p.name).collect();
+
+        let params = (0..).zip(&func.params).map(|(i, p)| (p.name, i)).collect();
+
+        let outer = (
+            std::mem::replace(&mut self.function, function),
+            std::mem::replace(&mut self.scopes, vec![params]),
+            std::mem::take(&mut self.loops),
+            std::mem::replace(&mut self.in_function, true),
+            std::mem::replace(&mut self.loc, func.loc.clone()),
+        );
+
+        let result = self.block(&func.body).map(|()| {
+            self.loc = func.loc.clone();
+            self.constant(Value::Null);
+            self.emit(Op::Return);
+        });
+
+        let function = std::mem::replace(&mut self.function, outer.0);
+        (self.scopes, self.loops, self.in_function, self.loc) =
+            (outer.1, outer.2, outer.3, outer.4);
+
+        result.map(|()| function)
+    }
+
+    /// Compiles a block of statements in a new scope.
+    ///
+    /// # Errors
+    /// - If a statement can't be compiled.
+    pub fn block(&mut self, stmts: &[Stmt]) -> CompileResult {
+        self.scopes.push(Vec::new());
+
+        let result = self
+            .hoist(stmts)
+            .and_then(|()| stmts.iter().try_for_each(|stmt| self.stmt(stmt)));
+
+        self.scopes.pop();
+        result
+    }
+
+    /// Compiles a single statement, which leaves the stack as it was.
+    ///
+    /// # Errors
+    /// - If the statement can't be compiled.
+    pub fn stmt(&mut self, stmt: &Stmt) -> CompileResult {
+        match stmt {
+            Stmt::Expr(expr, loc) => {
+                self.loc = loc.clone();
+                self.expr(expr)?;
+                self.emit(Op::Pop);
+            }
+            Stmt::VarDecl(decl) => {
+                self.loc = decl.loc.clone();
+                self.expr(&decl.value)?;
+                self.loc = decl.loc.clone();
+
+                let constant = decl.keyword == Some(Keyword::Const);
+                self.define(decl.name, decl.typ.clone(), constant);
+            }
+            Stmt::Assign(op, target, value, loc) => {
+                self.loc = loc.clone();
+                self.expr(value)?;
+                self.loc = loc.clone();
+
+                let mut depth = 0;
+                let mut root = target;
+
+                while let Expr::Index(base, index) = root {
+                    self.expr(index)?;
+                    depth += 1;
+                    root = base;
+                }
+
+                let Expr::Ident(name) = root else {
+                    return super::loc_exc!(loc, "Can't assign to {:?}", root);
+                };
+
+                let slot = self.resolve(*name);
+                self.emit(Op::Assign(slot, *op, depth));
+            }
+            Stmt::Func(_) | Stmt::Comment(..) => (),
+            Stmt::Return(value, loc) => {
+                self.loc = loc.clone();
+
+                if !self.in_function {
+                    return super::loc_exc!(loc, "'return' outside of a function");
+                }
+
+                match value {
+                    Some(value) => self.expr(value)?,
+                    None => self.constant(Value::Null),
+                }
+
+                self.loc = loc.clone();
+                self.emit(Op::Return);
+            }
+            Stmt::If(cond, body, otherwise, loc) => {
+                self.loc = loc.clone();
+                self.expr(cond)?;
+                let skip = self.emit(Op::JumpIfFalse(0));
+
+                self.block(body)?;
+
+                match otherwise {
+                    Some(otherwise) => {
+                        let end = self.emit(Op::Jump(0));
+                        self.patch(skip);
+                        self.block(otherwise)?;
+                        self.patch(end);
+                    }
+                    None => self.patch(skip),
+                }
+
+                self.loc = loc.clone();
+            }
+            Stmt::While(cond, body, loc) => {
+                self.loc = loc.clone();
+                let start = internal::index(self.function.chunk.code.len());
+
+                self.expr(cond)?;
+                let exit = self.emit(Op::JumpIfFalse(0));
+
+                self.loops.push(Vec::new());
+                let result = self.block(body);
+                let breaks = self.loops.pop().unwrap_or_default();
+                result?;
+
+                self.loc = loc.clone();
+                self.emit(Op::Jump(start));
+                self.patch(exit);
+
+                for offset in breaks {
+                    self.patch(offset);
+                }
+            }
+            Stmt::Break => {
+                let offset = self.function.chunk.code.len();
+
+                let Some(jumps) = self.loops.last_mut() else {
+                    return super::exc!("'break' outside of a loop");
+                };
+
+                jumps.push(offset);
+                self.emit(Op::Jump(0));
+            }
+            Stmt::Class(class) => {
+                return super::loc_exc!(class.loc, "Classes are not supported yet")
+            }
+            Stmt::Delete(name, loc) => {
+                self.loc = loc.clone();
+                let slot = self.resolve(*name);
+
+                // Later code sees the variables the deleted one shadowed
+                if let Slot::Local(idx) = slot {
+                    for scope in &mut self.scopes {
+                        scope.retain(|(n, i)| (*n, *i) != (*name, idx));
+                    }
+                }
+
+                self.emit(Op::Delete(slot));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles an expression, which pushes its value.
+    ///
+    /// # Errors
+    /// - If the expression can't be compiled.
+    pub fn expr(&mut self, expr: &Expr) -> CompileResult {
+        match expr {
+            Expr::Int(num) => self.constant(Value::Int(*num)),
+            Expr::Float(num) => self.constant(Value::Float(*num)),
+            Expr::String(s) => self.constant(Value::String(s.clone())),
+            Expr::Bool(b) => self.constant(Value::Bool(*b)),
+            Expr::Null => self.constant(Value::Null),
+            Expr::Error => return super::exc!("Can't run code that failed to parse"),
+            Expr::Main(_) => return super::exc!("Can't compile a program within a program"),
+            Expr::Parenthesized(inner) => self.expr(inner)?,
+            Expr::Ident(name) => match self.resolve(*name) {
+                Slot::Local(slot) => {
+                    self.emit(Op::GetLocal(slot));
+                }
+                Slot::Global(idx) => {
+                    self.emit(Op::GetGlobal(idx));
+                }
+            },
+            Expr::Builtin(name) if BUILTINS.contains(&name.as_str()) => {
+                self.constant(Value::Builtin(*name));
+            }
+            Expr::Builtin(name) => return super::exc!("Unknown builtin '%{}'", name),
+            Expr::Array(items) => {
+                for item in items {
+                    self.expr(item)?;
+                }
+
+                self.emit(Op::Array(internal::index(items.len())));
+            }
+            Expr::Mapping(pairs) => {
+                for (key, value) in pairs {
+                    self.expr(key)?;
+                    self.expr(value)?;
+                }
+
+                self.emit(Op::Mapping(internal::index(pairs.len())));
+            }
+            Expr::Index(base, index) => {
+                self.expr(base)?;
+                self.expr(index)?;
+                self.emit(Op::Index);
+            }
+            Expr::UnaryOp(op, inner) => {
+                self.expr(inner)?;
+                self.emit(Op::Unary(*op));
+            }
+            Expr::BinaryOp(op, left, right) => {
+                self.expr(left)?;
+                self.expr(right)?;
+                self.emit(Op::Binary(*op));
+            }
+            Expr::Compare(cond, left, right) => {
+                self.expr(left)?;
+                self.expr(right)?;
+                self.emit(Op::Compare(*cond));
+            }
+            Expr::Call(callee, args, loc) => {
+                self.loc = loc.clone();
+                self.expr(callee)?;
+
+                for arg in args {
+                    self.expr(arg)?;
+                }
+
+                self.loc = loc.clone();
+                self.emit(Op::Call(internal::index(args.len())));
+            }
+        }
+
+        Ok(())
+    }
+}
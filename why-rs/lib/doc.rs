@@ -0,0 +1,544 @@
+use super::json::Json;
+use super::ClassDecl;
+use super::Expr;
+use super::Formatter;
+use super::FuncDecl;
+use super::Keyword;
+use super::Lexer;
+use super::Stmt;
+use super::VarType;
+use std::fmt::Write;
+
+/// The output formats of `why doc`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DocFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+/// The kinds of documented items.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ItemKind {
+    Function,
+    Class,
+    Constant,
+    Field,
+    Method,
+}
+
+/// A documented declaration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub kind: ItemKind,
+    /// The name, with the class name in front for fields and methods,
+    /// e.g. `Person.greet`.
+    pub name: String,
+    /// The declaration as written in source, without its body.
+    pub signature: String,
+    pub doc: Option<String>,
+    /// The class an item inherits from, if any.
+    pub base: Option<String>,
+    /// The classes named by the types in the signature, which are
+    /// linked to.
+    pub types: Vec<String>,
+    /// The fields and methods of a class.
+    pub members: Vec<Item>,
+}
+
+impl ItemKind {
+    /// The plural heading of a section of items.
+    #[must_use]
+    pub fn heading(self) -> &'static str {
+        match self {
+            ItemKind::Function => "Functions",
+            ItemKind::Class => "Classes",
+            ItemKind::Constant => "Constants",
+            ItemKind::Field => "Fields",
+            ItemKind::Method => "Methods",
+        }
+    }
+
+    /// The prefix of anchors to items of this kind.
+    #[must_use]
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ItemKind::Function => "fn",
+            ItemKind::Class => "class",
+            ItemKind::Constant => "const",
+            ItemKind::Field => "field",
+            ItemKind::Method => "method",
+        }
+    }
+}
+
+impl std::fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ItemKind::Function => "function",
+            ItemKind::Class => "class",
+            ItemKind::Constant => "constant",
+            ItemKind::Field => "field",
+            ItemKind::Method => "method",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl TryFrom<&str> for DocFormat {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "markdown" | "md" => Ok(DocFormat::Markdown),
+            "html" => Ok(DocFormat::Html),
+            _ => super::exc!("Unknown doc format {:?}, expected markdown or html", name),
+        }
+    }
+}
+
+impl Item {
+    /// The anchor linking to the item, e.g. `fn.add`.
+    #[must_use]
+    pub fn anchor(&self) -> String {
+        format!("{}.{}", self.kind.prefix(), self.name)
+    }
+
+    /// The first paragraph of the doc comment.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let doc = self.doc.as_deref().unwrap_or_default();
+        let lines: Vec<&str> = doc.lines().take_while(|l| !l.trim().is_empty()).collect();
+        lines.join(" ")
+    }
+
+    fn func(func: &FuncDecl, kind: ItemKind, class: Option<&str>) -> Self {
+        let name = match class {
+            Some(class) => format!("{}.{}", class, func.name),
+            None => func.name.to_string(),
+        };
+
+        Item {
+            kind,
+            name,
+            signature: Formatter::signature(func),
+            doc: func.doc.clone(),
+            base: None,
+            types: classes(func.params.iter().map(|p| &p.typ).chain([&func.ret])),
+            members: Vec::new(),
+        }
+    }
+
+    fn class(class: &ClassDecl) -> Self {
+        let mut signature = format!("&{}", class.name);
+        if let Some(base) = &class.base {
+            let _ = write!(signature, " is {base}");
+        }
+
+        let fields = class.fields.iter().map(|field| {
+            let mut signature = format!("{} {}", Formatter::typ(&field.typ), field.name);
+            if let Some(value) = &field.value {
+                let _ = write!(signature, " = {}", Formatter::expr(value));
+            }
+
+            Item {
+                kind: ItemKind::Field,
+                name: format!("{}.{}", class.name, field.name),
+                signature,
+                doc: field.doc.clone(),
+                base: None,
+                types: classes([&field.typ]),
+                members: Vec::new(),
+            }
+        });
+
+        let methods = class
+            .methods
+            .iter()
+            .map(|method| Item::func(method, ItemKind::Method, Some(class.name.as_str())));
+
+        Item {
+            kind: ItemKind::Class,
+            name: class.name.to_string(),
+            signature,
+            doc: class.doc.clone(),
+            base: class.base.map(|base| base.to_string()),
+            types: Vec::new(),
+            members: fields.chain(methods).collect(),
+        }
+    }
+}
+
+/// The names of the classes some types name, each once.
+fn classes<'a>(types: impl IntoIterator<Item = &'a VarType>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+
+    for name in types.into_iter().flat_map(VarType::classes) {
+        if !names.iter().any(|n| *n == name.as_str()) {
+            names.push(name.to_string());
+        }
+    }
+
+    names
+}
+
+/// Collects the top level functions, classes and constants of a
+/// program, in the order they are declared.
+#[must_use]
+pub fn collect(program: &Expr) -> Vec<Item> {
+    let Expr::Main(stmts) = program else {
+        return Vec::new();
+    };
+
+    stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Func(func) => Some(Item::func(func, ItemKind::Function, None)),
+            Stmt::Class(class) => Some(Item::class(class)),
+            Stmt::VarDecl(decl) if decl.keyword == Some(Keyword::Const) => {
+                let typ = decl.typ.as_ref().map(Formatter::typ);
+                let signature = match typ {
+                    Some(typ) => format!("const {} {}", typ, decl.name),
+                    None => format!("const {}", decl.name),
+                };
+
+                Some(Item {
+                    kind: ItemKind::Constant,
+                    name: decl.name.to_string(),
+                    signature: format!("{} = {}", signature, Formatter::expr(&decl.value)),
+                    doc: decl.doc.clone(),
+                    base: None,
+                    types: classes(&decl.typ),
+                    members: Vec::new(),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Generates documentation for a program.
+#[must_use]
+pub fn generate(title: &str, program: &Expr, format: DocFormat) -> String {
+    let items = collect(program);
+
+    match format {
+        DocFormat::Markdown => markdown(title, &items),
+        DocFormat::Html => html(title, &items),
+    }
+}
+
+/// Every item, including class members, for the index and links.
+fn flatten(items: &[Item]) -> Vec<&Item> {
+    items
+        .iter()
+        .flat_map(|item| std::iter::once(item).chain(item.members.iter()))
+        .collect()
+}
+
+/// Finds the class a type in a signature names.
+fn resolve_class<'a>(items: &[&'a Item], name: &str) -> Option<&'a Item> {
+    items
+        .iter()
+        .copied()
+        .find(|item| item.kind == ItemKind::Class && item.name == name)
+}
+
+/// Finds the item a `[Name]` reference in a doc comment points to.
+fn resolve<'a>(items: &[&'a Item], name: &str) -> Option<&'a Item> {
+    let name = name.trim_matches('`');
+    items.iter().copied().find(|item| item.name == name)
+}
+
+/// Replaces `[Name]` references to items with links, made by `link`.
+/// Other text is passed through `text`.
+fn link_refs(
+    doc: &str,
+    items: &[&Item],
+    text: impl Fn(&str) -> String,
+    link: impl Fn(&Item, &str) -> String,
+) -> String {
+    let mut out = String::new();
+    let mut rest = doc;
+
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find(']').map(|i| open + i) else {
+            break;
+        };
+
+        match resolve(items, &rest[open + 1..close]) {
+            Some(item) => {
+                out.push_str(&text(&rest[..open]));
+                out.push_str(&link(item, &rest[open + 1..close]));
+            }
+            None => out.push_str(&text(&rest[..=close])),
+        }
+
+        rest = &rest[close + 1..];
+    }
+
+    out.push_str(&text(rest));
+    out
+}
+
+/// Generates Markdown documentation, with an index up front.
+#[must_use]
+pub fn markdown(title: &str, items: &[Item]) -> String {
+    let all = flatten(items);
+    let doc = |doc: &str| {
+        link_refs(doc, &all, str::to_string, |item, label| {
+            format!("[{}](#{})", label, item.anchor())
+        })
+    };
+
+    let mut out = format!("# {title}\n");
+
+    if items.is_empty() {
+        out.push_str("\nNothing is documented.\n");
+        return out;
+    }
+
+    let mut index = all.clone();
+    index.sort_by(|a, b| a.name.cmp(&b.name));
+    out.push_str("\n## Index\n\n");
+
+    for item in index {
+        let summary = item.summary();
+        let summary = if summary.is_empty() {
+            String::new()
+        } else {
+            format!(": {}", doc(&summary))
+        };
+
+        let _ = writeln!(
+            out,
+            "- [{}](#{}) ({}){}",
+            item.name,
+            item.anchor(),
+            item.kind,
+            summary
+        );
+    }
+
+    for kind in [ItemKind::Constant, ItemKind::Function, ItemKind::Class] {
+        let section: Vec<&Item> = items.iter().filter(|i| i.kind == kind).collect();
+        if section.is_empty() {
+            continue;
+        }
+
+        let _ = writeln!(out, "\n## {}", kind.heading());
+
+        for item in section {
+            markdown_item(&mut out, item, "###", &all, &doc);
+        }
+    }
+
+    out
+}
+
+fn markdown_item(
+    out: &mut String,
+    item: &Item,
+    heading: &str,
+    all: &[&Item],
+    doc: &impl Fn(&str) -> String,
+) {
+    let _ = writeln!(
+        out,
+        "\n<a id=\"{}\"></a>\n{} `{}`",
+        item.anchor(),
+        heading,
+        item.signature
+    );
+
+    // Code spans can't hold links, so the classes follow the signature
+    let types: Vec<String> = item
+        .types
+        .iter()
+        .filter_map(|name| resolve_class(all, name).map(|class| (name, class)))
+        .map(|(name, class)| format!("[{}](#{})", name, class.anchor()))
+        .collect();
+
+    if !types.is_empty() {
+        let _ = writeln!(out, "\nUses {}.", types.join(", "));
+    }
+
+    if let Some(base) = &item.base {
+        let base = match resolve(all, base) {
+            Some(base_item) => format!("[{}](#{})", base, base_item.anchor()),
+            None => format!("`{base}`"),
+        };
+
+        let _ = writeln!(out, "\nInherits from {base}.");
+    }
+
+    if let Some(text) = &item.doc {
+        let _ = writeln!(out, "\n{}", doc(text));
+    }
+
+    for kind in [ItemKind::Field, ItemKind::Method] {
+        let members: Vec<&Item> = item.members.iter().filter(|m| m.kind == kind).collect();
+        if members.is_empty() {
+            continue;
+        }
+
+        let _ = writeln!(out, "\n{}# {}", heading, kind.heading());
+
+        for member in members {
+            markdown_item(out, member, &format!("{heading}##"), all, doc);
+        }
+    }
+}
+
+/// Escapes text for HTML.
+#[must_use]
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const SCRIPT: &str = "
+const search = document.getElementById('search');
+const results = document.getElementById('results');
+search.addEventListener('input', () => {
+  const query = search.value.toLowerCase();
+  results.innerHTML = '';
+  if (!query) return;
+  for (const item of INDEX) {
+    if (!item.name.toLowerCase().includes(query)) continue;
+    const li = document.createElement('li');
+    const a = document.createElement('a');
+    a.href = '#' + item.anchor;
+    a.textContent = item.name + ' (' + item.kind + ')';
+    li.appendChild(a);
+    if (item.summary) li.append(' ' + item.summary);
+    results.appendChild(li);
+  }
+});
+";
+
+/// Generates a static HTML page, with a search box over an index of
+/// every item.
+#[must_use]
+pub fn html(title: &str, items: &[Item]) -> String {
+    let all = flatten(items);
+    let doc = |doc: &str| {
+        link_refs(doc, &all, escape, |item, label| {
+            format!("<a href=\"#{}\">{}</a>", item.anchor(), escape(label))
+        })
+    };
+
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape(title)
+    );
+
+    out.push_str(
+        "<input id=\"search\" type=\"search\" placeholder=\"Search\">\n<ul id=\"results\"></ul>\n",
+    );
+
+    for kind in [ItemKind::Constant, ItemKind::Function, ItemKind::Class] {
+        let section: Vec<&Item> = items.iter().filter(|i| i.kind == kind).collect();
+        if section.is_empty() {
+            continue;
+        }
+
+        let _ = writeln!(out, "<h2>{}</h2>", kind.heading());
+
+        for item in section {
+            html_item(&mut out, item, 3, &all, &doc);
+        }
+    }
+
+    let index = all.iter().map(|item| {
+        Json::object(vec![
+            ("name", Json::str(&item.name)),
+            ("kind", Json::String(item.kind.to_string())),
+            ("anchor", Json::String(item.anchor())),
+            ("summary", Json::String(item.summary())),
+        ])
+    });
+
+    let _ = writeln!(
+        out,
+        "<script>\nconst INDEX = {};\n{}</script>\n</body>\n</html>",
+        Json::Array(index.collect()).pretty().replace("</", "<\\/"),
+        SCRIPT
+    );
+
+    out
+}
+
+/// Escapes a signature for HTML, linking the classes named by its
+/// types.
+fn html_signature(item: &Item, all: &[&Item]) -> String {
+    let mut out = String::new();
+    let mut rest = item.signature.as_str();
+
+    while let Some(c) = rest.chars().next() {
+        // Split the signature into words and the text between them
+        let word = Lexer::can_be_ident(c);
+        let len = rest
+            .find(|o: char| Lexer::can_be_ident(o) != word)
+            .unwrap_or(rest.len());
+        let (text, tail) = rest.split_at(len);
+
+        match resolve_class(all, text) {
+            Some(class) if word && item.types.iter().any(|t| t == text) => {
+                let _ = write!(out, "<a href=\"#{}\">{}</a>", class.anchor(), text);
+            }
+            _ => out.push_str(&escape(text)),
+        }
+
+        rest = tail;
+    }
+
+    out
+}
+
+fn html_item(
+    out: &mut String,
+    item: &Item,
+    level: usize,
+    all: &[&Item],
+    doc: &impl Fn(&str) -> String,
+) {
+    let _ = writeln!(
+        out,
+        "<h{0} id=\"{1}\"><code>{2}</code></h{0}>",
+        level,
+        escape(&item.anchor()),
+        html_signature(item, all)
+    );
+
+    if let Some(base) = &item.base {
+        let base = match resolve(all, base) {
+            Some(base_item) => format!("<a href=\"#{}\">{}</a>", base_item.anchor(), escape(base)),
+            None => format!("<code>{}</code>", escape(base)),
+        };
+
+        let _ = writeln!(out, "<p>Inherits from {base}.</p>");
+    }
+
+    if let Some(text) = &item.doc {
+        for paragraph in text.split("\n\n") {
+            let _ = writeln!(out, "<p>{}</p>", doc(paragraph));
+        }
+    }
+
+    for kind in [ItemKind::Field, ItemKind::Method] {
+        let members: Vec<&Item> = item.members.iter().filter(|m| m.kind == kind).collect();
+        if members.is_empty() {
+            continue;
+        }
+
+        let _ = writeln!(out, "<h{0}>{1}</h{0}>", level + 1, kind.heading());
+
+        for member in members {
+            html_item(out, member, level + 2, all, doc);
+        }
+    }
+}
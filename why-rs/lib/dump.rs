@@ -0,0 +1,446 @@
+use super::json::{self, Json};
+use super::ClassDecl;
+use super::Expr;
+use super::FuncDecl;
+use super::Keyword;
+use super::Loc;
+use super::Stmt;
+use super::Token;
+use super::TokenType;
+use super::VarDecl;
+use std::fmt::Write;
+
+/// Lines longer than this are broken up when dumping s-expressions.
+pub const WIDTH: usize = 80;
+
+/// The output formats of `why tokens` and `why ast`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    /// The human readable output, which has no stable schema.
+    #[default]
+    Text,
+    Json,
+    Sexpr,
+}
+
+/// A format independent tree that tokens and syntax trees are dumped
+/// through, so JSON and s-expressions share a single schema.
+///
+/// A [`Dump::Node`] is written as `{"kind": tag, field: value, ...}` in
+/// JSON, and as `(tag value ...)` in s-expressions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Dump {
+    Node(&'static str, Vec<(&'static str, Dump)>),
+    List(Vec<Dump>),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+}
+
+impl TryFrom<&str> for Format {
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        match name {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "sexpr" => Ok(Format::Sexpr),
+            _ => super::exc!("Unknown format {:?}, expected text, json or sexpr", name),
+        }
+    }
+}
+
+/// Dumps tokens in the given format.
+#[must_use]
+pub fn tokens(tokens: &[Token], format: Format) -> String {
+    match format {
+        Format::Text => {
+            let lines: Vec<String> = tokens.iter().map(|t| format!("{t}\n")).collect();
+            lines.concat()
+        }
+        _ => Dump::List(tokens.iter().map(Dump::token).collect()).write(format),
+    }
+}
+
+/// Dumps a syntax tree in the given format.
+#[must_use]
+pub fn expr(expr: &Expr, format: Format) -> String {
+    match format {
+        Format::Text => format!("{expr:#?}\n"),
+        _ => Dump::expr(expr).write(format),
+    }
+}
+
+impl Dump {
+    fn node(tag: &'static str, fields: Vec<(&'static str, Dump)>) -> Self {
+        Dump::Node(tag, fields)
+    }
+
+    fn str(s: &str) -> Self {
+        Dump::Str(s.to_string())
+    }
+
+    fn opt_str(s: Option<&str>) -> Self {
+        s.map_or(Dump::Null, Dump::str)
+    }
+
+    /// A line, column or other count, which never nears `i64::MAX`.
+    fn count(n: usize) -> Self {
+        Dump::Int(i64::try_from(n).unwrap_or(i64::MAX))
+    }
+
+    fn loc(loc: &Loc) -> Self {
+        Dump::node(
+            "loc",
+            vec![
+                ("line", Dump::count(loc.line)),
+                ("col", Dump::count(loc.col)),
+            ],
+        )
+    }
+
+    fn exprs(exprs: &[Expr]) -> Self {
+        Dump::List(exprs.iter().map(Dump::expr).collect())
+    }
+
+    fn stmts(stmts: &[Stmt]) -> Self {
+        Dump::List(stmts.iter().map(Dump::stmt).collect())
+    }
+
+    /// The dump of a token.
+    #[must_use]
+    pub fn token(token: &Token) -> Self {
+        let typ = match token.typ {
+            TokenType::NumLiteral(_) => "NumLiteral".to_string(),
+            typ => format!("{typ:?}"),
+        };
+
+        Dump::node(
+            "token",
+            vec![
+                ("type", Dump::Str(typ)),
+                ("value", Dump::str(&token.value)),
+                (
+                    "float",
+                    Dump::Bool(token.typ == TokenType::NumLiteral(true)),
+                ),
+                ("line", Dump::count(token.loc.line)),
+                ("col", Dump::count(token.loc.col)),
+            ],
+        )
+    }
+
+    /// The dump of an expression.
+    #[must_use]
+    pub fn expr(expr: &Expr) -> Self {
+        match expr {
+            Expr::Int(num) => Dump::node("int", vec![("value", Dump::Int(*num))]),
+            Expr::Float(num) => Dump::node("float", vec![("value", Dump::Float(*num))]),
+            Expr::String(s) => Dump::node("string", vec![("value", Dump::str(s))]),
+            Expr::Bool(b) => Dump::node("bool", vec![("value", Dump::Bool(*b))]),
+            Expr::Null => Dump::node("null", vec![]),
+            Expr::Error => Dump::node("error", vec![]),
+            Expr::Ident(name) => Dump::node("ident", vec![("name", Dump::str(name.as_str()))]),
+            Expr::Builtin(name) => Dump::node("builtin", vec![("name", Dump::str(name.as_str()))]),
+            Expr::Parenthesized(inner) => Dump::node("paren", vec![("expr", Dump::expr(inner))]),
+            Expr::BinaryOp(op, left, right) => Dump::node(
+                "binary",
+                vec![
+                    ("op", Dump::Str(op.to_string())),
+                    ("left", Dump::expr(left)),
+                    ("right", Dump::expr(right)),
+                ],
+            ),
+            Expr::UnaryOp(op, inner) => Dump::node(
+                "unary",
+                vec![
+                    ("op", Dump::Str(op.to_string())),
+                    ("expr", Dump::expr(inner)),
+                ],
+            ),
+            Expr::Compare(cond, left, right) => Dump::node(
+                "compare",
+                vec![
+                    ("op", Dump::Str(cond.to_string())),
+                    ("left", Dump::expr(left)),
+                    ("right", Dump::expr(right)),
+                ],
+            ),
+            Expr::Call(callee, args, loc) => Dump::node(
+                "call",
+                vec![
+                    ("callee", Dump::expr(callee)),
+                    ("args", Dump::exprs(args)),
+                    ("loc", Dump::loc(loc)),
+                ],
+            ),
+            Expr::Index(base, index) => Dump::node(
+                "index",
+                vec![("base", Dump::expr(base)), ("index", Dump::expr(index))],
+            ),
+            Expr::Array(items) => Dump::node("array", vec![("items", Dump::exprs(items))]),
+            Expr::Mapping(pairs) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(k, v)| {
+                        Dump::node(
+                            "pair",
+                            vec![("key", Dump::expr(k)), ("value", Dump::expr(v))],
+                        )
+                    })
+                    .collect();
+
+                Dump::node("mapping", vec![("pairs", Dump::List(pairs))])
+            }
+            Expr::Main(stmts) => Dump::node("program", vec![("body", Dump::stmts(stmts))]),
+        }
+    }
+
+    /// The dump of a statement.
+    #[must_use]
+    pub fn stmt(stmt: &Stmt) -> Self {
+        match stmt {
+            Stmt::Expr(expr, loc) => Dump::node(
+                "expr",
+                vec![("expr", Dump::expr(expr)), ("loc", Dump::loc(loc))],
+            ),
+            Stmt::VarDecl(decl) => Dump::var_decl(decl),
+            Stmt::Assign(op, target, value, loc) => Dump::node(
+                "assign",
+                vec![
+                    ("op", Dump::Str(op.to_string())),
+                    ("target", Dump::expr(target)),
+                    ("value", Dump::expr(value)),
+                    ("loc", Dump::loc(loc)),
+                ],
+            ),
+            Stmt::Func(func) => Dump::func(func),
+            Stmt::Class(class) => Dump::class(class),
+            Stmt::Return(value, loc) => Dump::node(
+                "return",
+                vec![
+                    ("value", value.as_ref().map_or(Dump::Null, Dump::expr)),
+                    ("loc", Dump::loc(loc)),
+                ],
+            ),
+            Stmt::If(cond, body, otherwise, loc) => Dump::node(
+                "if",
+                vec![
+                    ("cond", Dump::expr(cond)),
+                    ("body", Dump::stmts(body)),
+                    (
+                        "else",
+                        otherwise.as_ref().map_or(Dump::Null, |o| Dump::stmts(o)),
+                    ),
+                    ("loc", Dump::loc(loc)),
+                ],
+            ),
+            Stmt::While(cond, body, loc) => Dump::node(
+                "while",
+                vec![
+                    ("cond", Dump::expr(cond)),
+                    ("body", Dump::stmts(body)),
+                    ("loc", Dump::loc(loc)),
+                ],
+            ),
+            Stmt::Break => Dump::node("break", vec![]),
+            Stmt::Delete(name, loc) => Dump::node(
+                "delete",
+                vec![("name", Dump::str(name.as_str())), ("loc", Dump::loc(loc))],
+            ),
+            Stmt::Comment(text, trailing) => Dump::node(
+                "comment",
+                vec![
+                    ("text", Dump::str(text)),
+                    ("trailing", Dump::Bool(*trailing)),
+                ],
+            ),
+        }
+    }
+
+    fn var_decl(decl: &VarDecl) -> Self {
+        let keyword = match decl.keyword {
+            Some(Keyword::Let) => Dump::str("let"),
+            Some(Keyword::Const) => Dump::str("const"),
+            _ => Dump::Null,
+        };
+
+        Dump::node(
+            "var",
+            vec![
+                ("keyword", keyword),
+                (
+                    "type",
+                    decl.typ
+                        .as_ref()
+                        .map_or(Dump::Null, |t| Dump::Str(t.to_string())),
+                ),
+                ("name", Dump::str(decl.name.as_str())),
+                ("value", Dump::expr(&decl.value)),
+                ("loc", Dump::loc(&decl.loc)),
+                ("doc", Dump::opt_str(decl.doc.as_deref())),
+            ],
+        )
+    }
+
+    fn func(func: &FuncDecl) -> Self {
+        let params = func
+            .params
+            .iter()
+            .map(|p| {
+                Dump::node(
+                    "param",
+                    vec![
+                        ("name", Dump::str(p.name.as_str())),
+                        ("type", Dump::Str(p.typ.to_string())),
+                    ],
+                )
+            })
+            .collect();
+
+        Dump::node(
+            "func",
+            vec![
+                ("name", Dump::str(func.name.as_str())),
+                ("params", Dump::List(params)),
+                ("ret", Dump::Str(func.ret.to_string())),
+                ("body", Dump::stmts(&func.body)),
+                ("loc", Dump::loc(&func.loc)),
+                ("doc", Dump::opt_str(func.doc.as_deref())),
+            ],
+        )
+    }
+
+    fn class(class: &ClassDecl) -> Self {
+        let fields = class
+            .fields
+            .iter()
+            .map(|f| {
+                Dump::node(
+                    "field",
+                    vec![
+                        ("name", Dump::str(f.name.as_str())),
+                        ("type", Dump::Str(f.typ.to_string())),
+                        ("value", f.value.as_ref().map_or(Dump::Null, Dump::expr)),
+                        ("loc", Dump::loc(&f.loc)),
+                        ("doc", Dump::opt_str(f.doc.as_deref())),
+                    ],
+                )
+            })
+            .collect();
+
+        Dump::node(
+            "class",
+            vec![
+                ("name", Dump::str(class.name.as_str())),
+                (
+                    "base",
+                    class
+                        .base
+                        .map_or(Dump::Null, |base| Dump::str(base.as_str())),
+                ),
+                ("fields", Dump::List(fields)),
+                (
+                    "methods",
+                    Dump::List(class.methods.iter().map(Dump::func).collect()),
+                ),
+                ("loc", Dump::loc(&class.loc)),
+                ("doc", Dump::opt_str(class.doc.as_deref())),
+            ],
+        )
+    }
+
+    /// The dump as JSON, with each node's tag as its `kind` field.
+    #[must_use]
+    pub fn json(&self) -> Json {
+        match self {
+            Dump::Node(tag, fields) => {
+                let kind = ("kind".to_string(), Json::str(tag));
+                let fields = fields.iter().map(|(k, v)| ((*k).to_string(), v.json()));
+                Json::Object(std::iter::once(kind).chain(fields).collect())
+            }
+            Dump::List(items) => Json::Array(items.iter().map(Dump::json).collect()),
+            Dump::Str(s) => Json::str(s),
+            Dump::Int(num) => Json::Int(*num),
+            Dump::Float(num) => Json::Number(*num),
+            Dump::Bool(b) => Json::Bool(*b),
+            Dump::Null => Json::Null,
+        }
+    }
+
+    /// Writes the dump in the given format.
+    #[must_use]
+    pub fn write(&self, format: Format) -> String {
+        let mut out = String::new();
+
+        match format {
+            Format::Json => out.push_str(&self.json().pretty()),
+            Format::Sexpr => self.write_sexpr(&mut out, 0),
+            Format::Text => {
+                let _ = write!(out, "{self:#?}");
+            }
+        }
+
+        out.push('\n');
+        out
+    }
+
+    /// Writes an s-expression starting at column `col`, breaking nodes
+    /// and lists that don't fit on the line into one child per line.
+    pub fn write_sexpr(&self, out: &mut String, col: usize) {
+        let flat = self.sexpr();
+
+        if col + flat.len() <= WIDTH {
+            out.push_str(&flat);
+            return;
+        }
+
+        // Node children are indented under the tag, list items line up
+        // after the opening parenthesis
+        let (head, children, child_col): (&str, Vec<&Dump>, usize) = match self {
+            Dump::Node(tag, fields) => (tag, fields.iter().map(|(_, v)| v).collect(), col + 2),
+            Dump::List(items) => ("", items.iter().collect(), col + 1),
+            _ => {
+                out.push_str(&flat);
+                return;
+            }
+        };
+
+        out.push('(');
+        out.push_str(head);
+
+        for (i, child) in children.iter().enumerate() {
+            if i > 0 || !head.is_empty() {
+                out.push('\n');
+                out.push_str(&" ".repeat(child_col));
+            }
+
+            child.write_sexpr(out, child_col);
+        }
+
+        out.push(')');
+    }
+
+    /// The s-expression of the dump on a single line.
+    #[must_use]
+    pub fn sexpr(&self) -> String {
+        match self {
+            Dump::Node(tag, fields) => {
+                let mut parts = vec![tag.to_string()];
+                parts.extend(fields.iter().map(|(_, v)| v.sexpr()));
+                format!("({})", parts.join(" "))
+            }
+            Dump::List(items) => {
+                let items: Vec<String> = items.iter().map(Dump::sexpr).collect();
+                format!("({})", items.join(" "))
+            }
+            Dump::Str(s) => json::quote(s),
+            Dump::Int(num) => num.to_string(),
+            Dump::Float(num) => format!("{num:?}"),
+            Dump::Bool(b) => b.to_string(),
+            Dump::Null => "nil".to_string(),
+        }
+    }
+}
@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::traceback;
+use super::Binding;
+use super::Checker;
+use super::Interpreter;
+use super::Loc;
+use super::Param;
+use super::Parser;
+use super::Symbol;
+use super::Value;
+use super::VarType;
+use super::BUILTINS;
+
+type EvalResult = Result<Value, String>;
+
+/// A Rust type that Why values convert to, for the arguments of native
+/// functions and the results read back from the [`Engine`].
+pub trait FromValue: Sized {
+    /// The Why type of the values that convert.
+    fn typ() -> VarType;
+
+    /// Converts a Why value.
+    ///
+    /// # Errors
+    /// - If the value isn't of the type.
+    fn from_value(value: Value) -> Result<Self, String>;
+}
+
+/// A Rust type that converts to Why values, for the results of native
+/// functions and the values passed to the [`Engine`].
+pub trait IntoValue {
+    /// The Why type of the converted values.
+    fn typ() -> VarType;
+
+    /// Converts to a Why value.
+    ///
+    /// # Errors
+    /// - If the value is an error, for native functions that fail.
+    fn into_value(self) -> EvalResult;
+}
+
+/// The arguments of a call from Rust to a Why function, as a tuple.
+pub trait IntoArgs {
+    /// Converts the arguments to Why values.
+    ///
+    /// # Errors
+    /// - If an argument fails to convert.
+    fn into_args(self) -> Result<Vec<Value>, String>;
+}
+
+/// A Rust closure that can be registered as a native function, taking
+/// arguments that are [`FromValue`] and returning a result that is
+/// [`IntoValue`]. `Args` is the tuple of its argument types.
+pub trait IntoNative<Args> {
+    /// Wraps the closure as a native function with a name.
+    fn into_native(self, name: Symbol) -> Native;
+}
+
+/// A Rust function registered with an [`Engine`], which Why code calls
+/// like a builtin, except that its arguments are type checked.
+#[derive(Clone)]
+pub struct Native {
+    pub name: Symbol,
+    pub params: Vec<Param>,
+    pub ret: VarType,
+    func: Rc<dyn Fn(Vec<Value>) -> EvalResult>,
+}
+
+/// Compiles and runs Why source for a host Rust application, keeping
+/// its variables and functions between calls to [`Engine::eval`].
+///
+/// The host can register native functions, set and read globals, and
+/// call Why functions, converting values with [`FromValue`] and
+/// [`IntoValue`]. Untrusted scripts can be limited in how many steps
+/// they take, how much memory their variables use and how deeply they
+/// nest calls.
+///
+/// Scripts run on the host's thread. By default their calls may take
+/// [`traceback::STACK_LIMIT`] of its stack before failing with a stack
+/// overflow, which leaves room for the host on a thread of 2MB or more.
+/// Hosts that want the default depth run the engine on a thread of
+/// [`traceback::stack_size`] and raise the limit with
+/// [`Engine::with_stack_limit`].
+///
+/// ```
+/// use why_rs::Engine;
+///
+/// let mut engine = Engine::new().with_instruction_budget(10_000);
+/// engine.register("twice", |n: i64| n * 2)?;
+/// engine.set_global("base", 20)?;
+/// engine.eval("$answer(int n) @int { @twice(base) + n; }")?;
+///
+/// assert_eq!(engine.call::<i64>("answer", (2,))?, 42);
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Engine {
+    pub interpreter: Interpreter,
+    pub checker: Checker,
+}
+
+impl std::fmt::Debug for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native {}>", self.name)
+    }
+}
+
+impl Native {
+    /// The type Why code sees the function as.
+    #[must_use]
+    pub fn typ(&self) -> VarType {
+        VarType::Function(
+            self.params.iter().map(|p| p.typ.clone()).collect(),
+            Box::new(self.ret.clone()),
+        )
+    }
+
+    /// Calls the function, checking the arguments and result like a
+    /// call to a Why function.
+    ///
+    /// # Errors
+    /// - If the arguments don't match the params, or the function
+    ///   fails.
+    pub fn call(&self, args: Vec<Value>) -> EvalResult {
+        let bindings = Interpreter::bind_args(self.name, &self.params, args)?;
+        let value = (self.func)(bindings.into_iter().map(|b| b.value).collect())?;
+
+        Interpreter::check_return(self.name, &self.ret, value)
+    }
+}
+
+impl Engine {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits how many statements and expressions each call to
+    /// [`Engine::eval`] or [`Engine::call`] may evaluate.
+    #[must_use]
+    pub fn with_instruction_budget(mut self, steps: u64) -> Self {
+        self.interpreter.budget = Some(steps);
+        self
+    }
+
+    /// Limits how many bytes the variables may take, roughly.
+    #[must_use]
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.interpreter = self.interpreter.with_memory_limit(bytes);
+        self
+    }
+
+    /// Sets how many calls may be nested before failing with a stack
+    /// overflow.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.interpreter.max_depth = max_depth;
+        self
+    }
+
+    /// Sets how many bytes of stack calls may take before failing with
+    /// a stack overflow, which must fit in the stack of the thread the
+    /// engine runs on.
+    #[must_use]
+    pub fn with_stack_limit(mut self, bytes: usize) -> Self {
+        self.interpreter = self.interpreter.with_stack_limit(bytes);
+        self
+    }
+
+    /// Registers a Rust closure as a global function, replacing any
+    /// global of the same name.
+    ///
+    /// # Errors
+    /// - If the name is a keyword or builtin.
+    pub fn register<Args>(
+        &mut self,
+        name: &str,
+        func: impl IntoNative<Args>,
+    ) -> Result<(), String> {
+        if super::is_keyword(name) || BUILTINS.contains(&name) {
+            return super::exc!("Can't register '{}', which is a keyword or builtin", name);
+        }
+
+        let name = Symbol::intern(name);
+        let native = func.into_native(name);
+        let typ = native.typ();
+
+        let binding = Binding {
+            value: Value::Builtin(name),
+            typ: typ.clone(),
+            constant: true,
+        };
+
+        // Between runs, the global scope is the only one
+        self.checker.scopes[0].insert(name, (typ, true));
+        self.interpreter.natives.insert(name, native);
+        self.interpreter.declare(name, binding);
+
+        Ok(())
+    }
+
+    /// Sets a global variable, declaring it if it's new.
+    ///
+    /// # Errors
+    /// - If the value fails to convert, or doesn't fit in the memory
+    ///   limit.
+    pub fn set_global<T: IntoValue>(&mut self, name: &str, value: T) -> Result<(), String> {
+        let name = Symbol::intern(name);
+        let value = value.into_value()?;
+
+        // Untyped values, like an empty array, get the type they convert from
+        let typ = match T::typ() {
+            VarType::Any => value.typ(),
+            typ => typ,
+        };
+
+        let binding = Binding::new(name, Some(typ.clone()), value, false)?;
+
+        if let Some(memory) = &self.interpreter.memory {
+            memory.reserve(&binding.value)?;
+        }
+
+        // Between runs, the global scope is the only one
+        self.checker.scopes[0].insert(name, (typ, false));
+        self.interpreter.declare(name, binding);
+        Ok(())
+    }
+
+    /// Reads a global variable.
+    ///
+    /// # Errors
+    /// - If the variable is undefined or doesn't convert to the type.
+    pub fn global<T: FromValue>(&self, name: &str) -> Result<T, String> {
+        match self.interpreter.scopes[0].get(&Symbol::intern(name)) {
+            Some(binding) => T::from_value(binding.value.clone()),
+            None => super::exc!("Undefined variable '{}'", name),
+        }
+    }
+
+    /// Type checks and runs source, keeping its declarations, or those
+    /// that ran if the run fails. Runtime errors are rendered with a
+    /// traceback of the calls they happened in.
+    ///
+    /// # Returns
+    /// - [`EvalResult`] - The value of the last statement, if it was an
+    ///   expression.
+    ///
+    /// # Errors
+    /// - If the source fails to lex, parse, type check or run, or goes
+    ///   over the engine's limits.
+    pub fn eval(&mut self, src: &str) -> EvalResult {
+        let mut parser = Parser::from_source(src)?;
+        parser.classes.extend(self.checker.classes.keys());
+        let program = parser.parse()?;
+
+        // Only keep the checker's declarations if all of them are valid
+        let mut checker = self.checker.clone();
+        checker.check(&program)?;
+
+        let globals: HashMap<Symbol, (VarType, bool)> = self.interpreter.scopes[0]
+            .iter()
+            .map(|(name, binding)| (*name, (binding.typ.clone(), binding.constant)))
+            .collect();
+
+        match self.interpreter.run(&program) {
+            Ok(value) => {
+                self.checker = checker;
+                Ok(value)
+            }
+            Err(e) => {
+                // The globals declared before the run failed stay
+                // declared, so the checker keeps those declarations too
+                for (name, decl) in checker.scopes.swap_remove(0) {
+                    let ran = self.interpreter.scopes[0]
+                        .get(&name)
+                        .is_some_and(|b| globals.get(&name) != Some(&(b.typ.clone(), b.constant)));
+
+                    if ran {
+                        self.checker.scopes[0].insert(name, decl);
+                    }
+                }
+
+                Err(traceback::render(&e, &self.interpreter.traceback, src))
+            }
+        }
+    }
+
+    /// Calls a global Why function, or a native one, converting its
+    /// arguments and result. Runtime errors are rendered with a
+    /// traceback of the calls they happened in, without their source,
+    /// which may have come from any call to [`Engine::eval`].
+    ///
+    /// # Errors
+    /// - If the global isn't a function, the arguments or result fail
+    ///   to convert, or the call fails or goes over the engine's limits.
+    pub fn call<R: FromValue>(&mut self, name: &str, args: impl IntoArgs) -> Result<R, String> {
+        let callee = match self.interpreter.scopes[0].get(&Symbol::intern(name)) {
+            Some(Binding {
+                value: callee @ (Value::Func(_) | Value::Builtin(_)),
+                ..
+            }) => callee.clone(),
+            Some(_) => return super::exc!("'{}' is not a function", name),
+            None => return super::exc!("Undefined function '{}'", name),
+        };
+
+        self.interpreter.steps = 0;
+        self.interpreter.traceback.clear();
+
+        // The call comes from the host, which has no place in the source
+        let value = self
+            .interpreter
+            .call(&callee, args.into_args()?, &Loc::at(0, 0))
+            .map_err(|e| traceback::render(&e, &self.interpreter.traceback, ""))?;
+
+        R::from_value(value)
+    }
+}
+
+/// Implements [`FromValue`] and [`IntoValue`] for a Rust type that
+/// corresponds to one variant of [`Value`].
+macro_rules! convert {
+    ($rust:ty, $typ:ident, $variant:ident) => {
+        impl FromValue for $rust {
+            fn typ() -> VarType {
+                VarType::$typ
+            }
+
+            fn from_value(value: Value) -> Result<Self, String> {
+                match value {
+                    Value::$variant(value) => Ok(value),
+                    value => super::exc!("Expected {}, but got {}", (VarType::$typ), (value.typ())),
+                }
+            }
+        }
+
+        impl IntoValue for $rust {
+            fn typ() -> VarType {
+                VarType::$typ
+            }
+
+            fn into_value(self) -> EvalResult {
+                Ok(Value::$variant(self))
+            }
+        }
+    };
+}
+
+convert!(i64, Int, Int);
+convert!(bool, Bool, Bool);
+convert!(String, String, String);
+
+impl FromValue for f64 {
+    fn typ() -> VarType {
+        VarType::Float
+    }
+
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value.as_float() {
+            Some(num) => Ok(num),
+            None => super::exc!("Expected float, but got {}", (value.typ())),
+        }
+    }
+}
+
+impl IntoValue for f64 {
+    fn typ() -> VarType {
+        VarType::Float
+    }
+
+    fn into_value(self) -> EvalResult {
+        Ok(Value::Float(self))
+    }
+}
+
+impl IntoValue for &str {
+    fn typ() -> VarType {
+        VarType::String
+    }
+
+    fn into_value(self) -> EvalResult {
+        Ok(Value::String(self.to_string()))
+    }
+}
+
+impl IntoValue for () {
+    fn typ() -> VarType {
+        VarType::Void
+    }
+
+    fn into_value(self) -> EvalResult {
+        Ok(Value::Null)
+    }
+}
+
+impl FromValue for () {
+    fn typ() -> VarType {
+        VarType::Void
+    }
+
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Null => Ok(()),
+            value => super::exc!("Expected void, but got {}", (value.typ())),
+        }
+    }
+}
+
+/// Values pass through as they are, accepting any type.
+impl FromValue for Value {
+    fn typ() -> VarType {
+        VarType::Any
+    }
+
+    fn from_value(value: Value) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for Value {
+    fn typ() -> VarType {
+        VarType::Any
+    }
+
+    fn into_value(self) -> EvalResult {
+        Ok(self)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn typ() -> VarType {
+        VarType::Array(Box::new(T::typ()))
+    }
+
+    fn from_value(value: Value) -> Result<Self, String> {
+        match value {
+            Value::Array(items) => items.into_iter().map(T::from_value).collect(),
+            value => super::exc!("Expected {}, but got {}", (Self::typ()), (value.typ())),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn typ() -> VarType {
+        VarType::Array(Box::new(T::typ()))
+    }
+
+    fn into_value(self) -> EvalResult {
+        let items: Result<Vec<Value>, String> = self.into_iter().map(T::into_value).collect();
+        Ok(Value::Array(items?))
+    }
+}
+
+/// Native functions fail by returning an error.
+impl<T: IntoValue> IntoValue for Result<T, String> {
+    fn typ() -> VarType {
+        T::typ()
+    }
+
+    fn into_value(self) -> EvalResult {
+        self.and_then(T::into_value)
+    }
+}
+
+impl IntoArgs for Vec<Value> {
+    fn into_args(self) -> Result<Vec<Value>, String> {
+        Ok(self)
+    }
+}
+
+/// Implements [`IntoArgs`] for tuples of arguments, and [`IntoNative`]
+/// for closures taking them, with the names of the params.
+macro_rules! natives {
+    ($($arg:ident $param:ident),*) => {
+        impl<$($arg: IntoValue),*> IntoArgs for ($($arg,)*) {
+            fn into_args(self) -> Result<Vec<Value>, String> {
+                let ($($param,)*) = self;
+                Ok(vec![$($param.into_value()?),*])
+            }
+        }
+
+        impl<Func, Ret, $($arg),*> IntoNative<($($arg,)*)> for Func
+        where
+            Func: Fn($($arg),*) -> Ret + 'static,
+            Ret: IntoValue,
+            $($arg: FromValue),*
+        {
+            fn into_native(self, name: Symbol) -> Native {
+                let params = vec![$(Param {
+                    name: Symbol::intern(stringify!($param)),
+                    typ: $arg::typ(),
+                }),*];
+
+                // The args were bound to the params, so there are enough
+                #[allow(unused_mut, unused_variables)]
+                let func = move |args: Vec<Value>| {
+                    let mut args = args.into_iter();
+                    $(let $param = $arg::from_value(args.next().unwrap_or(Value::Null))?;)*
+                    self($($param),*).into_value()
+                };
+
+                Native {
+                    name,
+                    params,
+                    ret: Ret::typ(),
+                    func: Rc::new(func),
+                }
+            }
+        }
+    };
+}
+
+natives!();
+natives!(A a);
+natives!(A a, B b);
+natives!(A a, B b, C c);
+natives!(A a, B b, C c, D d);
+natives!(A a, B b, C c, D d, E e);
+natives!(A a, B b, C c, D d, E e, F f);
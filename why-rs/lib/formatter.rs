@@ -0,0 +1,421 @@
+use super::ClassDecl;
+use super::Condition;
+use super::Expr;
+use super::FuncDecl;
+use super::Keyword;
+use super::Lexer;
+use super::Operator;
+use super::Parser;
+use super::Stmt;
+use super::VarType;
+use std::fmt::Write;
+
+/// The number of spaces per indentation level.
+pub const INDENT: usize = 4;
+
+/// Prints syntax trees back out as source code in the canonical style.
+#[derive(Clone, Debug, Default)]
+pub struct Formatter {
+    /// The current indentation level.
+    pub indent: usize,
+    /// The formatted source so far.
+    pub out: String,
+}
+
+impl Formatter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Formats a whole program.
+    #[must_use]
+    pub fn format(program: &Expr) -> String {
+        let mut formatter = Formatter::new();
+
+        match program {
+            Expr::Main(stmts) => formatter.write_stmts(stmts, true),
+            expr => {
+                let expr = Formatter::expr(expr);
+                formatter.line(&expr);
+            }
+        }
+
+        formatter.out
+    }
+
+    /// Formats source code, keeping its comments.
+    ///
+    /// # Returns
+    /// - [`Result<String, String>`] - The formatted source on success.
+    ///
+    /// # Errors
+    /// - If the source fails to lex or parse.
+    pub fn format_source(src: &str) -> Result<String, String> {
+        let tokens = Lexer::new(src)?.with_trivia().lex()?;
+        let program = Parser::from_tokens(tokens).parse()?;
+
+        Ok(Formatter::format(&program))
+    }
+
+    /// Writes a line at the current indentation.
+    pub fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.out.push_str(&" ".repeat(self.indent * INDENT));
+            self.out.push_str(text);
+        }
+
+        self.out.push('\n');
+    }
+
+    /// Writes a comment that trails code on the last line written.
+    pub fn trail(&mut self, text: &str) {
+        self.out.pop();
+        self.out.push(' ');
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Returns true if the statement at `idx` is a function or class,
+    /// or a comment on its own line above one.
+    #[must_use]
+    pub fn starts_func(stmts: &[Stmt], idx: usize) -> bool {
+        match stmts.get(idx) {
+            Some(Stmt::Func(_) | Stmt::Class(_)) => true,
+            Some(Stmt::Comment(_, false)) => Formatter::starts_func(stmts, idx + 1),
+            _ => false,
+        }
+    }
+
+    /// Writes statements, separating function and class declarations
+    /// at the top level from their neighbours with a blank line.
+    /// Comments above a declaration stay attached to it.
+    pub fn write_stmts(&mut self, stmts: &[Stmt], top_level: bool) {
+        for (i, stmt) in stmts.iter().enumerate() {
+            if let Stmt::Comment(text, true) = stmt {
+                if !self.out.is_empty() {
+                    self.trail(text);
+                    continue;
+                }
+            }
+
+            // Trailing comments were already written on the line before
+            let prev = stmts[..i]
+                .iter()
+                .rev()
+                .find(|s| !matches!(s, Stmt::Comment(_, true)));
+
+            let is_func =
+                Formatter::starts_func(stmts, i) && !matches!(prev, Some(Stmt::Comment(_, false)));
+            let after_func = matches!(prev, Some(Stmt::Func(_) | Stmt::Class(_)));
+
+            if top_level && prev.is_some() && (is_func || after_func) {
+                self.line("");
+            }
+
+            self.write_stmt(stmt);
+        }
+    }
+
+    /// Writes a `{ ... }` block, the opening brace ending `head`.
+    pub fn write_block(&mut self, head: &str, stmts: &[Stmt]) {
+        self.line(&format!("{head} {{"));
+        self.indent += 1;
+        self.write_stmts(stmts, false);
+        self.indent -= 1;
+    }
+
+    /// Writes a doc comment, a `///` line for each of its lines.
+    pub fn write_doc(&mut self, doc: &Option<String>) {
+        for line in doc.iter().flat_map(|doc| doc.lines()) {
+            if line.is_empty() {
+                self.line("///");
+            } else {
+                self.line(&format!("/// {line}"));
+            }
+        }
+    }
+
+    /// Writes a class, separating its fields from its methods, and the
+    /// methods from each other, with a blank line.
+    pub fn write_class(&mut self, class: &ClassDecl) {
+        let mut head = format!("&{}", class.name);
+        if let Some(base) = &class.base {
+            let _ = write!(head, " is {base}");
+        }
+
+        if class.fields.is_empty() && class.methods.is_empty() {
+            self.line(&format!("{head} {{}}"));
+            return;
+        }
+
+        self.line(&format!("{head} {{"));
+        self.indent += 1;
+
+        for field in &class.fields {
+            self.write_doc(&field.doc);
+
+            match &field.value {
+                Some(value) => self.line(&format!(
+                    "{} {} = {};",
+                    Formatter::typ(&field.typ),
+                    field.name,
+                    Formatter::expr(value)
+                )),
+                None => self.line(&format!("{} {};", Formatter::typ(&field.typ), field.name)),
+            }
+        }
+
+        for (i, method) in class.methods.iter().enumerate() {
+            if i > 0 || !class.fields.is_empty() {
+                self.line("");
+            }
+
+            self.write_doc(&method.doc);
+            self.write_block(&Formatter::signature(method), &method.body);
+            self.line("}");
+        }
+
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// Writes a single statement.
+    pub fn write_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(expr, _) => self.line(&format!("{};", Formatter::expr(expr))),
+            Stmt::VarDecl(decl) => {
+                self.write_doc(&decl.doc);
+                let mut text = String::new();
+
+                match decl.keyword {
+                    Some(Keyword::Let) => text.push_str("let "),
+                    Some(Keyword::Const) => text.push_str("const "),
+                    _ => (),
+                }
+
+                if let Some(typ) = &decl.typ {
+                    let _ = write!(text, "{} ", Formatter::typ(typ));
+                }
+
+                let _ = write!(text, "{} = {};", decl.name, Formatter::expr(&decl.value));
+                self.line(&text);
+            }
+            Stmt::Assign(op @ (Operator::Increment | Operator::Decrement), target, ..) => {
+                self.line(&format!("{}{};", Formatter::expr(target), op));
+            }
+            Stmt::Assign(op, target, value, _) => self.line(&format!(
+                "{} {} {};",
+                Formatter::expr(target),
+                op,
+                Formatter::expr(value)
+            )),
+            Stmt::Func(func) => {
+                self.write_doc(&func.doc);
+                self.write_block(&Formatter::signature(func), &func.body);
+                self.line("}");
+            }
+            Stmt::Class(class) => {
+                self.write_doc(&class.doc);
+                self.write_class(class);
+            }
+            Stmt::Return(None, _) => self.line("@;"),
+            Stmt::Return(Some(value), _) => self.line(&format!("@{};", Formatter::expr(value))),
+            Stmt::If(..) => {
+                self.write_if(stmt, "if");
+                self.line("}");
+            }
+            Stmt::While(cond, body, _) => {
+                self.write_block(&format!("while {}", Formatter::expr(cond)), body);
+                self.line("}");
+            }
+            Stmt::Break => self.line("break;"),
+            Stmt::Delete(name, _) => self.line(&format!("delete {name};")),
+            Stmt::Comment(text, _) => {
+                // Keep the inner lines of block comments as they were
+                let mut lines = text.lines();
+                self.line(lines.next().unwrap_or_default());
+
+                for line in lines {
+                    self.out.push_str(line);
+                    self.out.push('\n');
+                }
+            }
+        }
+    }
+
+    /// Writes an `if` statement and its `else if` chain, leaving the
+    /// final `}` to the caller.
+    pub fn write_if(&mut self, stmt: &Stmt, head: &str) {
+        if let Stmt::If(cond, body, otherwise, _) = stmt {
+            self.write_block(&format!("{} {}", head, Formatter::expr(cond)), body);
+
+            match otherwise.as_deref() {
+                Some([chained @ Stmt::If(..)]) => self.write_if(chained, "} else if"),
+                Some(otherwise) => self.write_block("} else", otherwise),
+                None => (),
+            }
+        }
+    }
+
+    /// The signature of a function, e.g. `$add(int a, int b) @int`.
+    #[must_use]
+    pub fn signature(func: &FuncDecl) -> String {
+        let params: Vec<String> = func
+            .params
+            .iter()
+            .map(|p| match p.typ {
+                VarType::Any if p.name == "self" => "&self".to_string(),
+                _ => format!("{} {}", Formatter::typ(&p.typ), p.name),
+            })
+            .collect();
+
+        match func.ret {
+            VarType::Void => format!("${}({})", func.name, params.join(", ")),
+            _ => format!(
+                "${}({}) @{}",
+                func.name,
+                params.join(", "),
+                Formatter::typ(&func.ret)
+            ),
+        }
+    }
+
+    /// A type annotation as it is written in source.
+    #[must_use]
+    pub fn typ(typ: &VarType) -> String {
+        typ.to_string()
+    }
+
+    /// How tightly an expression binds, higher binding tighter.
+    #[must_use]
+    pub fn precedence(expr: &Expr) -> u8 {
+        match expr {
+            Expr::Compare(..) => 1,
+            Expr::BinaryOp(Operator::Add | Operator::Subtract, ..) => 2,
+            Expr::BinaryOp(Operator::Mult | Operator::Div, ..) => 3,
+            Expr::UnaryOp(..) => 4,
+            Expr::BinaryOp(Operator::Dot, ..) => 6,
+            Expr::BinaryOp(..) => 5,
+            _ => 6,
+        }
+    }
+
+    /// Formats an expression, adding parentheses where the tree needs
+    /// them to survive being parsed again.
+    #[must_use]
+    pub fn expr(expr: &Expr) -> String {
+        match expr {
+            Expr::Int(num) => num.to_string(),
+            Expr::Float(num) => format!("{num:?}"),
+            Expr::String(s) => Formatter::string(s),
+            Expr::Bool(b) => b.to_string(),
+            Expr::Null => "NULL".to_string(),
+            Expr::Error => "<error>".to_string(),
+            Expr::Ident(name) => name.to_string(),
+            Expr::Builtin(name) => format!("%{name}"),
+            Expr::Parenthesized(inner) => format!("({})", Formatter::expr(inner)),
+            Expr::Array(items) => format!("[{}]", Formatter::list(items)),
+            Expr::Mapping(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(k, v)| format!("{} -> {}", Formatter::expr(k), Formatter::expr(v)))
+                    .collect();
+
+                format!("&{{{}}}", pairs.join(", "))
+            }
+            Expr::Call(callee, args, _) => {
+                format!(
+                    "{}({})",
+                    Formatter::operand(callee, 6),
+                    Formatter::list(args)
+                )
+            }
+            Expr::Index(base, index) => {
+                format!(
+                    "{}[{}]",
+                    Formatter::operand(base, 6),
+                    Formatter::expr(index)
+                )
+            }
+            Expr::UnaryOp(op, inner) => {
+                // The operand of a unary op is parsed as a factor
+                format!("{}{}", op, Formatter::operand(inner, 5))
+            }
+            Expr::BinaryOp(Operator::Dot, object, field) => {
+                format!(
+                    "{}.{}",
+                    Formatter::operand(object, 6),
+                    Formatter::expr(field)
+                )
+            }
+            Expr::BinaryOp(Operator::Pow, left, right) => format!(
+                "{} ** {}",
+                Formatter::operand(left, 6),
+                Formatter::operand(right, 4)
+            ),
+            Expr::BinaryOp(op, left, right) => {
+                let prec = Formatter::precedence(expr);
+
+                format!(
+                    "{} {} {}",
+                    Formatter::operand(left, prec),
+                    op,
+                    Formatter::operand(right, prec + 1)
+                )
+            }
+            Expr::Compare(cond, left, right) => Formatter::compare(*cond, left, right),
+            Expr::Main(stmts) => {
+                let mut formatter = Formatter::new();
+                formatter.write_stmts(stmts, true);
+                formatter.out
+            }
+        }
+    }
+
+    fn compare(cond: Condition, left: &Expr, right: &Expr) -> String {
+        format!(
+            "{} {} {}",
+            Formatter::operand(left, 2),
+            cond,
+            Formatter::operand(right, 2)
+        )
+    }
+
+    /// Formats an operand, wrapping it in parentheses when it binds
+    /// looser than `min`.
+    #[must_use]
+    pub fn operand(expr: &Expr, min: u8) -> String {
+        if Formatter::precedence(expr) < min {
+            format!("({})", Formatter::expr(expr))
+        } else {
+            Formatter::expr(expr)
+        }
+    }
+
+    /// Formats a comma separated list of expressions.
+    #[must_use]
+    pub fn list(items: &[Expr]) -> String {
+        let items: Vec<String> = items.iter().map(Formatter::expr).collect();
+        items.join(", ")
+    }
+
+    /// Quotes a string, using single quotes if it contains an unescaped
+    /// double quote.
+    #[must_use]
+    pub fn string(s: &str) -> String {
+        let mut escaped = false;
+        let mut has_double = false;
+
+        for c in s.chars() {
+            has_double |= c == '"' && !escaped;
+            escaped = c == '\\' && !escaped;
+        }
+
+        if has_double {
+            format!("'{s}'")
+        } else {
+            format!("\"{s}\"")
+        }
+    }
+}
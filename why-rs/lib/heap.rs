@@ -0,0 +1,368 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use super::Instance;
+use super::Object;
+use super::Value;
+
+/// How many objects are made between collections, at least.
+pub const GC_THRESHOLD: usize = 1000;
+
+/// Counts the heap values a runtime keeps in its variables, and the
+/// objects it makes and collects, for `why run --gc-stats`.
+///
+/// A variable owns its value, arrays and mappings being copied when
+/// they are assigned, so a value is freed as soon as its variable is
+/// deleted, reassigned or goes out of scope. Objects are shared by
+/// reference counting instead, and those that refer to each other in a
+/// cycle are freed by the [`Collector`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct HeapStats {
+    /// How many heap values were stored in variables.
+    pub allocated: usize,
+    /// How many of them were freed.
+    pub freed: usize,
+    /// The most that were live at once.
+    pub peak: usize,
+    /// How many objects were made.
+    pub objects: usize,
+    /// How many collections ran.
+    pub collections: usize,
+    /// How many objects they freed.
+    pub collected: usize,
+}
+
+/// Frees objects that are only kept alive by cycles of references
+/// between them, which reference counting alone never frees, such as an
+/// object holding one of its own methods.
+///
+/// Like `CPython`'s collector, it finds them by subtracting the
+/// references objects hold to each other from their reference counts.
+/// An object with references left over is held from outside, by a
+/// variable or a value being worked on, so it and everything it refers
+/// to are alive. The rest are garbage, and their fields are cleared to
+/// break the cycles. As every reference from outside is counted, a
+/// collection is safe to run at any point.
+#[derive(Clone, Debug, Default)]
+pub struct Collector {
+    /// Every object made since the last collection, and those that
+    /// survived it.
+    pub objects: Vec<Weak<RefCell<Instance>>>,
+    /// How many objects are made before the next collection, or as
+    /// many as survived the last one if more did, so a program that
+    /// keeps many objects isn't collected quadratically often.
+    pub threshold: usize,
+    /// How many objects were made since the last collection.
+    pub made: usize,
+    /// How many objects survived the last collection.
+    pub survivors: usize,
+}
+
+impl HeapStats {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many heap values a value is made of, counting itself if it's
+    /// on the heap and everything it holds. Objects are shared, so they
+    /// are counted when they are made rather than here.
+    #[must_use]
+    pub fn cells(value: &Value) -> usize {
+        match value {
+            Value::String(_)
+            | Value::Func(_)
+            | Value::Compiled(_)
+            | Value::Class(_)
+            | Value::Method(..) => 1,
+            Value::Array(items) => 1 + items.iter().map(HeapStats::cells).sum::<usize>(),
+            Value::Mapping(pairs) => {
+                let cells = pairs
+                    .iter()
+                    .map(|(k, v)| HeapStats::cells(k) + HeapStats::cells(v));
+                1 + cells.sum::<usize>()
+            }
+            Value::Int(_)
+            | Value::Float(_)
+            | Value::Bool(_)
+            | Value::Builtin(_)
+            | Value::Object(_)
+            | Value::Null => 0,
+        }
+    }
+
+    /// How many heap values are live.
+    #[must_use]
+    pub fn live(&self) -> usize {
+        self.allocated - self.freed
+    }
+
+    /// Counts a value being stored in a variable.
+    pub fn alloc(&mut self, value: &Value) {
+        self.allocated += HeapStats::cells(value);
+        self.peak = self.peak.max(self.live());
+    }
+
+    /// Counts a variable's value being freed.
+    pub fn free(&mut self, value: &Value) {
+        self.freed += HeapStats::cells(value);
+    }
+
+    /// Counts an assignment to a variable, or an element of it, which
+    /// replaced a value. The variable's value was made of `before` cells
+    /// and is now made of `after`.
+    pub fn reassign(&mut self, replaced: &Value, before: usize, after: usize) {
+        let replaced = HeapStats::cells(replaced);
+
+        self.freed += replaced;
+        self.allocated += (after + replaced).saturating_sub(before);
+        self.peak = self.peak.max(self.live() + replaced);
+    }
+}
+
+impl std::fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "heap: {} allocated, {} freed, {} live, {} at peak; {} objects made, {} collected in {} collections",
+            self.allocated,
+            self.freed,
+            self.live(),
+            self.peak,
+            self.objects,
+            self.collected,
+            self.collections
+        )
+    }
+}
+
+impl Collector {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            threshold: GC_THRESHOLD,
+            ..Self::default()
+        }
+    }
+
+    /// Keeps track of a new object.
+    ///
+    /// # Returns
+    /// - [`bool`] - Whether enough objects were made since the last
+    ///   collection that another should run.
+    pub fn track(&mut self, object: &Object) -> bool {
+        self.objects.push(Rc::downgrade(&object.0));
+        self.made += 1;
+        self.made >= self.threshold.max(self.survivors)
+    }
+
+    /// Frees the objects that are only kept alive by cycles.
+    ///
+    /// # Returns
+    /// - [`usize`] - How many objects were freed.
+    pub fn collect(&mut self) -> usize {
+        self.objects.retain(|object| object.strong_count() > 0);
+        let objects: Vec<Rc<RefCell<Instance>>> =
+            self.objects.iter().filter_map(Weak::upgrade).collect();
+
+        let index: HashMap<*const RefCell<Instance>, usize> = objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| (Rc::as_ptr(object), i))
+            .collect();
+
+        // References from outside, less the one just taken
+        let mut outside: Vec<usize> = objects.iter().map(|o| Rc::strong_count(o) - 1).collect();
+
+        for object in &objects {
+            // An object borrowed elsewhere is being worked on, so it's
+            // kept, along with everything it refers to
+            let Ok(instance) = object.try_borrow() else {
+                continue;
+            };
+
+            for binding in instance.fields.values() {
+                Object::children(&binding.value, &mut |child| {
+                    if let Some(&i) = index.get(&Rc::as_ptr(&child.0)) {
+                        outside[i] = outside[i].saturating_sub(1);
+                    }
+                });
+            }
+        }
+
+        let borrowed = |o: &Rc<RefCell<Instance>>| o.try_borrow().is_err();
+        let mut alive: Vec<bool> = objects
+            .iter()
+            .zip(&outside)
+            .map(|(o, outside)| *outside > 0 || borrowed(o))
+            .collect();
+        let mut pending: Vec<usize> = (0..objects.len()).filter(|i| alive[*i]).collect();
+
+        while let Some(i) = pending.pop() {
+            let Ok(instance) = objects[i].try_borrow() else {
+                continue;
+            };
+
+            for binding in instance.fields.values() {
+                Object::children(&binding.value, &mut |child| {
+                    if let Some(&j) = index.get(&Rc::as_ptr(&child.0)) {
+                        if !alive[j] {
+                            alive[j] = true;
+                            pending.push(j);
+                        }
+                    }
+                });
+            }
+        }
+
+        // Clearing the fields drops the references that formed the
+        // cycles, after which reference counting frees the objects
+        let mut garbage = Vec::new();
+
+        for (object, alive) in objects.iter().zip(&alive) {
+            if !alive {
+                garbage.push(std::mem::take(&mut object.borrow_mut().fields));
+            }
+        }
+
+        drop(garbage);
+        drop(objects);
+
+        self.objects.retain(|object| object.strong_count() > 0);
+        self.made = 0;
+        self.survivors = self.objects.len();
+
+        alive.iter().filter(|alive| !**alive).count()
+    }
+}
+
+/// Caps how many bytes the values in a runtime's variables take, for
+/// running code that can't be trusted not to exhaust the host's memory.
+///
+/// Sizes are estimates: each value counts the space it takes in its
+/// variable or container, plus the text of strings. An object is shared
+/// rather than held by a variable, so it counts its fields from when
+/// it's made until it's freed. Only growing past the limit fails, so a
+/// run can still free what an earlier one left.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Memory {
+    /// How many bytes the variables may take.
+    pub limit: usize,
+    /// How many bytes they take.
+    pub used: usize,
+    /// Whether they grew past the limit since the last check.
+    pub exceeded: bool,
+    /// How many bytes objects gave back when they were freed, which
+    /// they can be wherever the last reference to them is dropped.
+    pub freed: Rc<Cell<usize>>,
+}
+
+impl Memory {
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    /// How many bytes a value takes, counting everything it holds.
+    #[must_use]
+    pub fn size(value: &Value) -> usize {
+        let held = match value {
+            Value::String(s) => s.len(),
+            Value::Array(items) => items.iter().map(Memory::size).sum(),
+            Value::Mapping(pairs) => pairs
+                .iter()
+                .map(|(k, v)| Memory::size(k) + Memory::size(v))
+                .sum(),
+            _ => 0,
+        };
+
+        std::mem::size_of::<Value>() + held
+    }
+
+    /// Counts a value being stored in a variable.
+    pub fn alloc(&mut self, value: &Value) {
+        self.used += Memory::size(value);
+        self.exceeded |= self.used > self.limit;
+    }
+
+    /// Counts a variable's value being freed.
+    pub fn free(&mut self, value: &Value) {
+        self.used = self.used.saturating_sub(Memory::size(value));
+    }
+
+    /// Counts a new object's fields until it's freed.
+    pub fn adopt(&mut self, instance: &mut Instance) {
+        let size = instance
+            .fields
+            .values()
+            .map(|binding| Memory::size(&binding.value))
+            .sum::<usize>();
+
+        self.used += size;
+        self.exceeded |= self.used > self.limit;
+        instance.size = size;
+        instance.freed = Some(Rc::clone(&self.freed));
+    }
+
+    /// Counts an assignment to a variable, or an element of it, whose
+    /// value took `before` bytes and now takes `after`.
+    pub fn resize(&mut self, before: usize, after: usize) {
+        self.used = (self.used + after).saturating_sub(before);
+        self.exceeded |= after > before && self.used > self.limit;
+    }
+
+    /// Checks whether the variables grew past the limit since the last
+    /// check.
+    ///
+    /// # Errors
+    /// - If they did.
+    pub fn check(&mut self) -> Result<(), String> {
+        self.used = self.used.saturating_sub(self.freed.take());
+
+        if std::mem::take(&mut self.exceeded) {
+            return self.overflow();
+        }
+
+        Ok(())
+    }
+
+    /// Checks a value can be stored without going past the limit.
+    ///
+    /// # Errors
+    /// - If it can't.
+    pub fn reserve(&self, value: &Value) -> Result<(), String> {
+        let used = self.used.saturating_sub(self.freed.get());
+
+        if used + Memory::size(value) > self.limit {
+            return self.overflow();
+        }
+
+        Ok(())
+    }
+
+    /// Checks the variables fit in the limit now, once the objects freed
+    /// since the last check are given back, as after a collection.
+    ///
+    /// # Errors
+    /// - If they don't.
+    pub fn fits(&mut self) -> Result<(), String> {
+        self.used = self.used.saturating_sub(self.freed.take());
+
+        if self.used > self.limit {
+            return self.overflow();
+        }
+
+        Ok(())
+    }
+
+    fn overflow(&self) -> Result<(), String> {
+        super::exc!(
+            "Memory limit exceeded: more than {} bytes in use",
+            (self.limit)
+        )
+    }
+}
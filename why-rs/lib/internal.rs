@@ -1,26 +1,22 @@
-use std::env;
+use std::collections::HashSet;
+use std::fmt::Write;
 use std::fs;
+use std::io::{self, Read};
+use std::sync::LazyLock;
 
-pub const KEYWORDS: [&str; 10] = [
-    "if", "in", "is", "break", "return", "let", "else", "const", "for", "while",
+use super::Loc;
+
+pub const KEYWORDS: [&str; 11] = [
+    "if", "in", "is", "break", "return", "let", "else", "const", "for", "while", "delete",
 ];
 
-/// Obtains the CLI args passed to the programs execution.
-///
-/// # Returns
-/// - [`Result<String, WhyExc>`] - A vector of strings with representing
-/// the arguments on success.
-///
-/// # Errors
-/// - If the use passed no CLI args to the program.
-pub fn collect_cli_args() -> Result<Vec<String>, String> {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() >= 2 {
-        Ok(args)
-    } else {
-        super::exc!("Missing required argument: the file to compile.")
-    }
+/// The [`KEYWORDS`], to look names up in.
+static KEYWORD_SET: LazyLock<HashSet<&str>> = LazyLock::new(|| KEYWORDS.into_iter().collect());
+
+/// Returns true if the name is one of the [`KEYWORDS`].
+#[must_use]
+pub fn is_keyword(name: &str) -> bool {
+    KEYWORD_SET.contains(name)
 }
 
 /// Reads a source file into memory and returns it as a string
@@ -30,13 +26,74 @@ pub fn collect_cli_args() -> Result<Vec<String>, String> {
 ///
 /// # Errors
 /// - If the file was unable to be read for any reason.
-pub fn read_source_file(filename: &String) -> Result<String, String> {
-    match fs::read_to_string(&filename) {
+pub fn read_source_file(filename: &str) -> Result<String, String> {
+    match fs::read_to_string(filename) {
         Ok(src) => Ok(src),
         Err(e) => super::exc!("Failed to read file: {:?}: {}", filename, e),
     }
 }
 
+/// Reads source code from a file, or from stdin if the filename is `-`.
+///
+/// # Returns
+/// - [`Result<String, String>`] - The source code on success.
+///
+/// # Errors
+/// - If the file or stdin was unable to be read for any reason.
+pub fn read_source(filename: &str) -> Result<String, String> {
+    if filename != "-" {
+        return read_source_file(filename);
+    }
+
+    let mut src = String::new();
+
+    match io::stdin().read_to_string(&mut src) {
+        Ok(_) => Ok(src),
+        Err(e) => super::exc!("Failed to read stdin: {}", e),
+    }
+}
+
+/// Renders the line of source a location is on, with a caret under its
+/// column, in the style of the disassembler:
+///
+/// ```text
+///    3 | print(f(1));
+///      |       ^
+/// ```
+///
+/// The caret is left out when the column is 0, meaning it isn't known,
+/// and everything is when the line isn't in the source.
+#[must_use]
+pub fn snippet(src: &str, loc: &Loc) -> String {
+    let Some(line) = src.lines().nth(loc.line.wrapping_sub(1)) else {
+        return String::new();
+    };
+
+    let mut out = format!("{:>4} | {}\n", loc.line, line.trim_end());
+
+    if loc.col > 0 {
+        // Keep tabs, so the caret lines up however wide they are
+        let pad: String = line
+            .chars()
+            .take(loc.col - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let _ = writeln!(out, "{:>4} | {}^", "", pad);
+    }
+
+    out
+}
+
+/// A count or index as a `u32`, the width bytecode operands, `.whyc`
+/// files and WebAssembly indices use.
+///
+/// # Panics
+/// - If it doesn't fit, which would take billions of items in memory.
+#[must_use]
+pub fn index(n: usize) -> u32 {
+    u32::try_from(n).expect("index too large for a u32")
+}
+
 // #[derive(Clone, Debug)]
 // pub struct WhyExc {
 //     pub message: String,
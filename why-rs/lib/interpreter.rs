@@ -0,0 +1,1418 @@
+#[cfg(feature = "jit")]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::traceback;
+use super::CallSite;
+use super::Class;
+use super::ClassDecl;
+use super::Collector;
+use super::Condition;
+use super::Expr;
+use super::FuncDecl;
+use super::Function;
+use super::HeapStats;
+#[cfg(feature = "jit")]
+use super::Jit;
+use super::Loc;
+use super::Memory;
+use super::Native;
+use super::Object;
+use super::Operator;
+use super::Param;
+use super::Stmt;
+use super::Symbol;
+use super::VarType;
+
+type EvalResult = Result<Value, String>;
+
+/// The builtin functions, callable with or without a `%` prefix.
+pub const BUILTINS: [&str; 3] = ["print", "len", "str"];
+
+/// A value produced at runtime.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Array(Vec<Value>),
+    Mapping(Vec<(Value, Value)>),
+    Func(Rc<FuncDecl>),
+    /// A function compiled for the [`super::Vm`].
+    Compiled(Rc<Function>),
+    Builtin(Symbol),
+    /// A class, which makes a new object when called.
+    Class(Rc<Class>),
+    Object(Object),
+    /// A method bound to the object it was looked up on.
+    Method(Object, Rc<FuncDecl>),
+    Null,
+}
+
+/// A variable stored in a scope.
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub value: Value,
+    pub typ: VarType,
+    pub constant: bool,
+}
+
+/// How control leaves a statement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Flow {
+    Next,
+    Break,
+    Return(Value),
+}
+
+/// A tree walking interpreter, which keeps its variables between
+/// calls to [`Interpreter::run`].
+#[derive(Clone, Debug)]
+pub struct Interpreter {
+    /// The scopes, the first being the global scope.
+    pub scopes: Vec<HashMap<Symbol, Binding>>,
+    /// The index of the first scope belonging to the current call.
+    pub frame: usize,
+    /// Compiles the functions it can to native code, if enabled.
+    #[cfg(feature = "jit")]
+    pub jit: Option<Rc<RefCell<Jit>>>,
+    /// Counts the heap values stored in variables, if enabled.
+    pub heap: Option<HeapStats>,
+    /// Frees the objects kept alive by cycles.
+    pub gc: Collector,
+    /// The calls in progress, innermost last.
+    pub calls: Vec<CallSite>,
+    /// The calls that were in progress when the last run failed, then
+    /// where it failed.
+    pub traceback: Vec<CallSite>,
+    /// Where the statement or call being run is, for tracebacks.
+    pub loc: Loc,
+    /// How many calls may be nested before a run fails.
+    pub max_depth: usize,
+    /// How many bytes of stack the calls may take before a run fails,
+    /// see [`traceback::STACK_LIMIT`].
+    pub stack_limit: usize,
+    /// Where the stack was when the outermost call was made.
+    pub stack_base: usize,
+    /// The functions the host registered, called like builtins.
+    pub natives: HashMap<Symbol, Native>,
+    /// How many statements and expressions a run may evaluate, if
+    /// limited.
+    pub budget: Option<u64>,
+    /// How many statements and expressions the current run evaluated.
+    pub steps: u64,
+    /// Caps the memory the variables take, if enabled.
+    pub memory: Option<Memory>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Binding {
+    /// Creates a binding for a declaration, with the declared type or
+    /// else the type of its value.
+    ///
+    /// # Errors
+    /// - If the value doesn't have the declared type.
+    pub fn new(
+        name: Symbol,
+        typ: Option<VarType>,
+        value: Value,
+        constant: bool,
+    ) -> Result<Self, String> {
+        let typ = typ.unwrap_or_else(|| value.typ());
+
+        if !typ.accepts(&value.typ()) {
+            return super::exc!(
+                "Can't assign {} to '{}' of type {}",
+                (value.typ()),
+                name,
+                typ
+            );
+        }
+
+        Ok(Binding {
+            value: value.coerce(&typ),
+            typ,
+            constant,
+        })
+    }
+
+    /// Assigns a value to the variable, or to the element reached by
+    /// indexing it, applying a compound operator like `+=`. The indexes
+    /// are in the order they are evaluated, the last subscript first.
+    ///
+    /// # Returns
+    /// - [`Value`] - The value that was replaced.
+    ///
+    /// # Errors
+    /// - If the variable is constant or of another type, or an index
+    ///   or the operator fails.
+    pub fn assign(
+        &mut self,
+        name: Symbol,
+        op: &Operator,
+        indexes: &[Value],
+        value: Value,
+    ) -> Result<Value, String> {
+        if self.constant {
+            return super::exc!("Can't assign to constant '{}'", name);
+        }
+
+        let mut slot = &mut self.value;
+        let mut typ = &self.typ;
+
+        for index in indexes.iter().rev() {
+            slot = Interpreter::index_mut(slot, index, *op == Operator::Assign)?;
+            typ = typ.item().unwrap_or(&VarType::Any);
+        }
+
+        let value = match op {
+            Operator::Assign => value,
+            Operator::IncrementBy | Operator::Increment => {
+                Interpreter::binary(&Operator::Add, slot.clone(), value)?
+            }
+            Operator::DecrementBy | Operator::Decrement => {
+                Interpreter::binary(&Operator::Subtract, slot.clone(), value)?
+            }
+            Operator::MultBy => Interpreter::binary(&Operator::Mult, slot.clone(), value)?,
+            Operator::DivBy => Interpreter::binary(&Operator::Div, slot.clone(), value)?,
+            _ => return super::exc!("Invalid assignment operator {}", op),
+        };
+
+        if indexes.is_empty() && !self.typ.accepts(&value.typ()) {
+            return super::exc!(
+                "Can't assign {} to '{}' of type {}",
+                (value.typ()),
+                name,
+                (self.typ)
+            );
+        }
+
+        Ok(std::mem::replace(slot, value.coerce(typ)))
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{s}"),
+            _ => write!(f, "{}", self.repr()),
+        }
+    }
+}
+
+impl Value {
+    /// The runtime type of this value.
+    #[must_use]
+    pub fn typ(&self) -> VarType {
+        match self {
+            Value::Int(_) => VarType::Int,
+            Value::Float(_) => VarType::Float,
+            Value::String(_) => VarType::String,
+            Value::Bool(_) => VarType::Bool,
+            Value::Null => VarType::Void,
+            Value::Array(items) => {
+                VarType::Array(Box::new(items.first().map_or(VarType::Any, Value::typ)))
+            }
+            Value::Mapping(pairs) => match pairs.first() {
+                Some((k, v)) => VarType::Mapping(Box::new(k.typ()), Box::new(v.typ())),
+                None => VarType::Mapping(Box::new(VarType::Any), Box::new(VarType::Any)),
+            },
+            Value::Func(func) => VarType::Function(
+                func.params.iter().map(|p| p.typ.clone()).collect(),
+                Box::new(func.ret.clone()),
+            ),
+            Value::Compiled(func) => VarType::Function(
+                func.params.iter().map(|p| p.typ.clone()).collect(),
+                Box::new(func.ret.clone()),
+            ),
+            Value::Builtin(_) => VarType::Function(vec![], Box::new(VarType::Any)),
+            Value::Class(class) => VarType::Function(vec![], Box::new(VarType::Class(class.name))),
+            Value::Object(object) => VarType::Class(object.class()),
+            Value::Method(_, method) => Class::method_type(method),
+        }
+    }
+
+    /// The representation of this value as it would appear in source,
+    /// so strings are quoted.
+    #[must_use]
+    pub fn repr(&self) -> String {
+        match self {
+            Value::Int(num) => num.to_string(),
+            Value::Float(num) => format!("{num:?}"),
+            Value::String(s) => format!("{s:?}"),
+            Value::Bool(b) => b.to_string(),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(Value::repr).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Value::Mapping(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(k, v)| format!("{}->{}", k.repr(), v.repr()))
+                    .collect();
+                format!("&{{{}}}", pairs.join(", "))
+            }
+            Value::Func(func) => format!("<function {}>", func.name),
+            Value::Compiled(func) => format!("<function {}>", func.name),
+            Value::Builtin(name) => format!("<builtin {name}>"),
+            Value::Class(class) => format!("<class {}>", class.name),
+            Value::Object(object) => format!("<{} object>", object.class()),
+            Value::Method(object, method) => {
+                format!("<method {}.{}>", object.class(), method.name)
+            }
+            Value::Null => "NULL".to_string(),
+        }
+    }
+
+    /// Converts the value so it can be stored as the given type,
+    /// widening ints to floats, including those in arrays and mappings.
+    #[must_use]
+    pub fn coerce(self, typ: &VarType) -> Value {
+        #[allow(clippy::cast_precision_loss)]
+        match (self, typ) {
+            (Value::Int(num), VarType::Float) => Value::Float(num as f64),
+            (Value::Array(items), VarType::Array(item)) if item.widens() => {
+                Value::Array(items.into_iter().map(|i| i.coerce(item)).collect())
+            }
+            (Value::Mapping(pairs), VarType::Mapping(key, value))
+                if key.widens() || value.widens() =>
+            {
+                let pairs = pairs.into_iter();
+                Value::Mapping(
+                    pairs
+                        .map(|(k, v)| (k.coerce(key), v.coerce(value)))
+                        .collect(),
+                )
+            }
+            (value, _) => value,
+        }
+    }
+
+    /// The value a variable of a type starts as when it isn't given
+    /// one, such as a field without a default. Objects and functions
+    /// start as `NULL`.
+    #[must_use]
+    pub fn zero(typ: &VarType) -> Value {
+        match typ {
+            VarType::Int => Value::Int(0),
+            VarType::Float => Value::Float(0.0),
+            VarType::String => Value::String(String::new()),
+            VarType::Bool => Value::Bool(false),
+            VarType::Array(_) => Value::Array(Vec::new()),
+            VarType::Mapping(..) => Value::Mapping(Vec::new()),
+            _ => Value::Null,
+        }
+    }
+
+    /// Returns the number as a float, if this is a number.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Int(num) => Some(*num as f64),
+            Value::Float(num) => Some(*num),
+            _ => None,
+        }
+    }
+}
+
+impl Interpreter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            frame: 0,
+            #[cfg(feature = "jit")]
+            jit: None,
+            heap: None,
+            gc: Collector::new(),
+            calls: Vec::new(),
+            traceback: Vec::new(),
+            loc: Loc::new(),
+            max_depth: traceback::MAX_DEPTH,
+            stack_limit: traceback::STACK_LIMIT,
+            stack_base: 0,
+            natives: HashMap::new(),
+            budget: None,
+            steps: 0,
+            memory: None,
+        }
+    }
+
+    /// Creates an interpreter that calls functions compiled to native
+    /// code where it can, see [`Jit`].
+    ///
+    /// # Errors
+    /// - If the JIT doesn't support the machine.
+    #[cfg(feature = "jit")]
+    pub fn with_jit() -> Result<Self, String> {
+        Ok(Self {
+            jit: Some(Rc::new(RefCell::new(Jit::new()?))),
+            ..Self::new()
+        })
+    }
+
+    /// Counts the heap values stored in variables, see [`HeapStats`].
+    #[must_use]
+    pub fn with_heap_stats(mut self) -> Self {
+        self.heap = Some(HeapStats::new());
+        self
+    }
+
+    /// Sets how many calls may be nested before a run fails with a
+    /// stack overflow.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets how many bytes of stack calls may take before a run fails
+    /// with a stack overflow. Hosts that run programs on a thread with
+    /// more stack than a spawned thread gets can raise it.
+    #[must_use]
+    pub fn with_stack_limit(mut self, bytes: usize) -> Self {
+        self.stack_limit = bytes;
+        self
+    }
+
+    /// Sets how many objects are made before the first collection of
+    /// cycles, see [`Collector`].
+    #[must_use]
+    pub fn with_gc_threshold(mut self, objects: usize) -> Self {
+        self.gc.threshold = objects;
+        self
+    }
+
+    /// Limits how many statements and expressions each run may
+    /// evaluate, so code that doesn't finish fails instead.
+    #[must_use]
+    pub fn with_budget(mut self, steps: u64) -> Self {
+        self.budget = Some(steps);
+        self
+    }
+
+    /// Fails runs once the variables take more than a number of bytes,
+    /// see [`Memory`].
+    #[must_use]
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory = Some(Memory::new(bytes));
+        self
+    }
+
+    /// Runs a program, returning the value of its last statement if
+    /// that statement was an expression. If it fails, the calls it
+    /// failed in are kept in [`Interpreter::traceback`].
+    ///
+    /// # Returns
+    /// - [`EvalResult`] - The resulting value on success.
+    ///
+    /// # Errors
+    /// - If a runtime error occurs, or the run goes over its limits.
+    pub fn run(&mut self, program: &Expr) -> EvalResult {
+        self.traceback.clear();
+        self.steps = 0;
+        self.loc = Loc::new();
+
+        let result = self.run_main(program);
+
+        // Errors outside of any call fail at the top level
+        if result.is_err() && self.traceback.is_empty() {
+            self.traceback.push(CallSite {
+                name: Symbol::intern("<main>"),
+                loc: self.loc.clone(),
+            });
+        }
+
+        result
+    }
+
+    fn run_main(&mut self, program: &Expr) -> EvalResult {
+        let stmts = match program {
+            Expr::Main(stmts) => stmts,
+            expr => return self.eval(expr),
+        };
+
+        self.hoist(stmts);
+        let mut last = Value::Null;
+
+        for stmt in stmts {
+            last = Value::Null;
+
+            if let Stmt::Expr(expr, loc) = stmt {
+                self.loc = loc.clone();
+                last = self.eval(expr)?;
+                continue;
+            }
+
+            match self.exec(stmt)? {
+                Flow::Next => (),
+                Flow::Break => return super::exc!("'break' outside of a loop"),
+                Flow::Return(_) => return super::exc!("'return' outside of a function"),
+            }
+        }
+
+        Ok(last)
+    }
+
+    /// Looks up a variable by name, searching the current call's
+    /// scopes before the globals.
+    #[must_use]
+    pub fn lookup(&self, name: Symbol) -> Option<&Binding> {
+        self.scopes[self.frame..]
+            .iter()
+            .rev()
+            .chain(self.scopes.first())
+            .find_map(|scope| scope.get(&name))
+    }
+
+    /// Looks up a variable mutably, like [`Interpreter::lookup`].
+    pub fn lookup_mut(&mut self, name: Symbol) -> Option<&mut Binding> {
+        let idx = (self.frame..self.scopes.len())
+            .rev()
+            .chain([0])
+            .find(|i| self.scopes[*i].contains_key(&name))?;
+
+        self.scopes[idx].get_mut(&name)
+    }
+
+    /// Declares a variable in the innermost scope.
+    ///
+    /// # Panics
+    /// - If there are no scopes, though the global scope is never popped.
+    pub fn declare(&mut self, name: Symbol, binding: Binding) {
+        self.alloc(&binding.value);
+
+        let replaced = self.scopes.last_mut().unwrap().insert(name, binding);
+
+        if let Some(replaced) = replaced {
+            self.free(&replaced.value);
+        }
+    }
+
+    /// Counts a value being stored in a variable.
+    fn alloc(&mut self, value: &Value) {
+        if let Some(heap) = &mut self.heap {
+            heap.alloc(value);
+        }
+
+        if let Some(memory) = &mut self.memory {
+            memory.alloc(value);
+        }
+    }
+
+    /// Counts a variable's value being freed.
+    fn free(&mut self, value: &Value) {
+        if let Some(heap) = &mut self.heap {
+            heap.free(value);
+        }
+
+        if let Some(memory) = &mut self.memory {
+            memory.free(value);
+        }
+    }
+
+    /// Counts a statement or expression being evaluated, failing once
+    /// the run goes over its limits.
+    #[inline]
+    fn step(&mut self) -> Result<(), String> {
+        if self.budget.is_none() && self.memory.is_none() {
+            return Ok(());
+        }
+
+        self.limit()
+    }
+
+    /// Checks the limits that are set, see [`Interpreter::step`].
+    fn limit(&mut self) -> Result<(), String> {
+        if let Some(budget) = self.budget {
+            self.steps += 1;
+
+            if self.steps > budget {
+                return super::exc!("Instruction budget exceeded: more than {} steps", budget);
+            }
+        }
+
+        let Some(memory) = &mut self.memory else {
+            return Ok(());
+        };
+
+        if memory.check().is_ok() {
+            return Ok(());
+        }
+
+        // Cycles nothing else refers to may be what's taking it up
+        self.collect();
+        self.memory.as_mut().map_or(Ok(()), Memory::fits)
+    }
+
+    /// Deletes a variable, found like [`Interpreter::lookup`], freeing
+    /// its value.
+    ///
+    /// # Returns
+    /// - [`Option<Binding>`] - The deleted variable, or `None` if it was
+    ///   undefined.
+    pub fn delete(&mut self, name: Symbol) -> Option<Binding> {
+        let idx = (self.frame..self.scopes.len())
+            .rev()
+            .chain([0])
+            .find(|i| self.scopes[*i].contains_key(&name))?;
+        let binding = self.scopes[idx].remove(&name)?;
+
+        self.free(&binding.value);
+        Some(binding)
+    }
+
+    /// Pops the innermost scope, freeing its variables.
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            for binding in scope.values() {
+                self.free(&binding.value);
+            }
+        }
+    }
+
+    /// Declares every function in a block up front, so they can call
+    /// each other regardless of order.
+    pub fn hoist(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if let Stmt::Func(func) = stmt {
+                let value = Value::Func(Rc::new(func.clone()));
+                let binding = Binding {
+                    typ: value.typ(),
+                    value,
+                    constant: false,
+                };
+
+                self.loc = func.loc.clone();
+                self.declare(func.name, binding);
+            }
+        }
+    }
+
+    /// Executes a block of statements in a new scope.
+    ///
+    /// # Errors
+    /// - If a runtime error occurs.
+    pub fn exec_block(&mut self, stmts: &[Stmt]) -> Result<Flow, String> {
+        self.scopes.push(HashMap::new());
+        self.hoist(stmts);
+
+        let mut flow = Ok(Flow::Next);
+
+        for stmt in stmts {
+            flow = self.exec(stmt);
+
+            if flow != Ok(Flow::Next) {
+                break;
+            }
+        }
+
+        self.pop_scope();
+        flow
+    }
+
+    /// Executes a single statement.
+    ///
+    /// # Errors
+    /// - If a runtime error occurs.
+    pub fn exec(&mut self, stmt: &Stmt) -> Result<Flow, String> {
+        self.step()?;
+
+        match stmt {
+            Stmt::Expr(expr, loc) => {
+                self.loc = loc.clone();
+                self.eval(expr)?;
+            }
+            Stmt::VarDecl(decl) => {
+                self.loc = decl.loc.clone();
+                let value = self.eval(&decl.value)?;
+                self.loc = decl.loc.clone();
+
+                let constant = decl.keyword == Some(super::Keyword::Const);
+                let binding = Binding::new(decl.name, decl.typ.clone(), value, constant)?;
+
+                self.declare(decl.name, binding);
+            }
+            Stmt::Assign(op, target, value, loc) => {
+                self.loc = loc.clone();
+                let value = self.eval(value)?;
+                self.loc = loc.clone();
+                self.assign(op, target, value)?;
+            }
+            Stmt::Func(_) | Stmt::Comment(..) => (),
+            Stmt::Return(value, loc) => {
+                self.loc = loc.clone();
+                let value = match value {
+                    Some(expr) => self.eval(expr)?,
+                    None => Value::Null,
+                };
+
+                self.loc = loc.clone();
+                return Ok(Flow::Return(value));
+            }
+            Stmt::If(cond, body, otherwise, loc) => {
+                self.loc = loc.clone();
+
+                let flow = if self.eval_cond(cond)? {
+                    self.exec_block(body)?
+                } else if let Some(otherwise) = otherwise {
+                    self.exec_block(otherwise)?
+                } else {
+                    Flow::Next
+                };
+
+                // A return is reported where it is, not where its if is
+                if !matches!(flow, Flow::Return(_)) {
+                    self.loc = loc.clone();
+                }
+
+                return Ok(flow);
+            }
+            Stmt::While(cond, body, loc) => {
+                loop {
+                    self.loc = loc.clone();
+
+                    if !self.eval_cond(cond)? {
+                        break;
+                    }
+
+                    match self.exec_block(body)? {
+                        Flow::Next => (),
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Ok(flow),
+                    }
+                }
+
+                self.loc = loc.clone();
+            }
+            Stmt::Class(class) => {
+                self.loc = class.loc.clone();
+                self.declare_class(class)?;
+            }
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Delete(name, loc) => {
+                self.loc = loc.clone();
+
+                let Some(binding) = self.delete(*name) else {
+                    return super::exc!("Can't delete undefined variable '{}'", name);
+                };
+
+                // Deleting a reference to an object may leave a cycle
+                // behind
+                if Object::held_by(&binding.value) {
+                    drop(binding);
+                    self.collect();
+                }
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    /// Evaluates a condition, which must be a bool.
+    ///
+    /// # Errors
+    /// - If evaluation fails, or the condition was not a bool.
+    pub fn eval_cond(&mut self, cond: &Expr) -> Result<bool, String> {
+        match self.eval(cond)? {
+            Value::Bool(b) => Ok(b),
+            value => super::exc!("Condition must be a bool, but got {}", (value.typ())),
+        }
+    }
+
+    /// Assigns a value to a variable or indexed element.
+    ///
+    /// # Errors
+    /// - If the target is undefined, constant, or of another type.
+    pub fn assign(&mut self, op: &Operator, target: &Expr, value: Value) -> Result<(), String> {
+        // Evaluate every index before borrowing the variable mutably
+        let mut indexes = Vec::new();
+        let mut root = target;
+
+        while let Expr::Index(base, index) = root {
+            indexes.push(self.eval(index)?);
+            root = base;
+        }
+
+        if let Expr::BinaryOp(Operator::Dot, object, field) = root {
+            return self.assign_field(*op, object, field, &indexes, value);
+        }
+
+        let Expr::Ident(name) = root else {
+            return super::exc!("Can't assign to {:?}", root);
+        };
+
+        let (counting, capped) = (self.heap.is_some(), self.memory.is_some());
+        let sizes = |binding: &Binding| {
+            let cells = if counting {
+                HeapStats::cells(&binding.value)
+            } else {
+                0
+            };
+            let bytes = if capped {
+                Memory::size(&binding.value)
+            } else {
+                0
+            };
+
+            (cells, bytes)
+        };
+
+        let Some(binding) = self.lookup_mut(*name) else {
+            return super::exc!("Undefined variable '{}'", name);
+        };
+
+        let before = sizes(binding);
+        let replaced = binding.assign(*name, op, &indexes, value)?;
+        let after = sizes(binding);
+
+        if let Some(heap) = &mut self.heap {
+            heap.reassign(&replaced, before.0, after.0);
+        }
+
+        if let Some(memory) = &mut self.memory {
+            memory.resize(before.1, after.1);
+        }
+
+        Ok(())
+    }
+
+    /// Assigns a value to a field of an object, or to the element
+    /// reached by indexing it, like [`Binding::assign`].
+    ///
+    /// # Errors
+    /// - If the object isn't one, or has no such field, or the value
+    ///   doesn't fit it.
+    fn assign_field(
+        &mut self,
+        op: Operator,
+        object: &Expr,
+        field: &Expr,
+        indexes: &[Value],
+        value: Value,
+    ) -> Result<(), String> {
+        let Expr::Ident(field) = field else {
+            return super::exc!("Can't assign to {:?}", field);
+        };
+
+        let object = match self.eval(object)? {
+            Value::Object(object) => object,
+            value => return super::exc!("Can't set field '{}' of {}", field, (value.typ())),
+        };
+
+        // Taken out while it's assigned, as the value may be the object
+        let Some(mut binding) = object.0.borrow_mut().fields.remove(field) else {
+            return super::exc!("'{}' has no field '{}'", (object.class()), field);
+        };
+
+        let before = Memory::size(&binding.value);
+        let replaced = binding.assign(*field, &op, indexes, value);
+        let after = Memory::size(&binding.value);
+
+        let mut instance = object.0.borrow_mut();
+        instance.fields.insert(*field, binding);
+
+        if let Some(memory) = &mut self.memory {
+            memory.resize(before, after);
+            instance.size = (instance.size + after).saturating_sub(before);
+        }
+
+        // The replaced value may hold the last reference to an object
+        // that refers back to this one
+        drop(instance);
+        drop(replaced?);
+        Ok(())
+    }
+
+    /// Declares a class, which inherits the fields and methods of its
+    /// base class, if it has one.
+    ///
+    /// # Errors
+    /// - If the base class is undefined.
+    pub fn declare_class(&mut self, decl: &ClassDecl) -> Result<(), String> {
+        let (mut fields, mut methods) = match decl.base {
+            Some(base) => match self.lookup(base) {
+                Some(Binding {
+                    value: Value::Class(base),
+                    ..
+                }) => (base.fields.clone(), base.methods.clone()),
+                _ => return super::exc!("Undefined class '{}'", base),
+            },
+            None => (Vec::new(), HashMap::new()),
+        };
+
+        for field in &decl.fields {
+            fields.retain(|f| f.name != field.name);
+            fields.push(field.clone());
+        }
+
+        for method in &decl.methods {
+            methods.insert(method.name, Rc::new(method.clone()));
+        }
+
+        let class = Value::Class(Rc::new(Class {
+            name: decl.name,
+            fields,
+            methods,
+        }));
+        let binding = Binding {
+            typ: class.typ(),
+            value: class,
+            constant: true,
+        };
+
+        self.declare(decl.name, binding);
+        Ok(())
+    }
+
+    /// Makes an object of a class, setting each field to its default,
+    /// or else the zero value of its type. Defaults only see the
+    /// globals, like the body of a function.
+    ///
+    /// # Errors
+    /// - If arguments were given, or a default fails or doesn't fit
+    ///   its field.
+    pub fn construct(&mut self, class: &Rc<Class>, args: &[Value]) -> EvalResult {
+        if !args.is_empty() {
+            return super::exc!(
+                "'{}' takes 0 argument(s), but {} were given",
+                (class.name),
+                (args.len())
+            );
+        }
+
+        let frame = self.frame;
+        self.scopes.push(HashMap::new());
+        self.frame = self.scopes.len() - 1;
+
+        let mut fields = HashMap::new();
+        let mut result = Ok(());
+
+        for field in &class.fields {
+            let binding = match &field.value {
+                Some(value) => self.eval(value).and_then(|value| {
+                    Binding::new(field.name, Some(field.typ.clone()), value, false)
+                }),
+                None => Ok(Binding {
+                    value: Value::zero(&field.typ),
+                    typ: field.typ.clone(),
+                    constant: false,
+                }),
+            };
+
+            match binding {
+                Ok(binding) => _ = fields.insert(field.name, binding),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        self.pop_scope();
+        self.frame = frame;
+        result?;
+
+        let object = Object::new(Rc::clone(class), fields);
+
+        if let Some(memory) = &mut self.memory {
+            memory.adopt(&mut object.0.borrow_mut());
+        }
+
+        if let Some(heap) = &mut self.heap {
+            heap.objects += 1;
+        }
+
+        if self.gc.track(&object) {
+            self.collect();
+        }
+
+        Ok(Value::Object(object))
+    }
+
+    /// Looks up a field or method of an object. Methods that take
+    /// `&self` are bound to the object.
+    ///
+    /// # Errors
+    /// - If the value isn't an object, or has no such field or method.
+    pub fn field(object: &Value, field: &Expr) -> EvalResult {
+        let Expr::Ident(name) = field else {
+            return super::exc!("Expected a field name, but got {:?}", field);
+        };
+
+        let Value::Object(object) = object else {
+            return super::exc!("Can't get field '{}' of {}", name, (object.typ()));
+        };
+
+        let instance = object.0.borrow();
+
+        if let Some(binding) = instance.fields.get(name) {
+            return Ok(binding.value.clone());
+        }
+
+        match instance.class.methods.get(name) {
+            Some(method) if Class::binds(method) => {
+                Ok(Value::Method(object.clone(), Rc::clone(method)))
+            }
+            Some(method) => Ok(Value::Func(Rc::clone(method))),
+            None => super::exc!("'{}' has no field '{}'", (instance.class.name), name),
+        }
+    }
+
+    /// Frees the objects that are only kept alive by cycles, see
+    /// [`Collector`].
+    ///
+    /// # Returns
+    /// - [`usize`] - How many objects were freed.
+    pub fn collect(&mut self) -> usize {
+        let collected = self.gc.collect();
+
+        if let Some(heap) = &mut self.heap {
+            heap.collections += 1;
+            heap.collected += collected;
+        }
+
+        collected
+    }
+
+    /// Borrows an element of an array or mapping mutably. Missing
+    /// mapping keys are inserted when `insert` is true.
+    ///
+    /// # Errors
+    /// - If the value can't be indexed, or the index is out of range.
+    pub fn index_mut<'v>(
+        value: &'v mut Value,
+        index: &Value,
+        insert: bool,
+    ) -> Result<&'v mut Value, String> {
+        match (value, index) {
+            (Value::Array(items), Value::Int(i)) => {
+                let len = items.len();
+
+                match usize::try_from(*i).ok().and_then(|i| items.get_mut(i)) {
+                    Some(item) => Ok(item),
+                    None => super::exc!("Index {} out of range for array of length {}", i, len),
+                }
+            }
+            (Value::Mapping(pairs), key) => match pairs.iter().position(|(k, _)| k == key) {
+                Some(pos) => Ok(&mut pairs[pos].1),
+                None if insert => {
+                    pairs.push((key.clone(), Value::Null));
+                    let last = pairs.len() - 1;
+                    Ok(&mut pairs[last].1)
+                }
+                None => super::exc!("Key {} not found", (key.repr())),
+            },
+            (value, index) => super::exc!("Can't index {} with {}", (value.typ()), (index.typ())),
+        }
+    }
+
+    /// Evaluates an expression.
+    ///
+    /// # Returns
+    /// - [`EvalResult`] - The resulting value on success.
+    ///
+    /// # Errors
+    /// - If a runtime error occurs.
+    pub fn eval(&mut self, expr: &Expr) -> EvalResult {
+        self.step()?;
+
+        match expr {
+            Expr::Int(num) => Ok(Value::Int(*num)),
+            Expr::Float(num) => Ok(Value::Float(*num)),
+            Expr::String(s) => Ok(Value::String(s.clone())),
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
+            Expr::Null => Ok(Value::Null),
+            Expr::Error => super::exc!("Can't run code that failed to parse"),
+            Expr::Parenthesized(inner) => self.eval(inner),
+            Expr::Ident(name) => match self.lookup(*name) {
+                Some(binding) => Ok(binding.value.clone()),
+                None if BUILTINS.contains(&name.as_str()) => Ok(Value::Builtin(*name)),
+                None => super::exc!("Undefined variable '{}'", name),
+            },
+            Expr::Builtin(name) if BUILTINS.contains(&name.as_str()) => Ok(Value::Builtin(*name)),
+            Expr::Builtin(name) => super::exc!("Unknown builtin '%{}'", name),
+            Expr::Array(items) => {
+                let items: Result<Vec<Value>, String> =
+                    items.iter().map(|i| self.eval(i)).collect();
+                Ok(Value::Array(items?))
+            }
+            Expr::Mapping(pairs) => {
+                let mut mapping = Value::Mapping(Vec::with_capacity(pairs.len()));
+
+                for (key, value) in pairs {
+                    let key = self.eval(key)?;
+                    let value = self.eval(value)?;
+                    *Interpreter::index_mut(&mut mapping, &key, true)? = value;
+                }
+
+                Ok(mapping)
+            }
+            Expr::Index(base, index) => {
+                let base = self.eval(base)?;
+                let index = self.eval(index)?;
+                Interpreter::index(base, &index)
+            }
+            Expr::UnaryOp(op, inner) => {
+                let value = self.eval(inner)?;
+                Interpreter::unary(op, value)
+            }
+            Expr::BinaryOp(Operator::Dot, object, field) => {
+                let object = self.eval(object)?;
+                Interpreter::field(&object, field)
+            }
+            Expr::BinaryOp(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                Interpreter::binary(op, left, right)
+            }
+            Expr::Compare(cond, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                Interpreter::compare(cond, &left, &right)
+            }
+            Expr::Call(callee, args, loc) => {
+                self.loc = loc.clone();
+                let callee = self.eval(callee)?;
+                let args: Result<Vec<Value>, String> = args.iter().map(|a| self.eval(a)).collect();
+                let args = args?;
+
+                self.loc = loc.clone();
+                let value = self.call(&callee, args, loc)?;
+                self.loc = loc.clone();
+                Ok(value)
+            }
+            Expr::Main(_) => self.run(expr),
+        }
+    }
+
+    /// Calls a function, builtin or native function with the given
+    /// arguments, from the given location.
+    ///
+    /// # Errors
+    /// - If the value isn't callable, the arguments don't match the
+    ///   parameters, calls are nested too deeply, or the function itself
+    ///   fails.
+    pub fn call(&mut self, callee: &Value, args: Vec<Value>, loc: &Loc) -> EvalResult {
+        let (func, args) = match callee {
+            Value::Func(func) => (Rc::clone(func), args),
+            Value::Method(object, method) => {
+                let this = Value::Object(object.clone());
+                (Rc::clone(method), [vec![this], args].concat())
+            }
+            Value::Class(class) => {
+                // Defaults may make objects too, so they count as calls
+                self.check_depth()?;
+                return self.traced(class.name, loc, |interp| interp.construct(class, &args));
+            }
+            Value::Builtin(name) => match self.natives.get(name) {
+                Some(native) => return native.call(args),
+                None => return Interpreter::call_builtin(name.as_str(), &args),
+            },
+            value => return super::exc!("{} is not callable", (value.typ())),
+        };
+
+        // Compiled code isn't counted against the budget
+        #[cfg(feature = "jit")]
+        if let Some(jit) = self.jit.as_ref().filter(|_| self.budget.is_none()) {
+            let resolve = |name| match self.scopes[0].get(&name) {
+                Some(Binding {
+                    value: Value::Func(func),
+                    ..
+                }) => Some(Rc::clone(func)),
+                _ => None,
+            };
+
+            let depth = self.max_depth.saturating_sub(self.calls.len());
+
+            if let Some(value) = jit.borrow_mut().call(&func, &args, depth, &resolve) {
+                return Ok(value);
+            }
+        }
+
+        self.check_depth()?;
+        let bindings = Interpreter::bind_args(func.name, &func.params, args)?;
+        self.traced(func.name, loc, |interp| interp.call_func(&func, bindings))
+    }
+
+    /// Checks that another call wouldn't nest calls too deeply, or run
+    /// out of native stack.
+    fn check_depth(&mut self) -> Result<(), String> {
+        if self.calls.len() >= self.max_depth {
+            return traceback::overflow(self.max_depth);
+        }
+
+        if self.calls.is_empty() {
+            self.stack_base = traceback::stack_pointer();
+        } else if traceback::stack_pointer().abs_diff(self.stack_base) > self.stack_limit {
+            return traceback::out_of_stack(self.calls.len());
+        }
+
+        Ok(())
+    }
+
+    /// Runs a call made from `loc`, recording the traceback if it fails.
+    fn traced(
+        &mut self,
+        name: Symbol,
+        loc: &Loc,
+        call: impl FnOnce(&mut Self) -> EvalResult,
+    ) -> EvalResult {
+        self.calls.push(CallSite {
+            name,
+            loc: loc.clone(),
+        });
+
+        let result = call(self);
+
+        // The innermost call sees the error first, where it failed
+        if result.is_err() && self.traceback.is_empty() {
+            self.traceback.clone_from(&self.calls);
+            self.traceback.push(CallSite {
+                name,
+                loc: self.loc.clone(),
+            });
+        }
+
+        self.calls.pop();
+        result
+    }
+
+    fn call_func(&mut self, func: &FuncDecl, bindings: Vec<Binding>) -> EvalResult {
+        self.loc = func.loc.clone();
+
+        for binding in &bindings {
+            self.alloc(&binding.value);
+        }
+
+        let scope = func.params.iter().map(|p| p.name).zip(bindings).collect();
+
+        let frame = self.frame;
+        self.scopes.push(scope);
+        self.frame = self.scopes.len() - 1;
+
+        let flow = self.exec_block(&func.body);
+
+        self.pop_scope();
+        self.frame = frame;
+
+        let value = if let Flow::Return(value) = flow? {
+            value
+        } else {
+            self.loc = func.loc.clone();
+            Value::Null
+        };
+
+        Interpreter::check_return(func.name, &func.ret, value)
+    }
+
+    /// Binds the arguments of a call to the parameters of the function
+    /// being called, in order.
+    ///
+    /// # Errors
+    /// - If the number or types of the arguments don't match the
+    ///   parameters.
+    pub fn bind_args(
+        name: Symbol,
+        params: &[Param],
+        args: Vec<Value>,
+    ) -> Result<Vec<Binding>, String> {
+        if args.len() != params.len() {
+            return super::exc!(
+                "'{}' takes {} argument(s), but {} were given",
+                name,
+                (params.len()),
+                (args.len())
+            );
+        }
+
+        let mut bindings = Vec::with_capacity(args.len());
+
+        for (param, arg) in params.iter().zip(args) {
+            if !param.typ.accepts(&arg.typ()) {
+                return super::exc!(
+                    "'{}' expected {} for '{}', but got {}",
+                    name,
+                    (param.typ),
+                    (param.name),
+                    (arg.typ())
+                );
+            }
+
+            bindings.push(Binding {
+                value: arg.coerce(&param.typ),
+                typ: param.typ.clone(),
+                constant: false,
+            });
+        }
+
+        Ok(bindings)
+    }
+
+    /// Checks the value a function returned against its return type.
+    ///
+    /// # Errors
+    /// - If the value isn't of the return type.
+    pub fn check_return(name: Symbol, ret: &VarType, value: Value) -> EvalResult {
+        if !ret.accepts(&value.typ()) {
+            return super::exc!(
+                "'{}' should return {}, but returned {}",
+                name,
+                ret,
+                (value.typ())
+            );
+        }
+
+        Ok(value.coerce(ret))
+    }
+
+    /// Indexes an array, mapping or string.
+    ///
+    /// # Errors
+    /// - If the value can't be indexed, or the index is out of range.
+    pub fn index(mut base: Value, index: &Value) -> EvalResult {
+        if let (Value::String(s), Value::Int(i)) = (&base, index) {
+            return match usize::try_from(*i).ok().and_then(|i| s.chars().nth(i)) {
+                Some(c) => Ok(Value::String(c.to_string())),
+                None => super::exc!("Index {} out of range for string", i),
+            };
+        }
+
+        Ok(Interpreter::index_mut(&mut base, index, false)?.clone())
+    }
+
+    /// The result of `len`, for a length no value can get near
+    /// `i64::MAX` of.
+    fn len(len: usize) -> Value {
+        Value::Int(i64::try_from(len).unwrap_or(i64::MAX))
+    }
+
+    /// Calls a builtin function.
+    ///
+    /// # Errors
+    /// - If the builtin is unknown or given the wrong arguments.
+    pub fn call_builtin(name: &str, args: &[Value]) -> EvalResult {
+        match (name, args) {
+            ("print", _) => {
+                let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+                println!("{}", args.join(" "));
+                Ok(Value::Null)
+            }
+            ("len", [Value::String(s)]) => Ok(Interpreter::len(s.chars().count())),
+            ("len", [Value::Array(items)]) => Ok(Interpreter::len(items.len())),
+            ("len", [Value::Mapping(pairs)]) => Ok(Interpreter::len(pairs.len())),
+            ("str", [value]) => Ok(Value::String(value.to_string())),
+            _ => super::exc!("Invalid arguments for builtin '{}'", name),
+        }
+    }
+
+    /// Applies a unary operator.
+    ///
+    /// # Errors
+    /// - If the operator doesn't apply to the value.
+    pub fn unary(op: &Operator, value: Value) -> EvalResult {
+        match (op, value) {
+            (Operator::Subtract, Value::Int(num)) => match num.checked_neg() {
+                Some(num) => Ok(Value::Int(num)),
+                None => super::exc!("Integer overflow negating {}", num),
+            },
+            (Operator::Subtract, Value::Float(num)) => Ok(Value::Float(-num)),
+            (Operator::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+            (op, value) => super::exc!("Unsupported operand type for {}: {}", op, (value.typ())),
+        }
+    }
+
+    /// Applies a binary operator.
+    ///
+    /// # Errors
+    /// - If the operator doesn't apply to the values, integer
+    ///   arithmetic overflows, or an int is divided by zero.
+    pub fn binary(op: &Operator, left: Value, right: Value) -> EvalResult {
+        match (op, left, right) {
+            (op, Value::Int(l), Value::Int(r)) => {
+                let result = match op {
+                    Operator::Add => l.checked_add(r),
+                    Operator::Subtract => l.checked_sub(r),
+                    Operator::Mult => l.checked_mul(r),
+                    Operator::Div if r == 0 => return super::exc!("Division by zero"),
+                    Operator::Div => l.checked_div(r),
+                    Operator::Pow if r < 0 => {
+                        let l = Value::Int(l).coerce(&VarType::Float);
+                        return Interpreter::binary(op, l, Value::Int(r));
+                    }
+                    Operator::Pow => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+                    _ => return super::exc!("Unsupported operator {} for int", op),
+                };
+
+                match result {
+                    Some(num) => Ok(Value::Int(num)),
+                    None => super::exc!("Integer overflow in {} {} {}", l, op, r),
+                }
+            }
+            (Operator::Add, Value::String(l), Value::String(r)) => Ok(Value::String(l + &r)),
+            (Operator::Add, Value::Array(mut l), Value::Array(r)) => {
+                l.extend(r);
+                Ok(Value::Array(l))
+            }
+            (op, left, right) => match (left.as_float(), right.as_float()) {
+                (Some(l), Some(r)) => match op {
+                    Operator::Add => Ok(Value::Float(l + r)),
+                    Operator::Subtract => Ok(Value::Float(l - r)),
+                    Operator::Mult => Ok(Value::Float(l * r)),
+                    Operator::Div => Ok(Value::Float(l / r)),
+                    Operator::Pow => Ok(Value::Float(l.powf(r))),
+                    _ => super::exc!("Unsupported operator {} for float", op),
+                },
+                _ => super::exc!(
+                    "Unsupported operand types for {}: {} and {}",
+                    op,
+                    (left.typ()),
+                    (right.typ())
+                ),
+            },
+        }
+    }
+
+    /// Compares two values.
+    ///
+    /// # Errors
+    /// - If the values can't be ordered.
+    pub fn compare(cond: &Condition, left: &Value, right: &Value) -> EvalResult {
+        let ordering = match (left, right) {
+            (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+            _ => match (left.as_float(), right.as_float()) {
+                (Some(l), Some(r)) => l.partial_cmp(&r),
+                _ => None,
+            },
+        };
+
+        let result = match (cond, ordering) {
+            (Condition::EqEq, Some(ordering)) => ordering.is_eq(),
+            (Condition::Ne, Some(ordering)) => ordering.is_ne(),
+            (Condition::EqEq, None) => left == right,
+            (Condition::Ne, None) => left != right,
+            (Condition::Lt, Some(ordering)) => ordering.is_lt(),
+            (Condition::Gt, Some(ordering)) => ordering.is_gt(),
+            (Condition::Lte, Some(ordering)) => ordering.is_le(),
+            (Condition::Gte, Some(ordering)) => ordering.is_ge(),
+            (cond, None) => {
+                return super::exc!(
+                    "Can't compare {} and {} with {}",
+                    (left.typ()),
+                    (right.typ()),
+                    cond
+                )
+            }
+        };
+
+        Ok(Value::Bool(result))
+    }
+}
@@ -0,0 +1,937 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::Value as IrValue;
+use cranelift_codegen::ir::{types, AbiParam, Block, InstBuilder, MemFlags, Signature, Type};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::Context;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{FuncId, Linkage, Module};
+
+use super::Condition;
+use super::Expr;
+use super::FuncDecl;
+use super::Operator;
+use super::Stmt;
+use super::Symbol;
+use super::Value;
+use super::VarType;
+
+type JitResult<T> = Result<T, String>;
+
+/// How Rust calls a compiled function: with its arguments as 64 bit
+/// slots, how many more calls may be nested and a flag the code sets if
+/// it fails, returning the result as a slot.
+type Entry = unsafe extern "C" fn(*const u64, i64, *mut u8) -> u64;
+
+/// Identifies a function declaration across the copies the interpreter
+/// makes of it each time it is hoisted.
+type Key = (Symbol, usize, usize);
+
+/// A function declaration and what became of compiling it.
+struct Compiled {
+    decl: Rc<FuncDecl>,
+    /// The function's id in the module, or `None` if it couldn't be
+    /// compiled.
+    id: Option<FuncId>,
+    entry: Option<Entry>,
+    /// The globals the function calls, directly or not, and the
+    /// declarations they referred to when it was compiled.
+    deps: Rc<Vec<(Symbol, Rc<FuncDecl>)>>,
+}
+
+/// Compiles functions to native code with Cranelift, for the
+/// [`super::Interpreter`] to call instead of interpreting them.
+///
+/// Only functions that take and return ints, floats and bools, and
+/// only use local variables, arithmetic, comparisons and calls to other
+/// such functions, can be compiled. Those can't have side effects, so
+/// whenever compiled code would fail, like on integer overflow, the
+/// call is simply interpreted instead, which gives the interpreter's
+/// result or error.
+pub struct Jit {
+    module: JITModule,
+    ctx: Context,
+    builder_ctx: FunctionBuilderContext,
+    funcs: HashMap<Key, Compiled>,
+    /// The functions compiled code calls for `**`.
+    ipow: FuncId,
+    powf: FuncId,
+}
+
+impl std::fmt::Debug for Jit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let compiled = self.funcs.values().filter(|c| c.entry.is_some()).count();
+        write!(f, "Jit {{ compiled: {compiled} }}")
+    }
+}
+
+/// The functions a compiled function calls, as found while compiling.
+struct Batch {
+    /// The functions that still need to be compiled.
+    pending: Vec<(FuncId, Rc<FuncDecl>)>,
+    /// The functions compiled in this batch.
+    ids: HashMap<Key, (FuncId, Rc<FuncDecl>)>,
+    deps: Vec<(Symbol, Rc<FuncDecl>)>,
+}
+
+impl Jit {
+    /// Creates a JIT for the machine the program runs on.
+    ///
+    /// # Errors
+    /// - If Cranelift doesn't support the machine.
+    pub fn new() -> JitResult<Self> {
+        let mut flags = settings::builder();
+        let error = |e: &dyn std::fmt::Display| format!("Can't create the JIT: {e}");
+
+        for (flag, value) in [("opt_level", "speed"), ("is_pic", "false")] {
+            flags.set(flag, value).map_err(|e| error(&e))?;
+        }
+
+        let isa = cranelift_native::builder()
+            .map_err(|e| error(&e))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| error(&e))?;
+
+        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        builder.symbol("why_ipow", ipow as *const u8);
+        builder.symbol("why_powf", powf as *const u8);
+
+        let mut module = JITModule::new(builder);
+        let ptr = module.target_config().pointer_type();
+
+        let mut sig = module.make_signature();
+        sig.params
+            .extend([types::I64, types::I64, ptr].map(AbiParam::new));
+        sig.returns.push(AbiParam::new(types::I64));
+        let ipow = module
+            .declare_function("why_ipow", Linkage::Import, &sig)
+            .map_err(|e| error(&e))?;
+
+        let mut sig = module.make_signature();
+        sig.params
+            .extend([types::F64, types::F64].map(AbiParam::new));
+        sig.returns.push(AbiParam::new(types::F64));
+        let powf = module
+            .declare_function("why_powf", Linkage::Import, &sig)
+            .map_err(|e| error(&e))?;
+
+        Ok(Self {
+            ctx: module.make_context(),
+            module,
+            builder_ctx: FunctionBuilderContext::new(),
+            funcs: HashMap::new(),
+            ipow,
+            powf,
+        })
+    }
+
+    /// Returns true if a function has been compiled to native code.
+    #[must_use]
+    pub fn is_compiled(&self, func: &FuncDecl) -> bool {
+        self.funcs
+            .get(&key(func))
+            .is_some_and(|c| c.entry.is_some() && *c.decl == *func)
+    }
+
+    /// Calls a function with native code, compiling it first if it
+    /// hasn't been. The globals it calls are looked up with `resolve`,
+    /// and calls may be nested `depth` deep.
+    ///
+    /// # Returns
+    /// - [`Option<Value>`] - The function's result, or `None` if it
+    ///   has to be interpreted instead. That is when it can't be
+    ///   compiled, the arguments don't fit its params, the functions it
+    ///   calls have been redeclared since it was compiled, or it failed,
+    ///   which includes nesting calls too deep.
+    pub fn call(
+        &mut self,
+        func: &Rc<FuncDecl>,
+        args: &[Value],
+        depth: usize,
+        resolve: &dyn Fn(Symbol) -> Option<Rc<FuncDecl>>,
+    ) -> Option<Value> {
+        let current = self
+            .funcs
+            .get_mut(&key(func))
+            .filter(|c| same(&c.decl, func));
+
+        match current {
+            // Keep the latest copy, so the next call is quicker to match
+            Some(compiled) => compiled.decl = Rc::clone(func),
+            None => self.compile(func, resolve),
+        }
+
+        let compiled = &self.funcs[&key(func)];
+        let entry = compiled.entry?;
+
+        for (name, decl) in compiled.deps.iter() {
+            if !resolve(*name).is_some_and(|d| same(&d, decl)) {
+                return None;
+            }
+        }
+
+        if args.len() != func.params.len() {
+            return None;
+        }
+
+        let mut slots = Vec::with_capacity(args.len());
+
+        for (param, arg) in func.params.iter().zip(args) {
+            #[allow(clippy::cast_precision_loss, clippy::cast_sign_loss)]
+            slots.push(match (&param.typ, arg) {
+                (VarType::Int, Value::Int(num)) => *num as u64,
+                (VarType::Float, Value::Int(num)) => (*num as f64).to_bits(),
+                (VarType::Float, Value::Float(num)) => num.to_bits(),
+                (VarType::Bool, Value::Bool(b)) => u64::from(*b),
+                _ => return None,
+            });
+        }
+
+        let depth = i64::try_from(depth).unwrap_or(i64::MAX);
+        let mut failed = 0;
+        // SAFETY: The entry was compiled for this function, which takes
+        // one slot per param, and the module that owns its code lives as
+        // long as `self`
+        let result = unsafe { entry(slots.as_ptr(), depth, &raw mut failed) };
+
+        if failed != 0 {
+            return None;
+        }
+
+        #[allow(clippy::cast_possible_wrap)]
+        match func.ret {
+            VarType::Int => Some(Value::Int(result as i64)),
+            VarType::Float => Some(Value::Float(f64::from_bits(result))),
+            VarType::Bool => Some(Value::Bool(result != 0)),
+            _ => None,
+        }
+    }
+
+    /// Compiles a function and every function it calls that hasn't been
+    /// compiled yet, recording whether that worked.
+    fn compile(&mut self, func: &Rc<FuncDecl>, resolve: &dyn Fn(Symbol) -> Option<Rc<FuncDecl>>) {
+        let mut batch = Batch {
+            pending: Vec::new(),
+            ids: HashMap::new(),
+            deps: Vec::new(),
+        };
+
+        let result = declare(&mut self.module, func, &mut batch)
+            .and_then(|_| self.compile_batch(&mut batch, resolve));
+
+        if let Ok(entries) = result {
+            let deps = Rc::new(batch.deps);
+
+            for (key, (id, decl)) in batch.ids {
+                let compiled = Compiled {
+                    decl,
+                    id: Some(id),
+                    entry: Some(entries[&id]),
+                    deps: Rc::clone(&deps),
+                };
+
+                self.funcs.insert(key, compiled);
+            }
+        } else {
+            let compiled = Compiled {
+                decl: Rc::clone(func),
+                id: None,
+                entry: None,
+                deps: Rc::default(),
+            };
+
+            self.funcs.insert(key(func), compiled);
+        }
+    }
+
+    /// Compiles the pending functions of a batch, returning the entry of
+    /// each.
+    fn compile_batch(
+        &mut self,
+        batch: &mut Batch,
+        resolve: &dyn Fn(Symbol) -> Option<Rc<FuncDecl>>,
+    ) -> JitResult<HashMap<FuncId, Entry>> {
+        let mut functions = Vec::new();
+
+        while let Some((id, func)) = batch.pending.pop() {
+            self.module.clear_context(&mut self.ctx);
+            self.ctx.func.signature = signature(&self.module, &func)?;
+
+            let mut translator = Translator {
+                builder: FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_ctx),
+                module: &mut self.module,
+                funcs: &self.funcs,
+                batch,
+                resolve,
+                ipow: self.ipow,
+                powf: self.powf,
+                ret: func.ret.clone(),
+                scopes: Vec::new(),
+                vars: 0,
+                loops: Vec::new(),
+                failed: None,
+                depth: None,
+            };
+
+            if let Err(e) = translator.translate(&func) {
+                // The builder is left mid function, so start over
+                self.builder_ctx = FunctionBuilderContext::new();
+                return Err(e);
+            }
+
+            translator.builder.finalize();
+            functions.push((id, self.ctx.func.clone()));
+        }
+
+        let mut trampolines = Vec::new();
+
+        for (id, function) in functions {
+            self.ctx.clear();
+            self.ctx.func = function;
+            self.module
+                .define_function(id, &mut self.ctx)
+                .map_err(|e| e.to_string())?;
+
+            trampolines.push((id, self.trampoline(id)?));
+        }
+
+        self.module
+            .finalize_definitions()
+            .map_err(|e| e.to_string())?;
+
+        let entries = trampolines.into_iter().map(|(id, trampoline)| {
+            let code = self.module.get_finalized_function(trampoline);
+            // SAFETY: The trampoline was compiled with the `Entry`
+            // signature
+            (id, unsafe { std::mem::transmute::<*const u8, Entry>(code) })
+        });
+
+        Ok(entries.collect())
+    }
+
+    /// Compiles a function that calls the given one with the arguments
+    /// in an array of slots, for Rust to call through an [`Entry`].
+    fn trampoline(&mut self, id: FuncId) -> JitResult<FuncId> {
+        let ptr = self.module.target_config().pointer_type();
+        let callee = self
+            .module
+            .declarations()
+            .get_function_decl(id)
+            .signature
+            .clone();
+
+        self.module.clear_context(&mut self.ctx);
+        self.ctx.func.signature.params.extend([
+            AbiParam::new(ptr),
+            AbiParam::new(types::I64),
+            AbiParam::new(ptr),
+        ]);
+        self.ctx
+            .func
+            .signature
+            .returns
+            .push(AbiParam::new(types::I64));
+
+        let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_ctx);
+        let block = builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+
+        let (slots, depth, failed) = (
+            builder.block_params(block)[0],
+            builder.block_params(block)[1],
+            builder.block_params(block)[2],
+        );
+        let mut args = Vec::new();
+
+        for (i, param) in (0..).zip(&callee.params[..callee.params.len() - 2]) {
+            let slot = builder
+                .ins()
+                .load(types::I64, MemFlags::trusted(), slots, i * 8);
+
+            args.push(match param.value_type {
+                types::F64 => builder.ins().bitcast(types::F64, MemFlags::new(), slot),
+                types::I8 => builder.ins().ireduce(types::I8, slot),
+                _ => slot,
+            });
+        }
+
+        args.extend([depth, failed]);
+
+        let func_ref = self.module.declare_func_in_func(id, builder.func);
+        let call = builder.ins().call(func_ref, &args);
+        let result = builder.inst_results(call)[0];
+
+        let result = match callee.returns[0].value_type {
+            types::F64 => builder.ins().bitcast(types::I64, MemFlags::new(), result),
+            types::I8 => builder.ins().uextend(types::I64, result),
+            _ => result,
+        };
+
+        builder.ins().return_(&[result]);
+        builder.finalize();
+
+        let trampoline = self
+            .module
+            .declare_anonymous_function(&self.ctx.func.signature)
+            .map_err(|e| e.to_string())?;
+        self.module
+            .define_function(trampoline, &mut self.ctx)
+            .map_err(|e| e.to_string())?;
+
+        Ok(trampoline)
+    }
+}
+
+/// Translates the body of one function to Cranelift IR.
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    module: &'a mut JITModule,
+    funcs: &'a HashMap<Key, Compiled>,
+    batch: &'a mut Batch,
+    resolve: &'a dyn Fn(Symbol) -> Option<Rc<FuncDecl>>,
+    ipow: FuncId,
+    powf: FuncId,
+    ret: VarType,
+    /// The block scopes, innermost last, with the variables declared in
+    /// them and whether they are constant.
+    scopes: Vec<Vec<(Symbol, Variable, VarType, bool)>>,
+    vars: u32,
+    /// The block each enclosing loop breaks to, innermost last.
+    loops: Vec<Block>,
+    /// The flag that is set when the function fails.
+    failed: Option<IrValue>,
+    /// How many more calls may be nested, counting this one.
+    depth: Option<IrValue>,
+}
+
+impl<'a> Translator<'a> {
+    fn ins(&mut self) -> cranelift_frontend::FuncInstBuilder<'_, 'a> {
+        self.builder.ins()
+    }
+
+    fn translate(&mut self, func: &FuncDecl) -> JitResult<()> {
+        let entry = self.builder.create_block();
+        self.builder.append_block_params_for_function_params(entry);
+        self.builder.switch_to_block(entry);
+        self.builder.seal_block(entry);
+
+        let params = self.builder.block_params(entry).to_vec();
+        let depth = params[params.len() - 2];
+        self.failed = params.last().copied();
+        self.depth = Some(depth);
+        self.scopes.push(Vec::new());
+
+        // Nesting calls too deep fails, for the interpreter to report
+        let exhausted = self.ins().icmp_imm(IntCC::SignedLessThanOrEqual, depth, 0);
+        self.fail_if(exhausted);
+
+        for (param, value) in func.params.iter().zip(params) {
+            let var = self.var(&param.typ)?;
+            self.builder.def_var(var, value);
+            self.scopes[0].push((param.name, var, param.typ.clone(), false));
+        }
+
+        self.block(&func.body)?;
+
+        // Running off the end returns nothing, which isn't a value of
+        // the return type
+        self.fail();
+        Ok(())
+    }
+
+    fn var(&mut self, typ: &VarType) -> JitResult<Variable> {
+        let var = Variable::from_u32(self.vars);
+        self.vars += 1;
+        self.builder.declare_var(var, ir_type(typ)?);
+        Ok(var)
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<&(Symbol, Variable, VarType, bool)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|(n, ..)| *n == name))
+    }
+
+    /// Continues in a new block after a jump or return, as the code that
+    /// follows can't be reached.
+    fn unreachable(&mut self) {
+        let block = self.builder.create_block();
+        self.builder.switch_to_block(block);
+        self.builder.seal_block(block);
+    }
+
+    /// Sets the flag that the function failed and returns.
+    fn fail(&mut self) {
+        let one = self.builder.ins().iconst(types::I8, 1);
+        let failed = self.failed.unwrap();
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), one, failed, 0);
+
+        let zero = match self.ret {
+            VarType::Float => self.builder.ins().f64const(0.0),
+            VarType::Bool => self.builder.ins().iconst(types::I8, 0),
+            _ => self.builder.ins().iconst(types::I64, 0),
+        };
+
+        self.builder.ins().return_(&[zero]);
+    }
+
+    /// Fails if a condition is true.
+    fn fail_if(&mut self, cond: IrValue) {
+        let fail = self.builder.create_block();
+        let next = self.builder.create_block();
+
+        self.builder.ins().brif(cond, fail, &[], next, &[]);
+        self.builder.switch_to_block(fail);
+        self.builder.seal_block(fail);
+        self.fail();
+        self.builder.switch_to_block(next);
+        self.builder.seal_block(next);
+    }
+
+    /// Fails if a function that was called failed.
+    fn check_call(&mut self) {
+        let failed = self.failed.unwrap();
+        let flag = self
+            .builder
+            .ins()
+            .load(types::I8, MemFlags::trusted(), failed, 0);
+        self.fail_if(flag);
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> JitResult<()> {
+        self.scopes.push(Vec::new());
+        let result = stmts.iter().try_for_each(|stmt| self.stmt(stmt));
+        self.scopes.pop();
+        result
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> JitResult<()> {
+        match stmt {
+            Stmt::Expr(expr, _) => {
+                self.expr(expr)?;
+            }
+            Stmt::VarDecl(decl) => {
+                let (value, typ) = self.expr(&decl.value)?;
+                let declared = decl.typ.clone().unwrap_or_else(|| typ.clone());
+
+                if !declared.accepts(&typ) {
+                    return super::exc!("Can't assign {} to '{}'", typ, (decl.name));
+                }
+
+                let value = self.coerce(value, &typ, &declared);
+                let var = self.var(&declared)?;
+                let constant = decl.keyword == Some(super::Keyword::Const);
+
+                self.builder.def_var(var, value);
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .push((decl.name, var, declared, constant));
+            }
+            Stmt::Assign(op, Expr::Ident(name), value, _) => {
+                let (var, typ) = match self.lookup(*name) {
+                    Some((_, var, typ, false)) => (*var, typ.clone()),
+                    _ => return super::exc!("Can't assign to '{}'", name),
+                };
+
+                let (mut value, mut value_typ) = self.expr(value)?;
+
+                let binary = match op {
+                    Operator::Assign => None,
+                    Operator::IncrementBy | Operator::Increment => Some(Operator::Add),
+                    Operator::DecrementBy | Operator::Decrement => Some(Operator::Subtract),
+                    Operator::MultBy => Some(Operator::Mult),
+                    Operator::DivBy => Some(Operator::Div),
+                    _ => return super::exc!("Invalid assignment operator {}", op),
+                };
+
+                if let Some(binary) = binary {
+                    let current = self.builder.use_var(var);
+                    (value, value_typ) =
+                        self.binary(binary, (current, &typ), (value, &value_typ))?;
+                }
+
+                if !typ.accepts(&value_typ) {
+                    return super::exc!("Can't assign {} to '{}'", value_typ, name);
+                }
+
+                let value = self.coerce(value, &value_typ, &typ);
+                self.builder.def_var(var, value);
+            }
+            Stmt::Return(Some(value), _) => {
+                let (value, typ) = self.expr(value)?;
+
+                if !self.ret.accepts(&typ) {
+                    return super::exc!("Returned {} instead of {}", typ, (self.ret));
+                }
+
+                let ret = self.ret.clone();
+                let value = self.coerce(value, &typ, &ret);
+                self.builder.ins().return_(&[value]);
+                self.unreachable();
+            }
+            Stmt::If(cond, body, otherwise, _) => {
+                let cond = self.cond(cond)?;
+                let then = self.builder.create_block();
+                let other = self.builder.create_block();
+                let next = self.builder.create_block();
+
+                self.builder.ins().brif(cond, then, &[], other, &[]);
+
+                self.builder.switch_to_block(then);
+                self.builder.seal_block(then);
+                self.block(body)?;
+                self.builder.ins().jump(next, &[]);
+
+                self.builder.switch_to_block(other);
+                self.builder.seal_block(other);
+                self.block(otherwise.as_deref().unwrap_or_default())?;
+                self.builder.ins().jump(next, &[]);
+
+                self.builder.switch_to_block(next);
+                self.builder.seal_block(next);
+            }
+            Stmt::While(cond, body, _) => {
+                let header = self.builder.create_block();
+                let inner = self.builder.create_block();
+                let next = self.builder.create_block();
+
+                self.builder.ins().jump(header, &[]);
+                self.builder.switch_to_block(header);
+
+                let cond = self.cond(cond)?;
+                self.builder.ins().brif(cond, inner, &[], next, &[]);
+
+                self.builder.switch_to_block(inner);
+                self.builder.seal_block(inner);
+                self.loops.push(next);
+                let result = self.block(body);
+                self.loops.pop();
+                result?;
+                self.builder.ins().jump(header, &[]);
+                self.builder.seal_block(header);
+
+                self.builder.switch_to_block(next);
+                self.builder.seal_block(next);
+            }
+            Stmt::Break => {
+                let next = *self.loops.last().ok_or("'break' outside of a loop")?;
+                self.builder.ins().jump(next, &[]);
+                self.unreachable();
+            }
+            Stmt::Comment(..) => (),
+            _ => return super::exc!("Only numeric code can be compiled"),
+        }
+
+        Ok(())
+    }
+
+    fn cond(&mut self, cond: &Expr) -> JitResult<IrValue> {
+        match self.expr(cond)? {
+            (value, VarType::Bool) => Ok(value),
+            (_, typ) => super::exc!("Condition must be a bool, but got {}", typ),
+        }
+    }
+
+    /// Converts a value so it can be stored as the given type, widening
+    /// ints to floats.
+    fn coerce(&mut self, value: IrValue, from: &VarType, to: &VarType) -> IrValue {
+        match (from, to) {
+            (VarType::Int, VarType::Float) => self.builder.ins().fcvt_from_sint(types::F64, value),
+            _ => value,
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> JitResult<(IrValue, VarType)> {
+        let result = match expr {
+            Expr::Int(num) => (self.ins().iconst(types::I64, *num), VarType::Int),
+            Expr::Float(num) => (self.ins().f64const(*num), VarType::Float),
+            Expr::Bool(b) => (self.ins().iconst(types::I8, i64::from(*b)), VarType::Bool),
+            Expr::Parenthesized(inner) => self.expr(inner)?,
+            Expr::Ident(name) => match self.lookup(*name) {
+                Some((_, var, typ, _)) => {
+                    let (var, typ) = (*var, typ.clone());
+                    (self.builder.use_var(var), typ)
+                }
+                None => return super::exc!("Only locals can be read"),
+            },
+            Expr::UnaryOp(op, inner) => match (op, self.expr(inner)?) {
+                (Operator::Subtract, (value, VarType::Int)) => {
+                    let min = self.ins().icmp_imm(IntCC::Equal, value, i64::MIN);
+                    self.fail_if(min);
+                    (self.ins().ineg(value), VarType::Int)
+                }
+                (Operator::Subtract, (value, VarType::Float)) => {
+                    (self.ins().fneg(value), VarType::Float)
+                }
+                (Operator::Not, (value, VarType::Bool)) => {
+                    (self.ins().bxor_imm(value, 1), VarType::Bool)
+                }
+                _ => return super::exc!("Unsupported unary operator {}", op),
+            },
+            Expr::BinaryOp(op, left, right) => {
+                let (left, left_typ) = self.expr(left)?;
+                let (right, right_typ) = self.expr(right)?;
+
+                self.binary(*op, (left, &left_typ), (right, &right_typ))?
+            }
+            Expr::Compare(cond, left, right) => {
+                let (left, left_typ) = self.expr(left)?;
+                let (right, right_typ) = self.expr(right)?;
+
+                self.compare(*cond, (left, &left_typ), (right, &right_typ))?
+            }
+            Expr::Call(callee, args, _) => {
+                let Expr::Ident(name) = &**callee else {
+                    return super::exc!("Only functions can be called by name");
+                };
+
+                if self.lookup(*name).is_some() {
+                    return super::exc!("Locals can't be called");
+                }
+
+                self.call(*name, args)?
+            }
+            _ => return super::exc!("Only numeric code can be compiled"),
+        };
+
+        Ok(result)
+    }
+
+    /// Calls a global function, declaring it to be compiled in the same
+    /// batch if it hasn't been compiled yet.
+    fn call(&mut self, name: Symbol, args: &[Expr]) -> JitResult<(IrValue, VarType)> {
+        let callee = (self.resolve)(name).ok_or("Only functions can be called")?;
+        self.batch.deps.push((name, Rc::clone(&callee)));
+
+        let id = match self.funcs.get(&key(&callee)) {
+            Some(compiled) if same(&compiled.decl, &callee) => {
+                self.batch.deps.extend(compiled.deps.iter().cloned());
+                compiled.id.ok_or("The function can't be compiled")?
+            }
+            _ => match self.batch.ids.get(&key(&callee)) {
+                Some((id, decl)) if same(decl, &callee) => *id,
+                _ => declare(self.module, &callee, self.batch)?,
+            },
+        };
+
+        if args.len() != callee.params.len() {
+            return super::exc!("'{}' was given the wrong number of arguments", name);
+        }
+
+        let mut values = Vec::with_capacity(args.len() + 1);
+
+        for (param, arg) in callee.params.iter().zip(args) {
+            let (value, typ) = self.expr(arg)?;
+
+            if !param.typ.accepts(&typ) {
+                return super::exc!("'{}' was given {} for '{}'", name, typ, (param.name));
+            }
+
+            values.push(self.coerce(value, &typ, &param.typ));
+        }
+
+        let depth = self.depth.unwrap();
+        let depth = self.ins().iadd_imm(depth, -1);
+        values.extend([depth, self.failed.unwrap()]);
+
+        let func_ref = self.module.declare_func_in_func(id, self.builder.func);
+        let call = self.builder.ins().call(func_ref, &values);
+        let result = self.builder.inst_results(call)[0];
+
+        self.check_call();
+        Ok((result, callee.ret.clone()))
+    }
+
+    /// Calls one of the functions compiled code raises to a power with.
+    fn pow(&mut self, func: FuncId, args: &[IrValue]) -> IrValue {
+        let func_ref = self.module.declare_func_in_func(func, self.builder.func);
+        let call = self.builder.ins().call(func_ref, args);
+        self.builder.inst_results(call)[0]
+    }
+
+    /// Applies a binary operator, failing where the interpreter would.
+    fn binary(
+        &mut self,
+        op: Operator,
+        (left, left_typ): (IrValue, &VarType),
+        (right, right_typ): (IrValue, &VarType),
+    ) -> JitResult<(IrValue, VarType)> {
+        if (left_typ, right_typ) == (&VarType::Int, &VarType::Int) {
+            let value = match op {
+                Operator::Add | Operator::Subtract | Operator::Mult => {
+                    let (value, overflow) = match op {
+                        Operator::Add => self.ins().sadd_overflow(left, right),
+                        Operator::Subtract => self.ins().ssub_overflow(left, right),
+                        _ => self.ins().smul_overflow(left, right),
+                    };
+
+                    self.fail_if(overflow);
+                    value
+                }
+                Operator::Div => {
+                    let zero = self.ins().icmp_imm(IntCC::Equal, right, 0);
+                    self.fail_if(zero);
+
+                    let min = self.ins().icmp_imm(IntCC::Equal, left, i64::MIN);
+                    let minus_one = self.ins().icmp_imm(IntCC::Equal, right, -1);
+                    let overflow = self.ins().band(min, minus_one);
+                    self.fail_if(overflow);
+
+                    self.ins().sdiv(left, right)
+                }
+                Operator::Pow => {
+                    let failed = self.failed.unwrap();
+                    let value = self.pow(self.ipow, &[left, right, failed]);
+                    self.check_call();
+                    value
+                }
+                _ => return super::exc!("Unsupported operator {} for int", op),
+            };
+
+            return Ok((value, VarType::Int));
+        }
+
+        let numeric = |t: &VarType| matches!(t, VarType::Int | VarType::Float);
+
+        if !numeric(left_typ) || !numeric(right_typ) {
+            return super::exc!("Unsupported operand types for {}", op);
+        }
+
+        let left = self.coerce(left, left_typ, &VarType::Float);
+        let right = self.coerce(right, right_typ, &VarType::Float);
+
+        let value = match op {
+            Operator::Add => self.ins().fadd(left, right),
+            Operator::Subtract => self.ins().fsub(left, right),
+            Operator::Mult => self.ins().fmul(left, right),
+            Operator::Div => self.ins().fdiv(left, right),
+            Operator::Pow => self.pow(self.powf, &[left, right]),
+            _ => return super::exc!("Unsupported operator {} for float", op),
+        };
+
+        Ok((value, VarType::Float))
+    }
+
+    /// Compares two values. Numbers are compared as floats, like the
+    /// interpreter compares them, failing if they can't be ordered.
+    fn compare(
+        &mut self,
+        cond: Condition,
+        (left, left_typ): (IrValue, &VarType),
+        (right, right_typ): (IrValue, &VarType),
+    ) -> JitResult<(IrValue, VarType)> {
+        let numeric = |t: &VarType| matches!(t, VarType::Int | VarType::Float);
+
+        let value = match (left_typ, right_typ) {
+            (l, r) if numeric(l) && numeric(r) => {
+                let left = self.coerce(left, l, &VarType::Float);
+                let right = self.coerce(right, r, &VarType::Float);
+
+                let cc = match cond {
+                    Condition::EqEq => FloatCC::Equal,
+                    Condition::Ne => FloatCC::NotEqual,
+                    Condition::Lt => FloatCC::LessThan,
+                    Condition::Gt => FloatCC::GreaterThan,
+                    Condition::Lte => FloatCC::LessThanOrEqual,
+                    Condition::Gte => FloatCC::GreaterThanOrEqual,
+                };
+
+                let ordering = !matches!(cond, Condition::EqEq | Condition::Ne);
+
+                if ordering && (*l == VarType::Float || *r == VarType::Float) {
+                    let nan = self.ins().fcmp(FloatCC::Unordered, left, right);
+                    self.fail_if(nan);
+                }
+
+                self.ins().fcmp(cc, left, right)
+            }
+            (VarType::Bool, VarType::Bool) if cond == Condition::EqEq => {
+                self.ins().icmp(IntCC::Equal, left, right)
+            }
+            (VarType::Bool, VarType::Bool) if cond == Condition::Ne => {
+                self.ins().icmp(IntCC::NotEqual, left, right)
+            }
+            // Values of different types are never equal
+            _ if cond == Condition::EqEq => self.ins().iconst(types::I8, 0),
+            _ if cond == Condition::Ne => self.ins().iconst(types::I8, 1),
+            _ => return super::exc!("Can't compare with {}", cond),
+        };
+
+        Ok((value, VarType::Bool))
+    }
+}
+
+/// Declares a function to be compiled in a batch.
+fn declare(module: &mut JITModule, func: &Rc<FuncDecl>, batch: &mut Batch) -> JitResult<FuncId> {
+    let sig = signature(module, func)?;
+    let id = module
+        .declare_anonymous_function(&sig)
+        .map_err(|e| e.to_string())?;
+
+    batch.ids.insert(key(func), (id, Rc::clone(func)));
+    batch.pending.push((id, Rc::clone(func)));
+    Ok(id)
+}
+
+/// The signature of a compiled function, which takes how many more calls
+/// may be nested and the flag that it failed after its params.
+fn signature(module: &JITModule, func: &FuncDecl) -> JitResult<Signature> {
+    let mut sig = module.make_signature();
+
+    for param in &func.params {
+        sig.params.push(AbiParam::new(ir_type(&param.typ)?));
+    }
+
+    sig.params.extend([
+        AbiParam::new(types::I64),
+        AbiParam::new(module.target_config().pointer_type()),
+    ]);
+    sig.returns.push(AbiParam::new(ir_type(&func.ret)?));
+    Ok(sig)
+}
+
+/// The type of values of a type in Cranelift IR.
+fn ir_type(typ: &VarType) -> JitResult<Type> {
+    match typ {
+        VarType::Int => Ok(types::I64),
+        VarType::Float => Ok(types::F64),
+        VarType::Bool => Ok(types::I8),
+        typ => super::exc!("Only numeric code can be compiled, not {}", typ),
+    }
+}
+
+fn key(func: &FuncDecl) -> Key {
+    (func.name, func.loc.line, func.loc.col)
+}
+
+/// Returns true if two function declarations are copies of each other.
+fn same(a: &Rc<FuncDecl>, b: &Rc<FuncDecl>) -> bool {
+    Rc::ptr_eq(a, b) || a == b
+}
+
+/// Raises an int to a power for compiled code, setting the flag that
+/// it failed when the interpreter would fail or make a float.
+extern "C" fn ipow(base: i64, exp: i64, failed: *mut u8) -> i64 {
+    let result = u32::try_from(exp)
+        .ok()
+        .and_then(|exp| base.checked_pow(exp));
+
+    result.unwrap_or_else(|| {
+        // SAFETY: Compiled code passes the flag it was called with
+        unsafe { *failed = 1 };
+        0
+    })
+}
+
+extern "C" fn powf(base: f64, exp: f64) -> f64 {
+    base.powf(exp)
+}
@@ -0,0 +1,338 @@
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A JSON value, as read and written by the language server, the
+/// syntax tree dumps and the docs' search index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// A number written without a fraction or exponent, kept exact.
+    Int(i64),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// An object, keeping its keys in order.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Builds an object from its fields.
+    #[must_use]
+    pub fn object(fields: Vec<(&str, Json)>) -> Self {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    #[must_use]
+    pub fn str(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+
+    #[must_use]
+    pub fn int(value: usize) -> Self {
+        Json::Int(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+
+    /// Looks up a field of an object, or [`Json::Null`] if there is no
+    /// such field.
+    #[must_use]
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map_or(&Json::Null, |(_, v)| v),
+            _ => &Json::Null,
+        }
+    }
+
+    /// Looks up a field by following a path of keys, e.g.
+    /// `["textDocument", "uri"]`.
+    #[must_use]
+    pub fn path(&self, keys: &[&str]) -> &Json {
+        keys.iter().fold(self, |json, key| json.get(key))
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Int(n) => usize::try_from(*n).ok(),
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Parses a JSON document.
+    ///
+    /// # Errors
+    /// - If the text is not valid JSON.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut chars = text.chars().peekable();
+        let json = parse_value(&mut chars)?;
+
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(json),
+            Some(c) => super::exc!("Unexpected {:?} after JSON value", c),
+        }
+    }
+
+    /// Writes the value on a single line.
+    #[must_use]
+    pub fn write(&self) -> String {
+        let mut out = String::new();
+        self.write_to(&mut out, None);
+        out
+    }
+
+    /// Writes the value with one field or item per line, indented by
+    /// two spaces a level.
+    #[must_use]
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_to(&mut out, Some(0));
+        out
+    }
+
+    /// Writes the value, on a single line if `indent` is [`None`], or
+    /// else broken over lines starting at that level of indentation.
+    fn write_to(&self, out: &mut String, indent: Option<usize>) {
+        let (items, open, close): (Vec<(Option<&str>, &Json)>, char, char) = match self {
+            Json::Array(items) => (items.iter().map(|i| (None, i)).collect(), '[', ']'),
+            Json::Object(fields) => (
+                fields.iter().map(|(k, v)| (Some(k.as_str()), v)).collect(),
+                '{',
+                '}',
+            ),
+            Json::Null => return out.push_str("null"),
+            Json::Bool(b) => return out.push_str(if *b { "true" } else { "false" }),
+            Json::Int(n) => {
+                let _ = write!(out, "{n}");
+                return;
+            }
+            Json::Number(n) if !n.is_finite() => return out.push_str("null"),
+            Json::Number(n) => {
+                let _ = write!(out, "{n:?}");
+                return;
+            }
+            Json::String(s) => return out.push_str(&quote(s)),
+        };
+
+        out.push(open);
+
+        for (i, (key, value)) in items.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+
+            if let Some(indent) = indent {
+                out.push('\n');
+                out.push_str(&"  ".repeat(indent + 1));
+            }
+
+            if let Some(key) = key {
+                out.push_str(&quote(key));
+                out.push_str(if indent.is_some() { ": " } else { ":" });
+            }
+
+            value.write_to(out, indent.map(|indent| indent + 1));
+        }
+
+        if let Some(indent) = indent.filter(|_| !items.is_empty()) {
+            out.push('\n');
+            out.push_str(&"  ".repeat(indent));
+        }
+
+        out.push(close);
+    }
+}
+
+/// Quotes and escapes a string as JSON, which s-expressions share.
+#[must_use]
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect_word(chars: &mut Peekable<Chars>, word: &str, value: Json) -> Result<Json, String> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            return super::exc!("Expected JSON {}", word);
+        }
+    }
+
+    Ok(value)
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+
+    match chars.peek() {
+        Some('n') => expect_word(chars, "null", Json::Null),
+        Some('t') => expect_word(chars, "true", Json::Bool(true)),
+        Some('f') => expect_word(chars, "false", Json::Bool(false)),
+        Some('"') => parse_string(chars).map(Json::String),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(Json::Array(items));
+            }
+
+            loop {
+                items.push(parse_value(chars)?);
+                skip_whitespace(chars);
+
+                match chars.next() {
+                    Some(',') => (),
+                    Some(']') => return Ok(Json::Array(items)),
+                    _ => return super::exc!("Expected ',' or ']' in JSON array"),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut fields = Vec::new();
+
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(Json::Object(fields));
+            }
+
+            loop {
+                skip_whitespace(chars);
+                let key = parse_string(chars)?;
+
+                skip_whitespace(chars);
+                if chars.next() != Some(':') {
+                    return super::exc!("Expected ':' in JSON object");
+                }
+
+                fields.push((key, parse_value(chars)?));
+                skip_whitespace(chars);
+
+                match chars.next() {
+                    Some(',') => (),
+                    Some('}') => return Ok(Json::Object(fields)),
+                    _ => return super::exc!("Expected ',' or '}}' in JSON object"),
+                }
+            }
+        }
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+
+            while let Some(c) =
+                chars.next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+            {
+                number.push(c);
+            }
+
+            if let Ok(n) = number.parse() {
+                return Ok(Json::Int(n));
+            }
+
+            match number.parse() {
+                Ok(n) => Ok(Json::Number(n)),
+                Err(_) => super::exc!("Invalid JSON number {:?}", number),
+            }
+        }
+        Some(c) => super::exc!("Unexpected {:?} in JSON", (*c)),
+        None => super::exc!("Unexpected end of JSON"),
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let hex: String = chars.by_ref().take(4).collect();
+
+    match u32::from_str_radix(&hex, 16) {
+        Ok(n) if hex.len() == 4 => Ok(n),
+        _ => super::exc!("Invalid JSON escape \\u{}", hex),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    if chars.next() != Some('"') {
+        return super::exc!("Expected a JSON string");
+    }
+
+    let mut s = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some('n') => s.push('\n'),
+                Some('t') => s.push('\t'),
+                Some('r') => s.push('\r'),
+                Some('b') => s.push('\u{8}'),
+                Some('f') => s.push('\u{c}'),
+                Some('u') => {
+                    let mut code = parse_hex(chars)?;
+
+                    // A surrogate pair, for characters outside the BMP
+                    if (0xD800..0xDC00).contains(&code) {
+                        if chars.next() != Some('\\') || chars.next() != Some('u') {
+                            return super::exc!("Unpaired surrogate in JSON string");
+                        }
+
+                        let low = parse_hex(chars)?;
+                        code =
+                            0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                    }
+
+                    s.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                Some(c) => s.push(c),
+                None => return super::exc!("Unterminated JSON string"),
+            },
+            Some(c) => s.push(c),
+            None => return super::exc!("Unterminated JSON string"),
+        }
+    }
+}
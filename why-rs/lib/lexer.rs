@@ -1,13 +1,29 @@
+use std::rc::Rc;
+
+use super::trace::{self, Stage, Tracer};
+use super::Loc;
 use super::Token;
 use super::TokenType;
+use super::MAX_NAME_LEN;
 // use super::String;
 
+/// How much of the source has been given to tokens in trivia mode.
+#[derive(Clone, Debug)]
+struct Mark {
+    /// The index the source has been given to tokens up to.
+    idx: usize,
+    /// The location of that index.
+    loc: Loc,
+    /// The number of tokens at the time.
+    tokens: usize,
+}
+
 /// A lexer, for generating tokens from text.
 #[derive(Clone, Debug)]
-pub struct Lexer {
+pub struct Lexer<'src> {
     /// The source code to be lexed.
-    pub src: Vec<char>,
-    /// The current index being lexed.
+    pub src: &'src str,
+    /// The byte offset of the character being lexed.
     pub idx: usize,
     /// The current line being lexed.
     pub line: usize,
@@ -15,11 +31,28 @@ pub struct Lexer {
     pub col: usize,
     /// The current character being lexed.
     pub c: char,
-    /// The tokens that have already been lexed.
-    pub tokens: Vec<Token>,
+    /// The tokens that have been lexed, but not yet yielded by the
+    /// iterator.
+    pub tokens: Vec<Token<'src>>,
+    /// Receives a message for each character lexed.
+    pub tracer: Rc<dyn Tracer>,
+    /// Whether to lex whitespace and comments as trivia tokens, so the
+    /// texts of the tokens add up to the source.
+    pub trivia: bool,
+    /// Every error found so far. Lexing carries on past an error, so
+    /// they can all be reported at once.
+    pub errors: Vec<String>,
+    /// How much of the source trivia has been lexed for.
+    mark: Mark,
+    /// How many errors have been yielded by the iterator.
+    reported: usize,
+    /// How many tokens have been yielded by the iterator.
+    yielded: usize,
+    /// Whether the `Eof` token has been lexed.
+    done: bool,
 }
 
-impl Lexer {
+impl<'src> Lexer<'src> {
     /// Creates a new lexer to be used on a given string.
     ///
     /// # Returns
@@ -27,30 +60,60 @@ impl Lexer {
     ///
     /// # Errors
     /// - If the text file was empty.
-    pub fn new(src: &str) -> Result<Self, String> {
-        let src: Vec<char> = src.chars().collect();
-        let src_len = src.len();
-        let character = src.first();
-
-        if let Some(c) = character {
+    pub fn new(src: &'src str) -> Result<Self, String> {
+        if let Some(c) = src.chars().next() {
             Ok(Self {
-                c: *c,
+                c,
                 src,
                 idx: 0,
                 line: 1,
                 col: 1,
-                // Arbitrarily guessing a token will happen every 6 chars
-                tokens: Vec::with_capacity(src_len / 6 + 1),
+                // Only a few tokens are held back before being yielded
+                tokens: Vec::with_capacity(4),
+                tracer: trace::silent(),
+                trivia: false,
+                errors: Vec::new(),
+                mark: Mark {
+                    idx: 0,
+                    loc: Loc::new(),
+                    tokens: 0,
+                },
+                reported: 0,
+                yielded: 0,
+                done: false,
             })
         } else {
             super::exc!("There was no text in the file.")
         }
     }
 
+    /// Sets the tracer that receives a message for each character
+    /// lexed.
+    #[must_use]
+    pub fn with_tracer(mut self, tracer: Rc<dyn Tracer>) -> Self {
+        self.tracer = tracer;
+        self
+    }
+
+    /// Lexes whitespace and comments as trivia tokens, losing nothing:
+    /// concatenating [`Token::text`] of every token reproduces the
+    /// source exactly.
+    #[must_use]
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = true;
+        self
+    }
+
     /// True, if there is more src file to read.
     #[must_use]
     pub fn can_advance(&self) -> bool {
-        self.idx < self.src.len() - 1
+        self.end() < self.src.len()
+    }
+
+    /// The byte offset just past the current character.
+    #[must_use]
+    pub fn end(&self) -> usize {
+        self.idx + self.c.len_utf8()
     }
 
     /// Returns true if the character is a newline.
@@ -78,8 +141,22 @@ impl Lexer {
         if lexer.can_advance() {
             // The index always increases
             // Get the next character
-            lexer.idx += 1;
-            lexer.c = lexer.src[lexer.idx];
+            lexer.idx = lexer.end();
+            lexer.c = lexer.char_at(lexer.idx);
+        }
+    }
+
+    /// The character starting at byte `idx`, or `'\0'` past the end.
+    #[must_use]
+    pub fn char_at(&self, idx: usize) -> char {
+        match self.src.as_bytes().get(idx) {
+            // Most source is ASCII, which needs no decoding
+            Some(b) if b.is_ascii() => char::from(*b),
+            _ => self
+                .src
+                .get(idx..)
+                .and_then(|rest| rest.chars().next())
+                .unwrap_or_default(),
         }
     }
 
@@ -91,9 +168,17 @@ impl Lexer {
         }
 
         if offset < 0 {
-            Some(self.src[self.idx - offset.unsigned_abs()])
+            self.src[..self.idx]
+                .chars()
+                .nth_back(offset.unsigned_abs() - 1)
         } else {
-            Some(self.src[self.idx + offset.unsigned_abs()])
+            let mut idx = self.idx;
+
+            for _ in 0..offset {
+                idx += self.char_at(idx).len_utf8();
+            }
+
+            (idx < self.src.len()).then(|| self.char_at(idx))
         }
     }
 
@@ -117,7 +202,7 @@ impl Lexer {
             // This could be a while... :)
             while lexer.can_advance() {
                 if Lexer::end_multiline_comment(lexer) {
-                    Lexer::next(lexer);
+                    // Leave the lexer on the closing `/`
                     Lexer::next(lexer);
                     return Ok(());
                 }
@@ -154,9 +239,51 @@ impl Lexer {
         Ok(())
     }
 
+    /// Skips a comment, or lexes a `/=`. Comments are pushed as trivia
+    /// tokens in trivia mode, and `///` doc comments always are.
+    ///
+    /// # Returns
+    /// - [`Result<(), String>`] - Unit type on success.
+    ///
+    /// # Errors
+    /// - If an invalid char was encountered after the initial `/`.
+    pub fn lex_comment(lexer: &mut Lexer) -> Result<(), String> {
+        let (start, line, col) = (lexer.idx, lexer.line, lexer.col);
+        let token_count = lexer.tokens.len();
+
+        Lexer::skip_comment(lexer, false)?;
+
+        if lexer.tokens.len() != token_count {
+            return Ok(());
+        }
+
+        let text = lexer.src[start..lexer.end()].trim_end();
+        let is_doc = text.starts_with("///") && !text.starts_with("////");
+
+        let (typ, value) = if is_doc {
+            let doc = &text[3..];
+            (TokenType::DocComment, doc.strip_prefix(' ').unwrap_or(doc))
+        } else if lexer.trivia && text.starts_with("/*") {
+            (TokenType::BlockComment, text)
+        } else if lexer.trivia {
+            (TokenType::LineComment, text)
+        } else {
+            return Ok(());
+        };
+
+        let mut token = Token::with_value_at(typ, value, line, col);
+
+        if lexer.trivia && value != text {
+            token.raw = Some(text.into());
+        }
+
+        lexer.tokens.push(token);
+        Ok(())
+    }
+
     /// Determines which token this is, if the char was an equals/
     #[must_use]
-    pub fn get_eq_token(&self) -> Token {
+    pub fn get_eq_token(&self) -> Token<'src> {
         match self.peek(1).unwrap_or_default() {
             '>' => super::make_token!(TokenType::LargeRArrow, "=>", self),
             '=' => super::make_token!(TokenType::EqEq, "==", self),
@@ -171,14 +298,14 @@ impl Lexer {
         lexer.tokens.push(token.clone());
 
         match token.typ {
-            TokenType::Minus => (),
+            TokenType::Eq => (),
             _ => Lexer::next(lexer),
         }
     }
 
     /// Determines which token this is, if the char was a minus.
     #[must_use]
-    pub fn get_minus_token(&self) -> Token {
+    pub fn get_minus_token(&self) -> Token<'src> {
         match self.peek(1).unwrap_or_default() {
             '-' => super::make_token!(TokenType::MinusMinus, "--", self),
             '=' => super::make_token!(TokenType::MinusEq, "-=", self),
@@ -201,7 +328,7 @@ impl Lexer {
 
     /// Determines which token this is, if the char was a plus.
     #[must_use]
-    pub fn get_plus_token(&self) -> Token {
+    pub fn get_plus_token(&self) -> Token<'src> {
         match self.peek(1).unwrap_or_default() {
             '+' => super::make_token!(TokenType::PlusPlus, "++", self),
             '=' => super::make_token!(TokenType::PlusEq, "+=", self),
@@ -223,7 +350,7 @@ impl Lexer {
 
     /// Determines which token this is, if the char was a star.
     #[must_use]
-    pub fn get_star_token(&self) -> Token {
+    pub fn get_star_token(&self) -> Token<'src> {
         match self.peek(1).unwrap_or_default() {
             '*' => super::make_token!(TokenType::StarStar, "**", self),
             '=' => super::make_token!(TokenType::StarEq, "*=", self),
@@ -254,68 +381,170 @@ impl Lexer {
     /// Generate an Ident token, push to the stack, and advance.
     pub fn lex_ident(lexer: &mut Lexer) {
         let mut token = Token::at(TokenType::Ident, lexer.line, lexer.col);
-        let mut name = String::new();
+        let start = lexer.idx;
 
         while lexer.can_advance() && Lexer::can_be_ident(lexer.c) {
             // Keep going til its some other type of character like space or semi
-            name.push(lexer.c);
             Lexer::next(lexer);
         }
 
-        if !lexer.can_advance() && Lexer::can_be_ident(lexer.c) {
-            name.push(lexer.c);
-        }
+        let end = if !lexer.can_advance() && Lexer::can_be_ident(lexer.c) {
+            lexer.end()
+        } else {
+            lexer.idx
+        };
+        let name = &lexer.src[start..end];
 
-        if super::KEYWORDS.contains(&name.as_str()) {
+        if super::is_keyword(name) {
             token.typ = TokenType::Keyword;
+        } else if name.len() > MAX_NAME_LEN {
+            let error: Result<(), String> =
+                super::lex_exc!(lexer, "Names can't be longer than {} bytes", MAX_NAME_LEN);
+
+            lexer.errors.extend(error.err());
+            token.typ = TokenType::Error;
         }
 
-        token.value = name;
+        token.value = name.into();
         lexer.tokens.push(token);
     }
 
+    /// Returns true if the character is valid in a number with the
+    /// given radix, including the `_` digit separator.
+    #[must_use]
+    pub fn is_digit_of(c: char, radix: u32) -> bool {
+        c.is_digit(radix) || c == '_'
+    }
+
+    /// Counts how many characters, starting at byte `idx`, are digits
+    /// of the given radix (or `_` separators).
+    #[must_use]
+    pub fn count_digits(&self, idx: usize, radix: u32) -> usize {
+        self.src.as_bytes()[idx.min(self.src.len())..]
+            .iter()
+            .take_while(|b| Lexer::is_digit_of(char::from(**b), radix))
+            .count()
+    }
+
+    /// Returns true if a numeric literal starts at the current
+    /// character, i.e. a digit or a `.` directly followed by a digit.
+    /// A `.` straight after another `.` never starts a number, so `1..5`
+    /// lexes as `1`, `.`, `.`, `5`.
+    #[must_use]
+    pub fn starts_number(&self) -> bool {
+        self.c.is_ascii_digit()
+            || (self.c == '.'
+                && self.peek(1).unwrap_or_default().is_ascii_digit()
+                && self.peek(-1) != Some('.'))
+    }
+
     /// Generate an `Int` or `Float` token, push to the stack, and
-    /// advance.
+    /// advance to the last character of the literal.
+    ///
+    /// Supported forms:
+    /// - Decimal integers with `_` separators: `1_000_000`
+    /// - Hex, binary and octal integers: `0xFF`, `0b1010`, `0o17`
+    /// - Floats with an optional exponent: `3.14`, `.5`, `1e-9`
+    /// - Type suffixes: `i` and `u` for integers, `f` for floats,
+    ///   e.g. `10u` or `2f`
+    ///
+    /// A `.` is only part of the number when a digit follows it, so
+    /// `1.` lexes as `1` followed by a `Dot`.
     ///
     /// # Returns
     /// - [`Result<(), String>`] - Unit type on success.
     ///
     /// # Errors
     /// - If the number had more than 1 dot in it, indicating an invalid
-    /// float.
+    ///   float.
     ///     - Ex: `69.420.3` would trigger this error.
+    /// - If a digit is invalid for the literal's radix, e.g. `0b102`.
+    /// - If the literal ends with a `_`, has an empty exponent, or an
+    ///   unknown suffix.
     pub fn lex_number(lexer: &mut Lexer) -> Result<(), String> {
         let mut token = Token::at(TokenType::NumLiteral(false), lexer.line, lexer.col);
-        let mut digits = String::new();
-        let mut dot_count = 0;
+        let src = lexer.src;
+        let start = lexer.idx;
+        let mut end = start;
+        // Numbers are ASCII, so looking at bytes is enough
+        let at = |i: usize| src.as_bytes().get(i).map_or('\0', |b| char::from(*b));
+
+        let radix = match (at(start), at(start + 1).to_ascii_lowercase()) {
+            ('0', 'x') => Some((16, "hexadecimal")),
+            ('0', 'b') => Some((2, "binary")),
+            ('0', 'o') => Some((8, "octal")),
+            _ => None,
+        };
 
-        digits.push(lexer.c);
-        Lexer::next(lexer);
+        if let Some((radix, name)) = radix {
+            end += 2;
+            let digits = lexer.count_digits(end, radix);
+
+            if at(end + digits).is_ascii_digit() {
+                let digit = at(end + digits);
+                return super::lex_exc!(lexer, "Invalid digit '{}' in {} literal", digit, name);
+            }
+
+            if !src[end..end + digits]
+                .bytes()
+                .any(|b| b.is_ascii_alphanumeric())
+            {
+                return super::lex_exc!(lexer, "Expected digits after {} prefix", name);
+            }
+
+            end += digits;
+        } else {
+            end += lexer.count_digits(end, 10);
 
-        while lexer.can_advance() && (lexer.c.is_numeric() || lexer.c == '.') {
-            if lexer.c == '.' {
-                if dot_count > 0 {
+            if at(end) == '.' && at(end + 1).is_ascii_digit() {
+                token.typ = TokenType::NumLiteral(true);
+                end += 1 + lexer.count_digits(end + 1, 10);
+
+                if at(end) == '.' && at(end + 1).is_ascii_digit() {
                     // We already had a dot, there should't be another
                     return super::lex_exc!(lexer, "Invalid location for '.'");
                 }
+            }
+
+            if at(end).eq_ignore_ascii_case(&'e') {
+                let sign = usize::from(at(end + 1) == '+' || at(end + 1) == '-');
+
+                if !at(end + 1 + sign).is_ascii_digit() {
+                    return super::lex_exc!(lexer, "Expected digits in exponent");
+                }
 
                 token.typ = TokenType::NumLiteral(true);
-                dot_count += 1;
+                end += 1 + sign + lexer.count_digits(end + 1 + sign, 10);
             }
-
-            // Keep going til its some other type of character like space or semi
-            digits.push(lexer.c);
-            Lexer::next(lexer);
         }
 
-        if !lexer.can_advance() && lexer.c.is_numeric() && digits.contains('.') {
-            digits.push(lexer.c);
-        } // Why did we do this???
+        if at(end - 1) == '_' {
+            return super::lex_exc!(lexer, "Numeric literal cannot end with '_'");
+        }
 
-        // println!("{}", digits);
+        let suffix_len: usize = src[end..]
+            .chars()
+            .take_while(|c| Lexer::can_be_ident(*c))
+            .map(char::len_utf8)
+            .sum();
+        let suffix = &src[end..end + suffix_len];
+        let is_float = token.typ == TokenType::NumLiteral(true);
+
+        match suffix {
+            "" => (),
+            "i" | "u" if !is_float => (),
+            "f" if radix.is_none() => token.typ = TokenType::NumLiteral(true),
+            _ => return super::lex_exc!(lexer, "Invalid suffix '{}' on numeric literal", suffix),
+        }
 
-        token.value = digits;
+        end += suffix_len;
+        token.value = src[start..end].into();
         lexer.tokens.push(token);
+
+        for _ in src[start..end].chars().skip(1) {
+            Lexer::next(lexer);
+        }
+
         Ok(())
     }
 
@@ -354,9 +583,35 @@ impl Lexer {
         super::make_token_mut!(TokenType::Dollar, "$", lexer);
     }
 
-    /// Generate an `Exclamation` token, push to the stack, and advance.
+    /// Determines which token this is, if the char was an exclamation.
+    #[must_use]
+    pub fn get_exclamation_token(&self) -> Token<'src> {
+        match self.peek(1).unwrap_or_default() {
+            '=' => super::make_token!(TokenType::Ne, "!=", self),
+            _ => super::make_token!(TokenType::Exclamation, "!", self),
+        }
+    }
+
+    /// Pushes an `Exclamation` or `Ne` token onto the token stack, and
+    /// advances.
     pub fn lex_exclamation(lexer: &mut Lexer) {
-        super::make_token_mut!(TokenType::Exclamation, "!", lexer);
+        let token = lexer.get_exclamation_token();
+        lexer.tokens.push(token.clone());
+
+        match token.typ {
+            TokenType::Exclamation => (),
+            _ => Lexer::next(lexer),
+        }
+    }
+
+    /// Generate a `Slash` token, push to the stack, and advance.
+    pub fn lex_slash(lexer: &mut Lexer) {
+        super::make_token_mut!(TokenType::Slash, "/", lexer);
+    }
+
+    /// Generate a `Percent` token, push to the stack, and advance.
+    pub fn lex_percent(lexer: &mut Lexer) {
+        super::make_token_mut!(TokenType::Percent, "%", lexer);
     }
 
     /// Generate an `Caret` token, push to the stack, and advance.
@@ -376,7 +631,7 @@ impl Lexer {
     ///
     /// # Errors
     /// - If this function was called incorrectly on a non enclosure
-    /// type char. Valid chars: `(`, `)`, `[`, `]`, `{`, `}`
+    ///   type char. Valid chars: `(`, `)`, `[`, `]`, `{`, `}`
     pub fn lex_enclosures(lexer: &mut Lexer) -> Result<(), String> {
         match lexer.c {
             '(' => super::make_token_mut_ok!(TokenType::LParen, "(", lexer),
@@ -427,31 +682,25 @@ impl Lexer {
     /// # Errors
     /// - If the string was never closed.
     pub fn lex_string(lexer: &mut Lexer) -> Result<(), String> {
-        let mut content = String::new();
         let delim = lexer.c;
+        let loc = Loc::at(lexer.line, lexer.col);
         Lexer::next(lexer);
+        let start = lexer.idx;
 
         while lexer.c != delim && lexer.can_advance() {
-            if lexer.c == '\\' {
-                let next = lexer.peek(1).unwrap_or_default();
-
-                if next == delim {
-                    // We are escaping the delimiter
-                    content.push(lexer.c);
-                    content.push(next);
-                    Lexer::next(lexer);
-                    Lexer::next(lexer);
-                    continue;
-                }
+            if lexer.c == '\\' && lexer.peek(1) == Some(delim) {
+                // We are escaping the delimiter, which is kept as is
+                Lexer::next(lexer);
             }
 
-            content.push(lexer.c);
             Lexer::next(lexer);
         }
 
         if lexer.c == delim {
+            let content = &lexer.src[start..lexer.idx];
+            // Strings may span lines, so they start where the quote was
             let mut token = super::make_token!(TokenType::StrLiteral, content, lexer);
-            token.loc.col = lexer.col - content.len() - 1;
+            token.loc = loc;
             lexer.tokens.push(token);
             Ok(())
         } else {
@@ -460,86 +709,296 @@ impl Lexer {
         }
     }
 
+    /// Gives the source between the mark and `end` to the tokens lexed
+    /// since the mark was made, and lexes whatever is left over as
+    /// trivia.
+    fn settle(&mut self, end: usize) {
+        let mut start = self.mark.idx;
+
+        for idx in self.mark.tokens..self.tokens.len() {
+            let known = self.tokens[idx].text();
+            let rest = &self.src[start..end];
+
+            // Tokens usually span their value, but strings also span
+            // their quotes
+            let len = if !known.is_empty() && rest.starts_with(known) {
+                known.len()
+            } else if idx == self.tokens.len() - 1 {
+                rest.len()
+            } else {
+                0
+            };
+
+            let text = &rest[..len];
+            if text != known {
+                self.tokens[idx].raw = Some(text.into());
+            }
+
+            start += len;
+        }
+
+        let mut loc = self.mark.loc.clone();
+        Lexer::advance_loc(&mut loc, &self.src[self.mark.idx..start]);
+        self.push_trivia(start, end, &mut loc);
+
+        self.mark = Mark {
+            idx: end,
+            loc,
+            tokens: self.tokens.len(),
+        };
+    }
+
+    /// Pushes the source between `start` and `end` as whitespace and
+    /// newline trivia tokens.
+    pub fn push_trivia(&mut self, start: usize, end: usize, loc: &mut Loc) {
+        let mut idx = start;
+
+        while idx < end {
+            let rest = &self.src[idx..end];
+            let c = rest.chars().next().unwrap_or_default();
+            let len = if rest.starts_with("\r\n") {
+                2
+            } else if Lexer::is_newline(c) {
+                1
+            } else {
+                rest.chars()
+                    .take_while(|o| !Lexer::is_newline(*o))
+                    .map(char::len_utf8)
+                    .sum()
+            };
+
+            let typ = if Lexer::is_newline(c) {
+                TokenType::Newline
+            } else {
+                TokenType::Whitespace
+            };
+
+            let text = &rest[..len];
+
+            // The lexer steps over whitespace a character at a time, so
+            // join runs back up, along with any `\r\n` split in two.
+            // The run ends where this text starts, so the joined text is
+            // still a slice of the source.
+            let joins = |last: &Token| match typ {
+                TokenType::Newline => last.value == "\r" && text == "\n",
+                _ => true,
+            };
+
+            match self.tokens.last_mut() {
+                Some(last) if last.typ == typ && joins(last) => {
+                    last.value = self.src[idx - last.value.len()..idx + len].into();
+                }
+                _ => self
+                    .tokens
+                    .push(Token::with_value_at(typ, text, loc.line, loc.col)),
+            }
+
+            Lexer::advance_loc(loc, &self.src[idx..idx + len]);
+            idx += len;
+        }
+    }
+
+    /// Moves a location past some source, the same way [`Lexer::next`]
+    /// counts lines and columns.
+    pub fn advance_loc(loc: &mut Loc, text: &str) {
+        for c in text.chars() {
+            if Lexer::is_newline(c) {
+                loc.line += 1;
+                loc.col = 1;
+            } else {
+                loc.col += 1;
+            }
+        }
+    }
+
+    /// Runs one of the lexing functions, recording its error rather than
+    /// stopping, and lexing the text it failed on as an `Error` token.
+    /// If it failed without moving, the rest of the word it failed on
+    /// is skipped, so e.g. a bad number is only reported once.
+    pub fn recover(&mut self, lex: fn(&mut Lexer) -> Result<(), String>) {
+        let (idx, line, col) = (self.idx, self.line, self.col);
+
+        if let Err(e) = lex(self) {
+            self.errors.push(e);
+
+            if self.idx == idx {
+                while self
+                    .peek(1)
+                    .is_some_and(|c| Lexer::can_be_ident(c) || c == '.')
+                {
+                    Lexer::next(self);
+                }
+            }
+
+            let text = &self.src[idx..self.end()];
+            self.tokens
+                .push(Token::with_value_at(TokenType::Error, text, line, col));
+        }
+    }
+
+    /// Records a character no token starts with as an error, and lexes
+    /// it alone as an `Error` token.
+    fn lex_unexpected(lexer: &mut Lexer) {
+        let c = lexer.c;
+        let error: Result<(), String> = super::lex_exc!(lexer, "Unexpected character '{}'", c);
+
+        lexer.errors.extend(error.err());
+        lexer.tokens.push(Token::with_value_at(
+            TokenType::Error,
+            &lexer.src[lexer.idx..lexer.end()],
+            lexer.line,
+            lexer.col,
+        ));
+    }
+
     /// Lexes the text attached to this lexer.
     ///
     /// # Returns
     /// - [`Result<Vec<Token>, String>`] - A vector containing the lexed
-    /// tokens on success.
+    ///   tokens on success.
     ///
     /// # Errors
-    /// - If something went wrong during lexing.
-    pub fn lex(&mut self) -> Result<Vec<Token>, String> {
-        loop {
-            // println!(
-            //     "Index: {}, Max: {}, Char: {:?}",
-            //     self.idx,
-            //     self.src.len(),
-            //     self.c
-            // );
-
-            let next = self.peek(1).unwrap_or_default();
-            println!("NEXT: {}", next);
-
-            match self.c {
-                '=' => Lexer::lex_eq(self),
-                '&' => Lexer::lex_and(self),
-                ';' => Lexer::lex_semi(self),
-                '.' => Lexer::lex_dot(self),
-                ',' => Lexer::lex_comma(self),
-                ':' => Lexer::lex_colon(self),
-                '@' => Lexer::lex_at(self),
-                '$' => Lexer::lex_dollar(self),
-                '!' => Lexer::lex_exclamation(self),
-                '-' => Lexer::lex_minus(self),
-                '+' => Lexer::lex_plus(self),
-                '*' => Lexer::lex_star(self),
-                '^' => Lexer::lex_caret(self),
-                '?' => Lexer::lex_question_mark(self),
-                '/' => Lexer::skip_comment(self, false)?,
-                '<' | '>' => Lexer::lex_comparison(self)?,
-                '"' | '\'' => Lexer::lex_string(self)?,
-                ' ' | '\n' | '\r' => (),
-                '(' | ')' | '[' | ']' | '{' | '}' => Lexer::lex_enclosures(self)?,
-                _ => {
-                    if self.c.is_numeric() {
-                        Lexer::lex_number(self)?;
-
-                        if !self.can_advance() && !self.c.is_numeric() {
-                            continue;
-                        } else if !self.can_advance() {
-                            break;
-                        }
-
-                        continue;
-                    } else if self.c.is_alphabetic() || self.c == '_' {
-                        Lexer::lex_ident(self);
-
-                        if !self.can_advance() && Lexer::can_be_ident(self.c) {
-                            return super::lex_exc!(
-                                self,
-                                "';' or operand expected after identifier {:?}",
-                                self.c
-                            );
-                        } else if !self.can_advance() && !Lexer::can_be_ident(self.c) {
-                            continue;
-                        } else if !self.can_advance() {
-                            break;
-                        }
-
-                        continue;
-                    }
+    /// - If something went wrong during lexing. Every error is
+    ///   reported, one per line.
+    pub fn lex(&mut self) -> Result<Vec<Token<'src>>, String> {
+        let tokens = self.lex_recovering();
+
+        if self.errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(self.errors.join("\n"))
+        }
+    }
+
+    /// Lexes the text attached to this lexer, carrying on past errors.
+    /// The errors are left in [`Lexer::errors`], and the characters
+    /// they were found at are skipped.
+    ///
+    /// # Returns
+    /// - [`Vec<Token>`] - The tokens that could be lexed.
+    pub fn lex_recovering(&mut self) -> Vec<Token<'src>> {
+        // Arbitrarily guessing a token will happen every 6 bytes
+        let mut tokens = Vec::with_capacity(self.src.len() / 6 + 1);
+        tokens.extend(self.by_ref().filter_map(Result::ok));
+        tokens
+    }
+
+    /// Lexes from the current character, leaving any tokens found in
+    /// [`Lexer::tokens`].
+    ///
+    /// # Returns
+    /// - [`bool`] - False once the end of the source has been reached.
+    fn step(&mut self) -> bool {
+        if self.trivia {
+            self.settle(self.idx);
+        }
+
+        trace::trace(&self.tracer, Stage::Lexer, || {
+            format!("{}:{} {:?}", self.line, self.col, self.c)
+        });
+
+        let next = self.peek(1).unwrap_or_default();
+
+        match self.c {
+            '=' => Lexer::lex_eq(self),
+            '&' => Lexer::lex_and(self),
+            ';' => Lexer::lex_semi(self),
+            '.' if self.starts_number() => self.recover(Lexer::lex_number),
+            '.' => Lexer::lex_dot(self),
+            ',' => Lexer::lex_comma(self),
+            ':' => Lexer::lex_colon(self),
+            '@' => Lexer::lex_at(self),
+            '$' => Lexer::lex_dollar(self),
+            '!' => Lexer::lex_exclamation(self),
+            '%' => Lexer::lex_percent(self),
+            '-' => Lexer::lex_minus(self),
+            '+' => Lexer::lex_plus(self),
+            '*' => Lexer::lex_star(self),
+            '^' => Lexer::lex_caret(self),
+            '?' => Lexer::lex_question_mark(self),
+            '/' if matches!(next, '/' | '*' | '=') => self.recover(Lexer::lex_comment),
+            '/' => Lexer::lex_slash(self),
+            '<' | '>' => self.recover(Lexer::lex_comparison),
+            '"' | '\'' => self.recover(Lexer::lex_string),
+            ' ' | '\t' | '\n' | '\r' => (),
+            '(' | ')' | '[' | ']' | '{' | '}' => self.recover(Lexer::lex_enclosures),
+            _ => {
+                if self.starts_number() {
+                    self.recover(Lexer::lex_number);
+                } else if self.c.is_alphabetic() || self.c == '_' {
+                    Lexer::lex_ident(self);
+
+                    // Lexing an identifier steps past it, unless it ran
+                    // to the end of the source
+                    return self.can_advance() || !Lexer::can_be_ident(self.c);
+                } else if !self.c.is_whitespace() {
+                    Lexer::lex_unexpected(self);
                 }
             }
+        }
 
-            if !self.can_advance() {
-                break;
-            }
+        if !self.can_advance() {
+            return false;
+        }
 
-            Lexer::next(self);
+        Lexer::next(self);
+        true
+    }
+
+    /// Lexes the trivia left at the end of the source, and the `Eof`
+    /// token.
+    fn finish(&mut self) {
+        if self.trivia {
+            self.settle(self.src.len());
         }
 
         Lexer::next(self);
         super::make_token_mut!(TokenType::Eof, "", self);
-        Ok(self.tokens.clone())
+        self.done = true;
+
+        trace::trace(&self.tracer, Stage::Lexer, || {
+            format!("lexed {} tokens", self.yielded + self.tokens.len())
+        });
+    }
+
+    /// How many of the tokens in [`Lexer::tokens`] won't change any
+    /// more. Trivia joins onto the token before it, so in trivia mode
+    /// the last token is held back until lexing is done.
+    fn ready(&self) -> usize {
+        if self.done || !self.trivia {
+            self.tokens.len()
+        } else {
+            self.mark.tokens.saturating_sub(1)
+        }
+    }
+}
+
+/// Lexes tokens as they are asked for, rather than all at once. Each
+/// error is yielded just before the `Error` token standing in for it,
+/// as well as being kept in [`Lexer::errors`].
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<Token<'src>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.ready() > 0 {
+                if self.tokens[0].typ == TokenType::Error && self.reported < self.errors.len() {
+                    self.reported += 1;
+                    return Some(Err(self.errors[self.reported - 1].clone()));
+                }
+
+                self.yielded += 1;
+                self.mark.tokens = self.mark.tokens.saturating_sub(1);
+                return Some(Ok(self.tokens.remove(0)));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            if !self.step() {
+                self.finish();
+            }
+        }
     }
 }
@@ -1,20 +1,89 @@
 mod ast;
+mod bytecode;
+pub mod cgen;
+mod checker;
+pub mod cli;
+mod compiler;
+pub mod doc;
+pub mod dump;
+mod engine;
+mod formatter;
+mod heap;
 pub mod internal;
+mod interpreter;
+#[cfg(feature = "jit")]
+mod jit;
+pub mod json;
 mod lexer;
+pub mod lsp;
 pub mod macros;
+mod object;
+mod optimizer;
 mod parser;
+#[cfg(feature = "python")]
+pub mod python;
+mod repl;
+mod symbol;
 mod tokens;
+pub mod trace;
+pub mod traceback;
+mod vm;
+pub mod wasm;
+pub mod whyc;
 
+pub use ast::ClassDecl;
 pub use ast::Condition;
 pub use ast::Expr;
+pub use ast::Field;
+pub use ast::FuncDecl;
 pub use ast::Keyword;
 pub use ast::Operator;
-// pub use ast::Stmt;
-// pub use ast::VarType;
+pub use ast::Param;
+pub use ast::Stmt;
+pub use ast::VarDecl;
+pub use ast::VarType;
+pub use bytecode::Chunk;
+pub use bytecode::Function;
+pub use bytecode::Op;
+pub use bytecode::Program;
+pub use bytecode::Slot;
+pub use cgen::CGen;
+pub use checker::Checker;
+pub use checker::ClassType;
+pub use compiler::Compiler;
+pub use engine::Engine;
+pub use engine::FromValue;
+pub use engine::IntoArgs;
+pub use engine::IntoNative;
+pub use engine::IntoValue;
+pub use engine::Native;
+pub use formatter::Formatter;
+pub use heap::Collector;
+pub use heap::HeapStats;
+pub use heap::Memory;
 // pub use internal::WhyExc;
+pub use internal::is_keyword;
 pub use internal::KEYWORDS;
+pub use interpreter::Binding;
+pub use interpreter::Flow;
+pub use interpreter::Interpreter;
+pub use interpreter::Value;
+pub use interpreter::BUILTINS;
+#[cfg(feature = "jit")]
+pub use jit::Jit;
 pub use lexer::Lexer;
+pub use object::Class;
+pub use object::Instance;
+pub use object::Object;
+pub use optimizer::Optimizer;
 pub use parser::Parser;
+pub use repl::Repl;
+pub use symbol::Symbol;
+pub use symbol::MAX_NAME_LEN;
 pub use tokens::Loc;
 pub use tokens::Token;
 pub use tokens::TokenType;
+pub use traceback::CallSite;
+pub use vm::Frame;
+pub use vm::Vm;
+pub use wasm::WasmGen;
@@ -0,0 +1,1111 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Read, Write};
+
+use super::json::Json;
+use super::Checker;
+use super::ClassDecl;
+use super::Expr;
+use super::Formatter;
+use super::FuncDecl;
+use super::Keyword;
+use super::Lexer;
+use super::Loc;
+use super::Parser;
+use super::Stmt;
+use super::Token;
+use super::TokenType;
+use super::VarType;
+
+/// The semantic token types the server reports, in legend order.
+pub const TOKEN_TYPES: [&str; 12] = [
+    "keyword",
+    "type",
+    "function",
+    "variable",
+    "parameter",
+    "property",
+    "method",
+    "class",
+    "string",
+    "number",
+    "operator",
+    "comment",
+];
+
+/// The semantic token modifiers the server reports, in legend order.
+pub const TOKEN_MODIFIERS: [&str; 3] = ["declaration", "readonly", "defaultLibrary"];
+
+/// The largest message body the server reads, in bytes. Bigger ones
+/// are skipped and answered with an error, rather than allocated.
+pub const MAX_MESSAGE: usize = 64 << 20;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const SERVER_NOT_INITIALIZED: i32 = -32002;
+
+/// The kinds of declaration the server knows about.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Constant,
+    Function,
+    Class,
+    Field,
+    Method,
+    Parameter,
+}
+
+/// A declaration in a document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The location of the name where it's declared.
+    pub loc: Loc,
+    /// The declaration as shown on hover, e.g. `int x`.
+    pub detail: String,
+    pub doc: Option<String>,
+    /// The index of the name's token.
+    pub token: usize,
+    /// The range of token indices the symbol can be referred to in.
+    pub scope: (usize, usize),
+    /// How many blocks deep the symbol is declared.
+    pub depth: usize,
+}
+
+/// An error found in a document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub loc: Loc,
+    pub message: String,
+}
+
+/// What the server knows about a single document.
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    /// The lines of the source, for converting columns to UTF-16.
+    pub lines: Vec<String>,
+    /// The tokens the parser saw, without comments.
+    pub tokens: Vec<Token<'static>>,
+    /// How many blocks deep each of the tokens is.
+    pub depths: Vec<usize>,
+    /// The tokens lexed in trivia mode, for their exact source text.
+    pub trivia: Vec<Token<'static>>,
+    pub program: Option<Expr>,
+    pub symbols: Vec<Symbol>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A language server for Why, speaking JSON-RPC over a reader and a
+/// writer, usually stdin and stdout.
+#[derive(Clone, Debug, Default)]
+pub struct Server {
+    /// The analysis of every open document, by URI.
+    pub documents: HashMap<String, Analysis>,
+    pub initialized: bool,
+    pub shutdown: bool,
+    pub exited: bool,
+}
+
+impl SymbolKind {
+    /// The number of the kind in the protocol's `SymbolKind`.
+    #[must_use]
+    pub fn number(self) -> usize {
+        match self {
+            SymbolKind::Class => 5,
+            SymbolKind::Method => 6,
+            SymbolKind::Field => 8,
+            SymbolKind::Function => 12,
+            SymbolKind::Variable | SymbolKind::Parameter => 13,
+            SymbolKind::Constant => 14,
+        }
+    }
+
+    /// The semantic token type of the symbol's name.
+    #[must_use]
+    pub fn token_type(self) -> &'static str {
+        match self {
+            SymbolKind::Variable | SymbolKind::Constant => "variable",
+            SymbolKind::Function => "function",
+            SymbolKind::Class => "class",
+            SymbolKind::Field => "property",
+            SymbolKind::Method => "method",
+            SymbolKind::Parameter => "parameter",
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Splits an error message into its location and the message
+    /// itself. Errors without a location are put at the start of the
+    /// document.
+    #[must_use]
+    pub fn from_error(error: &str) -> Self {
+        let located = error.strip_prefix("line ").and_then(|rest| {
+            let (line, rest) = rest.split_once(", column ")?;
+            let (col, message) = rest.split_once(": --> ")?;
+            Some((Loc::at(line.parse().ok()?, col.parse().ok()?), message))
+        });
+
+        match located {
+            Some((loc, message)) => Diagnostic {
+                loc,
+                message: message.to_string(),
+            },
+            None => Diagnostic {
+                loc: Loc::new(),
+                message: error.trim_start_matches("Error! --> ").to_string(),
+            },
+        }
+    }
+}
+
+impl Analysis {
+    /// Lexes, parses and checks a document. Every lex and syntax error is
+    /// reported, and the checker runs on as much as could be parsed.
+    #[must_use]
+    pub fn new(src: &str) -> Self {
+        // The lexer counts `\r\n` as two lines, but editors count one
+        let src = src.replace("\r\n", "\n");
+
+        let mut analysis = Analysis {
+            lines: src.split(['\n', '\r']).map(String::from).collect(),
+            ..Default::default()
+        };
+
+        if let Err(e) = analysis.analyze(&src) {
+            analysis.diagnostics.push(Diagnostic::from_error(&e));
+        }
+
+        analysis
+    }
+
+    fn analyze(&mut self, src: &str) -> Result<(), String> {
+        // The analysis outlives the source, so it keeps copies
+        self.trivia = Lexer::new(src)?
+            .with_trivia()
+            .lex_recovering()
+            .into_iter()
+            .map(Token::into_owned)
+            .collect();
+        let mut lexer = Lexer::new(src)?;
+        let tokens = lexer.lex_recovering();
+
+        let (program, errors) = {
+            let mut parser = Parser::from_stream(tokens.iter().cloned());
+            (parser.parse_recovering(), parser.errors)
+        };
+
+        let errors = lexer.errors.iter().chain(&errors);
+        self.diagnostics
+            .extend(errors.map(|e| Diagnostic::from_error(e)));
+
+        self.tokens = tokens
+            .into_iter()
+            .filter(|t| !t.typ.is_trivia())
+            .map(Token::into_owned)
+            .collect();
+
+        let mut depth: usize = 0;
+        self.depths = self
+            .tokens
+            .iter()
+            .map(|token| match token.typ {
+                TokenType::LBrace => {
+                    depth += 1;
+                    depth - 1
+                }
+                TokenType::RBrace => {
+                    depth = depth.saturating_sub(1);
+                    depth
+                }
+                _ => depth,
+            })
+            .collect();
+
+        let mut checker = Checker::new();
+        let result = checker.check(&program);
+
+        if let Expr::Main(stmts) = &program {
+            self.collect(stmts, &checker.declared);
+        }
+
+        self.program = Some(program);
+        result
+    }
+
+    /// Finds the token of a name declared at a location.
+    fn token_at_loc(&self, loc: &Loc) -> Option<usize> {
+        self.tokens
+            .iter()
+            .position(|t| t.typ == TokenType::Ident && t.loc == *loc)
+    }
+
+    /// The index of the first token in the block containing a token.
+    fn block_start(&self, idx: usize) -> usize {
+        let depth = self.depths[idx];
+
+        (0..idx)
+            .rev()
+            .find(|&i| self.tokens[i].typ == TokenType::LBrace && self.depths[i] + 1 == depth)
+            .map_or(0, |i| i + 1)
+    }
+
+    /// The index of the `}` closing the block containing a token.
+    fn block_end(&self, idx: usize, depth: usize) -> usize {
+        (idx..self.tokens.len())
+            .find(|&i| self.tokens[i].typ == TokenType::RBrace && self.depths[i] + 1 == depth)
+            .unwrap_or(self.tokens.len())
+    }
+
+    /// The first token inside the next block after a token, e.g. a
+    /// function's body.
+    fn body_after(&self, idx: usize) -> Option<usize> {
+        (idx..self.tokens.len())
+            .find(|&i| self.tokens[i].typ == TokenType::LBrace)
+            .map(|i| i + 1)
+    }
+
+    fn declare(
+        &mut self,
+        name: &str,
+        kind: SymbolKind,
+        loc: &Loc,
+        detail: String,
+        doc: Option<&str>,
+    ) -> Option<usize> {
+        let token = self.token_at_loc(loc)?;
+        let depth = self.depths[token];
+
+        // Functions and classes can be used before they're declared
+        let start = match kind {
+            SymbolKind::Function | SymbolKind::Class | SymbolKind::Method | SymbolKind::Field => {
+                self.block_start(token)
+            }
+            _ => token,
+        };
+
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            loc: loc.clone(),
+            detail,
+            doc: doc.map(ToString::to_string),
+            token,
+            scope: (start, self.block_end(token, depth)),
+            depth,
+        });
+
+        Some(token)
+    }
+
+    fn collect(&mut self, stmts: &[Stmt], declared: &[(Loc, VarType)]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::VarDecl(decl) => {
+                    let typ = decl.typ.clone().or_else(|| {
+                        declared
+                            .iter()
+                            .find(|(loc, _)| *loc == decl.loc)
+                            .map(|(_, typ)| typ.clone())
+                    });
+
+                    let detail = match &typ {
+                        Some(typ) => format!("{} {}", Formatter::typ(typ), decl.name),
+                        None => format!("let {}", decl.name),
+                    };
+
+                    let (kind, detail) = match decl.keyword {
+                        Some(Keyword::Const) => (
+                            SymbolKind::Constant,
+                            format!("const {} = {}", detail, Formatter::expr(&decl.value)),
+                        ),
+                        _ => (SymbolKind::Variable, detail),
+                    };
+
+                    self.declare(
+                        decl.name.as_str(),
+                        kind,
+                        &decl.loc,
+                        detail,
+                        decl.doc.as_deref(),
+                    );
+                }
+                Stmt::Func(func) => self.collect_func(func, SymbolKind::Function, declared),
+                Stmt::Class(class) => self.collect_class(class, declared),
+                Stmt::If(_, body, orelse, _) => {
+                    self.collect(body, declared);
+                    self.collect(orelse.as_deref().unwrap_or_default(), declared);
+                }
+                Stmt::While(_, body, _) => self.collect(body, declared),
+                _ => (),
+            }
+        }
+    }
+
+    fn collect_func(&mut self, func: &FuncDecl, kind: SymbolKind, declared: &[(Loc, VarType)]) {
+        let detail = Formatter::signature(func);
+        let Some(token) = self.declare(
+            func.name.as_str(),
+            kind,
+            &func.loc,
+            detail,
+            func.doc.as_deref(),
+        ) else {
+            return;
+        };
+
+        if let Some(body) = self.body_after(token) {
+            let depth = self.depths[body - 1] + 1;
+            let scope = (body, self.block_end(body, depth));
+
+            for param in &func.params {
+                // A parameter's name is followed by a `,` or the `)`
+                let found = (token..body).find(|&i| {
+                    self.tokens[i].typ == TokenType::Ident
+                        && self.tokens[i].value == param.name.as_str()
+                        && matches!(
+                            self.tokens.get(i + 1).map(|t| t.typ),
+                            Some(TokenType::Comma | TokenType::RParen)
+                        )
+                });
+
+                let Some(param_token) = found else {
+                    continue;
+                };
+
+                let detail = match param.typ {
+                    VarType::Any if param.name == "self" => "&self".to_string(),
+                    _ => format!("{} {}", Formatter::typ(&param.typ), param.name),
+                };
+
+                self.symbols.push(Symbol {
+                    name: param.name.to_string(),
+                    kind: SymbolKind::Parameter,
+                    loc: self.tokens[param_token].loc.clone(),
+                    detail,
+                    doc: None,
+                    token: param_token,
+                    scope,
+                    depth,
+                });
+            }
+        }
+
+        self.collect(&func.body, declared);
+    }
+
+    fn collect_class(&mut self, class: &ClassDecl, declared: &[(Loc, VarType)]) {
+        let mut detail = format!("&{}", class.name);
+        if let Some(base) = &class.base {
+            let _ = write!(detail, " is {base}");
+        }
+
+        self.declare(
+            class.name.as_str(),
+            SymbolKind::Class,
+            &class.loc,
+            detail,
+            class.doc.as_deref(),
+        );
+
+        for field in &class.fields {
+            let mut detail = format!("{} {}", Formatter::typ(&field.typ), field.name);
+            if let Some(value) = &field.value {
+                let _ = write!(detail, " = {}", Formatter::expr(value));
+            }
+
+            self.declare(
+                field.name.as_str(),
+                SymbolKind::Field,
+                &field.loc,
+                detail,
+                field.doc.as_deref(),
+            );
+        }
+
+        for method in &class.methods {
+            self.collect_func(method, SymbolKind::Method, declared);
+        }
+    }
+
+    /// Finds the identifier at or just after a location.
+    #[must_use]
+    pub fn ident_at(&self, loc: &Loc) -> Option<usize> {
+        self.tokens.iter().position(|t| {
+            t.typ == TokenType::Ident
+                && t.loc.line == loc.line
+                && (t.loc.col..=t.loc.col + t.value.chars().count()).contains(&loc.col)
+        })
+    }
+
+    /// Finds the declaration an identifier refers to, preferring the
+    /// innermost, latest declaration in scope.
+    #[must_use]
+    pub fn resolve(&self, idx: usize) -> Option<&Symbol> {
+        let token = &self.tokens[idx];
+        let previous = idx.checked_sub(1).map(|i| self.tokens[i].typ);
+        let mut named = self.symbols.iter().filter(|s| s.name == token.value);
+
+        match previous {
+            Some(TokenType::Percent) => None,
+            Some(TokenType::Dot) => {
+                named.find(|s| matches!(s.kind, SymbolKind::Field | SymbolKind::Method))
+            }
+            _ => named
+                .filter(|s| (s.scope.0..s.scope.1).contains(&idx) || s.token == idx)
+                .max_by_key(|s| (s.depth, s.token <= idx, s.token)),
+        }
+    }
+
+    /// Converts a 1-based column to the number of UTF-16 code units
+    /// before it on its line, as the protocol counts them.
+    fn utf16_col(&self, line: usize, col: usize) -> usize {
+        match self.lines.get(line.wrapping_sub(1)) {
+            Some(text) => text
+                .chars()
+                .take(col.saturating_sub(1))
+                .map(char::len_utf16)
+                .sum(),
+            None => col.saturating_sub(1),
+        }
+    }
+
+    /// Converts a location to a protocol position.
+    #[must_use]
+    pub fn position(&self, loc: &Loc) -> Json {
+        Json::object(vec![
+            ("line", Json::int(loc.line.saturating_sub(1))),
+            ("character", Json::int(self.utf16_col(loc.line, loc.col))),
+        ])
+    }
+
+    /// Converts a protocol position to a location.
+    #[must_use]
+    pub fn loc(&self, position: &Json) -> Option<Loc> {
+        let line = position.get("line").as_usize()?;
+        let character = position.get("character").as_usize()?;
+        let text = self.lines.get(line).map_or("", String::as_str);
+
+        let mut units = 0;
+        let col = text
+            .chars()
+            .take_while(|c| {
+                units += c.len_utf16();
+                units <= character
+            })
+            .count();
+
+        Some(Loc::at(line + 1, col + 1))
+    }
+
+    /// A protocol range between two locations.
+    #[must_use]
+    pub fn range(&self, start: &Loc, end: &Loc) -> Json {
+        Json::object(vec![
+            ("start", self.position(start)),
+            ("end", self.position(end)),
+        ])
+    }
+
+    /// The range of a name starting at a location.
+    #[must_use]
+    pub fn name_range(&self, loc: &Loc, name: &str) -> Json {
+        self.range(loc, &Loc::at(loc.line, loc.col + name.chars().count()))
+    }
+
+    /// The diagnostics to publish, in protocol form.
+    #[must_use]
+    pub fn diagnostics(&self) -> Json {
+        let diagnostics = self.diagnostics.iter().map(|diagnostic| {
+            // Underline the token the error is at, if there is one
+            let len = self
+                .trivia
+                .iter()
+                .find(|t| t.loc == diagnostic.loc && !t.typ.is_trivia())
+                .map_or(1, |t| t.text().chars().count().max(1));
+
+            let loc = &diagnostic.loc;
+            Json::object(vec![
+                ("range", self.range(loc, &Loc::at(loc.line, loc.col + len))),
+                ("severity", Json::int(1)),
+                ("source", Json::str("why")),
+                ("message", Json::str(&diagnostic.message)),
+            ])
+        });
+
+        Json::Array(diagnostics.collect())
+    }
+
+    /// The hover for the identifier at a position, showing its
+    /// declaration with its type, and its doc comment.
+    #[must_use]
+    pub fn hover(&self, position: &Json) -> Option<Json> {
+        let idx = self.ident_at(&self.loc(position)?)?;
+        let symbol = self.resolve(idx)?;
+
+        let mut value = format!("```why\n{}\n```", symbol.detail);
+        if let Some(doc) = &symbol.doc {
+            let _ = write!(value, "\n\n{doc}");
+        }
+
+        let token = &self.tokens[idx];
+        Some(Json::object(vec![
+            (
+                "contents",
+                Json::object(vec![
+                    ("kind", Json::str("markdown")),
+                    ("value", Json::String(value)),
+                ]),
+            ),
+            ("range", self.name_range(&token.loc, &token.value)),
+        ]))
+    }
+
+    /// The declaration of the identifier at a position.
+    #[must_use]
+    pub fn definition(&self, position: &Json) -> Option<&Symbol> {
+        let idx = self.ident_at(&self.loc(position)?)?;
+        self.resolve(idx)
+    }
+
+    /// Classifies a token for highlighting, given its index among the
+    /// parsed tokens, returning its type and modifiers.
+    fn classify(&self, token: &Token, idx: Option<usize>) -> Option<(&'static str, usize)> {
+        let typ = match token.typ {
+            TokenType::Keyword => "keyword",
+            TokenType::StrLiteral => "string",
+            TokenType::NumLiteral(_) => "number",
+            TokenType::LineComment | TokenType::BlockComment | TokenType::DocComment => "comment",
+            TokenType::Ident if matches!(token.value.as_ref(), "true" | "false") => "keyword",
+            TokenType::Ident if VarType::NAMES.contains(&token.value.as_ref()) => "type",
+            TokenType::Ident => {
+                let idx = idx?;
+
+                if idx > 0 && self.tokens[idx - 1].typ == TokenType::Percent {
+                    return Some(("function", 0b100));
+                }
+
+                return Some(match self.resolve(idx) {
+                    Some(symbol) => {
+                        let declaration = usize::from(symbol.token == idx);
+                        let readonly = usize::from(symbol.kind == SymbolKind::Constant) << 1;
+                        (symbol.kind.token_type(), declaration | readonly)
+                    }
+                    None => ("variable", 0),
+                });
+            }
+            TokenType::Eq
+            | TokenType::EqEq
+            | TokenType::Ne
+            | TokenType::Lt
+            | TokenType::Gt
+            | TokenType::Lte
+            | TokenType::Gte
+            | TokenType::Plus
+            | TokenType::PlusPlus
+            | TokenType::PlusEq
+            | TokenType::Minus
+            | TokenType::MinusMinus
+            | TokenType::MinusEq
+            | TokenType::Star
+            | TokenType::StarStar
+            | TokenType::StarEq
+            | TokenType::Slash
+            | TokenType::SlashEq
+            | TokenType::Caret
+            | TokenType::Exclamation
+            | TokenType::SmallRArrow
+            | TokenType::LargeRArrow => "operator",
+            _ => return None,
+        };
+
+        Some((typ, 0))
+    }
+
+    /// The semantic tokens of the document, encoded relative to each
+    /// other as the protocol expects. Tokens spanning lines are split
+    /// into one token per line.
+    #[must_use]
+    pub fn semantic_tokens(&self) -> Vec<usize> {
+        let mut data = Vec::new();
+        let (mut last_line, mut last_start) = (0, 0);
+        let mut parsed = 0;
+
+        for token in &self.trivia {
+            // The trivia tokens that aren't trivia line up with the
+            // tokens the parser saw
+            let idx = (!token.typ.is_trivia()).then(|| {
+                parsed += 1;
+                parsed - 1
+            });
+
+            let Some((typ, modifiers)) = self.classify(token, idx) else {
+                continue;
+            };
+
+            let typ = TOKEN_TYPES
+                .iter()
+                .position(|t| *t == typ)
+                .unwrap_or_default();
+
+            for (n, part) in token.text().split(['\n', '\r']).enumerate() {
+                let len = part.encode_utf16().count();
+                if len == 0 {
+                    continue;
+                }
+
+                let line = token.loc.line - 1 + n;
+                let start = if n == 0 {
+                    self.utf16_col(token.loc.line, token.loc.col)
+                } else {
+                    0
+                };
+
+                let delta_start = if line == last_line {
+                    start - last_start
+                } else {
+                    start
+                };
+
+                data.extend([line - last_line, delta_start, len, typ, modifiers]);
+                (last_line, last_start) = (line, start);
+            }
+        }
+
+        data
+    }
+
+    /// The range of a declaration from its `$` or `&` up to the end of
+    /// its body.
+    fn extent(&self, loc: &Loc, name: &str) -> Json {
+        let Some(token) = self.token_at_loc(loc) else {
+            return self.name_range(loc, name);
+        };
+
+        let start = match token.checked_sub(1).map(|i| &self.tokens[i]) {
+            Some(sigil) if matches!(sigil.typ, TokenType::Dollar | TokenType::And) => &sigil.loc,
+            _ => loc,
+        };
+
+        match self.body_after(token) {
+            Some(body) => {
+                let end = self.block_end(body, self.depths[body - 1] + 1);
+                let end = self
+                    .tokens
+                    .get(end)
+                    .map_or(&self.tokens[body].loc, |t| &t.loc);
+                self.range(start, &Loc::at(end.line, end.col + 1))
+            }
+            _ => self.name_range(loc, name),
+        }
+    }
+
+    fn document_symbol(
+        &self,
+        name: &str,
+        kind: SymbolKind,
+        detail: String,
+        range: Json,
+        loc: &Loc,
+        children: Vec<Json>,
+    ) -> Json {
+        Json::object(vec![
+            ("name", Json::str(name)),
+            ("detail", Json::String(detail)),
+            ("kind", Json::int(kind.number())),
+            ("range", range),
+            ("selectionRange", self.name_range(loc, name)),
+            ("children", Json::Array(children)),
+        ])
+    }
+
+    fn func_symbol(&self, func: &FuncDecl, kind: SymbolKind) -> Json {
+        self.document_symbol(
+            func.name.as_str(),
+            kind,
+            Formatter::signature(func),
+            self.extent(&func.loc, func.name.as_str()),
+            &func.loc,
+            self.outline(&func.body),
+        )
+    }
+
+    /// The functions and classes declared in some statements, as
+    /// protocol document symbols.
+    #[must_use]
+    pub fn outline(&self, stmts: &[Stmt]) -> Vec<Json> {
+        let mut symbols = Vec::new();
+
+        for stmt in stmts {
+            match stmt {
+                Stmt::Func(func) => symbols.push(self.func_symbol(func, SymbolKind::Function)),
+                Stmt::Class(class) => {
+                    let fields = class.fields.iter().map(|field| {
+                        self.document_symbol(
+                            field.name.as_str(),
+                            SymbolKind::Field,
+                            format!("{} {}", Formatter::typ(&field.typ), field.name),
+                            self.name_range(&field.loc, field.name.as_str()),
+                            &field.loc,
+                            Vec::new(),
+                        )
+                    });
+
+                    let methods = class
+                        .methods
+                        .iter()
+                        .map(|method| self.func_symbol(method, SymbolKind::Method));
+
+                    symbols.push(self.document_symbol(
+                        class.name.as_str(),
+                        SymbolKind::Class,
+                        format!("&{}", class.name),
+                        self.extent(&class.loc, class.name.as_str()),
+                        &class.loc,
+                        fields.chain(methods).collect(),
+                    ));
+                }
+                Stmt::If(_, body, orelse, _) => {
+                    symbols.extend(self.outline(body));
+                    symbols.extend(self.outline(orelse.as_deref().unwrap_or_default()));
+                }
+                Stmt::While(_, body, _) => symbols.extend(self.outline(body)),
+                _ => (),
+            }
+        }
+
+        symbols
+    }
+}
+
+/// Reads a single message, framed by a `Content-Length` header.
+///
+/// # Returns
+/// - [`Result<Option<Json>, String>`] - The message, or [`None`] at the
+///   end of the input.
+///
+/// # Errors
+/// - If the input couldn't be read, or the message is malformed.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Json>, String> {
+    read_body(input)?.map(|body| Json::parse(&body)).transpose()
+}
+
+/// Reads the body of a single message, framed by a `Content-Length`
+/// header, without parsing it.
+///
+/// # Returns
+/// - [`Result<Option<String>, String>`] - The body, or [`None`] at the
+///   end of the input.
+///
+/// # Errors
+/// - If the input couldn't be read, the framing is malformed, or the
+///   body is bigger than [`MAX_MESSAGE`].
+pub fn read_body(input: &mut impl BufRead) -> Result<Option<String>, String> {
+    let Some(length) = read_length(input)? else {
+        return Ok(None);
+    };
+
+    if length > MAX_MESSAGE {
+        skip_body(input, length)?;
+        let message = too_large(length);
+        return super::exc!("{}", message);
+    }
+
+    read_exact_body(input, length).map(Some)
+}
+
+/// Why a message bigger than [`MAX_MESSAGE`] wasn't read.
+fn too_large(length: usize) -> String {
+    format!("Message of {length} bytes is bigger than the limit of {MAX_MESSAGE}")
+}
+
+/// Reads the headers of a message, returning its `Content-Length`, or
+/// [`None`] at the end of the input.
+fn read_length(input: &mut impl BufRead) -> Result<Option<usize>, String> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+
+        match input.read_line(&mut line) {
+            Ok(0) => return Ok(None),
+            Ok(_) => (),
+            Err(e) => return super::exc!("Failed to read message: {}", e),
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    match length {
+        Some(length) => Ok(Some(length)),
+        None => super::exc!("Message is missing a Content-Length header"),
+    }
+}
+
+/// Skips the body of a message without reading it into memory. A body
+/// cut short by the end of the input is skipped too.
+fn skip_body(input: &mut impl BufRead, length: usize) -> Result<(), String> {
+    let length = u64::try_from(length).unwrap_or(u64::MAX);
+
+    match io::copy(&mut input.take(length), &mut io::sink()) {
+        Ok(_) => Ok(()),
+        Err(e) => super::exc!("Failed to read message: {}", e),
+    }
+}
+
+/// Reads a body of the given length.
+fn read_exact_body(input: &mut impl BufRead, length: usize) -> Result<String, String> {
+    let mut body = vec![0; length];
+    if let Err(e) = input.read_exact(&mut body) {
+        return super::exc!("Failed to read message: {}", e);
+    }
+
+    match String::from_utf8(body) {
+        Ok(body) => Ok(body),
+        Err(_) => super::exc!("Message is not valid UTF-8"),
+    }
+}
+
+/// Writes a single message, framed by a `Content-Length` header.
+///
+/// # Errors
+/// - If the output couldn't be written to.
+pub fn write_message(output: &mut impl Write, message: &Json) -> Result<(), String> {
+    let body = message.write();
+
+    let written = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+
+    match written.and_then(|()| output.flush()) {
+        Ok(()) => Ok(()),
+        Err(e) => super::exc!("Failed to write message: {}", e),
+    }
+}
+
+/// The response to a request, with its result or error.
+fn reply(id: Json, result: Result<Json, (i32, String)>) -> Json {
+    let result = match result {
+        Ok(result) => ("result", result),
+        Err((code, message)) => (
+            "error",
+            Json::object(vec![
+                ("code", Json::Int(code.into())),
+                ("message", Json::String(message)),
+            ]),
+        ),
+    };
+
+    Json::object(vec![("jsonrpc", Json::str("2.0")), ("id", id), result])
+}
+
+/// The capabilities sent in reply to `initialize`.
+fn capabilities() -> Json {
+    let strings = |items: &[&str]| Json::Array(items.iter().map(|s| Json::str(s)).collect());
+
+    Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                // Documents are sent in full on every change
+                ("textDocumentSync", Json::int(1)),
+                ("hoverProvider", Json::Bool(true)),
+                ("definitionProvider", Json::Bool(true)),
+                ("documentSymbolProvider", Json::Bool(true)),
+                (
+                    "semanticTokensProvider",
+                    Json::object(vec![
+                        (
+                            "legend",
+                            Json::object(vec![
+                                ("tokenTypes", strings(&TOKEN_TYPES)),
+                                ("tokenModifiers", strings(&TOKEN_MODIFIERS)),
+                            ]),
+                        ),
+                        ("full", Json::Bool(true)),
+                    ]),
+                ),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![
+                ("name", Json::str("why-lsp")),
+                ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+            ]),
+        ),
+    ])
+}
+
+impl Server {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles messages from the input until the client exits or the
+    /// input ends. A message that isn't valid JSON is answered with a
+    /// parse error, and one bigger than [`MAX_MESSAGE`] with an invalid
+    /// request error, and the server carries on.
+    ///
+    /// # Errors
+    /// - If a message couldn't be read or written, or isn't framed by a
+    ///   `Content-Length` header.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> Result<(), String> {
+        while !self.exited {
+            let Some(length) = read_length(&mut input)? else {
+                break;
+            };
+
+            let replies = if length > MAX_MESSAGE {
+                skip_body(&mut input, length)?;
+                vec![reply(Json::Null, Err((INVALID_REQUEST, too_large(length))))]
+            } else {
+                match Json::parse(&read_exact_body(&mut input, length)?) {
+                    Ok(message) => self.handle(&message),
+                    Err(e) => vec![reply(Json::Null, Err((PARSE_ERROR, e)))],
+                }
+            };
+
+            for reply in replies {
+                write_message(&mut output, &reply)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a single message, returning the messages to send back.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let Some(method) = message.get("method").as_str() else {
+            // A response to a request we never sent
+            return Vec::new();
+        };
+
+        let params = message.get("params");
+
+        match message.get("id") {
+            Json::Null => self.notify(method, params),
+            id => vec![reply(id.clone(), self.request(method, params))],
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i32, String)> {
+        if self.shutdown {
+            return Err((INVALID_REQUEST, "The server is shutting down".to_string()));
+        }
+
+        if !self.initialized && method != "initialize" {
+            return Err((
+                SERVER_NOT_INITIALIZED,
+                "The server is not initialized".to_string(),
+            ));
+        }
+
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .as_str()
+            .unwrap_or_default();
+        let document = self.documents.get(uri);
+        let position = params.get("position");
+
+        match method {
+            "initialize" => {
+                self.initialized = true;
+                Ok(capabilities())
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => Ok(document
+                .and_then(|doc| doc.hover(position))
+                .unwrap_or(Json::Null)),
+            "textDocument/definition" => Ok(document
+                .and_then(|doc| {
+                    let symbol = doc.definition(position)?;
+                    Some(Json::object(vec![
+                        ("uri", Json::str(uri)),
+                        ("range", doc.name_range(&symbol.loc, &symbol.name)),
+                    ]))
+                })
+                .unwrap_or(Json::Null)),
+            "textDocument/semanticTokens/full" => {
+                let data = document.map(Analysis::semantic_tokens).unwrap_or_default();
+                Ok(Json::object(vec![(
+                    "data",
+                    Json::Array(data.into_iter().map(Json::int).collect()),
+                )]))
+            }
+            "textDocument/documentSymbol" => Ok(Json::Array(
+                document
+                    .and_then(|doc| match &doc.program {
+                        Some(Expr::Main(stmts)) => Some(doc.outline(stmts)),
+                        _ => None,
+                    })
+                    .unwrap_or_default(),
+            )),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{method}'"))),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let uri = params
+            .path(&["textDocument", "uri"])
+            .as_str()
+            .unwrap_or_default();
+
+        let text = match method {
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            "textDocument/didOpen" => params.path(&["textDocument", "text"]).as_str(),
+            // Only full changes are asked for, so the last one has the
+            // whole document
+            "textDocument/didChange" => params
+                .get("contentChanges")
+                .as_array()
+                .and_then(<[Json]>::last)
+                .and_then(|change| change.get("text").as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish(uri, Json::Array(Vec::new()))];
+            }
+            _ => return Vec::new(),
+        };
+
+        let Some(text) = text else {
+            return Vec::new();
+        };
+
+        let analysis = Analysis::new(text);
+        let diagnostics = analysis.diagnostics();
+        self.documents.insert(uri.to_string(), analysis);
+
+        vec![publish(uri, diagnostics)]
+    }
+}
+
+/// A `textDocument/publishDiagnostics` notification.
+fn publish(uri: &str, diagnostics: Json) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::str("2.0")),
+        ("method", Json::str("textDocument/publishDiagnostics")),
+        (
+            "params",
+            Json::object(vec![("uri", Json::str(uri)), ("diagnostics", diagnostics)]),
+        ),
+    ])
+}
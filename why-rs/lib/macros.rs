@@ -59,11 +59,11 @@ macro_rules! lex_exc {
 #[macro_export]
 macro_rules! make_token {
     ($typ:expr, $val:literal, $lexer:ident) => {{
-        $crate::Token::with_value_at($typ, $val.to_string(), $lexer.line, $lexer.col)
+        $crate::Token::with_value_at($typ, $val, $lexer.line, $lexer.col)
     }};
 
     ($typ:expr, $val:expr, $lexer:ident) => {{
-        $crate::Token::with_value_at($typ, $val.to_string(), $lexer.line, $lexer.col)
+        $crate::Token::with_value_at($typ, $val, $lexer.line, $lexer.col)
     }};
 }
 
@@ -90,3 +90,41 @@ macro_rules! make_token_mut_ok {
         Ok(())
     }};
 }
+
+#[macro_export]
+macro_rules! parse_exc {
+    ($token:expr, $msg:literal) => {{
+        let error = format!(
+            "line {}, column {}: --> {}",
+            $token.loc.line, $token.loc.col, $msg,
+        );
+
+        Err(error)
+    }};
+
+    ($token:expr, $msg:literal, $($args:expr),*) => {{
+        let error = format!(
+            "line {}, column {}: --> {}",
+            $token.loc.line, $token.loc.col, format!($msg, $($args),*)
+        );
+
+        Err(error)
+    }};
+}
+
+#[macro_export]
+macro_rules! loc_exc {
+    ($loc:expr, $msg:literal) => {{
+        let error = format!("line {}, column {}: --> {}", $loc.line, $loc.col, $msg);
+        Err(error)
+    }};
+
+    ($loc:expr, $msg:literal, $($args:expr),*) => {{
+        let error = format!(
+            "line {}, column {}: --> {}",
+            $loc.line, $loc.col, format!($msg, $($args),*)
+        );
+
+        Err(error)
+    }};
+}
@@ -0,0 +1,128 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::Binding;
+use super::Field;
+use super::FuncDecl;
+use super::Symbol;
+use super::Value;
+use super::VarType;
+
+/// A class declared at runtime, with the fields and methods it
+/// inherited from its base class, if any.
+#[derive(Debug, PartialEq)]
+pub struct Class {
+    pub name: Symbol,
+    /// Every field, those of the base class first.
+    pub fields: Vec<Field>,
+    /// Every method by name, overriding those of the base class.
+    pub methods: HashMap<Symbol, Rc<FuncDecl>>,
+}
+
+/// The fields of an object, an instance of a class.
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: HashMap<Symbol, Binding>,
+    /// How many bytes the fields take against a memory limit.
+    pub size: usize,
+    /// Where the bytes are given back once the object is freed, if
+    /// there is a memory limit, see [`super::Memory::freed`].
+    pub freed: Option<Rc<Cell<usize>>>,
+}
+
+/// A reference to an instance of a class. Unlike arrays and mappings,
+/// objects aren't copied when they are assigned, so they can refer to
+/// each other in cycles, which [`super::Collector`] frees.
+///
+/// Objects are equal if they are the same object.
+#[derive(Clone)]
+pub struct Object(pub Rc<RefCell<Instance>>);
+
+impl Class {
+    /// Whether a method takes `&self`, so it's bound to the object it
+    /// is looked up on.
+    #[must_use]
+    pub fn binds(method: &FuncDecl) -> bool {
+        method.params.first().is_some_and(|p| p.name == "self")
+    }
+
+    /// The type Why code sees a method as, without its `&self`.
+    #[must_use]
+    pub fn method_type(method: &FuncDecl) -> VarType {
+        let skip = usize::from(Class::binds(method));
+
+        VarType::Function(
+            method.params[skip..]
+                .iter()
+                .map(|p| p.typ.clone())
+                .collect(),
+            Box::new(method.ret.clone()),
+        )
+    }
+}
+
+impl Object {
+    #[must_use]
+    pub fn new(class: Rc<Class>, fields: HashMap<Symbol, Binding>) -> Self {
+        Object(Rc::new(RefCell::new(Instance {
+            class,
+            fields,
+            size: 0,
+            freed: None,
+        })))
+    }
+
+    /// The name of the object's class.
+    #[must_use]
+    pub fn class(&self) -> Symbol {
+        self.0.borrow().class.name
+    }
+
+    /// Calls `f` with every object a value refers to directly, without
+    /// looking inside those objects.
+    pub fn children(value: &Value, f: &mut impl FnMut(&Object)) {
+        match value {
+            Value::Object(object) | Value::Method(object, _) => f(object),
+            Value::Array(items) => items.iter().for_each(|item| Object::children(item, f)),
+            Value::Mapping(pairs) => pairs.iter().for_each(|(key, value)| {
+                Object::children(key, f);
+                Object::children(value, f);
+            }),
+            _ => (),
+        }
+    }
+
+    /// Whether a value refers to any object.
+    #[must_use]
+    pub fn held_by(value: &Value) -> bool {
+        let mut held = false;
+        Object::children(value, &mut |_| held = true);
+        held
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        if let Some(freed) = &self.freed {
+            freed.set(freed.get() + self.size);
+        }
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Objects may refer to themselves, so only their class is shown.
+impl std::fmt::Debug for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.try_borrow() {
+            Ok(instance) => write!(f, "<{} object>", instance.class.name),
+            Err(_) => write!(f, "<object>"),
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::HashSet;
+
+use super::ClassDecl;
+use super::Expr;
+use super::FuncDecl;
+use super::Interpreter;
+use super::Operator;
+use super::Stmt;
+use super::Symbol;
+use super::Value;
+
+/// Rewrites a program into a cheaper one that behaves the same, by
+/// passes enabled by its level:
+///
+/// - `1` folds arithmetic on literals, and removes branches whose
+///   condition is a literal.
+/// - `2` also removes declarations of variables that are never used.
+///
+/// The program should have passed the [`Checker`](super::Checker), as
+/// code that fails is left as it is to fail when it's run.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Optimizer {
+    pub level: u8,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Optimizer {
+    /// The highest level, which enables every pass.
+    pub const MAX_LEVEL: u8 = 2;
+
+    /// Creates an optimizer with every pass enabled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            level: Self::MAX_LEVEL,
+        }
+    }
+
+    /// Sets the level, which decides which passes run.
+    ///
+    /// # Errors
+    /// - If the level is above [`Optimizer::MAX_LEVEL`].
+    pub fn with_level(mut self, level: u8) -> Result<Self, String> {
+        if level > Self::MAX_LEVEL {
+            return super::exc!(
+                "Unknown optimization level {}, expected 0 to {}",
+                level,
+                (Self::MAX_LEVEL)
+            );
+        }
+
+        self.level = level;
+        Ok(self)
+    }
+
+    /// Optimizes a program.
+    ///
+    /// # Returns
+    /// - [`Expr`] - The optimized program.
+    #[must_use]
+    pub fn optimize(&self, program: &Expr) -> Expr {
+        if self.level == 0 {
+            return program.clone();
+        }
+
+        let Expr::Main(stmts) = program else {
+            return Optimizer::fold(program.clone());
+        };
+
+        // The last statement is what the program evaluates to, so it's
+        // kept even if it would be spliced in or removed
+        let mut stmts = stmts.clone();
+        let last = stmts.pop().map(Optimizer::stmt);
+        stmts = Optimizer::block(stmts);
+
+        if self.level >= 2 {
+            let mut used = HashSet::new();
+            Optimizer::mentions(&stmts, &mut used);
+            Optimizer::mentions(last.as_slice(), &mut used);
+            stmts = Optimizer::remove_unused(stmts, &used);
+        }
+
+        stmts.extend(last);
+        Expr::Main(stmts)
+    }
+
+    /// Optimizes the statements of a block, splicing in the branches of
+    /// `if`s that always go the same way.
+    fn block(stmts: Vec<Stmt>) -> Vec<Stmt> {
+        let mut optimized = Vec::with_capacity(stmts.len());
+
+        for stmt in stmts {
+            match Optimizer::stmt(stmt) {
+                Stmt::If(Expr::Bool(cond), body, otherwise, loc) => {
+                    let taken = if cond { Some(body) } else { otherwise };
+
+                    match taken {
+                        // A branch is its own scope, so it can only be
+                        // spliced in if it declares nothing
+                        Some(body) if body.iter().any(Optimizer::declares) => {
+                            optimized.push(Stmt::If(Expr::Bool(true), body, None, loc));
+                        }
+                        Some(body) => optimized.extend(body),
+                        None => (),
+                    }
+                }
+                Stmt::While(Expr::Bool(false), ..) => (),
+                stmt => optimized.push(stmt),
+            }
+        }
+
+        optimized
+    }
+
+    /// Whether a statement declares a name in its block.
+    fn declares(stmt: &Stmt) -> bool {
+        matches!(stmt, Stmt::VarDecl(_) | Stmt::Func(_) | Stmt::Class(_))
+    }
+
+    fn stmt(stmt: Stmt) -> Stmt {
+        match stmt {
+            Stmt::Expr(expr, loc) => Stmt::Expr(Optimizer::fold(expr), loc),
+            Stmt::VarDecl(mut decl) => {
+                decl.value = Optimizer::fold(decl.value);
+                Stmt::VarDecl(decl)
+            }
+            Stmt::Assign(op, target, value, loc) => {
+                Stmt::Assign(op, Optimizer::fold(target), Optimizer::fold(value), loc)
+            }
+            Stmt::Func(func) => Stmt::Func(Optimizer::func(func)),
+            Stmt::Class(mut class) => {
+                for field in &mut class.fields {
+                    field.value = field.value.take().map(Optimizer::fold);
+                }
+
+                class.methods = class.methods.into_iter().map(Optimizer::func).collect();
+                Stmt::Class(class)
+            }
+            Stmt::Return(value, loc) => Stmt::Return(value.map(Optimizer::fold), loc),
+            Stmt::If(cond, body, otherwise, loc) => Stmt::If(
+                Optimizer::fold(cond),
+                Optimizer::block(body),
+                otherwise.map(Optimizer::block),
+                loc,
+            ),
+            Stmt::While(cond, body, loc) => {
+                Stmt::While(Optimizer::fold(cond), Optimizer::block(body), loc)
+            }
+            stmt @ (Stmt::Break | Stmt::Delete(..) | Stmt::Comment(..)) => stmt,
+        }
+    }
+
+    fn func(mut func: FuncDecl) -> FuncDecl {
+        func.body = Optimizer::block(func.body);
+        func
+    }
+
+    /// Folds the operators applied to literals into the literal they
+    /// evaluate to. An operator that would fail is left to fail at
+    /// runtime.
+    ///
+    /// # Returns
+    /// - [`Expr`] - The folded expression.
+    #[must_use]
+    pub fn fold(expr: Expr) -> Expr {
+        let folded = match expr {
+            Expr::Parenthesized(inner) => match Optimizer::fold(*inner) {
+                inner if Optimizer::literal(&inner).is_some() => return inner,
+                inner => Expr::Parenthesized(Box::new(inner)),
+            },
+            Expr::BinaryOp(op, left, right) => Expr::BinaryOp(
+                op,
+                Box::new(Optimizer::fold(*left)),
+                Box::new(Optimizer::fold(*right)),
+            ),
+            Expr::UnaryOp(op, operand) => Expr::UnaryOp(op, Box::new(Optimizer::fold(*operand))),
+            Expr::Compare(cond, left, right) => Expr::Compare(
+                cond,
+                Box::new(Optimizer::fold(*left)),
+                Box::new(Optimizer::fold(*right)),
+            ),
+            Expr::Call(callee, args, loc) => Expr::Call(
+                Box::new(Optimizer::fold(*callee)),
+                args.into_iter().map(Optimizer::fold).collect(),
+                loc,
+            ),
+            Expr::Index(base, index) => Expr::Index(
+                Box::new(Optimizer::fold(*base)),
+                Box::new(Optimizer::fold(*index)),
+            ),
+            Expr::Array(items) => Expr::Array(items.into_iter().map(Optimizer::fold).collect()),
+            Expr::Mapping(pairs) => Expr::Mapping(
+                pairs
+                    .into_iter()
+                    .map(|(k, v)| (Optimizer::fold(k), Optimizer::fold(v)))
+                    .collect(),
+            ),
+            Expr::Main(stmts) => Expr::Main(Optimizer::block(stmts)),
+            expr => return expr,
+        };
+
+        let value = match &folded {
+            Expr::BinaryOp(op, left, right) if *op != Operator::Dot => {
+                match (Optimizer::literal(left), Optimizer::literal(right)) {
+                    (Some(l), Some(r)) => Interpreter::binary(op, l, r).ok(),
+                    _ => None,
+                }
+            }
+            // A negative literal is already as folded as it gets
+            Expr::UnaryOp(Operator::Subtract, operand)
+                if matches!(**operand, Expr::Int(_) | Expr::Float(_)) =>
+            {
+                None
+            }
+            Expr::UnaryOp(op, operand) => {
+                Optimizer::literal(operand).and_then(|value| Interpreter::unary(op, value).ok())
+            }
+            Expr::Compare(cond, left, right) => {
+                match (Optimizer::literal(left), Optimizer::literal(right)) {
+                    (Some(l), Some(r)) => Interpreter::compare(cond, &l, &r).ok(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        value.and_then(Optimizer::to_expr).unwrap_or(folded)
+    }
+
+    /// The value of a literal, including a negated number.
+    #[must_use]
+    pub fn literal(expr: &Expr) -> Option<Value> {
+        match expr {
+            Expr::Int(num) => Some(Value::Int(*num)),
+            Expr::Float(num) => Some(Value::Float(*num)),
+            Expr::Bool(b) => Some(Value::Bool(*b)),
+            Expr::String(s) => Some(Value::String(s.clone())),
+            Expr::UnaryOp(Operator::Subtract, operand) => match **operand {
+                Expr::Int(num) => num.checked_neg().map(Value::Int),
+                Expr::Float(num) => Some(Value::Float(-num)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The literal a value is written as, if it can be written as one
+    /// the parser could have made.
+    fn to_expr(value: Value) -> Option<Expr> {
+        let negate = |expr| Expr::UnaryOp(Operator::Subtract, Box::new(expr));
+
+        match value {
+            Value::Int(num) if num >= 0 => Some(Expr::Int(num)),
+            Value::Int(num) => num.checked_neg().map(|num| negate(Expr::Int(num))),
+            Value::Float(num) if !num.is_finite() => None,
+            Value::Float(num) if num.is_sign_negative() => Some(negate(Expr::Float(-num))),
+            Value::Float(num) => Some(Expr::Float(num)),
+            Value::Bool(b) => Some(Expr::Bool(b)),
+            Value::String(s) => Some(Expr::String(s)),
+            _ => None,
+        }
+    }
+
+    /// Removes the declarations of variables which are never mentioned,
+    /// when declaring them can't fail.
+    fn remove_unused(stmts: Vec<Stmt>, used: &HashSet<Symbol>) -> Vec<Stmt> {
+        let remove = |block| Optimizer::remove_unused(block, used);
+
+        stmts
+            .into_iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::VarDecl(decl) if !used.contains(&decl.name) => {
+                    let accepted = Optimizer::literal(&decl.value)
+                        .is_some_and(|v| decl.typ.as_ref().is_none_or(|t| t.accepts(&v.typ())));
+
+                    (!accepted).then_some(Stmt::VarDecl(decl))
+                }
+                Stmt::Func(func) => Some(Stmt::Func(Optimizer::remove_unused_in(func, used))),
+                Stmt::Class(mut class) => {
+                    class.methods = class
+                        .methods
+                        .into_iter()
+                        .map(|func| Optimizer::remove_unused_in(func, used))
+                        .collect();
+                    Some(Stmt::Class(class))
+                }
+                Stmt::If(cond, body, otherwise, loc) => {
+                    Some(Stmt::If(cond, remove(body), otherwise.map(remove), loc))
+                }
+                Stmt::While(cond, body, loc) => Some(Stmt::While(cond, remove(body), loc)),
+                stmt => Some(stmt),
+            })
+            .collect()
+    }
+
+    fn remove_unused_in(mut func: FuncDecl, used: &HashSet<Symbol>) -> FuncDecl {
+        func.body = Optimizer::remove_unused(func.body, used);
+        func
+    }
+
+    /// Collects every name the statements mention, other than where
+    /// they declare it.
+    fn mentions(stmts: &[Stmt], used: &mut HashSet<Symbol>) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Expr(expr, _) | Stmt::Return(Some(expr), _) => {
+                    Optimizer::mentions_in(expr, used);
+                }
+                Stmt::VarDecl(decl) => Optimizer::mentions_in(&decl.value, used),
+                Stmt::Assign(_, target, value, _) => {
+                    Optimizer::mentions_in(target, used);
+                    Optimizer::mentions_in(value, used);
+                }
+                Stmt::Func(func) => Optimizer::mentions(&func.body, used),
+                Stmt::Class(ClassDecl {
+                    fields, methods, ..
+                }) => {
+                    for value in fields.iter().filter_map(|f| f.value.as_ref()) {
+                        Optimizer::mentions_in(value, used);
+                    }
+
+                    for method in methods {
+                        Optimizer::mentions(&method.body, used);
+                    }
+                }
+                Stmt::If(cond, body, otherwise, _) => {
+                    Optimizer::mentions_in(cond, used);
+                    Optimizer::mentions(body, used);
+                    Optimizer::mentions(otherwise.as_deref().unwrap_or_default(), used);
+                }
+                Stmt::While(cond, body, _) => {
+                    Optimizer::mentions_in(cond, used);
+                    Optimizer::mentions(body, used);
+                }
+                // Deleting a variable needs it to have been declared
+                Stmt::Delete(name, _) => {
+                    used.insert(*name);
+                }
+                Stmt::Return(None, _) | Stmt::Break | Stmt::Comment(..) => (),
+            }
+        }
+    }
+
+    fn mentions_in(expr: &Expr, used: &mut HashSet<Symbol>) {
+        match expr {
+            Expr::Ident(name) => {
+                used.insert(*name);
+            }
+            Expr::Parenthesized(inner) | Expr::UnaryOp(_, inner) => {
+                Optimizer::mentions_in(inner, used);
+            }
+            Expr::BinaryOp(_, left, right)
+            | Expr::Compare(_, left, right)
+            | Expr::Index(left, right) => {
+                Optimizer::mentions_in(left, used);
+                Optimizer::mentions_in(right, used);
+            }
+            Expr::Call(callee, args, _) => {
+                Optimizer::mentions_in(callee, used);
+                for arg in args {
+                    Optimizer::mentions_in(arg, used);
+                }
+            }
+            Expr::Array(items) => {
+                for item in items {
+                    Optimizer::mentions_in(item, used);
+                }
+            }
+            Expr::Mapping(pairs) => {
+                for (key, value) in pairs {
+                    Optimizer::mentions_in(key, used);
+                    Optimizer::mentions_in(value, used);
+                }
+            }
+            Expr::Main(stmts) => Optimizer::mentions(stmts, used),
+            _ => (),
+        }
+    }
+}
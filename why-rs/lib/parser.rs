@@ -1,58 +1,384 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::rc::Rc;
 use std::{iter::Peekable, slice::Iter};
 
-use crate::Keyword;
-
-// use super::Condition;
+use super::trace::{self, Stage, Tracer};
+use super::ClassDecl;
+use super::Condition;
 use super::Expr;
-// use super::Keyword;
+use super::Field;
+use super::FuncDecl;
+use super::Keyword;
+use super::Lexer;
+use super::Loc;
 use super::Operator;
-// use super::Stmt;
+use super::Param;
+use super::Stmt;
+use super::Symbol;
 use super::Token;
 use super::TokenType;
-// use super::VarType;
+use super::VarDecl;
+use super::VarType;
+
+/// How deeply statements, expressions and types may nest, so deep
+/// nesting is a syntax error rather than overflowing the stack, here or
+/// in the passes that walk the tree afterwards. Each parenthesis or
+/// block nests two levels.
+pub const MAX_NESTING: usize = 256;
 
 type ParseResult = Result<Expr, String>;
-// type StmtRes = Result<Stmt, String>;
+type StmtResult = Result<Stmt, String>;
 
-#[derive(Debug)]
 pub struct Parser<'a> {
-    pub tokens: &'a mut Peekable<Iter<'a, Token>>,
+    /// Where tokens come from. They are only read as far as the parser
+    /// needs to look ahead, and any errors are added to
+    /// [`Parser::errors`] as they are read.
+    pub tokens: Box<dyn Iterator<Item = Result<Token<'a>, String>> + 'a>,
+    /// Tokens read but not consumed yet, trivia included.
+    pub lookahead: VecDeque<Token<'a>>,
+    /// Receives a message for each grammar rule entered.
+    pub tracer: Rc<dyn Tracer>,
+    /// Comments and doc comments that have been stepped over in trivia
+    /// mode, in source order, waiting to be placed as statements.
+    pub comments: Vec<Stmt>,
+    /// How many of the last comments are the lines of a `///` doc
+    /// comment, waiting for the declaration right below it.
+    pub doc: usize,
+    /// Every syntax error found so far. Parsing recovers from an error
+    /// by skipping to the next statement, so they can all be reported
+    /// at once.
+    pub errors: Vec<String>,
+    /// How many tokens have been consumed, to tell whether recovering
+    /// from an error needs to skip a token to make progress.
+    pub consumed: usize,
+    /// The last token consumed, where running out of tokens is
+    /// reported.
+    pub last: Option<Token<'a>>,
+    /// The names of the classes declared so far, which can be used as
+    /// types from then on.
+    pub classes: HashSet<Symbol>,
+    /// How deeply the rules being parsed are nested, see
+    /// [`MAX_NESTING`].
+    pub depth: usize,
+    /// Whether nesting went past [`MAX_NESTING`], after which the rest
+    /// of the input is skipped, and the errors that causes go
+    /// unreported.
+    pub too_deep: bool,
+}
+
+impl fmt::Debug for Parser<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Parser")
+            .field("lookahead", &self.lookahead)
+            .field("comments", &self.comments)
+            .field("doc", &self.doc)
+            .field("errors", &self.errors)
+            .field("consumed", &self.consumed)
+            .field("last", &self.last)
+            .field("classes", &self.classes)
+            .field("depth", &self.depth)
+            .field("too_deep", &self.too_deep)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<'a> Parser<'a> {
+    /// Creates a parser over borrowed tokens, copying each one as it is
+    /// read.
     #[must_use]
-    pub fn new(tokens: &'a mut Peekable<Iter<'a, Token>>) -> Self {
-        Self { tokens }
+    pub fn new(tokens: &'a mut Peekable<Iter<'a, Token<'a>>>) -> Self {
+        Self::from_stream(tokens.cloned())
     }
 
-    #[allow(clippy::should_implement_trait)]
-    pub fn next(&mut self) -> Option<&Token> {
-        self.tokens.next()
+    /// Creates a parser that owns its tokens.
+    #[must_use]
+    pub fn from_tokens(tokens: Vec<Token<'a>>) -> Self {
+        Self::from_stream(tokens.into_iter())
+    }
+
+    /// Creates a parser that reads tokens from an iterator as it needs
+    /// them.
+    #[must_use]
+    pub fn from_stream(tokens: impl Iterator<Item = Token<'a>> + 'a) -> Self {
+        Self::from_results(tokens.map(Ok))
+    }
+
+    /// Creates a parser that lexes tokens as it needs them. Lex errors
+    /// are reported along with syntax errors.
+    #[must_use]
+    pub fn from_lexer(lexer: Lexer<'a>) -> Self {
+        Self::from_results(lexer)
     }
 
-    pub fn peek(&mut self) -> Option<&&Token> {
-        self.tokens.peek()
+    fn from_results(tokens: impl Iterator<Item = Result<Token<'a>, String>> + 'a) -> Self {
+        Self {
+            tokens: Box::new(tokens),
+            lookahead: VecDeque::new(),
+            tracer: trace::silent(),
+            comments: Vec::new(),
+            doc: 0,
+            errors: Vec::new(),
+            consumed: 0,
+            last: None,
+            classes: HashSet::new(),
+            depth: 0,
+            too_deep: false,
+        }
     }
 
-    /// Expects a particular token type next.
+    /// Creates a parser that lexes source as it needs tokens.
     ///
     /// # Returns
-    /// - [`()`] - Unit type on success
+    /// - [`Result<Self, String>`] - The new parser on success.
+    ///
+    /// # Errors
+    /// - If the source is empty.
+    pub fn from_source(src: &'a str) -> Result<Self, String> {
+        Ok(Self::from_lexer(Lexer::new(src)?))
+    }
+
+    /// Sets the tracer that receives a message for each grammar rule
+    /// entered.
+    #[must_use]
+    pub fn with_tracer(mut self, tracer: Rc<dyn Tracer>) -> Self {
+        self.tracer = tracer;
+        self
+    }
+
+    /// Traces entering a grammar rule, along with the next token.
+    pub fn trace(&mut self, rule: &str) {
+        if self.tracer.enabled(Stage::Parser) {
+            let message = match self.peek_nth(0) {
+                Some(next) => format!("{rule} at {next}"),
+                None => format!("{rule} at end of input"),
+            };
+
+            self.tracer.trace(Stage::Parser, &message);
+        }
+    }
+
+    /// Steps over any trivia tokens, keeping comments and doc comments
+    /// to be placed later. A comment trails the code before it unless a
+    /// newline comes between them, and a doc comment is only kept for a
+    /// declaration if no other comment comes between them.
+    pub fn skip_comments(&mut self) {
+        self.peek_nth(0);
+        let trivia = self
+            .lookahead
+            .iter()
+            .take_while(|t| t.typ.is_trivia())
+            .count();
+
+        let mut trailing = self.last.is_some();
+
+        for token in self.lookahead.drain(..trivia) {
+            match token.typ {
+                TokenType::LineComment | TokenType::BlockComment => {
+                    self.comments
+                        .push(Stmt::Comment(token.value.into_owned(), trailing));
+                    self.doc = 0;
+                }
+                TokenType::DocComment => {
+                    let line = match token.value.as_ref() {
+                        "" => "///".to_string(),
+                        value => format!("/// {value}"),
+                    };
+
+                    self.comments.push(Stmt::Comment(line, false));
+                    self.doc += 1;
+                }
+                TokenType::Newline => trailing = false,
+                _ => (),
+            }
+        }
+    }
+
+    /// Takes the doc comment waiting for a declaration, joining its
+    /// lines.
+    pub fn take_doc(&mut self) -> Option<String> {
+        if self.doc == 0 {
+            return None;
+        }
+
+        let start = self.comments.len() - self.doc;
+        let lines: Vec<_> = self
+            .comments
+            .drain(start..)
+            .filter_map(|comment| match comment {
+                Stmt::Comment(line, _) => {
+                    let line = line.strip_prefix("///").unwrap_or(&line);
+                    Some(line.strip_prefix(' ').unwrap_or(line).to_string())
+                }
+                _ => None,
+            })
+            .collect();
+
+        self.doc = 0;
+        Some(lines.join("\n"))
+    }
+
+    /// Takes the comments stepped over so far as statements, including
+    /// doc comments that were orphaned, but not one that is waiting for
+    /// a declaration.
+    pub fn take_comments(&mut self) -> impl Iterator<Item = Stmt> + 'a {
+        let doc = self.comments.split_off(self.comments.len() - self.doc);
+
+        std::mem::replace(&mut self.comments, doc).into_iter()
+    }
+
+    /// Keeps the waiting doc comment as a plain comment, where it is, as
+    /// there is no declaration for it to document.
+    pub fn orphan_doc(&mut self) {
+        self.doc = 0;
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Token<'a>> {
+        self.skip_comments();
+        let next = self.lookahead.pop_front()?;
+
+        self.consumed += 1;
+        self.last = Some(next.clone());
+        Some(next)
+    }
+
+    pub fn peek(&mut self) -> Option<&Token<'a>> {
+        self.skip_comments();
+        self.lookahead.front()
+    }
+
+    /// Looks `n` tokens past the next one, skipping trivia, without
+    /// consuming anything. `peek_nth(0)` is the next token.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&Token<'a>> {
+        let mut idx = 0;
+        let mut skip = n;
+
+        loop {
+            while idx == self.lookahead.len() {
+                match self.tokens.next()? {
+                    Ok(token) => self.lookahead.push_back(token),
+                    Err(e) => self.errors.push(e),
+                }
+            }
+
+            if !self.lookahead[idx].typ.is_trivia() {
+                if skip == 0 {
+                    return self.lookahead.get(idx);
+                }
+
+                skip -= 1;
+            }
+
+            idx += 1;
+        }
+    }
+
+    /// Returns true if the next token is of the given type.
+    pub fn next_is(&mut self, typ: TokenType) -> bool {
+        self.peek().is_some_and(|t| t.typ == typ)
+    }
+
+    /// Peeks at the next token, which there has to be.
     ///
     /// # Errors
-    /// - If an unexpected token was next.
+    /// - If there are no tokens left.
+    pub fn peek_next(&mut self) -> Result<&Token<'a>, String> {
+        self.skip_comments();
+
+        match self.lookahead.front() {
+            Some(next) => Ok(next),
+            None => self.end_of_input(),
+        }
+    }
+
+    /// The error for running out of tokens, placed after the last token
+    /// consumed.
     ///
-    /// # Panics
-    /// - If the next token could not be unwrapped.
-    pub fn expect(&mut self, typ: TokenType) -> Result<(), String> {
-        let next = self.next();
+    /// # Errors
+    /// - Always.
+    pub fn end_of_input<T>(&self) -> Result<T, String> {
+        match &self.last {
+            Some(last) => super::parse_exc!(last, "Unexpected end of input after {}", last),
+            None => super::exc!("Unexpected end of input."),
+        }
+    }
+
+    /// The location of the last token consumed, used for statements
+    /// whose keyword has already been consumed.
+    #[must_use]
+    pub fn last_loc(&self) -> Loc {
+        self.last
+            .as_ref()
+            .map(|t| t.loc.clone())
+            .unwrap_or_default()
+    }
+
+    /// Parses a rule that may contain itself, failing if it is nested
+    /// more than [`MAX_NESTING`] deep. The error is recorded straight
+    /// away, as the rest of the input is skipped.
+    ///
+    /// # Errors
+    /// - If the rule fails, or is nested too deeply.
+    pub fn nested<T>(
+        &mut self,
+        rule: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.depth >= MAX_NESTING {
+            let next = self.peek_next()?;
+            let result = super::parse_exc!(next, "Nested more than {} levels deep", MAX_NESTING);
+
+            // What's left is at least as deep, so rather than report
+            // it level by level, give up on it
+            while !self.at_end() {
+                self.next();
+            }
+
+            self.too_deep = true;
+            self.errors.extend(result.as_ref().err().cloned());
+            return result;
+        }
+
+        self.depth += 1;
+        let result = rule(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Returns true if there are no tokens left but the EOF token.
+    pub fn at_end(&mut self) -> bool {
+        self.peek().is_none_or(|t| t.typ == TokenType::Eof)
+    }
+
+    /// Expects a particular token type next. An unexpected token is
+    /// left for error recovery to deal with.
+    ///
+    /// # Returns
+    /// - [`Result<Token, String>`] - The expected token on success
+    ///
+    /// # Errors
+    /// - If an unexpected token was next, or there were no tokens left.
+    pub fn expect(&mut self, typ: TokenType) -> Result<Token<'a>, String> {
+        let next = self.peek_next()?;
+
+        if next.typ != typ {
+            return super::parse_exc!(next, "Expected {:?}, but got {}", typ, next);
+        }
 
-        if next.is_none() {
-            return super::exc!("Unexpected end of input.");
+        match self.next() {
+            Some(next) => Ok(next),
+            None => self.end_of_input(),
         }
+    }
 
-        if (*next.unwrap()).typ != typ {
-            return super::exc!("Expected {:?}, but got {}", typ, next.unwrap());
+    /// Expects a `;` to end a statement. The final statement in the
+    /// input may leave it off.
+    ///
+    /// # Errors
+    /// - If something other than `;` or the end of input was next.
+    pub fn expect_semi(&mut self) -> Result<(), String> {
+        if !self.next_is(TokenType::Eof) {
+            self.expect(TokenType::Semi)?;
         }
 
         Ok(())
@@ -66,186 +392,838 @@ impl<'a> Parser<'a> {
     ///
     /// # Errors
     /// - If a syntax, or other, error was encountered, or no EOF token
-    /// was found.
+    ///   was found. Every syntax error is reported, one per line.
     pub fn parse(&mut self) -> ParseResult {
-        println!();
-        println!("Entering parse loop...");
-        println!();
+        let program = self.parse_recovering();
 
-        let ast = self.parse_expr()?;
-        self.expect(TokenType::Eof)?;
-        Ok(Expr::Main(Box::new(ast)))
+        if self.errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(self.errors.join("\n"))
+        }
     }
 
-    // pub fn parse_assignment(&mut self) -> ParseResult {
-    //     let next = self.peek().unwrap();
-    //     let ident = next.value.clone();
+    /// Parses a stream of tokens into a program, carrying on past syntax
+    /// errors. The errors are left in [`Parser::errors`], and each
+    /// statement that failed to parse is replaced by an [`Expr::Error`].
+    ///
+    /// # Returns
+    /// - [`Expr`] - The program, as much of it as could be parsed.
+    pub fn parse_recovering(&mut self) -> Expr {
+        self.trace("program");
+        let stmts = self.parse_stmts(TokenType::Eof);
+
+        if let Err(e) = self.expect(TokenType::Eof) {
+            if !self.too_deep && !self.next_is(TokenType::Error) {
+                self.errors.push(e);
+            }
+        }
 
-    //     self.next();
-    //     self.expect(TokenType::Eq)?;
+        Expr::Main(stmts)
+    }
 
-    //     let expr = self.parse_expr()?;
-    //     let assignment = Expr::Assign(Box::new(Expr::Ident(ident)), Box::new(expr));
+    /// Parses statements up to, but not including, the `end` token,
+    /// placing comments between them. A statement with a syntax error
+    /// is recorded and skipped.
+    pub fn parse_stmts(&mut self, end: TokenType) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
 
-    //     Ok(assignment)
-    // }
+        while !self.at_end() && !self.next_is(end) {
+            if !self.starts_decl() {
+                self.orphan_doc();
+            }
 
-    /// Parses a terminal ast node
+            stmts.extend(self.take_comments());
+            let consumed = self.consumed;
+            let loc = self.peek_next().map(|next| next.loc.clone());
+
+            match self.parse_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(_) if self.too_deep => break,
+                Err(e) => {
+                    // The lexer has already reported a bad token
+                    if !self.next_is(TokenType::Error) {
+                        self.errors.push(e);
+                    }
+
+                    self.synchronize(end, consumed);
+                    stmts.push(Stmt::Expr(Expr::Error, loc.unwrap_or_default()));
+                }
+            }
+        }
+
+        self.orphan_doc();
+        stmts.extend(self.take_comments());
+        stmts
+    }
+
+    /// Skips tokens after a syntax error, up to where a statement can
+    /// start again: after a `;` or a `{ ... }` block, or at a keyword, a
+    /// declaration or the `}` closing the enclosing block. If nothing
+    /// was consumed since `consumed`, at least one token is skipped so
+    /// parsing moves on.
+    pub fn synchronize(&mut self, end: TokenType, consumed: usize) {
+        self.trace("synchronize");
+        self.orphan_doc();
+
+        if self.consumed == consumed && !self.at_end() && !self.next_is(end) {
+            self.next();
+        }
+
+        let mut depth = 0;
+
+        while let Some(next) = self.peek().map(|t| t.typ) {
+            match next {
+                TokenType::Eof => break,
+                TokenType::LBrace => depth += 1,
+                TokenType::RBrace if depth > 0 => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        self.next();
+                        break;
+                    }
+                }
+                _ if depth > 0 => (),
+                TokenType::Keyword => break,
+                TokenType::RBrace if end == TokenType::RBrace => break,
+                TokenType::Semi => {
+                    self.next();
+                    break;
+                }
+                _ if self.starts_decl() => break,
+                _ => (),
+            }
+
+            self.next();
+        }
+    }
+
+    /// Returns true if the next tokens start a declaration that can
+    /// have a doc comment.
+    pub fn starts_decl(&mut self) -> bool {
+        let Some(next) = self.peek() else {
+            return false;
+        };
+
+        match next.typ {
+            TokenType::Dollar => true,
+            TokenType::And => self.peek_nth(1).map(|t| t.typ) == Some(TokenType::Ident),
+            TokenType::Keyword => matches!(next.value.as_ref(), "let" | "const"),
+            TokenType::Ident => self.starts_var_decl(),
+            _ => false,
+        }
+    }
+
+    /// Parses a single statement.
+    ///
+    /// # Returns
+    /// - [`StmtResult`] - The resulting statement on success
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_stmt(&mut self) -> StmtResult {
+        self.trace("statement");
+        self.nested(|parser| {
+            let next = parser.peek_next()?.typ;
+            let doc = parser.take_doc();
+
+            let mut stmt = match next {
+                TokenType::Keyword => parser.parse_keyword()?,
+                TokenType::Dollar => parser.parse_func()?,
+                TokenType::And if parser.peek_nth(1).map(|t| t.typ) == Some(TokenType::Ident) => {
+                    parser.parse_class()?
+                }
+                TokenType::At => {
+                    parser.next();
+                    parser.parse_return()?
+                }
+                TokenType::Ident if parser.starts_var_decl() => parser.parse_var_decl(None)?,
+                _ => parser.parse_expr_stmt()?,
+            };
+
+            stmt.set_doc(doc);
+            Ok(stmt)
+        })
+    }
+
+    /// Returns true if the next tokens look like `int x` or
+    /// `array@...`, the start of a typed variable declaration.
+    pub fn starts_var_decl(&mut self) -> bool {
+        self.peek();
+        let is_type = match self.lookahead.front() {
+            Some(t) if t.typ == TokenType::Ident => {
+                let name = t.value.as_ref();
+                VarType::NAMES.contains(&name) || self.is_class(name)
+            }
+            _ => false,
+        };
+        let second = self.peek_nth(1).map(|t| t.typ);
+
+        is_type && matches!(second, Some(TokenType::Ident | TokenType::At))
+    }
+
+    /// Parses a statement beginning with a keyword.
+    ///
+    /// # Returns
+    /// - [`StmtResult`] - The resulting statement on success
+    ///
+    /// # Errors
+    /// - If the keyword is not supported yet, or a syntax error was
+    ///   encountered.
+    pub fn parse_keyword(&mut self) -> StmtResult {
+        let token = self.expect(TokenType::Keyword)?;
+        let keyword = Keyword::try_from(&token)?;
+
+        match keyword {
+            Keyword::Let | Keyword::Const => self.parse_var_decl(Some(keyword)),
+            Keyword::Return => self.parse_return(),
+            Keyword::If => self.parse_if(),
+            Keyword::While => {
+                let cond = self.parse_expr()?;
+                let body = self.parse_block()?;
+                Ok(Stmt::While(cond, body, token.loc))
+            }
+            Keyword::Break => {
+                self.expect_semi()?;
+                Ok(Stmt::Break)
+            }
+            Keyword::Delete => {
+                let name = self.expect(TokenType::Ident)?;
+                self.expect_semi()?;
+                Ok(Stmt::Delete(Symbol::intern(&name.value), name.loc))
+            }
+            _ => super::parse_exc!(token, "{} is not implemented yet", keyword),
+        }
+    }
+
+    /// Returns true if a class of the name has been declared.
+    #[must_use]
+    pub fn is_class(&self, name: &str) -> bool {
+        // A name that was never interned can't be a class
+        Symbol::lookup(name).is_some_and(|name| self.classes.contains(&name))
+    }
+
+    /// Parses a type annotation like `int`, `array@int`,
+    /// `mapping@string->int` or the name of a declared class.
+    ///
+    /// # Returns
+    /// - [`Result<VarType, String>`] - The type on success
+    ///
+    /// # Errors
+    /// - If the type name is unknown, or the annotation is malformed.
+    pub fn parse_type(&mut self) -> Result<VarType, String> {
+        self.nested(|parser| {
+            let token = parser.expect(TokenType::Ident)?;
+
+            match token.value.as_ref() {
+                name if parser.is_class(name) => Ok(VarType::Class(Symbol::intern(name))),
+                "array" => {
+                    parser.expect(TokenType::At)?;
+                    Ok(VarType::Array(Box::new(parser.parse_type()?)))
+                }
+                "mapping" => {
+                    parser.expect(TokenType::At)?;
+                    let key = parser.parse_type()?;
+                    parser.expect(TokenType::SmallRArrow)?;
+                    let value = parser.parse_type()?;
+                    Ok(VarType::Mapping(Box::new(key), Box::new(value)))
+                }
+                _ => VarType::try_from(&token),
+            }
+        })
+    }
+
+    /// Parses a variable declaration. The keyword, if any, has already
+    /// been consumed.
+    ///
+    /// # Returns
+    /// - [`StmtResult`] - The resulting statement on success
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_var_decl(&mut self, keyword: Option<Keyword>) -> StmtResult {
+        let typ = if keyword == Some(Keyword::Let) || !self.starts_var_decl() {
+            None
+        } else {
+            Some(self.parse_type()?)
+        };
+
+        let name = self.expect(TokenType::Ident)?;
+        self.expect(TokenType::Eq)?;
+        let value = self.parse_expr()?;
+        self.expect_semi()?;
+
+        Ok(Stmt::VarDecl(VarDecl {
+            keyword,
+            typ,
+            name: Symbol::intern(&name.value),
+            value,
+            loc: name.loc,
+            doc: None,
+        }))
+    }
+
+    /// Parses a function declaration, the next token being the `$`.
+    ///
+    /// # Returns
+    /// - [`StmtResult`] - The resulting statement on success
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_func(&mut self) -> StmtResult {
+        Ok(Stmt::Func(self.parse_func_decl(false)?))
+    }
+
+    /// Parses a function or method declaration, the next token being
+    /// the `$`. Only methods may take `&self` as their first parameter.
+    ///
+    /// # Returns
+    /// - [`Result<FuncDecl, String>`] - The declaration on success
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_func_decl(&mut self, method: bool) -> Result<FuncDecl, String> {
+        self.expect(TokenType::Dollar)?;
+        let name = self.expect(TokenType::Ident)?;
+        self.expect(TokenType::LParen)?;
+
+        let mut params = Vec::new();
+
+        if method && self.next_is(TokenType::And) {
+            self.next();
+            let token = self.expect(TokenType::Ident)?;
+
+            if token.value != "self" {
+                return super::parse_exc!(token, "Expected 'self' after '&', but got {}", token);
+            }
+
+            params.push(Param {
+                name: Symbol::intern("self"),
+                typ: VarType::Any,
+            });
+
+            if !self.next_is(TokenType::RParen) {
+                self.expect(TokenType::Comma)?;
+            }
+        }
+
+        while !self.next_is(TokenType::RParen) {
+            let typ = self.parse_type()?;
+            let param = self.expect(TokenType::Ident)?;
+            params.push(Param {
+                name: Symbol::intern(&param.value),
+                typ,
+            });
+
+            if !self.next_is(TokenType::RParen) {
+                self.expect(TokenType::Comma)?;
+            }
+        }
+
+        self.expect(TokenType::RParen)?;
+
+        let ret = if self.next_is(TokenType::At) {
+            self.next();
+            self.parse_type()?
+        } else {
+            VarType::Void
+        };
+
+        Ok(FuncDecl {
+            name: Symbol::intern(&name.value),
+            params,
+            ret,
+            body: self.parse_block()?,
+            loc: name.loc,
+            doc: None,
+        })
+    }
+
+    /// Parses a class declaration like `&Person is Animal { ... }`, the
+    /// next token being the `&`.
+    ///
+    /// # Returns
+    /// - [`StmtResult`] - The resulting statement on success
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_class(&mut self) -> StmtResult {
+        self.trace("class");
+        self.expect(TokenType::And)?;
+        let name = self.expect(TokenType::Ident)?;
+
+        // The class is a type inside its own body, e.g. for methods
+        // returning a new instance
+        self.classes.insert(Symbol::intern(&name.value));
+
+        let is_base = self
+            .peek()
+            .is_some_and(|t| t.typ == TokenType::Keyword && t.value == "is");
+
+        let base = if is_base {
+            self.next();
+            Some(Symbol::intern(&self.expect(TokenType::Ident)?.value))
+        } else {
+            None
+        };
+
+        self.expect(TokenType::LBrace)?;
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+
+        while !self.next_is(TokenType::RBrace) && !self.next_is(TokenType::Eof) {
+            let doc = self.take_doc();
+
+            if self.next_is(TokenType::Dollar) {
+                let mut method = self.parse_func_decl(true)?;
+                method.doc = doc;
+                methods.push(method);
+                continue;
+            }
+
+            let typ = self.parse_type()?;
+            let field = self.expect(TokenType::Ident)?;
+
+            let value = if self.next_is(TokenType::Eq) {
+                self.next();
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+
+            self.expect(TokenType::Semi)?;
+            fields.push(Field {
+                name: Symbol::intern(&field.value),
+                typ,
+                value,
+                loc: field.loc,
+                doc,
+            });
+        }
+
+        self.orphan_doc();
+        self.expect(TokenType::RBrace)?;
+
+        // Like functions, classes don't need a `;`
+        if self.next_is(TokenType::Semi) {
+            self.next();
+        }
+
+        Ok(Stmt::Class(ClassDecl {
+            name: Symbol::intern(&name.value),
+            base,
+            fields,
+            methods,
+            loc: name.loc.clone(),
+            doc: None,
+        }))
+    }
+
+    /// Parses the value of a `@` or `return` statement.
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_return(&mut self) -> StmtResult {
+        let loc = self.last_loc();
+
+        if self.next_is(TokenType::Semi) || self.next_is(TokenType::Eof) {
+            self.expect_semi()?;
+            return Ok(Stmt::Return(None, loc));
+        }
+
+        let value = self.parse_expr()?;
+        self.expect_semi()?;
+        Ok(Stmt::Return(Some(value), loc))
+    }
+
+    /// Parses an `if` statement, the keyword having been consumed.
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_if(&mut self) -> StmtResult {
+        self.nested(|parser| {
+            let loc = parser.last_loc();
+            let cond = parser.parse_expr()?;
+            let body = parser.parse_block()?;
+            let is_else = parser
+                .peek()
+                .is_some_and(|t| t.typ == TokenType::Keyword && t.value == "else");
+
+            if !is_else {
+                return Ok(Stmt::If(cond, body, None, loc));
+            }
+
+            parser.next();
+            let next = parser.peek_next()?;
+
+            let otherwise = if next.typ == TokenType::Keyword && next.value == "if" {
+                parser.next();
+                vec![parser.parse_if()?]
+            } else {
+                parser.parse_block()?
+            };
+
+            Ok(Stmt::If(cond, body, Some(otherwise), loc))
+        })
+    }
+
+    /// Parses a `{ ... }` block of statements.
+    ///
+    /// # Returns
+    /// - [`Result<Vec<Stmt>, String>`] - The statements on success
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.trace("block");
+        self.expect(TokenType::LBrace)?;
+        let stmts = self.parse_stmts(TokenType::RBrace);
+        self.expect(TokenType::RBrace)?;
+        Ok(stmts)
+    }
+
+    /// Parses an expression statement, or an assignment like `x += 1;`.
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_expr_stmt(&mut self) -> StmtResult {
+        let loc = self.peek_next()?.loc.clone();
+        let expr = self.parse_expr()?;
+        let next = self.peek_next()?;
+
+        let operand = match next.typ {
+            TokenType::Eq
+            | TokenType::PlusEq
+            | TokenType::MinusEq
+            | TokenType::StarEq
+            | TokenType::SlashEq
+            | TokenType::PlusPlus
+            | TokenType::MinusMinus => Operator::try_from(next)?,
+            _ => {
+                self.expect_semi()?;
+                return Ok(Stmt::Expr(expr, loc));
+            }
+        };
+
+        if !matches!(
+            expr,
+            Expr::Ident(_) | Expr::Index(..) | Expr::BinaryOp(Operator::Dot, ..)
+        ) {
+            return super::parse_exc!(next, "Can't assign to {:?}", expr);
+        }
+
+        self.next();
+
+        let value = match operand {
+            Operator::Increment | Operator::Decrement => Expr::Int(1),
+            _ => self.parse_expr()?,
+        };
+
+        self.expect_semi()?;
+        Ok(Stmt::Assign(operand, expr, value, loc))
+    }
+
+    /// Converts a `NumLiteral` token into an `Int` or `Float`
+    /// expression, handling `_` separators, radix prefixes and type
+    /// suffixes.
     ///
     /// # Returns
     /// - [`ParseResult`] - The resulting expression on success
     ///
     /// # Errors
+    /// - If the literal does not fit in its type, or is not a number.
+    pub fn parse_number(token: &Token) -> ParseResult {
+        let raw = token.value.replace('_', "");
+        let is_float = token.typ == TokenType::NumLiteral(true);
+        let lower = raw.to_ascii_lowercase();
+
+        let (digits, radix) = match lower.get(..2) {
+            Some("0x") => (&raw[2..], 16),
+            Some("0b") => (&raw[2..], 2),
+            Some("0o") => (&raw[2..], 8),
+            _ => (raw.as_str(), 10),
+        };
+
+        // Type suffixes never overlap with digits of the radix
+        let digits = match digits.char_indices().last() {
+            Some((i, 'i' | 'u' | 'f')) if radix == 10 => &digits[..i],
+            Some((i, 'i' | 'u')) => &digits[..i],
+            _ => digits,
+        };
+
+        if is_float {
+            match digits.parse::<f64>() {
+                Ok(num) if num.is_finite() => Ok(Expr::Float(num)),
+                _ => super::parse_exc!(token, "Invalid float literal '{}'", token.value),
+            }
+        } else {
+            match i64::from_str_radix(digits, radix) {
+                Ok(num) => Ok(Expr::Int(num)),
+                Err(e) => {
+                    super::parse_exc!(token, "Invalid integer literal '{}': {}", token.value, e)
+                }
+            }
+        }
+    }
+
+    /// Parses the comma separated items of an array literal or call,
+    /// up to and including the closing token.
+    ///
+    /// # Errors
     /// - If a syntax, or other, error was encountered.
+    pub fn parse_list(&mut self, close: TokenType) -> Result<Vec<Expr>, String> {
+        let mut items = Vec::new();
+
+        while !self.next_is(close) {
+            items.push(self.parse_expr()?);
+
+            if !self.next_is(close) {
+                self.expect(TokenType::Comma)?;
+            }
+        }
+
+        self.expect(close)?;
+        Ok(items)
+    }
+
+    /// Parses the `key -> value` pairs of a mapping literal, the `&{`
+    /// having been consumed.
     ///
-    /// # Panics
-    /// - If there is no next token, or an unexpected token was
-    /// received.
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_mapping(&mut self) -> ParseResult {
+        let mut pairs = Vec::new();
+
+        while !self.next_is(TokenType::RBrace) {
+            let key = self.parse_expr()?;
+            self.expect(TokenType::SmallRArrow)?;
+            pairs.push((key, self.parse_expr()?));
+
+            if !self.next_is(TokenType::RBrace) {
+                self.expect(TokenType::Comma)?;
+            }
+        }
+
+        self.expect(TokenType::RBrace)?;
+        Ok(Expr::Mapping(pairs))
+    }
+
+    /// Parses a terminal ast node
+    ///
+    /// # Returns
+    /// - [`ParseResult`] - The resulting expression on success
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
     pub fn parse_primary(&mut self) -> ParseResult {
-        println!("Parsing primary");
-        let next = self.next().unwrap();
+        self.trace("primary");
+        let next = self.peek_next()?;
+
+        // Leave anything unexpected for error recovery to skip
+        let starts_primary = matches!(
+            next.typ,
+            TokenType::NumLiteral(_)
+                | TokenType::StrLiteral
+                | TokenType::Error
+                | TokenType::LParen
+                | TokenType::LBracket
+                | TokenType::And
+                | TokenType::Ident
+                | TokenType::Percent
+                | TokenType::Minus
+                | TokenType::Exclamation
+        );
+
+        if !starts_primary {
+            return super::parse_exc!(next, "Unexpected token: {}", next);
+        }
+
+        let typ = next.typ;
+        let next = self.expect(typ)?;
 
         match next.typ {
-            TokenType::NumLiteral(false) => Ok(Expr::Int((*next).value.parse::<i64>().unwrap())),
-            TokenType::NumLiteral(true) => Ok(Expr::Float((*next).value.parse::<f64>().unwrap())),
-            TokenType::StrLiteral => Ok(Expr::String((*next).value.clone())),
+            TokenType::NumLiteral(_) => Parser::parse_number(&next),
+            TokenType::StrLiteral => Ok(Expr::String(next.value.into_owned())),
+            // The lexer has already reported this one
+            TokenType::Error => Ok(Expr::Error),
             TokenType::LParen => {
                 let expr = self.parse_expr()?;
                 self.expect(TokenType::RParen)?;
                 Ok(Expr::Parenthesized(Box::new(expr)))
             }
-            TokenType::Ident => Ok(Expr::Ident((*next).value.clone())),
+            TokenType::LBracket => Ok(Expr::Array(self.parse_list(TokenType::RBracket)?)),
+            TokenType::And => {
+                self.expect(TokenType::LBrace)?;
+                self.parse_mapping()
+            }
+            TokenType::Ident => match next.value.as_ref() {
+                "true" => Ok(Expr::Bool(true)),
+                "false" => Ok(Expr::Bool(false)),
+                "NULL" => Ok(Expr::Null),
+                _ => Ok(Expr::Ident(Symbol::intern(&next.value))),
+            },
+            TokenType::Percent => {
+                let name = self.expect(TokenType::Ident)?;
+                Ok(Expr::Builtin(Symbol::intern(&name.value)))
+            }
             TokenType::Minus => {
                 let expr = self.parse_factor()?;
                 Ok(Expr::UnaryOp(Operator::Subtract, Box::new(expr)))
             }
-            _ => super::exc!("Unexpected token: {}", next),
+            TokenType::Exclamation => {
+                let expr = self.parse_factor()?;
+                Ok(Expr::UnaryOp(Operator::Not, Box::new(expr)))
+            }
+            _ => super::parse_exc!(next, "Unexpected token: {}", next),
+        }
+    }
+
+    /// Parses a primary node followed by any number of calls `f(x)`,
+    /// indexes `a[0]` or fields `a.b`.
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_postfix(&mut self) -> ParseResult {
+        let loc = self.peek_next()?.loc.clone();
+        let mut expr = self.parse_primary()?;
+
+        loop {
+            if self.next_is(TokenType::LParen) {
+                self.next();
+                let args = self.parse_list(TokenType::RParen)?;
+                expr = Expr::Call(Box::new(expr), args, loc.clone());
+            } else if self.next_is(TokenType::LBracket) {
+                self.next();
+                let index = self.parse_expr()?;
+                self.expect(TokenType::RBracket)?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else if self.next_is(TokenType::Dot) {
+                expr = self.parse_field(expr)?;
+            } else {
+                return Ok(expr);
+            }
         }
     }
 
-    /// Parses a factor
+    /// Parses the `.name` of a field of `object`. Kept out of
+    /// [`Parser::parse_postfix`], which nested expressions recurse
+    /// through, so its stack frame stays small.
+    ///
+    /// # Errors
+    /// - If the `.` isn't followed by a name.
+    fn parse_field(&mut self, object: Expr) -> ParseResult {
+        self.next();
+        let field = Symbol::intern(&self.expect(TokenType::Ident)?.value);
+
+        Ok(Expr::BinaryOp(
+            Operator::Dot,
+            Box::new(object),
+            Box::new(Expr::Ident(field)),
+        ))
+    }
+
+    /// Parses a factor, `**` is right associative.
     ///
     /// # Returns
     /// - [`ParseResult`] - The resulting expression on success
     ///
     /// # Errors
     /// - If a syntax, or other, error was encountered.
-    ///
-    /// # Panics
-    /// - If there is no next token.
     pub fn parse_factor(&mut self) -> ParseResult {
-        println!("Parsing factor");
-        let expr = self.parse_primary()?;
-        let next = self.peek().unwrap();
+        self.trace("factor");
+        self.nested(|parser| {
+            let expr = parser.parse_postfix()?;
 
-        if next.typ == TokenType::StarStar {
-            self.next();
-            let right = self.parse_factor()?;
+            if parser.next_is(TokenType::StarStar) {
+                parser.next();
+                let right = parser.parse_factor()?;
 
-            return Ok(Expr::BinaryOp(
-                Operator::Pow,
-                Box::new(expr),
-                Box::new(right),
-            ));
-        }
+                return Ok(Expr::BinaryOp(
+                    Operator::Pow,
+                    Box::new(expr),
+                    Box::new(right),
+                ));
+            }
 
-        Ok(expr)
+            Ok(expr)
+        })
     }
 
-    /// Parses a term
+    /// Parses a term, made of factors joined by `*` or `/`.
     ///
     /// # Returns
     /// - [`ParseResult`] - The resulting expression on success
     ///
     /// # Errors
     /// - If a syntax, or other, error was encountered.
-    ///
-    /// # Panics
-    /// - If there is no next token.
     pub fn parse_term(&mut self) -> ParseResult {
-        println!("Parsing term");
+        self.trace("term");
         let mut expr = self.parse_factor()?;
 
         loop {
-            let next = self.peek().unwrap();
-            let op = Operator::try_from(*next);
-
-            if let Ok(operand) = op {
-                self.next();
-                let right = self.parse_factor()?;
+            let operand = match self.peek().map(|t| t.typ) {
+                Some(TokenType::Star) => Operator::Mult,
+                Some(TokenType::Slash) => Operator::Div,
+                _ => break,
+            };
 
-                expr = Expr::BinaryOp(operand, Box::new(expr), Box::new(right));
-            } else {
-                break;
-            }
+            self.next();
+            let right = self.parse_factor()?;
+            expr = Expr::BinaryOp(operand, Box::new(expr), Box::new(right));
         }
 
         Ok(expr)
     }
 
-    // pub fn parse_keyword(&mut self, expr: &mut Option<Expr>) -> ParseResult {
-    //     todo!()
-    // }
-
-    /// Parses an expression
+    /// Parses a sum, made of terms joined by `+` or `-`.
     ///
     /// # Returns
     /// - [`ParseResult`] - The resulting expression on success
     ///
     /// # Errors
     /// - If a syntax, or other, error was encountered.
-    ///
-    /// # Panics
-    /// - If there is no next token.
-    pub fn parse_expr(&mut self) -> ParseResult {
-        println!("Parsing expression");
-        let mut expr: Option<Expr> = None;
-        // let current = self.next().unwrap().clone();
+    pub fn parse_sum(&mut self) -> ParseResult {
+        self.trace("sum");
+        let mut expr = self.parse_term()?;
 
         loop {
-            let next = self.peek().unwrap();
-            println!("NEXT: {:?}", next);
-
-            match next.typ {
-                TokenType::Keyword => {
-                    let keyword = Keyword::try_from(*next)?;
-                    match keyword {
-                        Keyword::Let => {
-                            println!("Got a let");
-                            break;
-                        }
-                        _ => return super::exc!("{} is not implemented yet", keyword),
-                    }
-                }
-                TokenType::Plus => {
-                    expr = Some(self.parse_term()?);
-                    self.next();
+            let operand = match self.peek().map(|t| t.typ) {
+                Some(TokenType::Plus) => Operator::Add,
+                Some(TokenType::Minus) => Operator::Subtract,
+                _ => break,
+            };
 
-                    let right = self.parse_term()?;
-                    expr = Some(Expr::BinaryOp(
-                        Operator::Add,
-                        Box::new(expr.unwrap()),
-                        Box::new(right),
-                    ));
-                }
-                TokenType::Minus => {
-                    expr = Some(self.parse_term()?);
-                    self.next();
+            self.next();
+            let right = self.parse_term()?;
+            expr = Expr::BinaryOp(operand, Box::new(expr), Box::new(right));
+        }
 
-                    let right = self.parse_term()?;
-                    expr = Some(Expr::BinaryOp(
-                        Operator::Subtract,
-                        Box::new(expr.unwrap()),
-                        Box::new(right),
-                    ));
-                }
-                _ => {
-                    println!("Skipping unknown token {:?}", next);
-                    break;
-                } // Unknown
+        Ok(expr)
+    }
+
+    /// Parses an expression, a sum optionally compared to another.
+    ///
+    /// # Returns
+    /// - [`ParseResult`] - The resulting expression on success
+    ///
+    /// # Errors
+    /// - If a syntax, or other, error was encountered.
+    pub fn parse_expr(&mut self) -> ParseResult {
+        self.trace("expression");
+        self.nested(|parser| {
+            let expr = parser.parse_sum()?;
+            let cond = parser
+                .peek()
+                .and_then(|next| Condition::try_from(next).ok());
+
+            if let Some(cond) = cond {
+                parser.next();
+                let right = parser.parse_sum()?;
+                return Ok(Expr::Compare(cond, Box::new(expr), Box::new(right)));
             }
-        }
 
-        Ok(expr.unwrap_or(Expr::Null))
+            Ok(expr)
+        })
     }
 }
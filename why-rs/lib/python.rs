@@ -0,0 +1,227 @@
+use std::thread;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyAttributeError, PyException};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use super::dump::Dump;
+use super::traceback;
+use super::Engine;
+use super::Lexer;
+use super::Parser;
+use super::Value;
+
+create_exception!(
+    why_rs,
+    WhyError,
+    PyException,
+    "An error lexing, parsing, checking or running Why source."
+);
+
+/// A token or syntax tree node, with the fields of `why tokens` and
+/// `why ast` in JSON as attributes. Tokens are nodes of kind `token`.
+#[pyclass(frozen, eq, module = "why_rs")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    kind: &'static str,
+    fields: Vec<(&'static str, Dump)>,
+}
+
+#[pymethods]
+impl Node {
+    /// What the node is, like `token`, `int` or `call`.
+    #[getter]
+    fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// The names of the node's fields, in order.
+    #[getter]
+    fn fields(&self) -> Vec<&'static str> {
+        self.fields.iter().map(|(name, _)| *name).collect()
+    }
+
+    fn __getattr__(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        match self.fields.iter().find(|(field, _)| *field == name) {
+            Some((_, value)) => to_python(py, value),
+            None => Err(PyAttributeError::new_err(format!(
+                "{} node has no field '{}'",
+                self.kind, name
+            ))),
+        }
+    }
+
+    /// The node as nested dicts and lists, in the same shape as the
+    /// JSON of `why tokens` and `why ast`.
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_dict(py, &Dump::Node(self.kind, self.fields.clone()))
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let mut parts = vec![format!("'{}'", self.kind)];
+
+        for (name, value) in &self.fields {
+            let value = to_python(py, value)?;
+            parts.push(format!("{}={}", name, value.bind(py).repr()?));
+        }
+
+        Ok(format!("Node({})", parts.join(", ")))
+    }
+}
+
+/// Converts a dump to Python, with nodes as [`Node`]s.
+fn to_python(py: Python<'_>, dump: &Dump) -> PyResult<PyObject> {
+    convert(py, dump, &|py, kind, fields| {
+        let node = Node {
+            kind,
+            fields: fields.to_vec(),
+        };
+        Ok(Py::new(py, node)?.into_any())
+    })
+}
+
+/// Converts a dump to Python, with nodes as dicts.
+fn to_dict(py: Python<'_>, dump: &Dump) -> PyResult<PyObject> {
+    convert(py, dump, &|py, kind, fields| {
+        let dict = PyDict::new(py);
+        dict.set_item("kind", kind)?;
+
+        for (name, value) in fields {
+            dict.set_item(name, to_dict(py, value)?)?;
+        }
+
+        Ok(dict.into_any().unbind())
+    })
+}
+
+type NodeFn<'a> =
+    dyn Fn(Python<'_>, &'static str, &[(&'static str, Dump)]) -> PyResult<PyObject> + 'a;
+
+fn convert(py: Python<'_>, dump: &Dump, node: &NodeFn<'_>) -> PyResult<PyObject> {
+    Ok(match dump {
+        Dump::Node(kind, fields) => node(py, kind, fields)?,
+        Dump::List(items) => {
+            let items: PyResult<Vec<PyObject>> =
+                items.iter().map(|i| convert(py, i, node)).collect();
+            PyList::new(py, items?)?.into_any().unbind()
+        }
+        Dump::Str(s) => s.into_pyobject(py)?.into_any().unbind(),
+        Dump::Int(num) => num.into_pyobject(py)?.into_any().unbind(),
+        Dump::Float(num) => num.into_pyobject(py)?.into_any().unbind(),
+        Dump::Bool(b) => b.into_pyobject(py)?.to_owned().into_any().unbind(),
+        Dump::Null => py.None(),
+    })
+}
+
+/// Converts a runtime value to Python. Mappings become dicts, so their
+/// keys must be hashable, and functions, classes and objects become
+/// their representation.
+fn value_to_python(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Int(num) => num.into_pyobject(py)?.into_any().unbind(),
+        Value::Float(num) => num.into_pyobject(py)?.into_any().unbind(),
+        Value::String(s) => s.into_pyobject(py)?.into_any().unbind(),
+        Value::Bool(b) => b.into_pyobject(py)?.to_owned().into_any().unbind(),
+        Value::Null => py.None(),
+        Value::Array(items) => {
+            let items: PyResult<Vec<PyObject>> =
+                items.iter().map(|i| value_to_python(py, i)).collect();
+            PyList::new(py, items?)?.into_any().unbind()
+        }
+        Value::Mapping(pairs) => {
+            let dict = PyDict::new(py);
+
+            for (key, value) in pairs {
+                let key = value_to_python(py, key)?;
+                let key = match key.downcast_bound::<PyList>(py) {
+                    // Lists aren't hashable, tuples of the same items are
+                    Ok(list) => list.to_tuple().into_any().unbind(),
+                    Err(_) => key,
+                };
+
+                dict.set_item(key, value_to_python(py, value)?)?;
+            }
+
+            dict.into_any().unbind()
+        }
+        Value::Func(_)
+        | Value::Compiled(_)
+        | Value::Builtin(_)
+        | Value::Class(_)
+        | Value::Object(_)
+        | Value::Method(..) => value.repr().into_pyobject(py)?.into_any().unbind(),
+    })
+}
+
+fn why_error(error: String) -> PyErr {
+    WhyError::new_err(error)
+}
+
+/// Lexes source into a list of token nodes.
+///
+/// Raises `WhyError` if the source fails to lex.
+#[pyfunction]
+fn lex(py: Python<'_>, src: &str) -> PyResult<PyObject> {
+    let tokens = Lexer::new(src)
+        .and_then(|mut lexer| lexer.lex())
+        .map_err(why_error)?;
+
+    to_python(py, &Dump::List(tokens.iter().map(Dump::token).collect()))
+}
+
+/// Parses source into the syntax tree of a program, a node of kind
+/// `main`.
+///
+/// Raises `WhyError` if the source fails to lex or parse.
+#[pyfunction]
+fn parse(py: Python<'_>, src: &str) -> PyResult<PyObject> {
+    let program = Parser::from_source(src)
+        .and_then(|mut parser| parser.parse())
+        .map_err(why_error)?;
+
+    to_python(py, &Dump::expr(&program))
+}
+
+/// Type checks and runs source, returning the value of its last
+/// statement if it was an expression, or else `None`.
+///
+/// Raises `WhyError` if the source fails to lex, parse, type check or
+/// run, with a traceback of the calls a runtime error happened in.
+#[pyfunction]
+fn run(py: Python<'_>, src: &str) -> PyResult<PyObject> {
+    // Run on a thread with enough stack for the deepest calls, letting
+    // other Python threads run until the result is converted
+    py.allow_threads(|| {
+        let stack = traceback::stack_size(traceback::MAX_DEPTH);
+        let limit = traceback::stack_limit(traceback::MAX_DEPTH);
+        let run = move || {
+            let result = Engine::new().with_stack_limit(limit).eval(src);
+            Python::with_gil(|py| value_to_python(py, &result.map_err(why_error)?))
+        };
+
+        thread::scope(|scope| {
+            thread::Builder::new()
+                .stack_size(stack)
+                .spawn_scoped(scope, run)
+                .map_err(|e| WhyError::new_err(format!("Failed to start: {e}")))?
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    })
+}
+
+/// The `why_rs` Python module, see `pyproject.toml`.
+///
+/// # Errors
+/// - If a member can't be added to the module.
+#[pymodule]
+pub fn why_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add("WhyError", m.py().get_type::<WhyError>())?;
+    m.add_class::<Node>()?;
+    m.add_function(wrap_pyfunction!(lex, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    Ok(())
+}
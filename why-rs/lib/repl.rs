@@ -0,0 +1,157 @@
+use super::Checker;
+use super::Expr;
+use super::Interpreter;
+use super::Lexer;
+use super::Parser;
+use super::TokenType;
+use super::Value;
+
+type ReplResult = Result<Option<String>, String>;
+
+pub const HELP: &str = "\
+Enter statements or expressions to evaluate them.
+Unclosed braces, brackets and parentheses continue onto the next line.
+
+Commands:
+  :tokens <src>  Show the tokens lexed from <src>
+  :ast <src>     Show the syntax tree parsed from <src>
+  :type <expr>   Show the type of <expr>
+  :help          Show this message
+  :quit          Exit the REPL";
+
+/// An interactive session, which keeps variables and functions
+/// between inputs.
+#[derive(Clone, Debug, Default)]
+pub struct Repl {
+    pub interpreter: Interpreter,
+    pub checker: Checker,
+    /// The lines of input that are waiting for their brackets to
+    /// be closed.
+    pub buffer: String,
+}
+
+impl Repl {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The prompt to show before reading the next line.
+    #[must_use]
+    pub fn prompt(&self) -> &'static str {
+        if self.buffer.is_empty() {
+            ">>> "
+        } else {
+            "... "
+        }
+    }
+
+    /// Returns true if the line asks to leave the REPL.
+    #[must_use]
+    pub fn is_quit(line: &str) -> bool {
+        matches!(line.trim(), ":quit" | ":q" | ":exit")
+    }
+
+    /// Counts the braces, brackets and parentheses left open in the
+    /// source. Source that fails to lex counts as complete, so the
+    /// error gets reported.
+    #[must_use]
+    pub fn depth(src: &str) -> isize {
+        let Ok(mut lexer) = Lexer::new(src) else {
+            return 0;
+        };
+
+        let depth = lexer.try_fold(0, |depth, token| match token?.typ {
+            TokenType::LBrace | TokenType::LBracket | TokenType::LParen => Ok(depth + 1),
+            TokenType::RBrace | TokenType::RBracket | TokenType::RParen => Ok(depth - 1),
+            _ => Ok::<_, String>(depth),
+        });
+
+        depth.unwrap_or(0)
+    }
+
+    /// Lexes and parses source into a program.
+    ///
+    /// # Errors
+    /// - If the source fails to lex or parse.
+    pub fn parse(src: &str) -> Result<Expr, String> {
+        Parser::from_source(src)?.parse()
+    }
+
+    /// Handles a line of input, returning the text to show, if any.
+    ///
+    /// # Returns
+    /// - [`ReplResult`] - Output to show on success, or `None` if
+    ///   there is nothing to show or more input is needed.
+    ///
+    /// # Errors
+    /// - If the input failed to lex, parse, type check or run.
+    pub fn feed(&mut self, line: &str) -> ReplResult {
+        if self.buffer.is_empty() && line.trim_start().starts_with(':') {
+            return self.command(line.trim());
+        }
+
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+
+        if self.buffer.trim().is_empty() {
+            self.buffer.clear();
+            return Ok(None);
+        }
+
+        if Repl::depth(&self.buffer) > 0 {
+            return Ok(None);
+        }
+
+        let src = std::mem::take(&mut self.buffer);
+        self.eval(&src)
+    }
+
+    /// Type checks and runs source, keeping its declarations.
+    ///
+    /// # Errors
+    /// - If the source failed to lex, parse, type check or run.
+    pub fn eval(&mut self, src: &str) -> ReplResult {
+        let program = Repl::parse(src)?;
+
+        // Only keep the checker's declarations if all of them are valid,
+        // and the run that declares them at runtime succeeds
+        let mut checker = self.checker.clone();
+        checker.check(&program)?;
+
+        let value = self.interpreter.run(&program)?;
+        self.checker = checker;
+
+        match value {
+            Value::Null => Ok(None),
+            value => Ok(Some(value.repr())),
+        }
+    }
+
+    /// Runs a `:` meta command.
+    ///
+    /// # Errors
+    /// - If the command is unknown, or its argument fails.
+    pub fn command(&mut self, line: &str) -> ReplResult {
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+
+        match command {
+            ":help" | ":h" => Ok(Some(HELP.to_string())),
+            ":tokens" => {
+                let tokens = Lexer::new(arg)?.lex()?;
+                let tokens: Vec<String> = tokens.iter().map(ToString::to_string).collect();
+                Ok(Some(tokens.join("\n")))
+            }
+            ":ast" => Ok(Some(format!("{:?}", Repl::parse(arg)?))),
+            ":type" => {
+                let mut parser = Parser::from_tokens(Lexer::new(arg)?.lex()?);
+                let expr = parser.parse_expr()?;
+                parser.expect(TokenType::Eof)?;
+
+                Ok(Some(self.checker.clone().infer(&expr)?.to_string()))
+            }
+            _ => super::exc!("Unknown command {:?}, try :help", command),
+        }
+    }
+}
@@ -0,0 +1,1433 @@
+/*
+ * The runtime of programs transpiled to C by `why build --emit c`, which
+ * is pasted at the top of every generated file.
+ *
+ * Values are tagged, and strings, arrays and mappings live on the heap
+ * with a reference count. Arrays and mappings are copied when they are
+ * changed while shared, so they behave as values, like they do in the
+ * interpreter. Runtime errors are printed to stderr and exit with the
+ * same code as `why run`.
+ */
+
+#include <math.h>
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define WHY_RUNTIME_ERROR 5
+
+typedef struct WhyValue WhyValue;
+typedef struct WhyType WhyType;
+typedef struct WhyFunc WhyFunc;
+
+typedef enum {
+    WHY_NULL,
+    WHY_INT,
+    WHY_FLOAT,
+    WHY_STRING,
+    WHY_BOOL,
+    WHY_ARRAY,
+    WHY_MAPPING,
+    WHY_FUNC,
+    WHY_BUILTIN,
+} WhyTag;
+
+typedef enum {
+    WHY_T_INT,
+    WHY_T_FLOAT,
+    WHY_T_STRING,
+    WHY_T_BOOL,
+    WHY_T_VOID,
+    WHY_T_ANY,
+    WHY_T_ARRAY,
+    WHY_T_MAPPING,
+    WHY_T_FUNCTION,
+} WhyKind;
+
+/* The operators, in the order of the interpreter's. */
+typedef enum {
+    WHY_OP_ADD,
+    WHY_OP_INCREMENT,
+    WHY_OP_INCREMENT_BY,
+    WHY_OP_SUBTRACT,
+    WHY_OP_DECREMENT,
+    WHY_OP_DECREMENT_BY,
+    WHY_OP_MULT_BY,
+    WHY_OP_DIV_BY,
+    WHY_OP_POW,
+    WHY_OP_MULT,
+    WHY_OP_DIV,
+    WHY_OP_DOT,
+    WHY_OP_ASSIGN,
+    WHY_OP_NOT,
+} WhyOp;
+
+static const char *const why_op_names[] = {
+    "+", "++", "+=", "-", "--", "-=", "*=", "/=", "**", "*", "/", ".", "=", "!",
+};
+
+typedef enum {
+    WHY_LT,
+    WHY_GT,
+    WHY_LTE,
+    WHY_GTE,
+    WHY_NE,
+    WHY_EQEQ,
+} WhyCond;
+
+static const char *const why_cond_names[] = {"<", ">", "<=", ">=", "!=", "=="};
+
+typedef enum {
+    WHY_PRINT,
+    WHY_LEN,
+    WHY_STR,
+} WhyBuiltin;
+
+static const char *const why_builtin_names[] = {"print", "len", "str"};
+
+/*
+ * A type, as declared or as found from a value. Declared types are
+ * static, and those found from values are allocated and owned by the
+ * binding they describe.
+ */
+struct WhyType {
+    WhyKind kind;
+    bool heap;
+    /* The item type of an array, or the key type of a mapping. */
+    const WhyType *a;
+    /* The value type of a mapping, or the return type of a function. */
+    const WhyType *b;
+    size_t n;
+    const WhyType *const *params;
+};
+
+typedef struct {
+    size_t rc;
+    size_t len;
+    char data[];
+} WhyString;
+
+typedef struct {
+    size_t rc;
+    size_t len;
+    size_t cap;
+    WhyValue *items;
+} WhyArray;
+
+typedef struct {
+    size_t rc;
+    size_t len;
+    size_t cap;
+    WhyValue *keys;
+    WhyValue *values;
+} WhyMapping;
+
+struct WhyValue {
+    WhyTag tag;
+    union {
+        int64_t i;
+        double f;
+        bool b;
+        WhyString *s;
+        WhyArray *a;
+        WhyMapping *m;
+        const WhyFunc *fn;
+        WhyBuiltin builtin;
+    } as;
+};
+
+/* A function, which is called with arguments checked against its type. */
+struct WhyFunc {
+    const char *name;
+    const char *const *params;
+    const WhyType *type;
+    WhyValue (*call)(WhyValue *args);
+};
+
+/* A variable. */
+typedef struct {
+    WhyValue value;
+    const WhyType *type;
+    bool constant;
+    bool defined;
+} WhyBinding;
+
+static const WhyType why_t_int = {WHY_T_INT, false, NULL, NULL, 0, NULL};
+static const WhyType why_t_float = {WHY_T_FLOAT, false, NULL, NULL, 0, NULL};
+static const WhyType why_t_string = {WHY_T_STRING, false, NULL, NULL, 0, NULL};
+static const WhyType why_t_bool = {WHY_T_BOOL, false, NULL, NULL, 0, NULL};
+static const WhyType why_t_void = {WHY_T_VOID, false, NULL, NULL, 0, NULL};
+static const WhyType why_t_any = {WHY_T_ANY, false, NULL, NULL, 0, NULL};
+static const WhyType why_t_builtin = {WHY_T_FUNCTION, false, NULL, &why_t_any, 0, NULL};
+
+/* A growable string, used to format values and messages. */
+typedef struct {
+    char *data;
+    size_t len;
+    size_t cap;
+} WhyBuf;
+
+static inline void *why_alloc(size_t size) {
+    void *ptr = malloc(size ? size : 1);
+
+    if (!ptr) {
+        fputs("Error! --> Out of memory\n", stderr);
+        exit(WHY_RUNTIME_ERROR);
+    }
+
+    return ptr;
+}
+
+static inline void *why_realloc(void *ptr, size_t size) {
+    ptr = realloc(ptr, size ? size : 1);
+
+    if (!ptr) {
+        fputs("Error! --> Out of memory\n", stderr);
+        exit(WHY_RUNTIME_ERROR);
+    }
+
+    return ptr;
+}
+
+static inline void why_buf_push(WhyBuf *buf, const char *s, size_t len) {
+    if (buf->len + len + 1 > buf->cap) {
+        buf->cap = (buf->len + len + 1) * 2;
+        buf->data = why_realloc(buf->data, buf->cap);
+    }
+
+    memcpy(buf->data + buf->len, s, len);
+    buf->len += len;
+    buf->data[buf->len] = '\0';
+}
+
+static inline void why_buf_str(WhyBuf *buf, const char *s) {
+    why_buf_push(buf, s, strlen(s));
+}
+
+static inline void why_buf_printf(WhyBuf *buf, const char *fmt, ...) {
+    char small[64];
+    va_list args;
+
+    va_start(args, fmt);
+    int len = vsnprintf(small, sizeof small, fmt, args);
+    va_end(args);
+
+    if (len < (int)sizeof small) {
+        why_buf_push(buf, small, (size_t)len);
+        return;
+    }
+
+    char *large = why_alloc((size_t)len + 1);
+    va_start(args, fmt);
+    vsnprintf(large, (size_t)len + 1, fmt, args);
+    va_end(args);
+
+    why_buf_push(buf, large, (size_t)len);
+    free(large);
+}
+
+/* Prints a runtime error and exits, like `why run` does. */
+static inline _Noreturn void why_fail(WhyBuf *message) {
+    fflush(stdout);
+    fprintf(stderr, "Error! --> %s\n", message->data ? message->data : "");
+    exit(WHY_RUNTIME_ERROR);
+}
+
+static inline _Noreturn void why_failf(const char *fmt, ...) {
+    WhyBuf message = {0};
+    char small[256];
+    va_list args;
+
+    va_start(args, fmt);
+    vsnprintf(small, sizeof small, fmt, args);
+    va_end(args);
+
+    why_buf_str(&message, small);
+    why_fail(&message);
+}
+
+/* Values */
+
+static inline WhyValue why_null(void) {
+    WhyValue v;
+    v.tag = WHY_NULL;
+    v.as.i = 0;
+    return v;
+}
+
+static inline WhyValue why_int(int64_t i) {
+    WhyValue v;
+    v.tag = WHY_INT;
+    v.as.i = i;
+    return v;
+}
+
+static inline WhyValue why_float(double f) {
+    WhyValue v;
+    v.tag = WHY_FLOAT;
+    v.as.f = f;
+    return v;
+}
+
+static inline WhyValue why_bool(bool b) {
+    WhyValue v;
+    v.tag = WHY_BOOL;
+    v.as.b = b;
+    return v;
+}
+
+static inline WhyValue why_func(const WhyFunc *fn) {
+    WhyValue v;
+    v.tag = WHY_FUNC;
+    v.as.fn = fn;
+    return v;
+}
+
+static inline WhyValue why_builtin(WhyBuiltin builtin) {
+    WhyValue v;
+    v.tag = WHY_BUILTIN;
+    v.as.builtin = builtin;
+    return v;
+}
+
+/* Allocates a string of a length, for its bytes to be filled in. */
+static inline WhyValue why_string_new(size_t len) {
+    WhyString *str = why_alloc(sizeof(WhyString) + len + 1);
+    str->rc = 1;
+    str->len = len;
+    str->data[len] = '\0';
+
+    WhyValue v;
+    v.tag = WHY_STRING;
+    v.as.s = str;
+    return v;
+}
+
+static inline WhyValue why_string(const char *s, size_t len) {
+    WhyValue v = why_string_new(len);
+    memcpy(v.as.s->data, s, len);
+    return v;
+}
+
+static inline WhyValue why_array_new(size_t cap) {
+    WhyArray *array = why_alloc(sizeof(WhyArray));
+    array->rc = 1;
+    array->len = 0;
+    array->cap = cap;
+    array->items = why_alloc(cap * sizeof(WhyValue));
+
+    WhyValue v;
+    v.tag = WHY_ARRAY;
+    v.as.a = array;
+    return v;
+}
+
+static inline WhyValue why_mapping_new(size_t cap) {
+    WhyMapping *mapping = why_alloc(sizeof(WhyMapping));
+    mapping->rc = 1;
+    mapping->len = 0;
+    mapping->cap = cap;
+    mapping->keys = why_alloc(cap * sizeof(WhyValue));
+    mapping->values = why_alloc(cap * sizeof(WhyValue));
+
+    WhyValue v;
+    v.tag = WHY_MAPPING;
+    v.as.m = mapping;
+    return v;
+}
+
+static inline WhyValue why_retain(WhyValue v) {
+    switch (v.tag) {
+    case WHY_STRING:
+        v.as.s->rc++;
+        break;
+    case WHY_ARRAY:
+        v.as.a->rc++;
+        break;
+    case WHY_MAPPING:
+        v.as.m->rc++;
+        break;
+    default:
+        break;
+    }
+
+    return v;
+}
+
+static inline void why_release(WhyValue v) {
+    switch (v.tag) {
+    case WHY_STRING:
+        if (--v.as.s->rc == 0) {
+            free(v.as.s);
+        }
+        break;
+    case WHY_ARRAY:
+        if (--v.as.a->rc == 0) {
+            for (size_t i = 0; i < v.as.a->len; i++) {
+                why_release(v.as.a->items[i]);
+            }
+            free(v.as.a->items);
+            free(v.as.a);
+        }
+        break;
+    case WHY_MAPPING:
+        if (--v.as.m->rc == 0) {
+            for (size_t i = 0; i < v.as.m->len; i++) {
+                why_release(v.as.m->keys[i]);
+                why_release(v.as.m->values[i]);
+            }
+            free(v.as.m->keys);
+            free(v.as.m->values);
+            free(v.as.m);
+        }
+        break;
+    default:
+        break;
+    }
+}
+
+static inline void why_array_push(WhyArray *array, WhyValue item) {
+    if (array->len == array->cap) {
+        array->cap = array->cap ? array->cap * 2 : 4;
+        array->items = why_realloc(array->items, array->cap * sizeof(WhyValue));
+    }
+
+    array->items[array->len++] = item;
+}
+
+static inline void why_mapping_push(WhyMapping *mapping, WhyValue key, WhyValue value) {
+    if (mapping->len == mapping->cap) {
+        mapping->cap = mapping->cap ? mapping->cap * 2 : 4;
+        mapping->keys = why_realloc(mapping->keys, mapping->cap * sizeof(WhyValue));
+        mapping->values = why_realloc(mapping->values, mapping->cap * sizeof(WhyValue));
+    }
+
+    mapping->keys[mapping->len] = key;
+    mapping->values[mapping->len] = value;
+    mapping->len++;
+}
+
+/* Makes sure an array or mapping isn't shared, so it can be changed. */
+static inline void why_make_unique(WhyValue *v) {
+    if (v->tag == WHY_ARRAY && v->as.a->rc > 1) {
+        WhyArray *shared = v->as.a;
+        WhyValue copy = why_array_new(shared->len);
+
+        for (size_t i = 0; i < shared->len; i++) {
+            why_array_push(copy.as.a, why_retain(shared->items[i]));
+        }
+
+        shared->rc--;
+        *v = copy;
+    } else if (v->tag == WHY_MAPPING && v->as.m->rc > 1) {
+        WhyMapping *shared = v->as.m;
+        WhyValue copy = why_mapping_new(shared->len);
+
+        for (size_t i = 0; i < shared->len; i++) {
+            why_mapping_push(copy.as.m, why_retain(shared->keys[i]), why_retain(shared->values[i]));
+        }
+
+        shared->rc--;
+        *v = copy;
+    }
+}
+
+/* Types */
+
+static inline void why_type_free(const WhyType *type) {
+    if (!type || !type->heap) {
+        return;
+    }
+
+    why_type_free(type->a);
+    why_type_free(type->b);
+    free((WhyType *)type);
+}
+
+static inline const WhyType *why_type_new(WhyKind kind, const WhyType *a, const WhyType *b) {
+    WhyType *type = why_alloc(sizeof(WhyType));
+    type->kind = kind;
+    type->heap = true;
+    type->a = a;
+    type->b = b;
+    type->n = 0;
+    type->params = NULL;
+    return type;
+}
+
+/* The type of a value, which must be freed with why_type_free. */
+static inline const WhyType *why_typeof(WhyValue v) {
+    switch (v.tag) {
+    case WHY_INT:
+        return &why_t_int;
+    case WHY_FLOAT:
+        return &why_t_float;
+    case WHY_STRING:
+        return &why_t_string;
+    case WHY_BOOL:
+        return &why_t_bool;
+    case WHY_ARRAY:
+        return why_type_new(WHY_T_ARRAY, v.as.a->len ? why_typeof(v.as.a->items[0]) : &why_t_any, NULL);
+    case WHY_MAPPING:
+        if (v.as.m->len == 0) {
+            return why_type_new(WHY_T_MAPPING, &why_t_any, &why_t_any);
+        }
+        return why_type_new(WHY_T_MAPPING, why_typeof(v.as.m->keys[0]), why_typeof(v.as.m->values[0]));
+    case WHY_FUNC:
+        return v.as.fn->type;
+    case WHY_BUILTIN:
+        return &why_t_builtin;
+    default:
+        return &why_t_void;
+    }
+}
+
+static inline void why_type_write(WhyBuf *buf, const WhyType *type) {
+    switch (type->kind) {
+    case WHY_T_INT:
+        why_buf_str(buf, "int");
+        break;
+    case WHY_T_FLOAT:
+        why_buf_str(buf, "float");
+        break;
+    case WHY_T_STRING:
+        why_buf_str(buf, "string");
+        break;
+    case WHY_T_BOOL:
+        why_buf_str(buf, "bool");
+        break;
+    case WHY_T_VOID:
+        why_buf_str(buf, "void");
+        break;
+    case WHY_T_ANY:
+        why_buf_str(buf, "any");
+        break;
+    case WHY_T_ARRAY:
+        why_buf_str(buf, "array@");
+        why_type_write(buf, type->a);
+        break;
+    case WHY_T_MAPPING:
+        why_buf_str(buf, "mapping@");
+        why_type_write(buf, type->a);
+        why_buf_str(buf, "->");
+        why_type_write(buf, type->b);
+        break;
+    case WHY_T_FUNCTION:
+        why_buf_str(buf, "$(");
+        for (size_t i = 0; i < type->n; i++) {
+            if (i > 0) {
+                why_buf_str(buf, ", ");
+            }
+            why_type_write(buf, type->params[i]);
+        }
+        why_buf_str(buf, ")@");
+        why_type_write(buf, type->b);
+        break;
+    }
+}
+
+static inline void why_typeof_write(WhyBuf *buf, WhyValue v) {
+    const WhyType *type = why_typeof(v);
+    why_type_write(buf, type);
+    why_type_free(type);
+}
+
+static inline bool why_type_eq(const WhyType *a, const WhyType *b) {
+    if (a == b) {
+        return true;
+    }
+
+    if (a->kind != b->kind) {
+        return false;
+    }
+
+    switch (a->kind) {
+    case WHY_T_ARRAY:
+        return why_type_eq(a->a, b->a);
+    case WHY_T_MAPPING:
+        return why_type_eq(a->a, b->a) && why_type_eq(a->b, b->b);
+    case WHY_T_FUNCTION:
+        if (a->n != b->n || !why_type_eq(a->b, b->b)) {
+            return false;
+        }
+        for (size_t i = 0; i < a->n; i++) {
+            if (!why_type_eq(a->params[i], b->params[i])) {
+                return false;
+            }
+        }
+        return true;
+    default:
+        return true;
+    }
+}
+
+/* Whether a value may be stored in a variable of a type. */
+static inline bool why_accepts(const WhyType *type, WhyValue v) {
+    switch (type->kind) {
+    case WHY_T_ANY:
+        return true;
+    case WHY_T_INT:
+        return v.tag == WHY_INT;
+    case WHY_T_FLOAT:
+        return v.tag == WHY_FLOAT || v.tag == WHY_INT;
+    case WHY_T_STRING:
+        return v.tag == WHY_STRING;
+    case WHY_T_BOOL:
+        return v.tag == WHY_BOOL;
+    case WHY_T_VOID:
+        return v.tag == WHY_NULL;
+    case WHY_T_ARRAY:
+        return v.tag == WHY_ARRAY && (v.as.a->len == 0 || why_accepts(type->a, v.as.a->items[0]));
+    case WHY_T_MAPPING:
+        return v.tag == WHY_MAPPING &&
+               (v.as.m->len == 0 ||
+                (why_accepts(type->a, v.as.m->keys[0]) && why_accepts(type->b, v.as.m->values[0])));
+    case WHY_T_FUNCTION:
+        return (v.tag == WHY_FUNC || v.tag == WHY_BUILTIN) && why_type_eq(type, why_typeof(v));
+    }
+
+    return false;
+}
+
+/* Whether storing a value as a type can widen ints in it to floats. */
+static inline bool why_widens(const WhyType *type) {
+    switch (type->kind) {
+    case WHY_T_FLOAT:
+        return true;
+    case WHY_T_ARRAY:
+        return why_widens(type->a);
+    case WHY_T_MAPPING:
+        return why_widens(type->a) || why_widens(type->b);
+    default:
+        return false;
+    }
+}
+
+/* The type of the items of an array, or the values of a mapping. */
+static inline const WhyType *why_item_type(const WhyType *type) {
+    if (type->kind == WHY_T_ARRAY) {
+        return type->a;
+    }
+
+    return type->kind == WHY_T_MAPPING ? type->b : &why_t_any;
+}
+
+/*
+ * Converts a value so it can be stored as a type, widening ints to
+ * floats, including those in arrays and mappings. Takes the value.
+ */
+static inline WhyValue why_coerce(WhyValue v, const WhyType *type) {
+    if (type->kind == WHY_T_FLOAT && v.tag == WHY_INT) {
+        return why_float((double)v.as.i);
+    }
+
+    if (type->kind == WHY_T_ARRAY && v.tag == WHY_ARRAY && why_widens(type->a)) {
+        why_make_unique(&v);
+
+        for (size_t i = 0; i < v.as.a->len; i++) {
+            v.as.a->items[i] = why_coerce(v.as.a->items[i], type->a);
+        }
+    } else if (type->kind == WHY_T_MAPPING && v.tag == WHY_MAPPING &&
+               (why_widens(type->a) || why_widens(type->b))) {
+        why_make_unique(&v);
+
+        for (size_t i = 0; i < v.as.m->len; i++) {
+            v.as.m->keys[i] = why_coerce(v.as.m->keys[i], type->a);
+            v.as.m->values[i] = why_coerce(v.as.m->values[i], type->b);
+        }
+    }
+
+    return v;
+}
+
+/* Formatting */
+
+/* Writes a float like Rust's `{:?}`, with the fewest digits that read back the same. */
+static inline void why_float_write(WhyBuf *buf, double f) {
+    if (isnan(f)) {
+        why_buf_str(buf, "NaN");
+        return;
+    }
+
+    if (isinf(f)) {
+        why_buf_str(buf, f < 0 ? "-inf" : "inf");
+        return;
+    }
+
+    if (f == 0) {
+        why_buf_str(buf, signbit(f) ? "-0.0" : "0.0");
+        return;
+    }
+
+    char sci[40];
+
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(sci, sizeof sci, "%.*e", precision, f);
+
+        if (strtod(sci, NULL) == f) {
+            break;
+        }
+    }
+
+    /* Split "-d.ddde+XX" into its digits and exponent */
+    char digits[20];
+    size_t ndigits = 0;
+    char *c = sci;
+
+    if (*c == '-') {
+        why_buf_str(buf, "-");
+        c++;
+    }
+
+    for (; *c && *c != 'e'; c++) {
+        if (*c != '.') {
+            digits[ndigits++] = *c;
+        }
+    }
+
+    int exp = atoi(c + 1);
+
+    while (ndigits > 1 && digits[ndigits - 1] == '0') {
+        ndigits--;
+    }
+
+    if (exp < -4 || exp >= 16) {
+        why_buf_push(buf, digits, 1);
+        if (ndigits > 1) {
+            why_buf_str(buf, ".");
+            why_buf_push(buf, digits + 1, ndigits - 1);
+        }
+        why_buf_printf(buf, "e%d", exp);
+    } else if (exp < 0) {
+        why_buf_str(buf, "0.");
+        for (int i = -1; i > exp; i--) {
+            why_buf_str(buf, "0");
+        }
+        why_buf_push(buf, digits, ndigits);
+    } else if ((size_t)exp + 1 >= ndigits) {
+        why_buf_push(buf, digits, ndigits);
+        for (size_t i = ndigits; i < (size_t)exp + 1; i++) {
+            why_buf_str(buf, "0");
+        }
+        why_buf_str(buf, ".0");
+    } else {
+        why_buf_push(buf, digits, (size_t)exp + 1);
+        why_buf_str(buf, ".");
+        why_buf_push(buf, digits + exp + 1, ndigits - (size_t)exp - 1);
+    }
+}
+
+/* Writes a string quoted and escaped like Rust's `{:?}`. */
+static inline void why_string_repr(WhyBuf *buf, const WhyString *s) {
+    why_buf_str(buf, "\"");
+
+    for (size_t i = 0; i < s->len; i++) {
+        unsigned char c = (unsigned char)s->data[i];
+
+        switch (c) {
+        case '"':
+            why_buf_str(buf, "\\\"");
+            break;
+        case '\\':
+            why_buf_str(buf, "\\\\");
+            break;
+        case '\n':
+            why_buf_str(buf, "\\n");
+            break;
+        case '\r':
+            why_buf_str(buf, "\\r");
+            break;
+        case '\t':
+            why_buf_str(buf, "\\t");
+            break;
+        case '\0':
+            why_buf_str(buf, "\\0");
+            break;
+        default:
+            if (c < 0x20 || c == 0x7f) {
+                why_buf_printf(buf, "\\u{%x}", c);
+            } else {
+                why_buf_push(buf, (const char *)&c, 1);
+            }
+        }
+    }
+
+    why_buf_str(buf, "\"");
+}
+
+/* Writes a value as it would appear in source, or as `print` shows it. */
+static inline void why_write(WhyBuf *buf, WhyValue v, bool repr) {
+    switch (v.tag) {
+    case WHY_NULL:
+        why_buf_str(buf, "NULL");
+        break;
+    case WHY_INT:
+        why_buf_printf(buf, "%lld", (long long)v.as.i);
+        break;
+    case WHY_FLOAT:
+        why_float_write(buf, v.as.f);
+        break;
+    case WHY_STRING:
+        if (repr) {
+            why_string_repr(buf, v.as.s);
+        } else {
+            why_buf_push(buf, v.as.s->data, v.as.s->len);
+        }
+        break;
+    case WHY_BOOL:
+        why_buf_str(buf, v.as.b ? "true" : "false");
+        break;
+    case WHY_ARRAY:
+        why_buf_str(buf, "[");
+        for (size_t i = 0; i < v.as.a->len; i++) {
+            if (i > 0) {
+                why_buf_str(buf, ", ");
+            }
+            why_write(buf, v.as.a->items[i], true);
+        }
+        why_buf_str(buf, "]");
+        break;
+    case WHY_MAPPING:
+        why_buf_str(buf, "&{");
+        for (size_t i = 0; i < v.as.m->len; i++) {
+            if (i > 0) {
+                why_buf_str(buf, ", ");
+            }
+            why_write(buf, v.as.m->keys[i], true);
+            why_buf_str(buf, "->");
+            why_write(buf, v.as.m->values[i], true);
+        }
+        why_buf_str(buf, "}");
+        break;
+    case WHY_FUNC:
+        why_buf_printf(buf, "<function %s>", v.as.fn->name);
+        break;
+    case WHY_BUILTIN:
+        why_buf_printf(buf, "<builtin %s>", why_builtin_names[v.as.builtin]);
+        break;
+    }
+}
+
+/* Operators */
+
+static inline bool why_eq(WhyValue a, WhyValue b) {
+    if (a.tag != b.tag) {
+        return false;
+    }
+
+    switch (a.tag) {
+    case WHY_NULL:
+        return true;
+    case WHY_INT:
+        return a.as.i == b.as.i;
+    case WHY_FLOAT:
+        return a.as.f == b.as.f;
+    case WHY_STRING:
+        return a.as.s->len == b.as.s->len && memcmp(a.as.s->data, b.as.s->data, a.as.s->len) == 0;
+    case WHY_BOOL:
+        return a.as.b == b.as.b;
+    case WHY_ARRAY:
+        if (a.as.a->len != b.as.a->len) {
+            return false;
+        }
+        for (size_t i = 0; i < a.as.a->len; i++) {
+            if (!why_eq(a.as.a->items[i], b.as.a->items[i])) {
+                return false;
+            }
+        }
+        return true;
+    case WHY_MAPPING:
+        if (a.as.m->len != b.as.m->len) {
+            return false;
+        }
+        for (size_t i = 0; i < a.as.m->len; i++) {
+            if (!why_eq(a.as.m->keys[i], b.as.m->keys[i]) ||
+                !why_eq(a.as.m->values[i], b.as.m->values[i])) {
+                return false;
+            }
+        }
+        return true;
+    case WHY_FUNC:
+        return a.as.fn == b.as.fn;
+    case WHY_BUILTIN:
+        return a.as.builtin == b.as.builtin;
+    }
+
+    return false;
+}
+
+static inline bool why_as_float(WhyValue v, double *out) {
+    if (v.tag == WHY_INT) {
+        *out = (double)v.as.i;
+        return true;
+    }
+
+    if (v.tag == WHY_FLOAT) {
+        *out = v.as.f;
+        return true;
+    }
+
+    return false;
+}
+
+static inline bool why_checked_mul(int64_t l, int64_t r, int64_t *out) {
+    if (l > 0 ? (r > 0 ? l > INT64_MAX / r : r < INT64_MIN / l)
+              : (r > 0 ? l < INT64_MIN / r : l != 0 && r < INT64_MAX / l)) {
+        return false;
+    }
+
+    *out = l * r;
+    return true;
+}
+
+static inline bool why_checked_pow(int64_t base, int64_t exp, int64_t *out) {
+    int64_t result = 1;
+
+    if (exp > UINT32_MAX) {
+        return false;
+    }
+
+    while (exp > 0) {
+        if (exp & 1 && !why_checked_mul(result, base, &result)) {
+            return false;
+        }
+
+        exp >>= 1;
+
+        if (exp > 0 && !why_checked_mul(base, base, &base)) {
+            return false;
+        }
+    }
+
+    *out = result;
+    return true;
+}
+
+static inline WhyValue why_binary(WhyOp op, WhyValue l, WhyValue r);
+
+static inline WhyValue why_binary_int(WhyOp op, int64_t l, int64_t r) {
+    int64_t result = 0;
+    bool ok = true;
+
+    switch (op) {
+    case WHY_OP_ADD:
+        ok = !(r > 0 ? l > INT64_MAX - r : l < INT64_MIN - r);
+        result = ok ? l + r : 0;
+        break;
+    case WHY_OP_SUBTRACT:
+        ok = !(r < 0 ? l > INT64_MAX + r : l < INT64_MIN + r);
+        result = ok ? l - r : 0;
+        break;
+    case WHY_OP_MULT:
+        ok = why_checked_mul(l, r, &result);
+        break;
+    case WHY_OP_DIV:
+        if (r == 0) {
+            why_failf("Division by zero");
+        }
+        ok = !(l == INT64_MIN && r == -1);
+        result = ok ? l / r : 0;
+        break;
+    case WHY_OP_POW:
+        if (r < 0) {
+            return why_binary(op, why_float((double)l), why_int(r));
+        }
+        ok = why_checked_pow(l, r, &result);
+        break;
+    default:
+        why_failf("Unsupported operator %s for int", why_op_names[op]);
+    }
+
+    if (!ok) {
+        why_failf("Integer overflow in %lld %s %lld", (long long)l, why_op_names[op], (long long)r);
+    }
+
+    return why_int(result);
+}
+
+/* Applies a binary operator to borrowed values, returning a new one. */
+static inline WhyValue why_binary(WhyOp op, WhyValue l, WhyValue r) {
+    if (l.tag == WHY_INT && r.tag == WHY_INT) {
+        return why_binary_int(op, l.as.i, r.as.i);
+    }
+
+    if (op == WHY_OP_ADD && l.tag == WHY_STRING && r.tag == WHY_STRING) {
+        WhyValue v = why_string_new(l.as.s->len + r.as.s->len);
+        memcpy(v.as.s->data, l.as.s->data, l.as.s->len);
+        memcpy(v.as.s->data + l.as.s->len, r.as.s->data, r.as.s->len);
+        return v;
+    }
+
+    if (op == WHY_OP_ADD && l.tag == WHY_ARRAY && r.tag == WHY_ARRAY) {
+        WhyValue v = why_array_new(l.as.a->len + r.as.a->len);
+
+        for (size_t i = 0; i < l.as.a->len; i++) {
+            why_array_push(v.as.a, why_retain(l.as.a->items[i]));
+        }
+
+        for (size_t i = 0; i < r.as.a->len; i++) {
+            why_array_push(v.as.a, why_retain(r.as.a->items[i]));
+        }
+
+        return v;
+    }
+
+    double lf, rf;
+
+    if (why_as_float(l, &lf) && why_as_float(r, &rf)) {
+        switch (op) {
+        case WHY_OP_ADD:
+            return why_float(lf + rf);
+        case WHY_OP_SUBTRACT:
+            return why_float(lf - rf);
+        case WHY_OP_MULT:
+            return why_float(lf * rf);
+        case WHY_OP_DIV:
+            return why_float(lf / rf);
+        case WHY_OP_POW:
+            return why_float(pow(lf, rf));
+        default:
+            why_failf("Unsupported operator %s for float", why_op_names[op]);
+        }
+    }
+
+    WhyBuf message = {0};
+    why_buf_printf(&message, "Unsupported operand types for %s: ", why_op_names[op]);
+    why_typeof_write(&message, l);
+    why_buf_str(&message, " and ");
+    why_typeof_write(&message, r);
+    why_fail(&message);
+}
+
+static inline WhyValue why_unary(WhyOp op, WhyValue v) {
+    if (op == WHY_OP_SUBTRACT && v.tag == WHY_INT) {
+        if (v.as.i == INT64_MIN) {
+            why_failf("Integer overflow negating %lld", (long long)v.as.i);
+        }
+        return why_int(-v.as.i);
+    }
+
+    if (op == WHY_OP_SUBTRACT && v.tag == WHY_FLOAT) {
+        return why_float(-v.as.f);
+    }
+
+    if (op == WHY_OP_NOT && v.tag == WHY_BOOL) {
+        return why_bool(!v.as.b);
+    }
+
+    WhyBuf message = {0};
+    why_buf_printf(&message, "Unsupported operand type for %s: ", why_op_names[op]);
+    why_typeof_write(&message, v);
+    why_fail(&message);
+}
+
+static inline WhyValue why_compare(WhyCond cond, WhyValue l, WhyValue r) {
+    int ordering = 0;
+    bool ordered = false;
+    double lf, rf;
+
+    if (l.tag == WHY_STRING && r.tag == WHY_STRING) {
+        size_t len = l.as.s->len < r.as.s->len ? l.as.s->len : r.as.s->len;
+        int cmp = memcmp(l.as.s->data, r.as.s->data, len);
+
+        if (cmp == 0) {
+            cmp = (l.as.s->len > r.as.s->len) - (l.as.s->len < r.as.s->len);
+        }
+
+        ordering = (cmp > 0) - (cmp < 0);
+        ordered = true;
+    } else if (why_as_float(l, &lf) && why_as_float(r, &rf) && !isnan(lf) && !isnan(rf)) {
+        ordering = (lf > rf) - (lf < rf);
+        ordered = true;
+    }
+
+    if (!ordered) {
+        switch (cond) {
+        case WHY_EQEQ:
+            return why_bool(why_eq(l, r));
+        case WHY_NE:
+            return why_bool(!why_eq(l, r));
+        default: {
+            WhyBuf message = {0};
+            why_buf_str(&message, "Can't compare ");
+            why_typeof_write(&message, l);
+            why_buf_str(&message, " and ");
+            why_typeof_write(&message, r);
+            why_buf_printf(&message, " with %s", why_cond_names[cond]);
+            why_fail(&message);
+        }
+        }
+    }
+
+    switch (cond) {
+    case WHY_LT:
+        return why_bool(ordering < 0);
+    case WHY_GT:
+        return why_bool(ordering > 0);
+    case WHY_LTE:
+        return why_bool(ordering <= 0);
+    case WHY_GTE:
+        return why_bool(ordering >= 0);
+    case WHY_NE:
+        return why_bool(ordering != 0);
+    case WHY_EQEQ:
+        return why_bool(ordering == 0);
+    }
+
+    return why_bool(false);
+}
+
+/* Indexing */
+
+/* The length of a UTF-8 sequence from its first byte. */
+static inline size_t why_utf8_len(unsigned char c) {
+    return c < 0x80 ? 1 : c < 0xe0 ? 2 : c < 0xf0 ? 3 : 4;
+}
+
+static inline size_t why_chars(const WhyString *s) {
+    size_t count = 0;
+
+    for (size_t i = 0; i < s->len; i += why_utf8_len((unsigned char)s->data[i])) {
+        count++;
+    }
+
+    return count;
+}
+
+static inline _Noreturn void why_fail_index(WhyValue v, WhyValue index) {
+    WhyBuf message = {0};
+    why_buf_str(&message, "Can't index ");
+    why_typeof_write(&message, v);
+    why_buf_str(&message, " with ");
+    why_typeof_write(&message, index);
+    why_fail(&message);
+}
+
+/* Finds the element of an array or mapping to change, inserting missing keys if asked to. */
+static inline WhyValue *why_index_mut(WhyValue *v, WhyValue index, bool insert) {
+    if (v->tag == WHY_ARRAY && index.tag == WHY_INT) {
+        why_make_unique(v);
+
+        if (index.as.i < 0 || (uint64_t)index.as.i >= v->as.a->len) {
+            why_failf("Index %lld out of range for array of length %zu", (long long)index.as.i,
+                      v->as.a->len);
+        }
+
+        return &v->as.a->items[index.as.i];
+    }
+
+    if (v->tag == WHY_MAPPING) {
+        why_make_unique(v);
+        WhyMapping *mapping = v->as.m;
+
+        for (size_t i = 0; i < mapping->len; i++) {
+            if (why_eq(mapping->keys[i], index)) {
+                return &mapping->values[i];
+            }
+        }
+
+        if (!insert) {
+            WhyBuf message = {0};
+            why_buf_str(&message, "Key ");
+            why_write(&message, index, true);
+            why_buf_str(&message, " not found");
+            why_fail(&message);
+        }
+
+        why_mapping_push(mapping, why_retain(index), why_null());
+        return &mapping->values[mapping->len - 1];
+    }
+
+    why_fail_index(*v, index);
+}
+
+/* Indexes an array, mapping or string, returning a new reference. */
+static inline WhyValue why_index(WhyValue v, WhyValue index) {
+    if (v.tag == WHY_STRING && index.tag == WHY_INT) {
+        const WhyString *s = v.as.s;
+        int64_t n = index.as.i;
+
+        for (size_t i = 0; i < s->len && n >= 0; i += why_utf8_len((unsigned char)s->data[i]), n--) {
+            if (n == 0) {
+                return why_string(s->data + i, why_utf8_len((unsigned char)s->data[i]));
+            }
+        }
+
+        why_failf("Index %lld out of range for string", (long long)index.as.i);
+    }
+
+    if (v.tag == WHY_ARRAY && index.tag == WHY_INT) {
+        if (index.as.i < 0 || (uint64_t)index.as.i >= v.as.a->len) {
+            why_failf("Index %lld out of range for array of length %zu", (long long)index.as.i,
+                      v.as.a->len);
+        }
+
+        return why_retain(v.as.a->items[index.as.i]);
+    }
+
+    if (v.tag == WHY_MAPPING) {
+        for (size_t i = 0; i < v.as.m->len; i++) {
+            if (why_eq(v.as.m->keys[i], index)) {
+                return why_retain(v.as.m->values[i]);
+            }
+        }
+
+        WhyBuf message = {0};
+        why_buf_str(&message, "Key ");
+        why_write(&message, index, true);
+        why_buf_str(&message, " not found");
+        why_fail(&message);
+    }
+
+    why_fail_index(v, index);
+}
+
+/* Builds an array, taking the items. */
+static inline WhyValue why_array_of(size_t len, const WhyValue *items) {
+    WhyValue v = why_array_new(len);
+
+    for (size_t i = 0; i < len; i++) {
+        why_array_push(v.as.a, items[i]);
+    }
+
+    return v;
+}
+
+/* Builds a mapping from keys and values in turn, taking them. */
+static inline WhyValue why_mapping_of(size_t len, const WhyValue *items) {
+    WhyValue v = why_mapping_new(len);
+
+    for (size_t i = 0; i < len; i++) {
+        WhyValue *slot = why_index_mut(&v, items[2 * i], true);
+        why_release(items[2 * i]);
+        why_release(*slot);
+        *slot = items[2 * i + 1];
+    }
+
+    return v;
+}
+
+/* Variables */
+
+static inline _Noreturn void why_fail_assign(WhyValue v, const char *name, const WhyType *type) {
+    WhyBuf message = {0};
+    why_buf_str(&message, "Can't assign ");
+    why_typeof_write(&message, v);
+    why_buf_printf(&message, " to '%s' of type ", name);
+    why_type_write(&message, type);
+    why_fail(&message);
+}
+
+static inline void why_binding_release(WhyBinding *binding) {
+    why_release(binding->value);
+    why_type_free(binding->type);
+    binding->value = why_null();
+    binding->type = NULL;
+    binding->defined = false;
+}
+
+static inline void why_bindings_release(WhyBinding *bindings, size_t len) {
+    for (size_t i = 0; i < len; i++) {
+        why_binding_release(&bindings[i]);
+    }
+}
+
+/* Declares a variable, with its declared type or else its value's, taking the value. */
+static inline void why_define(WhyBinding *binding, const char *name, const WhyType *type, bool constant,
+                       WhyValue v) {
+    if (!type) {
+        type = why_typeof(v);
+    } else if (!why_accepts(type, v)) {
+        why_fail_assign(v, name, type);
+    }
+
+    why_binding_release(binding);
+    binding->value = why_coerce(v, type);
+    binding->type = type;
+    binding->constant = constant;
+    binding->defined = true;
+}
+
+/* Binds an argument, which has been checked, to a parameter. */
+static inline void why_param(WhyBinding *binding, WhyValue arg, const WhyType *type) {
+    binding->value = why_coerce(why_retain(arg), type);
+    binding->type = type;
+    binding->constant = false;
+    binding->defined = true;
+}
+
+/* Reads a global, which might be a builtin that hasn't been shadowed. */
+static inline WhyValue why_global(const WhyBinding *binding, const char *name, int builtin) {
+    if (binding->defined) {
+        return why_retain(binding->value);
+    }
+
+    if (builtin >= 0) {
+        return why_builtin((WhyBuiltin)builtin);
+    }
+
+    why_failf("Undefined variable '%s'", name);
+}
+
+/*
+ * Assigns a value to a variable, or to the element its indexes reach,
+ * applying a compound operator like `+=`. The indexes are in the order
+ * they were evaluated, the last subscript first. Takes the value.
+ */
+static inline void why_assign(WhyBinding *binding, const char *name, WhyOp op, size_t depth,
+                       const WhyValue *indexes, WhyValue v) {
+    if (!binding->defined) {
+        why_failf("Undefined variable '%s'", name);
+    }
+
+    if (binding->constant) {
+        why_failf("Can't assign to constant '%s'", name);
+    }
+
+    WhyValue *slot = &binding->value;
+    const WhyType *type = binding->type;
+
+    for (size_t i = depth; i > 0; i--) {
+        slot = why_index_mut(slot, indexes[i - 1], op == WHY_OP_ASSIGN);
+        type = why_item_type(type);
+    }
+
+    WhyValue value;
+
+    switch (op) {
+    case WHY_OP_ASSIGN:
+        value = v;
+        break;
+    case WHY_OP_INCREMENT:
+    case WHY_OP_INCREMENT_BY:
+        value = why_binary(WHY_OP_ADD, *slot, v);
+        why_release(v);
+        break;
+    case WHY_OP_DECREMENT:
+    case WHY_OP_DECREMENT_BY:
+        value = why_binary(WHY_OP_SUBTRACT, *slot, v);
+        why_release(v);
+        break;
+    case WHY_OP_MULT_BY:
+        value = why_binary(WHY_OP_MULT, *slot, v);
+        why_release(v);
+        break;
+    case WHY_OP_DIV_BY:
+        value = why_binary(WHY_OP_DIV, *slot, v);
+        why_release(v);
+        break;
+    default:
+        why_failf("Invalid assignment operator %s", why_op_names[op]);
+    }
+
+    if (depth == 0 && !why_accepts(binding->type, value)) {
+        why_fail_assign(value, name, binding->type);
+    }
+
+    why_release(*slot);
+    *slot = why_coerce(value, type);
+}
+
+/* Calls */
+
+static inline bool why_cond(WhyValue v) {
+    if (v.tag != WHY_BOOL) {
+        WhyBuf message = {0};
+        why_buf_str(&message, "Condition must be a bool, but got ");
+        why_typeof_write(&message, v);
+        why_fail(&message);
+    }
+
+    return v.as.b;
+}
+
+static inline WhyValue why_call_builtin(WhyBuiltin builtin, size_t argc, const WhyValue *args) {
+    switch (builtin) {
+    case WHY_PRINT: {
+        WhyBuf out = {0};
+
+        for (size_t i = 0; i < argc; i++) {
+            if (i > 0) {
+                why_buf_str(&out, " ");
+            }
+            why_write(&out, args[i], false);
+        }
+
+        why_buf_str(&out, "\n");
+        fwrite(out.data, 1, out.len, stdout);
+        free(out.data);
+        return why_null();
+    }
+    case WHY_LEN:
+        if (argc == 1 && args[0].tag == WHY_STRING) {
+            return why_int((int64_t)why_chars(args[0].as.s));
+        }
+        if (argc == 1 && args[0].tag == WHY_ARRAY) {
+            return why_int((int64_t)args[0].as.a->len);
+        }
+        if (argc == 1 && args[0].tag == WHY_MAPPING) {
+            return why_int((int64_t)args[0].as.m->len);
+        }
+        break;
+    case WHY_STR:
+        if (argc == 1) {
+            WhyBuf out = {0};
+            why_write(&out, args[0], false);
+            WhyValue v = why_string(out.data ? out.data : "", out.len);
+            free(out.data);
+            return v;
+        }
+        break;
+    }
+
+    why_failf("Invalid arguments for builtin '%s'", why_builtin_names[builtin]);
+}
+
+/* Calls a function or builtin with borrowed arguments, checking them and the returned value. */
+static inline WhyValue why_call(WhyValue callee, size_t argc, WhyValue *args) {
+    if (callee.tag == WHY_BUILTIN) {
+        return why_call_builtin(callee.as.builtin, argc, args);
+    }
+
+    if (callee.tag != WHY_FUNC) {
+        WhyBuf message = {0};
+        why_typeof_write(&message, callee);
+        why_buf_str(&message, " is not callable");
+        why_fail(&message);
+    }
+
+    const WhyFunc *fn = callee.as.fn;
+    const WhyType *type = fn->type;
+
+    if (argc != type->n) {
+        why_failf("'%s' takes %zu argument(s), but %zu were given", fn->name, type->n, argc);
+    }
+
+    for (size_t i = 0; i < argc; i++) {
+        if (!why_accepts(type->params[i], args[i])) {
+            WhyBuf message = {0};
+            why_buf_printf(&message, "'%s' expected ", fn->name);
+            why_type_write(&message, type->params[i]);
+            why_buf_printf(&message, " for '%s', but got ", fn->params[i]);
+            why_typeof_write(&message, args[i]);
+            why_fail(&message);
+        }
+    }
+
+    WhyValue result = fn->call(args);
+
+    if (!why_accepts(type->b, result)) {
+        WhyBuf message = {0};
+        why_buf_printf(&message, "'%s' should return ", fn->name);
+        why_type_write(&message, type->b);
+        why_buf_str(&message, ", but returned ");
+        why_typeof_write(&message, result);
+        why_fail(&message);
+    }
+
+    return why_coerce(result, type->b);
+}
@@ -0,0 +1,172 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex, OnceLock};
+
+use super::KEYWORDS;
+
+/// An interned name, such as an identifier or keyword. Symbols are
+/// compared and hashed as integers, and every occurrence of the same
+/// name is the same symbol.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Symbol(u32);
+
+/// The longest a name may be, in bytes. Longer identifiers are a lex
+/// error, so a name being edited can't leak more than this for each
+/// version of it.
+pub const MAX_NAME_LEN: usize = 255;
+
+/// How many names the first chunk of [`NAMES`] holds. Each chunk after
+/// it holds twice as many as the one before.
+const FIRST_CHUNK: usize = 64;
+
+/// Enough chunks for every `u32` to be a symbol.
+const CHUNKS: usize = 27;
+
+/// The name of every symbol, by id. A chunk is only ever added and a
+/// slot only ever filled once, so names are read without a lock, and
+/// a chunk never moves once it has been handed out.
+static NAMES: [OnceLock<Box<[OnceLock<&'static str>]>>; CHUNKS] =
+    [const { OnceLock::new() }; CHUNKS];
+
+/// The symbol of every name interned so far, shared by every thread so
+/// symbols can be passed between them.
+///
+/// Names are never freed, as a symbol may be copied anywhere. Instead,
+/// the memory they take is bounded: each distinct name is stored once,
+/// in at most [`MAX_NAME_LEN`] bytes, and only names that are parsed or
+/// declared are interned, never those that are merely looked up (see
+/// [`Symbol::lookup`]). So a long-running process, like the language
+/// server re-parsing a document on every change, grows with the
+/// distinct names it has seen rather than with how often it sees them,
+/// which [`Symbol::count`] reports. Keywords take no memory as they are
+/// static.
+static IDS: LazyLock<Mutex<HashMap<&'static str, Symbol>>> = LazyLock::new(|| {
+    let mut ids = HashMap::new();
+
+    // Keywords take the first ids
+    for keyword in KEYWORDS {
+        insert(&mut ids, keyword);
+    }
+
+    Mutex::new(ids)
+});
+
+thread_local! {
+    /// The symbols this thread has interned, so interning a name seen
+    /// before doesn't take the lock.
+    static SEEN: RefCell<HashMap<&'static str, Symbol>> = RefCell::new(HashMap::new());
+}
+
+/// The chunk of [`NAMES`] a symbol's name is in, and where in it.
+fn slot(symbol: Symbol) -> (usize, usize) {
+    let idx = symbol.0 as usize + FIRST_CHUNK;
+    let chunk = idx.ilog2() as usize - FIRST_CHUNK.ilog2() as usize;
+
+    (chunk, idx - (FIRST_CHUNK << chunk))
+}
+
+/// Gives a name the next id. The caller holds the lock on [`IDS`], so
+/// ids are handed out in order.
+fn insert(ids: &mut HashMap<&'static str, Symbol>, name: &'static str) -> Symbol {
+    let symbol = Symbol(u32::try_from(ids.len()).expect("too many symbols"));
+    let (chunk, offset) = slot(symbol);
+
+    NAMES[chunk].get_or_init(|| (0..FIRST_CHUNK << chunk).map(|_| OnceLock::new()).collect())
+        [offset]
+        .get_or_init(|| name);
+    ids.insert(name, symbol);
+    symbol
+}
+
+impl Symbol {
+    /// Interns a name, returning the symbol every occurrence of it
+    /// shares.
+    ///
+    /// # Panics
+    /// - If the interner's lock was poisoned, or more than `u32::MAX`
+    ///   distinct names were interned.
+    #[must_use]
+    pub fn intern(name: &str) -> Self {
+        if let Some(symbol) = SEEN.with_borrow(|seen| seen.get(name).copied()) {
+            return symbol;
+        }
+
+        let mut ids = IDS.lock().unwrap();
+        let (name, symbol) = if let Some((name, symbol)) = ids.get_key_value(name) {
+            (*name, *symbol)
+        } else {
+            let name: &'static str = Box::leak(name.into());
+            (name, insert(&mut ids, name))
+        };
+
+        SEEN.with_borrow_mut(|seen| seen.insert(name, symbol));
+        symbol
+    }
+
+    /// The symbol a name was interned as, without interning it if it
+    /// wasn't.
+    ///
+    /// # Panics
+    /// - If the interner's lock was poisoned.
+    #[must_use]
+    pub fn lookup(name: &str) -> Option<Self> {
+        SEEN.with_borrow(|seen| seen.get(name).copied())
+            .or_else(|| IDS.lock().unwrap().get(name).copied())
+    }
+
+    /// How many distinct names have been interned, keywords included,
+    /// which is what the memory the interner takes grows with.
+    ///
+    /// # Panics
+    /// - If the interner's lock was poisoned.
+    #[must_use]
+    pub fn count() -> usize {
+        IDS.lock().unwrap().len()
+    }
+
+    /// The name this symbol was interned from.
+    ///
+    /// # Panics
+    /// - If the symbol wasn't made by [`Symbol::intern`].
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        let (chunk, offset) = slot(self);
+
+        NAMES[chunk]
+            .get()
+            .and_then(|names| names[offset].get())
+            .expect("symbol was never interned")
+    }
+
+    /// Returns true if this is one of the [`KEYWORDS`], which only
+    /// compares the id as the keywords are interned first.
+    #[must_use]
+    pub fn is_keyword(self) -> bool {
+        (self.0 as usize) < KEYWORDS.len()
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
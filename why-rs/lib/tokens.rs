@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum TokenType {
     Eof,
@@ -29,6 +31,7 @@ pub enum TokenType {
     SmallRArrow,
     LargeRArrow,
     NumLiteral(bool), // Indicating whether this is a float
+    DocComment,
     StrLiteral,
     Lt,
     Gt,
@@ -46,6 +49,14 @@ pub enum TokenType {
     Percent,
     Keyword,
     Null,
+    /// Text that failed to lex, standing in for the token it should
+    /// have been so the parser can carry on.
+    Error,
+    // Trivia, only lexed in the lexer's trivia mode
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -54,21 +65,43 @@ pub struct Loc {
     pub col: usize,
 }
 
+/// A token, borrowing its text from the source it was lexed from.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Token {
+pub struct Token<'src> {
     pub typ: TokenType,
-    pub value: String,
+    /// The text of the token, a slice of the source unless the token
+    /// was made some other way.
+    pub value: Cow<'src, str>,
     pub loc: Loc,
-    pub addtl: Option<Vec<String>>,
+    /// The exact source text of the token, if it was lexed in trivia
+    /// mode and the text differs from the value, like the quotes around
+    /// a string.
+    pub raw: Option<Cow<'src, str>>,
+}
+
+impl TokenType {
+    /// Returns true for tokens that don't affect the meaning of the
+    /// program, such as comments and whitespace.
+    #[must_use]
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenType::DocComment
+                | TokenType::Whitespace
+                | TokenType::Newline
+                | TokenType::LineComment
+                | TokenType::BlockComment
+        )
+    }
 }
 
-impl Default for Token {
+impl Default for Token<'_> {
     fn default() -> Self {
         Self {
             typ: TokenType::Null,
-            value: String::new(),
+            value: Cow::Borrowed(""),
             loc: Loc::default(),
-            addtl: None,
+            raw: None,
         }
     }
 }
@@ -97,19 +130,37 @@ impl std::fmt::Display for Loc {
     }
 }
 
-impl std::fmt::Display for Token {
+impl std::fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}('{}') @ {}", self.typ, self.value, self.loc)
     }
 }
 
-impl Token {
+impl<'src> Token<'src> {
+    /// The exact source text of a token lexed in trivia mode.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        self.raw.as_deref().unwrap_or(&self.value)
+    }
+
+    /// Copies the token's text, so it can outlive the source it was
+    /// lexed from.
+    #[must_use]
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            typ: self.typ,
+            value: Cow::Owned(self.value.into_owned()),
+            loc: self.loc,
+            raw: self.raw.map(|raw| Cow::Owned(raw.into_owned())),
+        }
+    }
+
     #[must_use]
     pub fn new(typ: TokenType) -> Self {
         Self {
             typ,
-            value: "".to_string(),
-            addtl: None,
+            value: Cow::Borrowed(""),
+            raw: None,
             loc: Loc::new(),
         }
     }
@@ -118,28 +169,33 @@ impl Token {
     pub fn at(typ: TokenType, line: usize, col: usize) -> Self {
         Self {
             typ,
-            value: "".to_string(),
-            addtl: None,
+            value: Cow::Borrowed(""),
+            raw: None,
             loc: Loc::at(line, col),
         }
     }
 
     #[must_use]
-    pub fn with_value(typ: TokenType, value: String) -> Self {
+    pub fn with_value(typ: TokenType, value: impl Into<Cow<'src, str>>) -> Self {
         Self {
             typ,
-            value,
-            addtl: None,
+            value: value.into(),
+            raw: None,
             loc: Loc::new(),
         }
     }
 
     #[must_use]
-    pub fn with_value_at(typ: TokenType, value: String, line: usize, col: usize) -> Self {
+    pub fn with_value_at(
+        typ: TokenType,
+        value: impl Into<Cow<'src, str>>,
+        line: usize,
+        col: usize,
+    ) -> Self {
         Self {
             typ,
-            value,
-            addtl: None,
+            value: value.into(),
+            raw: None,
             loc: Loc::at(line, col),
         }
     }
@@ -0,0 +1,87 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// The stage of the pipeline a trace message comes from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stage {
+    Lexer,
+    Parser,
+}
+
+/// Receives trace messages from the lexer and parser.
+///
+/// Messages are only built for stages the tracer is enabled for, so a
+/// disabled tracer costs nothing.
+pub trait Tracer: Debug {
+    /// Returns true if messages from the stage should be traced.
+    fn enabled(&self, stage: Stage) -> bool;
+
+    /// Handles a single trace message.
+    fn trace(&self, stage: Stage, message: &str);
+}
+
+/// A tracer that discards everything, the default for library users.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SilentTracer;
+
+impl Tracer for SilentTracer {
+    fn enabled(&self, _stage: Stage) -> bool {
+        false
+    }
+
+    fn trace(&self, _stage: Stage, _message: &str) {}
+}
+
+/// A tracer that writes the enabled stages to stderr.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StderrTracer {
+    pub lexer: bool,
+    pub parser: bool,
+}
+
+impl Tracer for StderrTracer {
+    fn enabled(&self, stage: Stage) -> bool {
+        match stage {
+            Stage::Lexer => self.lexer,
+            Stage::Parser => self.parser,
+        }
+    }
+
+    fn trace(&self, stage: Stage, message: &str) {
+        eprintln!("[{stage:?}] {message}");
+    }
+}
+
+/// A tracer that keeps the messages of every stage, for inspecting
+/// them afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct RecordingTracer {
+    pub messages: RefCell<Vec<(Stage, String)>>,
+}
+
+impl Tracer for RecordingTracer {
+    fn enabled(&self, _stage: Stage) -> bool {
+        true
+    }
+
+    fn trace(&self, stage: Stage, message: &str) {
+        self.messages
+            .borrow_mut()
+            .push((stage, message.to_string()));
+    }
+}
+
+/// Traces a message if the tracer is enabled for the stage, building
+/// the message lazily.
+pub fn trace(tracer: &Rc<dyn Tracer>, stage: Stage, message: impl FnOnce() -> String) {
+    if tracer.enabled(stage) {
+        tracer.trace(stage, &message());
+    }
+}
+
+/// The tracer used when none is given.
+#[must_use]
+pub fn silent() -> Rc<dyn Tracer> {
+    Rc::new(SilentTracer)
+}
@@ -0,0 +1,133 @@
+use std::fmt::Write;
+
+use super::internal;
+use super::Loc;
+use super::Symbol;
+
+/// How many calls may be nested before a run fails with a stack
+/// overflow, unless their stack runs past the [`STACK_LIMIT`] first.
+/// Each takes up to [`STACK_PER_CALL`], so running this deep takes a
+/// thread of [`stack_size`].
+pub const MAX_DEPTH: usize = 512;
+
+/// The most stack a call in a Why program takes in a debug build, with
+/// room to spare. Release builds take much less.
+pub const STACK_PER_CALL: usize = 32 * 1024;
+
+/// The deepest calls may be allowed to nest, so a thread of
+/// [`stack_size`] for it takes about a gigabyte, which is still
+/// reserved rather than used until the calls run that deep.
+pub const DEPTH_LIMIT: usize = 1 << 15;
+
+/// How much stack the calls of a run may take by default, which leaves
+/// room for the host in the 2MB a spawned thread gets. Hosts that run
+/// programs on a thread of [`stack_size`] can allow [`stack_limit`].
+pub const STACK_LIMIT: usize = 1 << 20;
+
+/// How much stack calls nested up to a depth may take.
+#[must_use]
+pub fn stack_limit(max_depth: usize) -> usize {
+    max_depth.saturating_mul(STACK_PER_CALL)
+}
+
+/// How big a thread's stack should be to run programs that nest calls
+/// up to a depth, for hosts that run them on a thread of their own.
+#[must_use]
+pub fn stack_size(max_depth: usize) -> usize {
+    stack_limit(max_depth).saturating_add(8 << 20)
+}
+
+/// Where the top of the stack is, to measure how much a run has taken
+/// by comparing it with where the run started.
+#[must_use]
+pub fn stack_pointer() -> usize {
+    let marker = 0_u8;
+    std::hint::black_box(&raw const marker) as usize
+}
+
+/// A call in progress, kept for tracebacks. The last entry of a
+/// traceback is where the run failed instead, in the function named.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CallSite {
+    /// The function called.
+    pub name: Symbol,
+    /// Where it was called from. The column is 0 when only the line is
+    /// known.
+    pub loc: Loc,
+}
+
+/// The error for a call nested deeper than the maximum depth.
+///
+/// # Errors
+/// - Always, as a stack overflow.
+pub fn overflow<T>(max_depth: usize) -> Result<T, String> {
+    super::exc!("Stack overflow: more than {} nested calls", max_depth)
+}
+
+/// The error for calls that took more than their stack limit before
+/// reaching the maximum depth.
+///
+/// # Errors
+/// - Always, as a stack overflow.
+pub fn out_of_stack<T>(depth: usize) -> Result<T, String> {
+    super::exc!("Stack overflow: out of stack after {} nested calls", depth)
+}
+
+/// Renders an error with the calls it happened in, innermost last, each
+/// with the source it was called from, then where it failed.
+#[must_use]
+pub fn render(error: &str, calls: &[CallSite], src: &str) -> String {
+    if calls.is_empty() {
+        return error.to_string();
+    }
+
+    let mut out = String::from("Traceback (innermost last):\n");
+    let caller = |i: usize| match i {
+        0 => Symbol::intern("<main>"),
+        i => calls[i - 1].name,
+    };
+
+    // Repeated calls, like runaway recursion, are collapsed
+    let mut i = 0;
+
+    while i < calls.len() {
+        let call = &calls[i];
+        let repeats = (i..calls.len())
+            .take_while(|j| calls[*j] == *call && caller(*j) == caller(i))
+            .count();
+
+        match (call.loc.line, call.loc.col) {
+            // Calls from a host have no place in the source
+            (0, 0) => out.push_str("  called from the host\n"),
+            (line, 0) => {
+                let _ = writeln!(out, "  line {}, in {}", line, caller(i));
+            }
+            (_, col) => {
+                let _ = writeln!(
+                    out,
+                    "  line {}, column {}, in {}",
+                    call.loc.line,
+                    col,
+                    caller(i)
+                );
+            }
+        }
+
+        for line in internal::snippet(src, &call.loc).lines() {
+            let _ = writeln!(out, "  {line}");
+        }
+
+        if repeats > 1 {
+            let _ = writeln!(
+                out,
+                "  [the call above repeated {} more times]",
+                repeats - 1
+            );
+        }
+
+        i += repeats;
+    }
+
+    out.push_str(error);
+    out
+}
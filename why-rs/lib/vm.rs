@@ -0,0 +1,374 @@
+use std::rc::Rc;
+
+use super::traceback;
+use super::Binding;
+use super::CallSite;
+use super::Function;
+use super::HeapStats;
+use super::Interpreter;
+use super::Loc;
+use super::Op;
+use super::Program;
+use super::Slot;
+use super::Value;
+use super::BUILTINS;
+
+type EvalResult = Result<Value, String>;
+
+/// A call in progress.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub function: Rc<Function>,
+    /// The offset of the next op to run.
+    pub ip: usize,
+    /// Where the call's locals start in [`Vm::locals`].
+    pub locals: usize,
+    /// How many values were on the stack when the call started.
+    pub stack: usize,
+    /// Where the call was made from, `None` for the top level.
+    pub call: Option<Loc>,
+}
+
+/// A stack based virtual machine, which runs programs compiled by the
+/// [`super::Compiler`]. It behaves like the [`Interpreter`], with which
+/// it shares its operators and builtins.
+#[derive(Clone, Debug)]
+pub struct Vm {
+    /// The globals by index, `None` until they are declared.
+    pub globals: Vec<Option<Binding>>,
+    /// The values being worked on.
+    pub stack: Vec<Value>,
+    /// The locals of every call, each call's after its caller's, and
+    /// `None` until declared or once deleted.
+    pub locals: Vec<Option<Binding>>,
+    /// The calls waiting for the current one to return.
+    pub frames: Vec<Frame>,
+    /// Counts the heap values stored in variables, if enabled.
+    pub heap: Option<HeapStats>,
+    /// The calls that were in progress when the last run failed, then
+    /// where it failed.
+    pub traceback: Vec<CallSite>,
+    /// How many calls may be nested before a run fails.
+    pub max_depth: usize,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self {
+            globals: Vec::new(),
+            stack: Vec::new(),
+            locals: Vec::new(),
+            frames: Vec::new(),
+            heap: None,
+            traceback: Vec::new(),
+            max_depth: traceback::MAX_DEPTH,
+        }
+    }
+}
+
+impl Frame {
+    /// Where the op the call last ran was compiled from.
+    #[must_use]
+    pub fn loc(&self) -> Loc {
+        let locs = &self.function.chunk.locs;
+        locs.get(self.ip.wrapping_sub(1))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Vm {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the heap values stored in variables, see [`HeapStats`].
+    #[must_use]
+    pub fn with_heap_stats(mut self) -> Self {
+        self.heap = Some(HeapStats::new());
+        self
+    }
+
+    /// Sets how many calls may be nested before a run fails with a
+    /// stack overflow.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Runs a program, returning the value of its last statement if
+    /// that statement was an expression. If it fails, the calls it
+    /// failed in are kept in [`Vm::traceback`].
+    ///
+    /// # Returns
+    /// - [`EvalResult`] - The resulting value on success.
+    ///
+    /// # Errors
+    /// - If a runtime error occurs.
+    pub fn run(&mut self, program: &Program) -> EvalResult {
+        self.globals = vec![None; program.globals.len()];
+        self.stack.clear();
+        self.locals.clear();
+        self.frames.clear();
+        self.traceback.clear();
+
+        let main = Rc::clone(&program.main);
+        self.locals.resize(main.locals.len(), None);
+
+        let mut frame = Frame {
+            function: main,
+            ip: 0,
+            locals: 0,
+            stack: 0,
+            call: None,
+        };
+
+        let result = self.execute(program, &mut frame);
+
+        if result.is_err() {
+            let calls = self.frames.iter().chain([&frame]).filter_map(|frame| {
+                Some(CallSite {
+                    name: frame.function.name,
+                    loc: frame.call.clone()?,
+                })
+            });
+
+            self.traceback = calls.collect();
+            self.traceback.push(CallSite {
+                name: frame.function.name,
+                loc: frame.loc(),
+            });
+        }
+
+        result
+    }
+
+    /// Pops the value on top of the stack, which the compiler makes
+    /// sure is there.
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap_or(Value::Null)
+    }
+
+    /// Pops the given number of values, in the order they were pushed.
+    fn pop_n(&mut self, n: u32) -> Vec<Value> {
+        let len = self.stack.len().saturating_sub(n as usize);
+        self.stack.split_off(len)
+    }
+
+    /// The variable in a slot, if it has been declared.
+    fn binding(&mut self, frame: &Frame, slot: Slot) -> Option<&mut Binding> {
+        let binding = match slot {
+            Slot::Local(idx) => self.locals.get_mut(frame.locals + idx as usize),
+            Slot::Global(idx) => self.globals.get_mut(idx as usize),
+        };
+
+        binding?.as_mut()
+    }
+
+    /// Runs ops from a frame until the program's top level returns.
+    #[allow(clippy::too_many_lines)] // The dispatch loop, one arm per op
+    fn execute(&mut self, program: &Program, frame: &mut Frame) -> EvalResult {
+        loop {
+            let Some(op) = frame.function.chunk.code.get(frame.ip).copied() else {
+                return Ok(Value::Null);
+            };
+            frame.ip += 1;
+
+            match op {
+                Op::Constant(idx) => {
+                    let value = frame.function.chunk.constants[idx as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::GetLocal(slot) => {
+                    let Some(binding) = &self.locals[frame.locals + slot as usize] else {
+                        let name = frame.function.slot_name(Slot::Local(slot), program);
+                        return super::exc!("Undefined variable '{}'", name);
+                    };
+
+                    self.stack.push(binding.value.clone());
+                }
+                Op::GetGlobal(idx) => {
+                    let name = program.globals[idx as usize];
+
+                    let value = match &self.globals[idx as usize] {
+                        Some(binding) => binding.value.clone(),
+                        None if BUILTINS.contains(&name.as_str()) => Value::Builtin(name),
+                        None => return super::exc!("Undefined variable '{}'", name),
+                    };
+
+                    self.stack.push(value);
+                }
+                Op::Define(slot, decl) => {
+                    let value = self.pop();
+                    let name = frame.function.slot_name(slot, program);
+                    let (typ, constant) = &frame.function.chunk.decls[decl as usize];
+                    let binding = Binding::new(name, typ.clone(), value, *constant)?;
+
+                    if let Some(heap) = &mut self.heap {
+                        heap.alloc(&binding.value);
+                    }
+
+                    let replaced = match slot {
+                        Slot::Local(idx) => {
+                            self.locals[frame.locals + idx as usize].replace(binding)
+                        }
+                        Slot::Global(idx) => self.globals[idx as usize].replace(binding),
+                    };
+
+                    if let (Some(heap), Some(replaced)) = (&mut self.heap, replaced) {
+                        heap.free(&replaced.value);
+                    }
+                }
+                Op::Assign(slot, op, depth) => {
+                    let indexes = self.pop_n(depth);
+                    let value = self.pop();
+                    let name = frame.function.slot_name(slot, program);
+
+                    let tracking = self.heap.is_some();
+                    let cells = |b: &Binding| {
+                        if tracking {
+                            HeapStats::cells(&b.value)
+                        } else {
+                            0
+                        }
+                    };
+
+                    let Some(binding) = self.binding(frame, slot) else {
+                        return super::exc!("Undefined variable '{}'", name);
+                    };
+
+                    let before = cells(binding);
+                    let replaced = binding.assign(name, &op, &indexes, value)?;
+                    let after = cells(binding);
+
+                    if let Some(heap) = &mut self.heap {
+                        heap.reassign(&replaced, before, after);
+                    }
+                }
+                Op::Delete(slot) => {
+                    let deleted = match slot {
+                        Slot::Local(idx) => self.locals[frame.locals + idx as usize].take(),
+                        Slot::Global(idx) => self.globals[idx as usize].take(),
+                    };
+
+                    match (&mut self.heap, deleted) {
+                        (Some(heap), Some(deleted)) => heap.free(&deleted.value),
+                        (None, Some(_)) => (),
+                        (_, None) => {
+                            let name = frame.function.slot_name(slot, program);
+                            return super::exc!("Can't delete undefined variable '{}'", name);
+                        }
+                    }
+                }
+                Op::Array(len) => {
+                    let items = self.pop_n(len);
+                    self.stack.push(Value::Array(items));
+                }
+                Op::Mapping(len) => {
+                    let mut items = self.pop_n(len * 2).into_iter();
+                    let mut mapping = Value::Mapping(Vec::with_capacity(len as usize));
+
+                    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                        *Interpreter::index_mut(&mut mapping, &key, true)? = value;
+                    }
+
+                    self.stack.push(mapping);
+                }
+                Op::Index => {
+                    let index = self.pop();
+                    let base = self.pop();
+                    self.stack.push(Interpreter::index(base, &index)?);
+                }
+                Op::Unary(op) => {
+                    let value = self.pop();
+                    self.stack.push(Interpreter::unary(&op, value)?);
+                }
+                Op::Binary(op) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Interpreter::binary(&op, left, right)?);
+                }
+                Op::Compare(cond) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Interpreter::compare(&cond, &left, &right)?);
+                }
+                Op::Jump(target) => frame.ip = target as usize,
+                Op::JumpIfFalse(target) => match self.pop() {
+                    Value::Bool(true) => (),
+                    Value::Bool(false) => frame.ip = target as usize,
+                    value => {
+                        return super::exc!("Condition must be a bool, but got {}", (value.typ()))
+                    }
+                },
+                Op::Call(len) => {
+                    let args = self.pop_n(len);
+
+                    let function = match self.pop() {
+                        Value::Compiled(function) => function,
+                        Value::Builtin(name) => {
+                            self.stack
+                                .push(Interpreter::call_builtin(name.as_str(), &args)?);
+                            continue;
+                        }
+                        value => return super::exc!("{} is not callable", (value.typ())),
+                    };
+
+                    if self.frames.len() >= self.max_depth {
+                        return traceback::overflow(self.max_depth);
+                    }
+
+                    let call = frame.loc();
+                    let bindings = Interpreter::bind_args(function.name, &function.params, args)?;
+                    let locals = self.locals.len();
+
+                    if let Some(heap) = &mut self.heap {
+                        for binding in &bindings {
+                            heap.alloc(&binding.value);
+                        }
+                    }
+
+                    self.locals.extend(bindings.into_iter().map(Some));
+                    self.locals.resize(locals + function.locals.len(), None);
+
+                    let callee = Frame {
+                        function,
+                        ip: 0,
+                        locals,
+                        stack: self.stack.len(),
+                        call: Some(call),
+                    };
+
+                    self.frames.push(std::mem::replace(frame, callee));
+                }
+                Op::Return => {
+                    let value = self.pop();
+
+                    let Some(caller) = self.frames.pop() else {
+                        return Ok(value);
+                    };
+
+                    let function = &frame.function;
+                    let value = Interpreter::check_return(function.name, &function.ret, value)?;
+
+                    if let Some(heap) = &mut self.heap {
+                        let freed = self.locals[frame.locals..].iter().flatten();
+                        for binding in freed {
+                            heap.free(&binding.value);
+                        }
+                    }
+
+                    self.locals.truncate(frame.locals);
+                    self.stack.truncate(frame.stack);
+                    *frame = caller;
+                    self.stack.push(value);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1386 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::internal;
+use super::Condition;
+use super::Expr;
+use super::FuncDecl;
+use super::Keyword;
+use super::Operator;
+use super::Stmt;
+use super::Symbol;
+use super::VarType;
+
+type GenResult = Result<(), String>;
+
+/// The functions a host must provide to run a generated module, all
+/// imported from the `why` module, as their name, params and result.
+///
+/// `print` writes each argument with the `print_*` function for its
+/// type, separated by `print_char(' ')` and followed by
+/// `print_char('\n')`. Floats are raised to a power with `pow`.
+pub const IMPORTS: [(&str, &[ValType], Option<ValType>); 5] = [
+    ("print_int", &[ValType::I64], None),
+    ("print_float", &[ValType::F64], None),
+    ("print_bool", &[ValType::I32], None),
+    ("print_char", &[ValType::I32], None),
+    ("pow", &[ValType::F64, ValType::F64], Some(ValType::F64)),
+];
+
+const PRINT_INT: u32 = 0;
+const PRINT_FLOAT: u32 = 1;
+const PRINT_BOOL: u32 = 2;
+const PRINT_CHAR: u32 = 3;
+const POW: u32 = 4;
+
+// The helpers every module defines, which come right after the imports
+const ADD: u32 = 5;
+const SUB: u32 = 6;
+const MUL: u32 = 7;
+const IPOW: u32 = 8;
+const CMP: u32 = 9;
+
+const I32_EQZ: Instr = Instr::Numeric("i32.eqz", 0x45);
+const I32_EQ: Instr = Instr::Numeric("i32.eq", 0x46);
+const I32_NE: Instr = Instr::Numeric("i32.ne", 0x47);
+const I32_LT_S: Instr = Instr::Numeric("i32.lt_s", 0x48);
+const I32_GT_S: Instr = Instr::Numeric("i32.gt_s", 0x4a);
+const I32_LE_S: Instr = Instr::Numeric("i32.le_s", 0x4c);
+const I32_GE_S: Instr = Instr::Numeric("i32.ge_s", 0x4e);
+const I64_EQZ: Instr = Instr::Numeric("i64.eqz", 0x50);
+const I64_EQ: Instr = Instr::Numeric("i64.eq", 0x51);
+const I64_NE: Instr = Instr::Numeric("i64.ne", 0x52);
+const I64_LT_S: Instr = Instr::Numeric("i64.lt_s", 0x53);
+const I64_GT_S: Instr = Instr::Numeric("i64.gt_s", 0x55);
+const F64_EQ: Instr = Instr::Numeric("f64.eq", 0x61);
+const F64_NE: Instr = Instr::Numeric("f64.ne", 0x62);
+const F64_LT: Instr = Instr::Numeric("f64.lt", 0x63);
+const F64_GT: Instr = Instr::Numeric("f64.gt", 0x64);
+const F64_LE: Instr = Instr::Numeric("f64.le", 0x65);
+const F64_GE: Instr = Instr::Numeric("f64.ge", 0x66);
+const I32_SUB: Instr = Instr::Numeric("i32.sub", 0x6b);
+const I32_OR: Instr = Instr::Numeric("i32.or", 0x72);
+const I64_ADD: Instr = Instr::Numeric("i64.add", 0x7c);
+const I64_SUB: Instr = Instr::Numeric("i64.sub", 0x7d);
+const I64_MUL: Instr = Instr::Numeric("i64.mul", 0x7e);
+const I64_DIV_S: Instr = Instr::Numeric("i64.div_s", 0x7f);
+const I64_AND: Instr = Instr::Numeric("i64.and", 0x83);
+const I64_XOR: Instr = Instr::Numeric("i64.xor", 0x85);
+const I64_SHR_S: Instr = Instr::Numeric("i64.shr_s", 0x87);
+const F64_NEG: Instr = Instr::Numeric("f64.neg", 0x9a);
+const F64_ADD: Instr = Instr::Numeric("f64.add", 0xa0);
+const F64_SUB: Instr = Instr::Numeric("f64.sub", 0xa1);
+const F64_MUL: Instr = Instr::Numeric("f64.mul", 0xa2);
+const F64_DIV: Instr = Instr::Numeric("f64.div", 0xa3);
+const F64_CONVERT_I64_S: Instr = Instr::Numeric("f64.convert_i64_s", 0xb9);
+
+/// A WebAssembly value type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ValType {
+    /// A bool.
+    I32,
+    /// An int.
+    I64,
+    /// A float.
+    F64,
+}
+
+/// A WebAssembly instruction, of the few the backend generates.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instr {
+    Unreachable,
+    Block,
+    Loop,
+    If,
+    Else,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Return,
+    Call(u32),
+    Drop,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    I32Const(i32),
+    I64Const(i64),
+    F64Const(f64),
+    /// An instruction without immediates, by name and opcode.
+    Numeric(&'static str, u8),
+}
+
+/// A function defined by a module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Func {
+    /// The name of the function in WAT, which is unique in its module.
+    pub name: String,
+    pub params: Vec<ValType>,
+    pub result: Option<ValType>,
+    /// The name and type of each local, the params first.
+    pub locals: Vec<(String, ValType)>,
+    pub code: Vec<Instr>,
+}
+
+/// A mutable global of a module, which starts as zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub typ: ValType,
+}
+
+/// A WebAssembly module, which imports the [`IMPORTS`] and exports the
+/// top level of the program as `main`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    pub globals: Vec<Global>,
+    /// The functions the module defines, which are numbered after the
+    /// imports.
+    pub funcs: Vec<Func>,
+    /// The index of `main` in `funcs`.
+    pub main: usize,
+}
+
+/// Where a name refers to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry<'a> {
+    /// A local if `global` is false, or else a global.
+    Var {
+        index: u32,
+        typ: VarType,
+        constant: bool,
+        global: bool,
+    },
+    /// A function, by its index in the module.
+    Func(u32, &'a FuncDecl),
+}
+
+/// Compiles a checked syntax tree to a WebAssembly [`Module`], for the
+/// subset of the language that only uses ints, floats, bools and
+/// functions that are called by name.
+///
+/// Every variable has the type it is declared with, or else the type
+/// of its value, which lets the module do without the runtime types of
+/// the [`super::CGen`] runtime. Top level declarations are globals and
+/// every other variable is a local of the function it is declared in.
+///
+/// Runtime errors, like integer overflow or a function ending without
+/// returning, trap instead of printing the interpreter's message. As
+/// the types are known up front, an int raised to a negative power
+/// traps too, rather than making a float.
+#[derive(Clone, Debug)]
+pub struct WasmGen<'a> {
+    /// The functions generated so far, the helpers first.
+    pub funcs: Vec<Func>,
+    pub globals: Vec<Global>,
+    /// The globals and top level functions by name.
+    pub global_ids: HashMap<Symbol, Entry<'a>>,
+    /// The functions that have been declared but not generated yet.
+    pub pending: Vec<(u32, &'a FuncDecl)>,
+    /// The code of the function being generated.
+    pub code: Vec<Instr>,
+    /// The locals of the function being generated, the params first.
+    pub locals: Vec<(String, ValType)>,
+    /// The block scopes of the function being generated, innermost
+    /// last. Empty at the top level of the program, where declarations
+    /// are globals.
+    pub scopes: Vec<Vec<(Symbol, Entry<'a>)>>,
+    /// How many blocks, loops and ifs enclose the code being generated.
+    pub labels: u32,
+    /// The label each enclosing loop breaks to, innermost last.
+    pub loops: Vec<u32>,
+    /// The name and return type of the function being generated, or
+    /// `None` at the top level.
+    pub ret: Option<(Symbol, VarType)>,
+}
+
+impl Default for WasmGen<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> WasmGen<'a> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            funcs: helpers(),
+            globals: Vec::new(),
+            global_ids: HashMap::new(),
+            pending: Vec::new(),
+            code: Vec::new(),
+            locals: Vec::new(),
+            scopes: Vec::new(),
+            labels: 0,
+            loops: Vec::new(),
+            ret: None,
+        }
+    }
+
+    /// Generates a module that runs the given program. Its `main`
+    /// returns the value of the last statement, if that is an int,
+    /// float or bool expression.
+    ///
+    /// # Returns
+    /// - [`Result<Module, String>`] - The module on success.
+    ///
+    /// # Errors
+    /// - If the program uses a value or statement outside of the subset
+    ///   the backend supports, or declares variables inconsistently.
+    pub fn generate(mut self, program: &'a Expr) -> Result<Module, String> {
+        let main = self.reserve("main");
+        let mut result = VarType::Void;
+
+        match program {
+            Expr::Main(stmts) => {
+                self.hoist(stmts)?;
+
+                for (i, stmt) in stmts.iter().enumerate() {
+                    match stmt {
+                        Stmt::Expr(expr, _) if i + 1 == stmts.len() => result = self.expr(expr)?,
+                        stmt => self.stmt(stmt)?,
+                    }
+                }
+            }
+            expr => result = self.expr(expr)?,
+        }
+
+        let result = match result {
+            VarType::Void => None,
+            typ => Some(val_type(&typ)?),
+        };
+        self.finish(main, Vec::new(), result);
+
+        while !self.pending.is_empty() {
+            for (index, func) in std::mem::take(&mut self.pending) {
+                self.func(index, func)?;
+            }
+        }
+
+        Ok(Module {
+            globals: self.globals,
+            funcs: self.funcs,
+            main: main as usize - IMPORTS.len(),
+        })
+    }
+
+    /// Adds an empty function to be generated later, returning its
+    /// index.
+    fn reserve(&mut self, name: &str) -> u32 {
+        let name = unique(name, |n| self.funcs.iter().any(|f| f.name == n));
+
+        self.funcs.push(Func {
+            name,
+            params: Vec::new(),
+            result: None,
+            locals: Vec::new(),
+            code: Vec::new(),
+        });
+
+        internal::index(IMPORTS.len() + self.funcs.len() - 1)
+    }
+
+    /// Moves the code and locals generated so far into a function.
+    fn finish(&mut self, index: u32, params: Vec<ValType>, result: Option<ValType>) {
+        let func = &mut self.funcs[index as usize - IMPORTS.len()];
+
+        func.params = params;
+        func.result = result;
+        func.locals = std::mem::take(&mut self.locals);
+        func.code = std::mem::take(&mut self.code);
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<&Entry<'a>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|(n, _)| *n == name))
+            .map(|(_, entry)| entry)
+            .or_else(|| self.global_ids.get(&name))
+    }
+
+    /// Adds a local to the function being generated, returning its index.
+    fn local(&mut self, name: &str, typ: ValType) -> u32 {
+        let name = unique(name, |n| self.locals.iter().any(|(l, _)| l == n));
+        self.locals.push((name, typ));
+        internal::index(self.locals.len() - 1)
+    }
+
+    /// Declares every function in a block up front, so they can call
+    /// each other regardless of order.
+    ///
+    /// # Errors
+    /// - If a function has a param or return type the backend doesn't
+    ///   support.
+    pub fn hoist(&mut self, stmts: &'a [Stmt]) -> GenResult {
+        for stmt in stmts {
+            if let Stmt::Func(func) = stmt {
+                for param in &func.params {
+                    val_type(&param.typ)?;
+                }
+
+                if func.ret != VarType::Void {
+                    val_type(&func.ret)?;
+                }
+
+                let index = self.reserve(func.name.as_str());
+                let entry = Entry::Func(index, func);
+
+                match self.scopes.last_mut() {
+                    Some(scope) => scope.push((func.name, entry)),
+                    None => {
+                        self.global_ids.insert(func.name, entry);
+                    }
+                }
+
+                self.pending.push((index, func));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generates a function that was reserved by [`WasmGen::hoist`].
+    ///
+    /// # Errors
+    /// - If the body contains code that can't be compiled.
+    pub fn func(&mut self, index: u32, func: &'a FuncDecl) -> GenResult {
+        let mut params = Vec::with_capacity(func.params.len());
+        let mut scope = Vec::with_capacity(func.params.len());
+
+        for param in &func.params {
+            let typ = val_type(&param.typ)?;
+            let entry = Entry::Var {
+                index: self.local(param.name.as_str(), typ),
+                typ: param.typ.clone(),
+                constant: false,
+                global: false,
+            };
+
+            params.push(typ);
+            scope.push((param.name, entry));
+        }
+
+        self.scopes = vec![scope];
+        self.labels = 0;
+        self.loops.clear();
+        self.ret = Some((func.name, func.ret.clone()));
+
+        self.block(&func.body)?;
+
+        let result = match func.ret {
+            VarType::Void => None,
+            // Running off the end returns nothing, which isn't a value
+            // of the return type
+            ref typ => {
+                self.code.push(Instr::Unreachable);
+                Some(val_type(typ)?)
+            }
+        };
+
+        self.finish(index, params, result);
+        Ok(())
+    }
+
+    /// Generates a block of statements in a new scope.
+    ///
+    /// # Errors
+    /// - If a statement can't be compiled.
+    pub fn block(&mut self, stmts: &'a [Stmt]) -> GenResult {
+        self.scopes.push(Vec::new());
+
+        let result = self
+            .hoist(stmts)
+            .and_then(|()| stmts.iter().try_for_each(|stmt| self.stmt(stmt)));
+
+        self.scopes.pop();
+        result
+    }
+
+    /// Generates a single statement.
+    ///
+    /// # Errors
+    /// - If the statement can't be compiled.
+    #[allow(clippy::too_many_lines)] // One arm per kind of statement
+    pub fn stmt(&mut self, stmt: &'a Stmt) -> GenResult {
+        match stmt {
+            Stmt::Expr(expr, _) => {
+                if self.expr(expr)? != VarType::Void {
+                    self.code.push(Instr::Drop);
+                }
+            }
+            Stmt::VarDecl(decl) => {
+                let value = self.expr(&decl.value)?;
+                let typ = decl.typ.clone().unwrap_or_else(|| value.clone());
+                let constant = decl.keyword == Some(Keyword::Const);
+
+                if !typ.accepts(&value) {
+                    return super::exc!(
+                        "Can't assign {} to '{}' of type {}",
+                        value,
+                        (decl.name),
+                        typ
+                    );
+                }
+
+                self.coerce(&value, &typ);
+                let val = val_type(&typ)?;
+
+                if let Some(innermost) = self.scopes.len().checked_sub(1) {
+                    let index = self.local(decl.name.as_str(), val);
+                    let entry = Entry::Var {
+                        index,
+                        typ,
+                        constant,
+                        global: false,
+                    };
+
+                    self.scopes[innermost].push((decl.name, entry));
+                    self.code.push(Instr::LocalSet(index));
+                    return Ok(());
+                }
+
+                let index = match self.global_ids.get(&decl.name) {
+                    None => {
+                        self.globals.push(Global {
+                            name: decl.name.to_string(),
+                            typ: val,
+                        });
+                        internal::index(self.globals.len() - 1)
+                    }
+                    Some(Entry::Var {
+                        index, typ: old, ..
+                    }) if *old == typ => *index,
+                    Some(_) => {
+                        return super::loc_exc!(
+                            decl.loc,
+                            "Can't redeclare global '{}' as {} in WebAssembly",
+                            (decl.name),
+                            typ
+                        )
+                    }
+                };
+
+                let entry = Entry::Var {
+                    index,
+                    typ,
+                    constant,
+                    global: true,
+                };
+
+                self.global_ids.insert(decl.name, entry);
+                self.code.push(Instr::GlobalSet(index));
+            }
+            Stmt::Assign(op, target, value, loc) => {
+                let Expr::Ident(name) = target else {
+                    return super::loc_exc!(loc, "Only variables can be assigned in WebAssembly");
+                };
+
+                let (index, typ, global) = match self.lookup(*name) {
+                    Some(Entry::Var { constant: true, .. }) => {
+                        return super::loc_exc!(loc, "Can't assign to constant '{}'", name)
+                    }
+                    Some(Entry::Var {
+                        index, typ, global, ..
+                    }) => (*index, typ.clone(), *global),
+                    Some(Entry::Func(..)) => {
+                        return super::loc_exc!(loc, "Can't assign to function '{}'", name)
+                    }
+                    None => return super::loc_exc!(loc, "Undefined variable '{}'", name),
+                };
+
+                let mut value = self.expr(value)?;
+
+                let binary = match op {
+                    Operator::Assign => None,
+                    Operator::IncrementBy | Operator::Increment => Some(Operator::Add),
+                    Operator::DecrementBy | Operator::Decrement => Some(Operator::Subtract),
+                    Operator::MultBy => Some(Operator::Mult),
+                    Operator::DivBy => Some(Operator::Div),
+                    _ => return super::loc_exc!(loc, "Invalid assignment operator {}", op),
+                };
+
+                if let Some(binary) = binary {
+                    // The value is computed before the variable is read
+                    let temp = self.local("temp", val_type(&value)?);
+                    self.code.push(Instr::LocalSet(temp));
+                    self.code.push(get(index, global));
+
+                    let mark = self.code.len();
+                    self.code.push(Instr::LocalGet(temp));
+                    value = self.binary(binary, &typ, &value, mark)?;
+                }
+
+                if !typ.accepts(&value) {
+                    return super::loc_exc!(
+                        loc,
+                        "Can't assign {} to '{}' of type {}",
+                        value,
+                        name,
+                        typ
+                    );
+                }
+
+                self.coerce(&value, &typ);
+                self.code.push(if global {
+                    Instr::GlobalSet(index)
+                } else {
+                    Instr::LocalSet(index)
+                });
+            }
+            Stmt::Func(_) | Stmt::Comment(..) => (),
+            Stmt::Return(value, loc) => {
+                let Some((name, ret)) = self.ret.clone() else {
+                    return super::loc_exc!(loc, "'return' outside of a function");
+                };
+
+                let typ = match value {
+                    Some(value) => self.expr(value)?,
+                    None => VarType::Void,
+                };
+
+                if !ret.accepts(&typ) || (ret == VarType::Void) != (typ == VarType::Void) {
+                    return super::loc_exc!(
+                        loc,
+                        "'{}' should return {}, but returned {}",
+                        name,
+                        ret,
+                        typ
+                    );
+                }
+
+                self.coerce(&typ, &ret);
+                self.code.push(Instr::Return);
+            }
+            Stmt::If(cond, body, otherwise, _) => {
+                self.cond(cond)?;
+                self.code.push(Instr::If);
+                self.labels += 1;
+                self.block(body)?;
+
+                if let Some(otherwise) = otherwise {
+                    self.code.push(Instr::Else);
+                    self.block(otherwise)?;
+                }
+
+                self.labels -= 1;
+                self.code.push(Instr::End);
+            }
+            Stmt::While(cond, body, _) => {
+                self.code.push(Instr::Block);
+                self.code.push(Instr::Loop);
+                self.loops.push(self.labels);
+                self.labels += 2;
+
+                self.cond(cond)?;
+                self.code.push(I32_EQZ);
+                self.code.push(Instr::BrIf(1));
+
+                let result = self.block(body);
+                self.labels -= 2;
+                self.loops.pop();
+                result?;
+
+                self.code.push(Instr::Br(0));
+                self.code.push(Instr::End);
+                self.code.push(Instr::End);
+            }
+            Stmt::Break => match self.loops.last() {
+                Some(label) => self.code.push(Instr::Br(self.labels - 1 - label)),
+                None => return super::exc!("'break' outside of a loop"),
+            },
+            Stmt::Class(class) => {
+                return super::loc_exc!(class.loc, "Classes are not supported yet")
+            }
+            Stmt::Delete(_, loc) => {
+                return super::loc_exc!(loc, "delete is not supported in WebAssembly yet")
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generates a condition, which must be a bool.
+    fn cond(&mut self, cond: &'a Expr) -> GenResult {
+        match self.expr(cond)? {
+            VarType::Bool => Ok(()),
+            typ => super::exc!("Condition must be a bool, but got {}", typ),
+        }
+    }
+
+    /// Converts the value on top of the stack so it can be stored as
+    /// the given type, widening ints to floats.
+    fn coerce(&mut self, value: &VarType, typ: &VarType) {
+        if (value, typ) == (&VarType::Int, &VarType::Float) {
+            self.code.push(F64_CONVERT_I64_S);
+        }
+    }
+
+    /// Converts both operands of a binary instruction to floats, given
+    /// where the code of the right operand starts.
+    fn floats(&mut self, left: &VarType, right: &VarType, mark: usize) {
+        if *left == VarType::Int {
+            self.code.insert(mark, F64_CONVERT_I64_S);
+        }
+
+        self.coerce(right, &VarType::Float);
+    }
+
+    /// Generates the code computing an expression, returning its type.
+    ///
+    /// # Errors
+    /// - If the expression can't be compiled.
+    #[allow(clippy::too_many_lines)] // One arm per kind of expression
+    pub fn expr(&mut self, expr: &'a Expr) -> Result<VarType, String> {
+        let typ = match expr {
+            Expr::Int(num) => {
+                self.code.push(Instr::I64Const(*num));
+                VarType::Int
+            }
+            Expr::Float(num) => {
+                self.code.push(Instr::F64Const(*num));
+                VarType::Float
+            }
+            Expr::Bool(b) => {
+                self.code.push(Instr::I32Const(i32::from(*b)));
+                VarType::Bool
+            }
+            Expr::String(_) => return unsupported(&VarType::String),
+            Expr::Array(_) => return super::exc!("Arrays are not supported in WebAssembly"),
+            Expr::Mapping(_) => return super::exc!("Mappings are not supported in WebAssembly"),
+            Expr::Index(..) => return super::exc!("Indexing is not supported in WebAssembly"),
+            Expr::Null => return super::exc!("NULL is not supported in WebAssembly"),
+            Expr::Error => return super::exc!("Can't run code that failed to parse"),
+            Expr::Main(_) => return super::exc!("Can't compile a program within a program"),
+            Expr::Parenthesized(inner) => self.expr(inner)?,
+            Expr::Ident(name) => match self.lookup(*name) {
+                Some(Entry::Var {
+                    index, typ, global, ..
+                }) => {
+                    let (instr, typ) = (get(*index, *global), typ.clone());
+                    self.code.push(instr);
+                    typ
+                }
+                Some(Entry::Func(..)) => {
+                    return super::exc!("Functions can only be called in WebAssembly")
+                }
+                None if super::BUILTINS.contains(&name.as_str()) => {
+                    return super::exc!("Builtins can only be called in WebAssembly")
+                }
+                None => return super::exc!("Undefined variable '{}'", name),
+            },
+            Expr::Builtin(name) if super::BUILTINS.contains(&name.as_str()) => {
+                return super::exc!("Builtins can only be called in WebAssembly")
+            }
+            Expr::Builtin(name) => return super::exc!("Unknown builtin '%{}'", name),
+            Expr::UnaryOp(op, inner) => {
+                let mark = self.code.len();
+
+                match (op, self.expr(inner)?) {
+                    (Operator::Subtract, VarType::Int) => {
+                        self.code.insert(mark, Instr::I64Const(0));
+                        self.code.push(Instr::Call(SUB));
+                        VarType::Int
+                    }
+                    (Operator::Subtract, VarType::Float) => {
+                        self.code.push(F64_NEG);
+                        VarType::Float
+                    }
+                    (Operator::Not, VarType::Bool) => {
+                        self.code.push(I32_EQZ);
+                        VarType::Bool
+                    }
+                    (op, typ) => {
+                        return super::exc!("Unsupported operand type for {}: {}", op, typ)
+                    }
+                }
+            }
+            Expr::BinaryOp(op, left, right) => {
+                let left = self.expr(left)?;
+                let mark = self.code.len();
+                let right = self.expr(right)?;
+
+                self.binary(*op, &left, &right, mark)?
+            }
+            Expr::Compare(cond, left, right) => {
+                let left = self.expr(left)?;
+                let mark = self.code.len();
+                let right = self.expr(right)?;
+
+                self.compare(*cond, &left, &right, mark)?
+            }
+            Expr::Call(callee, args, loc) => {
+                let entry = match &**callee {
+                    Expr::Ident(name) => match self.lookup(*name) {
+                        Some(entry) => Some(entry.clone()),
+                        None if super::BUILTINS.contains(&name.as_str()) => None,
+                        None => return super::loc_exc!(loc, "Undefined variable '{}'", name),
+                    },
+                    Expr::Builtin(name) if super::BUILTINS.contains(&name.as_str()) => None,
+                    _ => {
+                        return super::loc_exc!(
+                            loc,
+                            "Only functions can be called by name in WebAssembly"
+                        )
+                    }
+                };
+
+                match entry {
+                    Some(Entry::Func(index, func)) => self.call(index, func, args)?,
+                    Some(Entry::Var { typ, .. }) => {
+                        return super::loc_exc!(loc, "{} is not callable", typ)
+                    }
+                    None => match &**callee {
+                        Expr::Ident(name) | Expr::Builtin(name) if *name == "print" => {
+                            self.print(args)?
+                        }
+                        _ => {
+                            return super::loc_exc!(
+                                loc,
+                                "Only the print builtin is supported in WebAssembly"
+                            )
+                        }
+                    },
+                }
+            }
+        };
+
+        Ok(typ)
+    }
+
+    /// Applies a binary operator to the values on top of the stack,
+    /// given where the code of the right operand starts.
+    fn binary(
+        &mut self,
+        op: Operator,
+        left: &VarType,
+        right: &VarType,
+        mark: usize,
+    ) -> Result<VarType, String> {
+        let numeric = |t: &VarType| matches!(t, VarType::Int | VarType::Float);
+
+        if !numeric(left) || !numeric(right) {
+            return super::exc!(
+                "Unsupported operand types for {}: {} and {}",
+                op,
+                left,
+                right
+            );
+        }
+
+        if (left, right) == (&VarType::Int, &VarType::Int) {
+            let instr = match op {
+                Operator::Add => Instr::Call(ADD),
+                Operator::Subtract => Instr::Call(SUB),
+                Operator::Mult => Instr::Call(MUL),
+                Operator::Div => I64_DIV_S,
+                Operator::Pow => Instr::Call(IPOW),
+                _ => return super::exc!("Unsupported operator {} for int", op),
+            };
+
+            self.code.push(instr);
+            return Ok(VarType::Int);
+        }
+
+        let instr = match op {
+            Operator::Add => F64_ADD,
+            Operator::Subtract => F64_SUB,
+            Operator::Mult => F64_MUL,
+            Operator::Div => F64_DIV,
+            Operator::Pow => Instr::Call(POW),
+            _ => return super::exc!("Unsupported operator {} for float", op),
+        };
+
+        self.floats(left, right, mark);
+        self.code.push(instr);
+        Ok(VarType::Float)
+    }
+
+    /// Compares the values on top of the stack, given where the code of
+    /// the right operand starts. Numbers are compared as floats, like
+    /// the interpreter compares them.
+    fn compare(
+        &mut self,
+        cond: Condition,
+        left: &VarType,
+        right: &VarType,
+        mark: usize,
+    ) -> Result<VarType, String> {
+        let numeric = |t: &VarType| matches!(t, VarType::Int | VarType::Float);
+
+        match (left, right) {
+            (l, r) if numeric(l) && numeric(r) => {
+                self.floats(l, r, mark);
+
+                let instrs = match cond {
+                    Condition::EqEq => vec![F64_EQ],
+                    Condition::Ne => vec![F64_NE],
+                    // Ints are never NaN, so they are always ordered
+                    Condition::Lt if l == r && *l == VarType::Int => vec![F64_LT],
+                    Condition::Gt if l == r && *l == VarType::Int => vec![F64_GT],
+                    Condition::Lte if l == r && *l == VarType::Int => vec![F64_LE],
+                    Condition::Gte if l == r && *l == VarType::Int => vec![F64_GE],
+                    cond => {
+                        let instr = match cond {
+                            Condition::Lt => I32_LT_S,
+                            Condition::Gt => I32_GT_S,
+                            Condition::Lte => I32_LE_S,
+                            _ => I32_GE_S,
+                        };
+                        vec![Instr::Call(CMP), Instr::I32Const(0), instr]
+                    }
+                };
+
+                self.code.extend(instrs);
+            }
+            (VarType::Bool, VarType::Bool) if cond == Condition::EqEq => self.code.push(I32_EQ),
+            (VarType::Bool, VarType::Bool) if cond == Condition::Ne => self.code.push(I32_NE),
+            (l, r)
+                if matches!(cond, Condition::EqEq | Condition::Ne)
+                    && l != r
+                    && *l != VarType::Void
+                    && *r != VarType::Void =>
+            {
+                // Values of different types are never equal
+                let ne = cond == Condition::Ne;
+                self.code
+                    .extend([Instr::Drop, Instr::Drop, Instr::I32Const(i32::from(ne))]);
+            }
+            (l, r) => return super::exc!("Can't compare {} and {} with {}", l, r, cond),
+        }
+
+        Ok(VarType::Bool)
+    }
+
+    /// Calls a function declared in the program.
+    fn call(
+        &mut self,
+        index: u32,
+        func: &'a FuncDecl,
+        args: &'a [Expr],
+    ) -> Result<VarType, String> {
+        if args.len() != func.params.len() {
+            return super::exc!(
+                "'{}' takes {} argument(s), but {} were given",
+                (func.name),
+                (func.params.len()),
+                (args.len())
+            );
+        }
+
+        for (param, arg) in func.params.iter().zip(args) {
+            let typ = self.expr(arg)?;
+
+            if !param.typ.accepts(&typ) {
+                return super::exc!(
+                    "'{}' expected {} for '{}', but got {}",
+                    (func.name),
+                    (param.typ),
+                    (param.name),
+                    typ
+                );
+            }
+
+            self.coerce(&typ, &param.typ);
+        }
+
+        self.code.push(Instr::Call(index));
+        Ok(func.ret.clone())
+    }
+
+    /// Prints the arguments with the host's print functions.
+    fn print(&mut self, args: &'a [Expr]) -> Result<VarType, String> {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.code.push(Instr::I32Const(i32::from(b' ')));
+                self.code.push(Instr::Call(PRINT_CHAR));
+            }
+
+            let print = match self.expr(arg)? {
+                VarType::Int => PRINT_INT,
+                VarType::Float => PRINT_FLOAT,
+                VarType::Bool => PRINT_BOOL,
+                typ => return unsupported(&typ),
+            };
+
+            self.code.push(Instr::Call(print));
+        }
+
+        self.code.push(Instr::I32Const(i32::from(b'\n')));
+        self.code.push(Instr::Call(PRINT_CHAR));
+        Ok(VarType::Void)
+    }
+}
+
+impl ValType {
+    /// The byte encoding the type in a binary module.
+    #[must_use]
+    pub fn code(self) -> u8 {
+        match self {
+            ValType::I32 => 0x7f,
+            ValType::I64 => 0x7e,
+            ValType::F64 => 0x7c,
+        }
+    }
+}
+
+impl std::fmt::Display for ValType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValType::I32 => write!(f, "i32"),
+            ValType::I64 => write!(f, "i64"),
+            ValType::F64 => write!(f, "f64"),
+        }
+    }
+}
+
+impl Instr {
+    /// Appends the binary encoding of the instruction.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let (opcode, index) = match self {
+            Instr::Unreachable => (0x00, None),
+            // Blocks never produce a value
+            Instr::Block => return out.extend([0x02, 0x40]),
+            Instr::Loop => return out.extend([0x03, 0x40]),
+            Instr::If => return out.extend([0x04, 0x40]),
+            Instr::Else => (0x05, None),
+            Instr::End => (0x0b, None),
+            Instr::Br(label) => (0x0c, Some(*label)),
+            Instr::BrIf(label) => (0x0d, Some(*label)),
+            Instr::Return => (0x0f, None),
+            Instr::Call(func) => (0x10, Some(*func)),
+            Instr::Drop => (0x1a, None),
+            Instr::LocalGet(local) => (0x20, Some(*local)),
+            Instr::LocalSet(local) => (0x21, Some(*local)),
+            Instr::LocalTee(local) => (0x22, Some(*local)),
+            Instr::GlobalGet(global) => (0x23, Some(*global)),
+            Instr::GlobalSet(global) => (0x24, Some(*global)),
+            Instr::I32Const(num) => {
+                out.push(0x41);
+                return sleb(out, i64::from(*num));
+            }
+            Instr::I64Const(num) => {
+                out.push(0x42);
+                return sleb(out, *num);
+            }
+            Instr::F64Const(num) => {
+                out.push(0x44);
+                return out.extend(num.to_le_bytes());
+            }
+            Instr::Numeric(_, opcode) => (*opcode, None),
+        };
+
+        out.push(opcode);
+
+        if let Some(index) = index {
+            uleb(out, u64::from(index));
+        }
+    }
+}
+
+impl Module {
+    /// The module in the WebAssembly text format.
+    #[must_use]
+    pub fn wat(&self) -> String {
+        let func_name = |index: u32| match IMPORTS.get(index as usize) {
+            Some((name, ..)) => format!("why.{name}"),
+            None => self.funcs[index as usize - IMPORTS.len()].name.clone(),
+        };
+
+        let mut out = String::from("(module\n");
+
+        for (name, params, result) in IMPORTS {
+            let _ = writeln!(
+                out,
+                "  (import \"why\" \"{}\" (func $why.{}{}))",
+                name,
+                name,
+                signature(params, result)
+            );
+        }
+
+        for global in &self.globals {
+            let _ = writeln!(
+                out,
+                "  (global ${} (mut {}) ({}.const 0))",
+                global.name, global.typ, global.typ
+            );
+        }
+
+        for func in &self.funcs {
+            let _ = write!(out, "  (func ${}", func.name);
+
+            for (name, typ) in &func.locals[..func.params.len()] {
+                let _ = write!(out, " (param ${name} {typ})");
+            }
+
+            if let Some(result) = func.result {
+                let _ = write!(out, " (result {result})");
+            }
+
+            for (name, typ) in &func.locals[func.params.len()..] {
+                let _ = write!(out, " (local ${name} {typ})");
+            }
+
+            let mut indent = 2;
+
+            for instr in &func.code {
+                if matches!(instr, Instr::Else | Instr::End) {
+                    indent -= 1;
+                }
+
+                out.push('\n');
+                out.push_str(&"  ".repeat(indent));
+
+                let local = |i: &u32| &func.locals[*i as usize].0;
+                let global = |i: &u32| &self.globals[*i as usize].name;
+
+                let _ = match instr {
+                    Instr::Unreachable => write!(out, "unreachable"),
+                    Instr::Block => write!(out, "block"),
+                    Instr::Loop => write!(out, "loop"),
+                    Instr::If => write!(out, "if"),
+                    Instr::Else => write!(out, "else"),
+                    Instr::End => write!(out, "end"),
+                    Instr::Br(label) => write!(out, "br {label}"),
+                    Instr::BrIf(label) => write!(out, "br_if {label}"),
+                    Instr::Return => write!(out, "return"),
+                    Instr::Call(index) => write!(out, "call ${}", func_name(*index)),
+                    Instr::Drop => write!(out, "drop"),
+                    Instr::LocalGet(i) => write!(out, "local.get ${}", local(i)),
+                    Instr::LocalSet(i) => write!(out, "local.set ${}", local(i)),
+                    Instr::LocalTee(i) => write!(out, "local.tee ${}", local(i)),
+                    Instr::GlobalGet(i) => write!(out, "global.get ${}", global(i)),
+                    Instr::GlobalSet(i) => write!(out, "global.set ${}", global(i)),
+                    Instr::I32Const(num) => write!(out, "i32.const {num}"),
+                    Instr::I64Const(num) => write!(out, "i64.const {num}"),
+                    Instr::F64Const(num) if num.is_nan() => write!(out, "f64.const nan"),
+                    Instr::F64Const(num) => write!(out, "f64.const {num:?}"),
+                    Instr::Numeric(name, _) => write!(out, "{name}"),
+                };
+
+                if matches!(instr, Instr::Block | Instr::Loop | Instr::If | Instr::Else) {
+                    indent += 1;
+                }
+            }
+
+            out.push_str(")\n");
+        }
+
+        let _ = writeln!(
+            out,
+            "  (export \"main\" (func ${})))",
+            self.funcs[self.main].name
+        );
+        out
+    }
+
+    /// The module in the WebAssembly binary format.
+    #[must_use]
+    pub fn wasm(&self) -> Vec<u8> {
+        let mut types: Vec<(Vec<ValType>, Option<ValType>)> = Vec::new();
+        let mut type_index = |params: &[ValType], result: Option<ValType>| {
+            let existing = types.iter().position(|(p, r)| p == params && *r == result);
+
+            existing.unwrap_or_else(|| {
+                types.push((params.to_vec(), result));
+                types.len() - 1
+            })
+        };
+
+        let mut imports = Vec::new();
+        uleb(&mut imports, IMPORTS.len() as u64);
+
+        for (name, params, result) in IMPORTS {
+            bytes(&mut imports, b"why");
+            bytes(&mut imports, name.as_bytes());
+            imports.push(0x00);
+            uleb(&mut imports, type_index(params, result) as u64);
+        }
+
+        let mut funcs = Vec::new();
+        uleb(&mut funcs, self.funcs.len() as u64);
+
+        for func in &self.funcs {
+            uleb(&mut funcs, type_index(&func.params, func.result) as u64);
+        }
+
+        let mut globals = Vec::new();
+        uleb(&mut globals, self.globals.len() as u64);
+
+        for global in &self.globals {
+            globals.extend([global.typ.code(), 0x01]);
+
+            match global.typ {
+                ValType::I32 => Instr::I32Const(0).encode(&mut globals),
+                ValType::I64 => Instr::I64Const(0).encode(&mut globals),
+                ValType::F64 => Instr::F64Const(0.0).encode(&mut globals),
+            }
+
+            Instr::End.encode(&mut globals);
+        }
+
+        let mut exports = vec![1];
+        bytes(&mut exports, b"main");
+        exports.push(0x00);
+        uleb(&mut exports, (IMPORTS.len() + self.main) as u64);
+
+        let mut code = Vec::new();
+        uleb(&mut code, self.funcs.len() as u64);
+
+        for func in &self.funcs {
+            // Consecutive locals of the same type are declared together
+            let mut runs: Vec<(u32, ValType)> = Vec::new();
+
+            for (_, typ) in &func.locals[func.params.len()..] {
+                match runs.last_mut() {
+                    Some((count, last)) if last == typ => *count += 1,
+                    _ => runs.push((1, *typ)),
+                }
+            }
+
+            let mut body = Vec::new();
+            uleb(&mut body, runs.len() as u64);
+
+            for (count, typ) in runs {
+                uleb(&mut body, u64::from(count));
+                body.push(typ.code());
+            }
+
+            for instr in &func.code {
+                instr.encode(&mut body);
+            }
+
+            Instr::End.encode(&mut body);
+            bytes(&mut code, &body);
+        }
+
+        let mut type_section = Vec::new();
+        uleb(&mut type_section, types.len() as u64);
+
+        for (params, result) in &types {
+            type_section.push(0x60);
+            uleb(&mut type_section, params.len() as u64);
+            type_section.extend(params.iter().map(|p| p.code()));
+            uleb(&mut type_section, u64::from(result.is_some()));
+            type_section.extend(result.iter().map(|r| r.code()));
+        }
+
+        let mut out = b"\0asm".to_vec();
+        out.extend(1u32.to_le_bytes());
+
+        for (id, section) in [
+            (1, type_section),
+            (2, imports),
+            (3, funcs),
+            (6, globals),
+            (7, exports),
+            (10, code),
+        ] {
+            out.push(id);
+            bytes(&mut out, &section);
+        }
+
+        out
+    }
+}
+
+/// The WebAssembly type of the values of a type.
+///
+/// # Errors
+/// - If the type isn't an int, float or bool.
+pub fn val_type(typ: &VarType) -> Result<ValType, String> {
+    match typ {
+        VarType::Int => Ok(ValType::I64),
+        VarType::Float => Ok(ValType::F64),
+        VarType::Bool => Ok(ValType::I32),
+        typ => unsupported(typ),
+    }
+}
+
+fn unsupported<T>(typ: &VarType) -> Result<T, String> {
+    super::exc!("{} values are not supported in WebAssembly", typ)
+}
+
+fn get(index: u32, global: bool) -> Instr {
+    if global {
+        Instr::GlobalGet(index)
+    } else {
+        Instr::LocalGet(index)
+    }
+}
+
+/// The name, or the name with the first free numbered suffix if it is
+/// already taken.
+fn unique(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+
+    (1..=usize::MAX)
+        .map(|i| format!("{name}.{i}"))
+        .find(|n| !taken(n))
+        .unwrap()
+}
+
+fn signature(params: &[ValType], result: Option<ValType>) -> String {
+    let mut out = String::new();
+
+    for param in params {
+        let _ = write!(out, " (param {param})");
+    }
+
+    if let Some(result) = result {
+        let _ = write!(out, " (result {result})");
+    }
+
+    out
+}
+
+/// The helpers that implement the interpreter's checked arithmetic,
+/// which trap where it would fail.
+fn helpers() -> Vec<Func> {
+    use Instr::{Call, End, If, LocalGet as Get, LocalSet as Set, Return, Unreachable};
+
+    let helper = |name: &str, params: &[(&str, ValType)], result, locals: &[(&str, ValType)]| {
+        let locals = params.iter().chain(locals);
+
+        Func {
+            name: format!("why.{name}"),
+            params: params.iter().map(|(_, t)| *t).collect(),
+            result: Some(result),
+            locals: locals.map(|(n, t)| (n.to_string(), *t)).collect(),
+            code: Vec::new(),
+        }
+    };
+
+    let ints = [("a", ValType::I64), ("b", ValType::I64)];
+    let result = [("r", ValType::I64)];
+    // Traps if the value on top of the stack is negative
+    let trap_if_negative = [Instr::I64Const(0), I64_LT_S, If, Unreachable, End];
+
+    // Overflow when the result's sign differs from both operands'
+    let mut add = helper("add", &ints, ValType::I64, &result);
+    add.code = vec![Get(0), Get(1), I64_ADD, Instr::LocalTee(2), Get(0), I64_XOR];
+    add.code.extend([Get(2), Get(1), I64_XOR, I64_AND]);
+    add.code.extend(trap_if_negative.clone());
+    add.code.push(Get(2));
+
+    // Overflow when the operands' signs differ and the result's sign
+    // differs from the left operand's
+    let mut sub = helper("sub", &ints, ValType::I64, &result);
+    sub.code = vec![Get(0), Get(1), I64_SUB, Set(2), Get(0), Get(1), I64_XOR];
+    sub.code.extend([Get(0), Get(2), I64_XOR, I64_AND]);
+    sub.code.extend(trap_if_negative.clone());
+    sub.code.push(Get(2));
+
+    // Overflow when dividing the result doesn't give the operand back,
+    // where dividing `i64::MIN` by -1 traps by itself
+    let mut mul = helper("mul", &ints, ValType::I64, &result);
+    mul.code = vec![
+        Get(0),
+        Get(1),
+        I64_MUL,
+        Set(2),
+        Get(0),
+        I64_EQZ,
+        I32_EQZ,
+        If,
+    ];
+    mul.code.extend([
+        Get(2),
+        Get(0),
+        I64_DIV_S,
+        Get(1),
+        I64_NE,
+        If,
+        Unreachable,
+        End,
+    ]);
+    mul.code.extend([End, Get(2)]);
+
+    // Exponentiation by squaring, like `i64::checked_pow`
+    let mut ipow = helper("ipow", &ints, ValType::I64, &result);
+    ipow.code = vec![Get(1)];
+    ipow.code.extend(trap_if_negative);
+    ipow.code.extend([
+        Get(1),
+        Instr::I64Const(u32::MAX.into()),
+        I64_GT_S,
+        If,
+        Unreachable,
+    ]);
+    ipow.code
+        .extend([End, Get(1), I64_EQZ, If, Instr::I64Const(1), Return, End]);
+    ipow.code
+        .extend([Instr::I64Const(1), Set(2), Instr::Block, Instr::Loop]);
+    ipow.code
+        .extend([Get(1), Instr::I64Const(1), I64_AND, I64_EQZ, I32_EQZ, If]);
+    ipow.code.extend([Get(2), Get(0), Call(MUL), Set(2)]);
+    ipow.code
+        .extend([Get(1), Instr::I64Const(1), I64_EQ, Instr::BrIf(2), End]);
+    ipow.code
+        .extend([Get(1), Instr::I64Const(1), I64_SHR_S, Set(1)]);
+    ipow.code.extend([
+        Get(0),
+        Get(0),
+        Call(MUL),
+        Set(0),
+        Instr::Br(0),
+        End,
+        End,
+        Get(2),
+    ]);
+
+    // Orders two floats as -1, 0 or 1, trapping if either is NaN
+    let floats = [("a", ValType::F64), ("b", ValType::F64)];
+    let mut cmp = helper("cmp", &floats, ValType::I32, &[]);
+    cmp.code = vec![Get(0), Get(0), F64_NE, Get(1), Get(1), F64_NE, I32_OR, If];
+    cmp.code.extend([
+        Unreachable,
+        End,
+        Get(0),
+        Get(1),
+        F64_GT,
+        Get(0),
+        Get(1),
+        F64_LT,
+    ]);
+    cmp.code.push(I32_SUB);
+
+    vec![add, sub, mul, ipow, cmp]
+}
+
+fn uleb(out: &mut Vec<u8>, mut num: u64) {
+    loop {
+        let byte = num.to_le_bytes()[0] & 0x7f;
+        num >>= 7;
+
+        if num == 0 {
+            return out.push(byte);
+        }
+
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(out: &mut Vec<u8>, mut num: i64) {
+    loop {
+        let byte = num.to_le_bytes()[0] & 0x7f;
+        num >>= 7;
+
+        // Done once the rest is only the sign, which the byte carries
+        if (num == 0 && byte & 0x40 == 0) || (num == -1 && byte & 0x40 != 0) {
+            return out.push(byte);
+        }
+
+        out.push(byte | 0x80);
+    }
+}
+
+/// Appends a length prefixed vector of bytes.
+fn bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    uleb(out, bytes.len() as u64);
+    out.extend(bytes);
+}
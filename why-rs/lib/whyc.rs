@@ -0,0 +1,830 @@
+//! The `.whyc` format, in which compiled programs are saved so they can
+//! be shipped and run without their source.
+//!
+//! A file starts with a header, followed by the names of the globals
+//! and a table of every function in the program:
+//!
+//! ```text
+//! magic      b"WHYC"
+//! version    u16, see [`VERSION`]
+//! hash       u64, the [`hash`] of the source it was built from
+//! source     str, the path of that source, relative to the file
+//! globals    u32 count, then a str for each
+//! functions  u32 count, then each function, the top level last
+//! ```
+//!
+//! Each function has its name, parameters, return type, locals,
+//! constant pool, declarations, ops and a location table, which maps
+//! runs of ops to the source line and column they were compiled from. A compiled
+//! function in a constant pool refers to an earlier function in the
+//! table by index. Numbers are little endian, and a `str` is its length
+//! as a u32 followed by that many bytes of UTF-8.
+
+use std::fs;
+use std::io;
+use std::path::{self, Path};
+use std::rc::Rc;
+
+use super::internal;
+use super::parser::MAX_NESTING;
+use super::Chunk;
+use super::Condition;
+use super::Function;
+use super::Loc;
+use super::Op;
+use super::Operator;
+use super::Param;
+use super::Program;
+use super::Slot;
+use super::Symbol;
+use super::Value;
+use super::VarType;
+
+/// The bytes every `.whyc` file starts with.
+pub const MAGIC: [u8; 4] = *b"WHYC";
+
+/// The version of the format, which must be bumped whenever the ops or
+/// the way they are written change.
+pub const VERSION: u16 = 3;
+
+/// The operators an op can refer to, in the order they are numbered.
+const OPERATORS: [Operator; 14] = [
+    Operator::Add,
+    Operator::Increment,
+    Operator::IncrementBy,
+    Operator::Subtract,
+    Operator::Decrement,
+    Operator::DecrementBy,
+    Operator::MultBy,
+    Operator::DivBy,
+    Operator::Pow,
+    Operator::Mult,
+    Operator::Div,
+    Operator::Dot,
+    Operator::Assign,
+    Operator::Not,
+];
+
+/// The conditions an op can refer to, in the order they are numbered.
+const CONDITIONS: [Condition; 6] = [
+    Condition::Lt,
+    Condition::Gt,
+    Condition::Lte,
+    Condition::Gte,
+    Condition::Ne,
+    Condition::EqEq,
+];
+
+/// What a `.whyc` file says about how it was built.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Header {
+    pub version: u16,
+    /// The [`hash`] of the source the program was compiled from.
+    pub source_hash: u64,
+    /// The path of that source, as [`source_path`] recorded it. Empty if
+    /// it was read from stdin.
+    pub source: String,
+}
+
+/// Hashes source code with 64 bit FNV-1a, which unlike the standard
+/// library's hasher is the same on every platform and release.
+#[must_use]
+pub fn hash(src: &str) -> u64 {
+    src.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The path a `.whyc` file written to `output` records for its source:
+/// relative to the directory of `output`, so the source is found
+/// wherever the two are moved together, or absolute if the source isn't
+/// under that directory. Stdin is recorded as an empty path, as there
+/// is no file to check.
+#[must_use]
+pub fn source_path(source: &str, output: &str) -> String {
+    if source == "-" {
+        return String::new();
+    }
+
+    let dir = Path::new(output).parent().unwrap_or(Path::new(""));
+    let (Ok(source), Ok(dir)) = (path::absolute(source), path::absolute(dir)) else {
+        return source.to_string();
+    };
+
+    source
+        .strip_prefix(&dir)
+        .unwrap_or(&source)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Writes a compiled program in the `.whyc` format.
+///
+/// # Returns
+/// - [`Result<Vec<u8>, String>`] - The bytes of the file on success.
+///
+/// # Errors
+/// - If the program holds a value that can't be saved, such as a
+///   function that wasn't compiled.
+pub fn encode(program: &Program, source: &str, src: &str) -> Result<Vec<u8>, String> {
+    let mut functions = Vec::new();
+    flatten(&program.main, &mut functions);
+
+    let mut out = Writer::default();
+    out.bytes(&MAGIC);
+    out.u16(VERSION);
+    out.u64(hash(src));
+    out.str(source);
+
+    out.len(program.globals.len());
+    for global in &program.globals {
+        out.str(global.as_str());
+    }
+
+    out.len(functions.len());
+    for function in &functions {
+        out.function(function, &functions)?;
+    }
+
+    Ok(out.buf)
+}
+
+/// Reads a program written by [`encode`], checking it can be run.
+///
+/// # Returns
+/// - [`Result<(Header, Program), String>`] - The header and the program
+///   on success.
+///
+/// # Errors
+/// - If the bytes aren't a `.whyc` file, were written by another version
+///   of the format, or are truncated or corrupt.
+pub fn decode(bytes: &[u8]) -> Result<(Header, Program), String> {
+    let mut input = Reader {
+        bytes,
+        pos: 0,
+        depth: 0,
+    };
+
+    if input.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return super::exc!("Not a .whyc file");
+    }
+
+    let version = input.u16()?;
+
+    if version != VERSION {
+        return super::exc!(
+            "The .whyc file was built for version {} of the .whyc format, but this is version {}; rebuild it with `why build`",
+            version,
+            VERSION
+        );
+    }
+
+    let header = Header {
+        version,
+        source_hash: input.u64()?,
+        source: input.str()?.to_string(),
+    };
+
+    let globals = (0..input.len()?)
+        .map(|_| input.str().map(Symbol::intern))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut functions: Vec<Rc<Function>> = Vec::new();
+
+    for _ in 0..input.len()? {
+        let function = input.function(&functions)?;
+        validate(&function, globals.len())?;
+        functions.push(Rc::new(function));
+    }
+
+    if input.pos != bytes.len() {
+        return super::exc!("The .whyc file is corrupt: unexpected bytes at its end");
+    }
+
+    let Some(main) = functions.pop() else {
+        return super::exc!("The .whyc file is corrupt: it has no functions");
+    };
+
+    Ok((header, Program { main, globals }))
+}
+
+/// Loads a `.whyc` file, rejecting it if the source it was built from
+/// is still around but has changed since. The source is looked for
+/// where [`source_path`] recorded it, relative to the `.whyc` file.
+///
+/// # Returns
+/// - [`Result<(Program, Option<String>), String>`] - The program and,
+///   if it was shipped along with it, its source on success.
+///
+/// # Errors
+/// - If the file can't be read or decoded, or is stale.
+/// - If the source is there but can't be read.
+pub fn load(path: &str) -> Result<(Program, Option<String>), String> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return super::exc!("Failed to read file: {:?}: {}", path, e),
+    };
+
+    let (header, program) = decode(&bytes)?;
+
+    if header.source.is_empty() {
+        return Ok((program, None));
+    }
+
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let source = dir.join(&header.source);
+
+    let src = match fs::read_to_string(&source) {
+        Ok(src) => src,
+        // A shipped program doesn't need its source
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((program, None)),
+        Err(e) => return super::exc!("Failed to read file: {:?}: {}", source, e),
+    };
+
+    if hash(&src) != header.source_hash {
+        return super::exc!(
+            "{} is stale: {:?} has changed since it was built; rebuild it with `why build`",
+            path,
+            source
+        );
+    }
+
+    Ok((program, Some(src)))
+}
+
+/// Whether a file should be loaded as a compiled program.
+#[must_use]
+pub fn is_compiled(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext == "whyc")
+}
+
+/// Lists a function and the functions it contains, so that every
+/// function comes after the ones it contains.
+fn flatten(function: &Rc<Function>, out: &mut Vec<Rc<Function>>) {
+    for constant in &function.chunk.constants {
+        if let Value::Compiled(nested) = constant {
+            flatten(nested, out);
+        }
+    }
+
+    out.push(Rc::clone(function));
+}
+
+/// Checks the operands of every op are in range, so a corrupt file
+/// can't make the VM index out of bounds.
+fn validate(function: &Function, globals: usize) -> Result<(), String> {
+    let chunk = &function.chunk;
+    let locals = function.locals.len();
+
+    let slot = |slot: Slot| match slot {
+        Slot::Local(idx) => (idx as usize) < locals,
+        Slot::Global(idx) => (idx as usize) < globals,
+    };
+
+    let valid = chunk.locs.len() == chunk.code.len()
+        && function.params.len() <= locals
+        && chunk.code.iter().all(|op| match *op {
+            Op::Constant(idx) => (idx as usize) < chunk.constants.len(),
+            Op::GetLocal(idx) => (idx as usize) < locals,
+            Op::GetGlobal(idx) => (idx as usize) < globals,
+            Op::Define(target, decl) => slot(target) && (decl as usize) < chunk.decls.len(),
+            Op::Assign(target, _, _) | Op::Delete(target) => slot(target),
+            Op::Jump(target) | Op::JumpIfFalse(target) => (target as usize) <= chunk.code.len(),
+            _ => true,
+        });
+
+    if valid {
+        Ok(())
+    } else {
+        super::exc!(
+            "The .whyc file is corrupt: function '{}' has an op out of range",
+            (function.name)
+        )
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, n: u8) {
+        self.buf.push(n);
+    }
+
+    fn u16(&mut self, n: u16) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn u64(&mut self, n: u64) {
+        self.bytes(&n.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(internal::index(len));
+    }
+
+    fn str(&mut self, s: &str) {
+        self.len(s.len());
+        self.bytes(s.as_bytes());
+    }
+
+    fn typ(&mut self, typ: &VarType) {
+        match typ {
+            VarType::Int => self.u8(0),
+            VarType::Float => self.u8(1),
+            VarType::String => self.u8(2),
+            VarType::Bool => self.u8(3),
+            VarType::Void => self.u8(4),
+            VarType::Any => self.u8(5),
+            VarType::Array(item) => {
+                self.u8(6);
+                self.typ(item);
+            }
+            VarType::Mapping(key, value) => {
+                self.u8(7);
+                self.typ(key);
+                self.typ(value);
+            }
+            VarType::Function(params, ret) => {
+                self.u8(8);
+                self.len(params.len());
+                for param in params {
+                    self.typ(param);
+                }
+                self.typ(ret);
+            }
+            VarType::Class(name) => {
+                self.u8(9);
+                self.str(name.as_str());
+            }
+        }
+    }
+
+    fn value(&mut self, value: &Value, functions: &[Rc<Function>]) -> Result<(), String> {
+        match value {
+            Value::Null => self.u8(0),
+            Value::Int(num) => {
+                self.u8(1);
+                self.u64(num.cast_unsigned());
+            }
+            Value::Float(num) => {
+                self.u8(2);
+                self.u64(num.to_bits());
+            }
+            Value::String(s) => {
+                self.u8(3);
+                self.str(s);
+            }
+            Value::Bool(b) => {
+                self.u8(4);
+                self.u8(u8::from(*b));
+            }
+            Value::Array(items) => {
+                self.u8(5);
+                self.len(items.len());
+                for item in items {
+                    self.value(item, functions)?;
+                }
+            }
+            Value::Mapping(items) => {
+                self.u8(6);
+                self.len(items.len());
+                for (key, item) in items {
+                    self.value(key, functions)?;
+                    self.value(item, functions)?;
+                }
+            }
+            Value::Builtin(name) => {
+                self.u8(7);
+                self.str(name.as_str());
+            }
+            Value::Compiled(function) => {
+                let Some(idx) = functions.iter().position(|f| Rc::ptr_eq(f, function)) else {
+                    return super::exc!("Function '{}' was not compiled", (function.name));
+                };
+
+                self.u8(8);
+                self.len(idx);
+            }
+            Value::Func(func) => {
+                return super::exc!("Function '{}' was not compiled", (func.name));
+            }
+            Value::Class(_) | Value::Object(_) | Value::Method(..) => {
+                return super::exc!("{} can't be saved in a .whyc file", (value.repr()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn slot(&mut self, slot: Slot) {
+        match slot {
+            Slot::Local(idx) => {
+                self.u8(0);
+                self.u32(idx);
+            }
+            Slot::Global(idx) => {
+                self.u8(1);
+                self.u32(idx);
+            }
+        }
+    }
+
+    fn op(&mut self, op: Op) {
+        let operator = |op: Operator| {
+            let pos = OPERATORS.iter().position(|o| *o == op);
+            pos.and_then(|pos| u8::try_from(pos).ok()).unwrap_or(0)
+        };
+
+        match op {
+            Op::Constant(idx) => {
+                self.u8(0);
+                self.u32(idx);
+            }
+            Op::Pop => self.u8(1),
+            Op::GetLocal(idx) => {
+                self.u8(2);
+                self.u32(idx);
+            }
+            Op::GetGlobal(idx) => {
+                self.u8(3);
+                self.u32(idx);
+            }
+            Op::Define(slot, decl) => {
+                self.u8(4);
+                self.slot(slot);
+                self.u32(decl);
+            }
+            Op::Assign(slot, op, depth) => {
+                self.u8(5);
+                self.slot(slot);
+                self.u8(operator(op));
+                self.u32(depth);
+            }
+            Op::Array(len) => {
+                self.u8(6);
+                self.u32(len);
+            }
+            Op::Mapping(len) => {
+                self.u8(7);
+                self.u32(len);
+            }
+            Op::Index => self.u8(8),
+            Op::Unary(op) => {
+                self.u8(9);
+                self.u8(operator(op));
+            }
+            Op::Binary(op) => {
+                self.u8(10);
+                self.u8(operator(op));
+            }
+            Op::Compare(cond) => {
+                self.u8(11);
+                let pos = CONDITIONS.iter().position(|c| *c == cond);
+                self.u8(pos.and_then(|pos| u8::try_from(pos).ok()).unwrap_or(0));
+            }
+            Op::Jump(target) => {
+                self.u8(12);
+                self.u32(target);
+            }
+            Op::JumpIfFalse(target) => {
+                self.u8(13);
+                self.u32(target);
+            }
+            Op::Call(args) => {
+                self.u8(14);
+                self.u32(args);
+            }
+            Op::Return => self.u8(15),
+            Op::Delete(slot) => {
+                self.u8(16);
+                self.slot(slot);
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function, functions: &[Rc<Function>]) -> Result<(), String> {
+        let chunk = &function.chunk;
+
+        self.str(function.name.as_str());
+
+        self.len(function.params.len());
+        for param in &function.params {
+            self.str(param.name.as_str());
+            self.typ(&param.typ);
+        }
+
+        self.typ(&function.ret);
+
+        self.len(function.locals.len());
+        for local in &function.locals {
+            self.str(local.as_str());
+        }
+
+        self.len(chunk.constants.len());
+        for constant in &chunk.constants {
+            self.value(constant, functions)?;
+        }
+
+        self.len(chunk.decls.len());
+        for (typ, constant) in &chunk.decls {
+            match typ {
+                Some(typ) => {
+                    self.u8(1);
+                    self.typ(typ);
+                }
+                None => self.u8(0),
+            }
+            self.u8(u8::from(*constant));
+        }
+
+        self.len(chunk.code.len());
+        for op in &chunk.code {
+            self.op(*op);
+        }
+
+        // The location table, as runs of ops from the same location
+        let mut runs: Vec<(usize, &Loc)> = Vec::new();
+        for loc in &chunk.locs {
+            match runs.last_mut() {
+                Some((count, last)) if *last == loc => *count += 1,
+                _ => runs.push((1, loc)),
+            }
+        }
+
+        self.len(runs.len());
+        for (count, loc) in runs {
+            self.len(count);
+            self.len(loc.line);
+            self.len(loc.col);
+        }
+
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// How many types or values deep the reader is, so a corrupt file
+    /// can't nest them deeply enough to overflow the stack.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        match self.bytes.get(self.pos..self.pos.saturating_add(n)) {
+            Some(bytes) => {
+                self.pos += n;
+                Ok(bytes)
+            }
+            None => super::exc!("The .whyc file is truncated"),
+        }
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn bool(&mut self) -> Result<bool, String> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Reader::corrupt("bool", tag),
+        }
+    }
+
+    fn str(&mut self) -> Result<&'a str, String> {
+        let len = self.len()?;
+
+        match std::str::from_utf8(self.take(len)?) {
+            Ok(s) => Ok(s),
+            Err(_) => super::exc!("The .whyc file is corrupt: a string isn't valid UTF-8"),
+        }
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, String> {
+        self.str().map(Symbol::intern)
+    }
+
+    fn corrupt<T>(what: &str, tag: u8) -> Result<T, String> {
+        super::exc!("The .whyc file is corrupt: unknown {} tag {}", what, tag)
+    }
+
+    /// Reads a type or value inside another, failing if they nest more
+    /// than [`MAX_NESTING`] deep, which no program the parser accepts
+    /// can.
+    fn nested<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.depth >= MAX_NESTING {
+            return super::exc!(
+                "The .whyc file is corrupt: it nests more than {} levels deep",
+                MAX_NESTING
+            );
+        }
+
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn typ(&mut self) -> Result<VarType, String> {
+        let typ = match self.u8()? {
+            0 => VarType::Int,
+            1 => VarType::Float,
+            2 => VarType::String,
+            3 => VarType::Bool,
+            4 => VarType::Void,
+            5 => VarType::Any,
+            6 => VarType::Array(Box::new(self.nested(Reader::typ)?)),
+            7 => VarType::Mapping(
+                Box::new(self.nested(Reader::typ)?),
+                Box::new(self.nested(Reader::typ)?),
+            ),
+            8 => {
+                let params = (0..self.len()?)
+                    .map(|_| self.nested(Reader::typ))
+                    .collect::<Result<_, _>>()?;
+                VarType::Function(params, Box::new(self.nested(Reader::typ)?))
+            }
+            9 => VarType::Class(self.symbol()?),
+            tag => return Reader::corrupt("type", tag),
+        };
+
+        Ok(typ)
+    }
+
+    fn value(&mut self, functions: &[Rc<Function>]) -> Result<Value, String> {
+        let value = match self.u8()? {
+            0 => Value::Null,
+            1 => Value::Int(self.u64()?.cast_signed()),
+            2 => Value::Float(f64::from_bits(self.u64()?)),
+            3 => Value::String(self.str()?.to_string()),
+            4 => Value::Bool(self.bool()?),
+            5 => Value::Array(
+                (0..self.len()?)
+                    .map(|_| self.nested(|input| input.value(functions)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            6 => Value::Mapping(
+                (0..self.len()?)
+                    .map(|_| {
+                        Ok((
+                            self.nested(|input| input.value(functions))?,
+                            self.nested(|input| input.value(functions))?,
+                        ))
+                    })
+                    .collect::<Result<_, String>>()?,
+            ),
+            7 => Value::Builtin(self.symbol()?),
+            8 => match functions.get(self.len()?) {
+                Some(function) => Value::Compiled(Rc::clone(function)),
+                None => return super::exc!("The .whyc file is corrupt: unknown function"),
+            },
+            tag => return Reader::corrupt("value", tag),
+        };
+
+        Ok(value)
+    }
+
+    fn slot(&mut self) -> Result<Slot, String> {
+        match self.u8()? {
+            0 => Ok(Slot::Local(self.u32()?)),
+            1 => Ok(Slot::Global(self.u32()?)),
+            tag => Reader::corrupt("slot", tag),
+        }
+    }
+
+    fn operator(&mut self) -> Result<Operator, String> {
+        let tag = self.u8()?;
+
+        match OPERATORS.get(tag as usize) {
+            Some(op) => Ok(*op),
+            None => Reader::corrupt("operator", tag),
+        }
+    }
+
+    fn op(&mut self) -> Result<Op, String> {
+        let op = match self.u8()? {
+            0 => Op::Constant(self.u32()?),
+            1 => Op::Pop,
+            2 => Op::GetLocal(self.u32()?),
+            3 => Op::GetGlobal(self.u32()?),
+            4 => Op::Define(self.slot()?, self.u32()?),
+            5 => Op::Assign(self.slot()?, self.operator()?, self.u32()?),
+            6 => Op::Array(self.u32()?),
+            7 => Op::Mapping(self.u32()?),
+            8 => Op::Index,
+            9 => Op::Unary(self.operator()?),
+            10 => Op::Binary(self.operator()?),
+            11 => {
+                let tag = self.u8()?;
+
+                match CONDITIONS.get(tag as usize) {
+                    Some(cond) => Op::Compare(*cond),
+                    None => return Reader::corrupt("condition", tag),
+                }
+            }
+            12 => Op::Jump(self.u32()?),
+            13 => Op::JumpIfFalse(self.u32()?),
+            14 => Op::Call(self.u32()?),
+            15 => Op::Return,
+            16 => Op::Delete(self.slot()?),
+            tag => return Reader::corrupt("op", tag),
+        };
+
+        Ok(op)
+    }
+
+    fn function(&mut self, functions: &[Rc<Function>]) -> Result<Function, String> {
+        let name = self.symbol()?;
+
+        let params = (0..self.len()?)
+            .map(|_| {
+                Ok(Param {
+                    name: self.symbol()?,
+                    typ: self.typ()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        let ret = self.typ()?;
+
+        let locals = (0..self.len()?)
+            .map(|_| self.symbol())
+            .collect::<Result<_, _>>()?;
+
+        let mut chunk = Chunk::default();
+
+        for _ in 0..self.len()? {
+            chunk.constants.push(self.value(functions)?);
+        }
+
+        for _ in 0..self.len()? {
+            let typ = if self.bool()? {
+                Some(self.typ()?)
+            } else {
+                None
+            };
+            chunk.decls.push((typ, self.bool()?));
+        }
+
+        for _ in 0..self.len()? {
+            chunk.code.push(self.op()?);
+        }
+
+        for _ in 0..self.len()? {
+            let count = self.len()?;
+            let loc = Loc::at(self.len()?, self.len()?);
+
+            if chunk.locs.len() + count > chunk.code.len() {
+                return super::exc!("The .whyc file is corrupt: its location table is too long");
+            }
+
+            chunk.locs.extend(std::iter::repeat_n(loc, count));
+        }
+
+        Ok(Function {
+            name,
+            params,
+            ret,
+            chunk,
+            locals,
+        })
+    }
+}
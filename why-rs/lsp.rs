@@ -0,0 +1,18 @@
+use std::io;
+use std::process;
+
+use why_rs::lsp::Server;
+
+fn main() {
+    let mut server = Server::new();
+
+    if let Err(e) = server.run(io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+
+    // Exiting without being asked to shut down first is an error
+    if !server.shutdown {
+        process::exit(1);
+    }
+}
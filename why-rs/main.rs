@@ -1,27 +1,329 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+use std::thread;
+
+use why_rs::cli::{Cli, Command, Emit, Failure, USAGE};
+use why_rs::doc;
+use why_rs::dump;
 use why_rs::internal;
+use why_rs::trace::{StderrTracer, Tracer};
+use why_rs::traceback;
+use why_rs::whyc;
+use why_rs::CGen;
+use why_rs::Checker;
+use why_rs::Compiler;
+use why_rs::Expr;
+use why_rs::Formatter;
+use why_rs::HeapStats;
+use why_rs::Interpreter;
 use why_rs::Lexer;
+use why_rs::Optimizer;
 use why_rs::Parser;
+use why_rs::Program;
+use why_rs::Repl;
+use why_rs::Token;
+use why_rs::Vm;
+use why_rs::WasmGen;
 
-fn main() -> Result<(), String> {
-    let args = internal::collect_cli_args()?;
-    let src = internal::read_source_file(&args[1])?;
+type Outcome<T> = Result<T, (Failure, String)>;
 
-    let mut lexer = Lexer::new(&src)?;
-    let tokens = lexer.lex()?;
+fn main() {
+    let result = Cli::parse(env::args().skip(1))
+        .map_err(|e| (Failure::Usage, format!("{e}\n\n{USAGE}")))
+        .and_then(spawn);
 
-    for token in &tokens {
-        println!("{:?}", token);
+    if let Err((failure, message)) = result {
+        eprintln!("{message}");
+        process::exit(failure.code());
     }
+}
 
-    let mut peekable_tokens = tokens.iter().peekable();
+/// Runs the command on a thread with enough stack for the deepest calls
+/// the program may nest, rather than however much the main thread has.
+fn spawn(cli: Cli) -> Outcome<()> {
+    thread::Builder::new()
+        .stack_size(traceback::stack_size(cli.max_depth))
+        .spawn(move || run(cli))
+        .map_err(|e| (Failure::Io, format!("Failed to start: {e}")))?
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Runs the command described by the command line arguments.
+fn run(cli: Cli) -> Outcome<()> {
+    match cli.command {
+        Command::Help => {
+            println!("{USAGE}");
+            return Ok(());
+        }
+        Command::Version => {
+            println!("why {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Command::Repl => return repl().map_err(|e| (Failure::Io, e)),
+        _ => (),
+    }
 
-    let mut parser = Parser::new(&mut peekable_tokens);
-    let ast = parser.parse()?;
+    let tracer: Rc<dyn Tracer> = Rc::new(StderrTracer {
+        lexer: cli.trace_lexer,
+        parser: cli.trace_parser,
+    });
 
-    println!("Resulting AST:");
-    println!("{:?}", ast);
+    let file = cli.file.clone().unwrap_or_default();
 
-    println!();
+    if whyc::is_compiled(&file) {
+        return run_compiled(&file, &cli);
+    }
+
+    let src = internal::read_source(&file).map_err(|e| (Failure::Io, e))?;
+    let mut lexer = lexer(&src, &tracer, &cli)?;
+
+    if cli.command == Command::Tokens {
+        let tokens = lexer.lex().map_err(|e| (Failure::Lex, e))?;
+        print!("{}", dump::tokens(&tokens, cli.format));
+        return Ok(());
+    }
+
+    let ast = parse(&mut lexer, &tracer)?;
+
+    match cli.command {
+        Command::Ast if cli.optimized => {
+            print!("{}", dump::expr(&optimize(&ast, &cli)?, cli.format));
+        }
+        Command::Ast => print!("{}", dump::expr(&ast, cli.format)),
+        Command::Fmt if cli.check => {
+            if Formatter::format(&ast) != src {
+                let name = if file == "-" { "<stdin>" } else { &file };
+                return Err((Failure::Unformatted, format!("{name} is not formatted")));
+            }
+        }
+        Command::Fmt => print!("{}", Formatter::format(&ast)),
+        Command::Doc => {
+            let title = if file == "-" { "<stdin>" } else { &file };
+            print!("{}", doc::generate(title, &ast, cli.doc_format));
+        }
+        Command::Check => check(&ast)?,
+        Command::Disasm => {
+            let ast = optimize(&ast, &cli)?;
+            print!("{}", compile(&ast)?.disassemble(&src));
+        }
+        Command::Build => {
+            check(&ast)?;
+            let ast = optimize(&ast, &cli)?;
+
+            let output = cli
+                .output
+                .unwrap_or_else(|| output_path(&file, cli.emit.extension()));
+
+            let bytes = match cli.emit {
+                Emit::Whyc => {
+                    let source = whyc::source_path(&file, &output);
+                    whyc::encode(&compile(&ast)?, &source, &src)
+                }
+                Emit::C => CGen::new().generate(&ast).map(String::into_bytes),
+                Emit::Wat => WasmGen::new().generate(&ast).map(|m| m.wat().into_bytes()),
+                Emit::Wasm => WasmGen::new().generate(&ast).map(|m| m.wasm()),
+            };
+            let bytes = bytes.map_err(|e| (Failure::Runtime, e))?;
+
+            fs::write(&output, bytes)
+                .map_err(|e| (Failure::Io, format!("Failed to write {output:?}: {e}")))?;
+        }
+        _ if cli.vm => {
+            check(&ast)?;
+            let ast = optimize(&ast, &cli)?;
+            let program = compile(&ast)?;
+
+            run_vm(&program, &src, &cli)?;
+        }
+        _ => {
+            check(&ast)?;
+            let ast = optimize(&ast, &cli)?;
+
+            let interpreter = if cli.jit { jit()? } else { Interpreter::new() };
+            let interpreter = interpreter
+                .with_max_depth(cli.max_depth)
+                .with_stack_limit(traceback::stack_limit(cli.max_depth));
+            let mut interpreter = if cli.heap_stats {
+                interpreter.with_heap_stats()
+            } else {
+                interpreter
+            };
+
+            let result = interpreter.run(&ast);
+            report_heap(interpreter.heap);
+            result.map_err(|e| {
+                let e = traceback::render(&e, &interpreter.traceback, &src);
+                (Failure::Runtime, e)
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// An interpreter which compiles numeric functions to native code.
+#[cfg(feature = "jit")]
+fn jit() -> Outcome<Interpreter> {
+    Interpreter::with_jit().map_err(|e| (Failure::Runtime, e))
+}
+
+#[cfg(not(feature = "jit"))]
+fn jit() -> Outcome<Interpreter> {
+    let message = "--jit needs why to be built with the jit feature";
+    Err((Failure::Usage, message.to_string()))
+}
+
+/// Runs or disassembles a program loaded from a `.whyc` file, which
+/// was checked when it was built.
+fn run_compiled(file: &str, cli: &Cli) -> Outcome<()> {
+    if !matches!(cli.command, Command::Run | Command::Disasm) {
+        let message = format!("{file:?} is compiled, so it can only be run or disassembled");
+        return Err((Failure::Usage, message));
+    }
+
+    let (program, src) = whyc::load(file).map_err(|e| (Failure::Io, e))?;
+
+    let src = src.unwrap_or_default();
+
+    match cli.command {
+        Command::Disasm => print!("{}", program.disassemble(&src)),
+        _ => run_vm(&program, &src, cli)?,
+    }
 
     Ok(())
 }
+
+/// Runs a compiled program on the VM, tracing errors back through the
+/// source it was compiled from, if it's known.
+fn run_vm(program: &Program, src: &str, cli: &Cli) -> Outcome<()> {
+    let vm = Vm::new().with_max_depth(cli.max_depth);
+    let mut vm = if cli.heap_stats {
+        vm.with_heap_stats()
+    } else {
+        vm
+    };
+
+    let result = vm.run(program);
+    report_heap(vm.heap);
+    result
+        .map(|_| ())
+        .map_err(|e| (Failure::Runtime, traceback::render(&e, &vm.traceback, src)))
+}
+
+/// Reports the heap values a run allocated and freed, if it
+/// counted them, even if it failed.
+fn report_heap(stats: Option<HeapStats>) {
+    if let Some(stats) = stats {
+        eprintln!("{stats}");
+    }
+}
+
+/// The default path `build` writes a source file's program to.
+fn output_path(file: &str, extension: &str) -> String {
+    Path::new(file)
+        .with_extension(extension)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Creates a lexer for the source, set up for the command.
+fn lexer<'src>(src: &'src str, tracer: &Rc<dyn Tracer>, cli: &Cli) -> Outcome<Lexer<'src>> {
+    let mut lexer = Lexer::new(src)
+        .map_err(|e| (Failure::Lex, e))?
+        .with_tracer(Rc::clone(tracer));
+
+    // The formatter keeps comments, which are only lexed as trivia
+    if cli.trivia || cli.command == Command::Fmt {
+        lexer = lexer.with_trivia();
+    }
+
+    Ok(lexer)
+}
+
+/// Parses tokens as they are lexed, carrying on past errors so every
+/// lex and syntax error is reported at once.
+fn parse(lexer: &mut Lexer, tracer: &Rc<dyn Tracer>) -> Outcome<Expr> {
+    let (ast, parse_errors) = {
+        // Tokens borrow the source for longer than the parser borrows
+        // the lexer, so their lifetime is shortened to match
+        let tokens = lexer
+            .by_ref()
+            .filter_map(|token| -> Option<Token> { token.ok() });
+        let mut parser = Parser::from_stream(tokens).with_tracer(Rc::clone(tracer));
+        (parser.parse_recovering(), parser.errors)
+    };
+    let lex_errors = std::mem::take(&mut lexer.errors);
+
+    let failure = if lex_errors.is_empty() {
+        Failure::Parse
+    } else {
+        Failure::Lex
+    };
+
+    let errors: Vec<String> = lex_errors.into_iter().chain(parse_errors).collect();
+
+    if errors.is_empty() {
+        Ok(ast)
+    } else {
+        Err((failure, errors.join("\n")))
+    }
+}
+
+fn check(ast: &Expr) -> Outcome<()> {
+    Checker::new().check(ast).map_err(|e| (Failure::Type, e))
+}
+
+/// Optimizes the program at the level given on the command line. The
+/// program is checked first, so type errors are reported as written.
+fn optimize(ast: &Expr, cli: &Cli) -> Outcome<Expr> {
+    let optimizer = Optimizer::new()
+        .with_level(cli.opt_level)
+        .map_err(|e| (Failure::Usage, e))?;
+
+    Ok(optimizer.optimize(ast))
+}
+
+/// Compiles the program to bytecode. Code that compiles to nothing
+/// useful, like a `break` outside of a loop, fails as it would at
+/// runtime.
+fn compile(ast: &Expr) -> Outcome<Program> {
+    Compiler::new()
+        .compile(ast)
+        .map_err(|e| (Failure::Runtime, e))
+}
+
+/// Runs the interactive read-eval-print loop until `:quit` or the end
+/// of input.
+fn repl() -> Result<(), String> {
+    let mut repl = Repl::new();
+    let mut stdin = io::stdin().lock();
+    let mut line = String::new();
+
+    println!("Why {} - type :help for help", env!("CARGO_PKG_VERSION"));
+
+    loop {
+        print!("{}", repl.prompt());
+        io::stdout().flush().map_err(|e| e.to_string())?;
+
+        line.clear();
+        if stdin.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            println!();
+            return Ok(());
+        }
+
+        if Repl::is_quit(&line) {
+            return Ok(());
+        }
+
+        match repl.feed(line.trim_end_matches(['\n', '\r'])) {
+            Ok(Some(output)) => println!("{output}"),
+            Ok(None) => (),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}